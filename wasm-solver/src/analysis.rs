@@ -0,0 +1,498 @@
+//! Cheap structural and numerical probes of a CSR matrix
+
+use wasm_bindgen::prelude::*;
+
+use crate::dense::symmetric_eigen;
+use crate::kernels::{axpy, dot, Csr};
+use crate::options::SolverOptions;
+use crate::precond::{setup, Jacobi, Preconditioner};
+
+/// Properties of a matrix used to pick a solver
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug)]
+pub struct MatrixInfo {
+    n: u32,
+    nnz: u32,
+    symmetric: bool,
+    positive_diagonal: bool,
+    diagonally_dominant: bool,
+    bandwidth: u32,
+}
+
+#[wasm_bindgen]
+impl MatrixInfo {
+    /// Number of rows
+    #[wasm_bindgen(getter)]
+    pub fn n(&self) -> u32 {
+        self.n
+    }
+
+    /// Number of stored entries
+    #[wasm_bindgen(getter)]
+    pub fn nnz(&self) -> u32 {
+        self.nnz
+    }
+
+    /// a_ij == a_ji up to rounding (relative to the largest entry)
+    #[wasm_bindgen(getter)]
+    pub fn symmetric(&self) -> bool {
+        self.symmetric
+    }
+
+    /// Every diagonal entry is present and positive
+    #[wasm_bindgen(getter)]
+    pub fn positive_diagonal(&self) -> bool {
+        self.positive_diagonal
+    }
+
+    /// |a_ii| >= sum_{j != i} |a_ij| in every row
+    #[wasm_bindgen(getter)]
+    pub fn diagonally_dominant(&self) -> bool {
+        self.diagonally_dominant
+    }
+
+    /// max |i - j| over the stored entries
+    #[wasm_bindgen(getter)]
+    pub fn bandwidth(&self) -> u32 {
+        self.bandwidth
+    }
+}
+
+/// Defect of malformed CSR input found by `validate_csr`
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CsrError {
+    /// `row_ptr` is empty (it needs n + 1 entries)
+    EmptyRowPtr = 0,
+    /// `row_ptr[0]` is not 0
+    RowPtrStart = 1,
+    /// `row_ptr[index + 1] < row_ptr[index]`
+    RowPtrDecreasing = 2,
+    /// The last entry of `row_ptr` differs from the number of column
+    /// indices
+    RowPtrEnd = 3,
+    /// `values` and `col_indices` differ in length; `index` is the
+    /// shorter length
+    LengthMismatch = 4,
+    /// `col_indices[index]` in `row` is not below n
+    ColumnOutOfRange = 5,
+    /// `values[index]` in `row` is NaN or infinite
+    NonFiniteValue = 6,
+    /// The right-hand side or initial guess does not have n entries;
+    /// `index` is its length (checked by the solvers only)
+    VectorLength = 7,
+}
+
+/// Outcome of `validate_csr`: the first defect found, if any
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CsrValidation {
+    error: Option<CsrError>,
+    row: Option<u32>,
+    index: Option<u32>,
+}
+
+impl CsrValidation {
+    fn failed(error: CsrError, row: Option<usize>, index: usize) -> Self {
+        CsrValidation {
+            error: Some(error),
+            row: row.map(|r| r as u32),
+            index: Some(index as u32),
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl CsrValidation {
+    /// Whether the arrays form a well-defined square CSR matrix
+    #[wasm_bindgen(getter)]
+    pub fn valid(&self) -> bool {
+        self.error.is_none()
+    }
+
+    /// The defect; `undefined` if valid
+    #[wasm_bindgen(getter)]
+    pub fn error(&self) -> Option<CsrError> {
+        self.error
+    }
+
+    /// Row of the defect, for those tied to one
+    #[wasm_bindgen(getter)]
+    pub fn row(&self) -> Option<u32> {
+        self.row
+    }
+
+    /// Position of the defect in the array it concerns
+    #[wasm_bindgen(getter)]
+    pub fn index(&self) -> Option<u32> {
+        self.index
+    }
+}
+
+/// Check that CSR arrays describe a square matrix the solvers can read:
+/// `row_ptr` of n + 1 non-decreasing offsets from 0 to the number of
+/// entries, one value per column index, columns below n and finite values.
+/// Unsorted or duplicate columns are allowed. The slice-taking solvers
+/// trust their input and may panic or return garbage otherwise; set
+/// `SolverOptions::validate` to have them run this check first.
+#[wasm_bindgen]
+pub fn validate_csr(values: &[f64], col_indices: &[u32], row_ptr: &[u32]) -> CsrValidation {
+    validate(values, col_indices, row_ptr)
+}
+
+pub(crate) fn validate(values: &[f64], col_indices: &[u32], row_ptr: &[u32]) -> CsrValidation {
+    let Some(&last) = row_ptr.last() else {
+        return CsrValidation::failed(CsrError::EmptyRowPtr, None, 0);
+    };
+    if row_ptr[0] != 0 {
+        return CsrValidation::failed(CsrError::RowPtrStart, None, 0);
+    }
+    if let Some(i) = row_ptr.windows(2).position(|w| w[1] < w[0]) {
+        return CsrValidation::failed(CsrError::RowPtrDecreasing, Some(i), i);
+    }
+    if last as usize != col_indices.len() {
+        return CsrValidation::failed(CsrError::RowPtrEnd, None, row_ptr.len() - 1);
+    }
+    if values.len() != col_indices.len() {
+        let shorter = values.len().min(col_indices.len());
+        return CsrValidation::failed(CsrError::LengthMismatch, None, shorter);
+    }
+    let n = row_ptr.len() - 1;
+    for i in 0..n {
+        for q in row_ptr[i] as usize..row_ptr[i + 1] as usize {
+            if col_indices[q] as usize >= n {
+                return CsrValidation::failed(CsrError::ColumnOutOfRange, Some(i), q);
+            }
+            if !values[q].is_finite() {
+                return CsrValidation::failed(CsrError::NonFiniteValue, Some(i), q);
+            }
+        }
+    }
+    CsrValidation {
+        error: None,
+        row: None,
+        index: None,
+    }
+}
+
+/// `validate` plus the lengths of the solver vectors
+pub(crate) fn validate_system(a: &Csr, b: &[f64], x0: &[f64]) -> CsrValidation {
+    let report = validate(a.values, a.col_indices, a.row_ptr);
+    match [b.len(), x0.len()].into_iter().find(|&len| len != a.n()) {
+        Some(len) if report.valid() => CsrValidation::failed(CsrError::VectorLength, None, len),
+        _ => report,
+    }
+}
+
+/// Probe symmetry, diagonal sign and dominance, size and bandwidth
+#[wasm_bindgen]
+pub fn analyze_matrix(values: &[f64], col_indices: &[u32], row_ptr: &[u32]) -> MatrixInfo {
+    analyze(&Csr::new(values, col_indices, row_ptr))
+}
+
+/// Rows whose diagonal entry is missing or tiny (|a_ii| <= 1e-30), which
+/// the Jacobi diagonal silently replaces with 1 under the default
+/// `DiagonalPolicy`
+#[wasm_bindgen]
+pub fn invalid_diagonal_rows(values: &[f64], col_indices: &[u32], row_ptr: &[u32]) -> Vec<u32> {
+    Csr::new(values, col_indices, row_ptr).invalid_diagonal_rows()
+}
+
+pub(crate) fn analyze(a: &Csr) -> MatrixInfo {
+    let n = a.n();
+    let scale = a.values.iter().fold(0.0f64, |acc, v| acc.max(v.abs()));
+    let sym_tol = 1e-12 * scale;
+
+    let mut symmetric = true;
+    let mut positive_diagonal = true;
+    let mut diagonally_dominant = true;
+    let mut bandwidth = 0usize;
+
+    for i in 0..n {
+        let mut diag = 0.0;
+        let mut off = 0.0;
+        for k in a.row_ptr[i] as usize..a.row_ptr[i + 1] as usize {
+            let j = a.col_indices[k] as usize;
+            let v = a.values[k];
+            bandwidth = bandwidth.max(i.abs_diff(j));
+            if j == i {
+                diag += v;
+            } else {
+                off += v.abs();
+            }
+            if symmetric && j != i && (v - entry(a, j, i)).abs() > sym_tol {
+                symmetric = false;
+            }
+        }
+        positive_diagonal &= diag > 0.0;
+        diagonally_dominant &= diag.abs() >= off;
+    }
+
+    MatrixInfo {
+        n: n as u32,
+        nnz: a.values.len() as u32,
+        symmetric,
+        positive_diagonal,
+        diagonally_dominant,
+        bandwidth: bandwidth as u32,
+    }
+}
+
+/// a_ij, summing duplicates; zero when not stored
+fn entry(a: &Csr, i: usize, j: usize) -> f64 {
+    if i >= a.n() {
+        return 0.0;
+    }
+    (a.row_ptr[i] as usize..a.row_ptr[i + 1] as usize)
+        .filter(|&k| a.col_indices[k] as usize == j)
+        .map(|k| a.values[k])
+        .sum()
+}
+
+/// Extreme eigenvalues of A (or M^{-1} A) from `estimate_condition`
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug)]
+pub struct ConditionEstimate {
+    lambda_min: f64,
+    lambda_max: f64,
+    steps: u32,
+    positive_definite: bool,
+}
+
+#[wasm_bindgen]
+impl ConditionEstimate {
+    /// Smallest Ritz value, an upper bound on the smallest eigenvalue
+    #[wasm_bindgen(getter)]
+    pub fn lambda_min(&self) -> f64 {
+        self.lambda_min
+    }
+
+    /// Largest Ritz value, a lower bound on the largest eigenvalue
+    #[wasm_bindgen(getter)]
+    pub fn lambda_max(&self) -> f64 {
+        self.lambda_max
+    }
+
+    /// lambda_max / lambda_min, a lower bound on the condition number;
+    /// infinite unless the matrix looked positive definite
+    #[wasm_bindgen(getter)]
+    pub fn condition(&self) -> f64 {
+        if self.positive_definite && self.lambda_min > 0.0 {
+            self.lambda_max / self.lambda_min
+        } else {
+            f64::INFINITY
+        }
+    }
+
+    /// Lanczos steps taken; fewer than asked when a Krylov space became
+    /// invariant (the estimate is then exact)
+    #[wasm_bindgen(getter)]
+    pub fn steps(&self) -> u32 {
+        self.steps
+    }
+
+    /// Whether every step saw positive curvature p^T A p; `false` means A
+    /// (or M) is not SPD and CG may fail
+    #[wasm_bindgen(getter)]
+    pub fn positive_definite(&self) -> bool {
+        self.positive_definite
+    }
+}
+
+/// Estimate the extreme eigenvalues and condition number of an SPD matrix
+/// by `steps` Lanczos steps (30 to 50 usually give lambda_max to a few
+/// percent and the condition number within a factor of two)
+///
+/// With `preconditioned` the estimate is for M^{-1} A with M the
+/// `options.preconditioner`, which governs PCG convergence: roughly
+/// sqrt(condition) iterations per digit. The Lanczos coefficients come
+/// from PCG recurrences started from a fixed non-smooth vector.
+#[wasm_bindgen]
+pub fn estimate_condition(
+    values: &[f64],
+    col_indices: &[u32],
+    row_ptr: &[u32],
+    steps: u32,
+    preconditioned: bool,
+    options: &SolverOptions,
+) -> ConditionEstimate {
+    let a = Csr::new(values, col_indices, row_ptr);
+    let m: Box<dyn Preconditioner> = if preconditioned {
+        setup(options, &a)
+    } else {
+        Box::new(Jacobi::from_diagonal(vec![1.0; a.n()]))
+    };
+    lanczos(&a, m.as_ref(), steps as usize)
+}
+
+/// Ritz values of M^{-1} A from the tridiagonal matrix of `steps` PCG
+/// steps: T_jj = 1 / alpha_j + beta_{j-1} / alpha_{j-1} and
+/// T_j,j+1 = sqrt(beta_j) / alpha_j
+pub(crate) fn lanczos(a: &Csr, m: &dyn Preconditioner, steps: usize) -> ConditionEstimate {
+    let n = a.n();
+    let mut r: Vec<f64> = (0..n)
+        .map(|i| 1.0 + ((i * 7919) % 101) as f64 / 101.0)
+        .collect();
+    let mut z = vec![0.0; n];
+    m.apply(&r, &mut z);
+    let mut p = z.clone();
+    let mut ap = vec![0.0; n];
+    let mut rz = dot(&r, &z);
+    let rz0 = rz;
+    let mut coefficients: Vec<(f64, f64)> = Vec::with_capacity(steps);
+    let mut positive_definite = rz > 0.0;
+
+    while positive_definite && coefficients.len() < steps.min(n) {
+        a.spmv(&p, &mut ap);
+        let pap = dot(&p, &ap);
+        if pap.is_nan() || pap <= 0.0 {
+            positive_definite = false;
+            break;
+        }
+        let alpha = rz / pap;
+        axpy(-alpha, &ap, &mut r);
+        m.apply(&r, &mut z);
+        let rz_new = dot(&r, &z);
+        let beta = rz_new / rz;
+        coefficients.push((alpha, beta));
+        if rz_new.abs() <= 1e-28 * rz0 {
+            break;
+        }
+        positive_definite = rz_new > 0.0;
+        for (pi, zi) in p.iter_mut().zip(&z) {
+            *pi = zi + beta * *pi;
+        }
+        rz = rz_new;
+    }
+
+    let k = coefficients.len();
+    let mut t = vec![0.0; k * k];
+    for (j, &(alpha, beta)) in coefficients.iter().enumerate() {
+        t[j * k + j] = 1.0 / alpha;
+        if j > 0 {
+            let (alpha_prev, beta_prev) = coefficients[j - 1];
+            t[j * k + j] += beta_prev / alpha_prev;
+        }
+        if j + 1 < k {
+            let off = beta.sqrt() / alpha;
+            t[j * k + j + 1] = off;
+            t[(j + 1) * k + j] = off;
+        }
+    }
+    let (ritz, _) = symmetric_eigen(&t, k);
+    ConditionEstimate {
+        lambda_min: ritz.first().copied().unwrap_or(f64::NAN),
+        lambda_max: ritz.last().copied().unwrap_or(f64::NAN),
+        steps: k as u32,
+        positive_definite,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::PreconditionerKind;
+    use crate::test_util::{convection_diffusion_1d, dense_to_csr, diffusion_2d, laplacian_1d};
+
+    #[test]
+    fn test_laplacian_properties() {
+        let (values, col_indices, row_ptr) = laplacian_1d(10);
+        let info = analyze_matrix(&values, &col_indices, &row_ptr);
+
+        assert_eq!(info.n(), 10);
+        assert_eq!(info.nnz(), 28);
+        assert!(info.symmetric());
+        assert!(info.positive_diagonal());
+        assert!(info.diagonally_dominant());
+        assert_eq!(info.bandwidth(), 1);
+    }
+
+    #[test]
+    fn test_nonsymmetric_detected() {
+        let (values, col_indices, row_ptr) = convection_diffusion_1d(10, 0.5);
+        let info = analyze_matrix(&values, &col_indices, &row_ptr);
+        assert!(!info.symmetric());
+        assert!(info.diagonally_dominant());
+    }
+
+    #[test]
+    fn test_validation_reports_first_defect() {
+        let (values, col_indices, row_ptr) = laplacian_1d(4);
+        assert!(validate_csr(&values, &col_indices, &row_ptr).valid());
+        let check = |values: &[f64], col_indices: &[u32], row_ptr: &[u32]| {
+            let report = validate_csr(values, col_indices, row_ptr);
+            (report.error().unwrap(), report.row(), report.index())
+        };
+
+        assert_eq!(
+            check(&values, &col_indices, &[]),
+            (CsrError::EmptyRowPtr, None, Some(0))
+        );
+        let mut bad = row_ptr.clone();
+        bad[2] = 1;
+        assert_eq!(
+            check(&values, &col_indices, &bad),
+            (CsrError::RowPtrDecreasing, Some(1), Some(1))
+        );
+        assert_eq!(
+            check(&values, &col_indices, &row_ptr[..4]),
+            (CsrError::RowPtrEnd, None, Some(3))
+        );
+        assert_eq!(
+            check(&values[1..], &col_indices, &row_ptr),
+            (CsrError::LengthMismatch, None, Some(9))
+        );
+        let mut bad = col_indices.clone();
+        bad[6] = 4;
+        assert_eq!(
+            check(&values, &bad, &row_ptr),
+            (CsrError::ColumnOutOfRange, Some(2), Some(6))
+        );
+        let mut bad = values.clone();
+        bad[3] = f64::NAN;
+        assert_eq!(
+            check(&bad, &col_indices, &row_ptr),
+            (CsrError::NonFiniteValue, Some(1), Some(3))
+        );
+    }
+
+    #[test]
+    fn test_lanczos_eigenvalues() {
+        // 1D Laplacian: eigenvalues 2 - 2 cos(k pi / (n + 1)); n steps are
+        // exact
+        let n = 40;
+        let (values, col_indices, row_ptr) = laplacian_1d(n);
+        let options = SolverOptions::new();
+        let estimate = estimate_condition(&values, &col_indices, &row_ptr, 60, false, &options);
+        let eigen = |k: usize| 2.0 - 2.0 * (k as f64 * std::f64::consts::PI / (n + 1) as f64).cos();
+        assert!(estimate.positive_definite());
+        assert!(estimate.steps() as usize <= n);
+        assert!((estimate.lambda_min() - eigen(1)).abs() < 1e-8);
+        assert!((estimate.lambda_max() - eigen(n)).abs() < 1e-8);
+
+        // A few steps: Ritz values lie inside the spectrum
+        let rough = estimate_condition(&values, &col_indices, &row_ptr, 10, false, &options);
+        assert!(rough.lambda_min() >= eigen(1) && rough.lambda_max() <= eigen(n) + 1e-12);
+        assert!(rough.condition() < estimate.condition());
+
+        let (values, col_indices, row_ptr) = dense_to_csr(&[1.0, 0.0, 0.0, -1.0], 2);
+        let indefinite = estimate_condition(&values, &col_indices, &row_ptr, 5, false, &options);
+        assert!(!indefinite.positive_definite());
+        assert!(indefinite.condition().is_infinite());
+    }
+
+    #[test]
+    fn test_preconditioned_condition() {
+        let (nx, ny) = (24, 24);
+        let kappa: Vec<f64> = (0..nx * ny)
+            .map(|c| if (c / nx) % 6 < 3 { 1.0 } else { 100.0 })
+            .collect();
+        let (values, col_indices, row_ptr) = diffusion_2d(nx, ny, &kappa);
+        let mut options = SolverOptions::new();
+        let plain = estimate_condition(&values, &col_indices, &row_ptr, 50, false, &options);
+        options.preconditioner = PreconditionerKind::Ic0;
+        let ic0 = estimate_condition(&values, &col_indices, &row_ptr, 50, true, &options);
+        assert!(ic0.positive_definite());
+        assert!(10.0 * ic0.condition() < plain.condition());
+    }
+}
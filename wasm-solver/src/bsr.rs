@@ -0,0 +1,180 @@
+//! Block CSR (BSR) storage for matrices made of dense nodal blocks
+//!
+//! Elasticity matrices with nodal DOF numbering couple every DOF of a node
+//! to every DOF of its neighbours, so the CSR pattern is made of dense
+//! 2 x 2 (2D) or 3 x 3 (3D) blocks. Storing one column index per block
+//! instead of per entry cuts the index memory by the block size squared,
+//! and the product works on small dense blocks.
+
+use crate::kernels::{Csr, LinearOperator, SparseMatrix};
+
+/// Square matrix of `block_size` x `block_size` blocks in CSR order
+///
+/// Block row r holds the blocks `block_ptr[r]..block_ptr[r + 1]`, block q
+/// at block column `block_cols[q]` with its entries row-major in
+/// `blocks[q * bs * bs..(q + 1) * bs * bs]`. Entries of a block missing
+/// from the input are stored as zeros.
+pub(crate) struct Bsr {
+    pub block_size: usize,
+    pub block_ptr: Vec<u32>,
+    pub block_cols: Vec<u32>,
+    pub blocks: Vec<f64>,
+}
+
+impl Bsr {
+    /// Group the entries of `a` into blocks. Returns `None` if the number
+    /// of rows is not a multiple of `block_size`.
+    pub fn from_csr(a: &Csr, block_size: usize) -> Option<Self> {
+        let (n, bs) = (a.n(), block_size);
+        if bs == 0 || !n.is_multiple_of(bs) {
+            return None;
+        }
+        let nb = n / bs;
+        // Slot of block column c within the current block row
+        let mut slot = vec![usize::MAX; nb];
+        let mut out = Bsr {
+            block_size: bs,
+            block_ptr: vec![0u32],
+            block_cols: Vec::new(),
+            blocks: Vec::new(),
+        };
+        for r in 0..nb {
+            let rows = a.row_ptr[r * bs] as usize..a.row_ptr[(r + 1) * bs] as usize;
+            let start = out.block_cols.len();
+            out.block_cols
+                .extend(a.col_indices[rows].iter().map(|&j| j / bs as u32));
+            out.block_cols[start..].sort_unstable();
+            out.block_cols.dedup();
+            let row_blocks = &out.block_cols[start..];
+            for (k, &c) in row_blocks.iter().enumerate() {
+                slot[c as usize] = start + k;
+            }
+            out.blocks.resize(out.block_cols.len() * bs * bs, 0.0);
+            for i in r * bs..(r + 1) * bs {
+                for q in a.row_ptr[i] as usize..a.row_ptr[i + 1] as usize {
+                    let j = a.col_indices[q] as usize;
+                    let at = slot[j / bs] * bs * bs + (i % bs) * bs + j % bs;
+                    out.blocks[at] += a.values[q];
+                }
+            }
+            out.block_ptr.push(out.block_cols.len() as u32);
+        }
+        Some(out)
+    }
+
+    pub fn n(&self) -> usize {
+        (self.block_ptr.len() - 1) * self.block_size
+    }
+
+    /// Number of stored blocks
+    pub fn num_blocks(&self) -> usize {
+        self.block_cols.len()
+    }
+
+    /// Diagonal block of block row r, row-major, if stored
+    pub fn diagonal_block(&self, r: usize) -> Option<&[f64]> {
+        let bb = self.block_size * self.block_size;
+        (self.block_ptr[r] as usize..self.block_ptr[r + 1] as usize)
+            .find(|&q| self.block_cols[q] as usize == r)
+            .map(|q| &self.blocks[q * bb..(q + 1) * bb])
+    }
+
+    /// The same matrix in CSR, every block entry stored
+    pub fn to_csr(&self) -> SparseMatrix {
+        let bs = self.block_size;
+        let mut entries = Vec::with_capacity(self.blocks.len());
+        for r in 0..self.block_ptr.len() - 1 {
+            for q in self.block_ptr[r] as usize..self.block_ptr[r + 1] as usize {
+                let c = self.block_cols[q] as usize;
+                for (k, &v) in self.blocks[q * bs * bs..(q + 1) * bs * bs]
+                    .iter()
+                    .enumerate()
+                {
+                    entries.push((r * bs + k / bs, (c * bs + k % bs) as u32, v));
+                }
+            }
+        }
+        SparseMatrix::from_triplets(self.n(), self.n(), entries)
+    }
+
+    /// y = A x with the block size known at compile time, so the block
+    /// loops unroll
+    fn product<const B: usize>(&self, x: &[f64], y: &mut [f64]) {
+        for (r, yr) in y.chunks_exact_mut(B).enumerate() {
+            let mut acc = [0.0; B];
+            for q in self.block_ptr[r] as usize..self.block_ptr[r + 1] as usize {
+                let c = self.block_cols[q] as usize * B;
+                let xc = &x[c..c + B];
+                let block = &self.blocks[q * B * B..(q + 1) * B * B];
+                for (a, row) in acc.iter_mut().zip(block.chunks_exact(B)) {
+                    *a += row.iter().zip(xc).map(|(v, xj)| v * xj).sum::<f64>();
+                }
+            }
+            yr.copy_from_slice(&acc);
+        }
+    }
+}
+
+impl LinearOperator for Bsr {
+    /// y = A x, one dense block product per stored block
+    fn spmv(&self, x: &[f64], y: &mut [f64]) {
+        let bs = self.block_size;
+        match bs {
+            2 => self.product::<2>(x, y),
+            3 => self.product::<3>(x, y),
+            _ => {
+                for (r, yr) in y.chunks_exact_mut(bs).enumerate() {
+                    yr.fill(0.0);
+                    for q in self.block_ptr[r] as usize..self.block_ptr[r + 1] as usize {
+                        let c = self.block_cols[q] as usize * bs;
+                        let xc = &x[c..c + bs];
+                        let block = &self.blocks[q * bs * bs..(q + 1) * bs * bs];
+                        for (a, row) in yr.iter_mut().zip(block.chunks_exact(bs)) {
+                            *a += row.iter().zip(xc).map(|(v, xj)| v * xj).sum::<f64>();
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{coupled_2x2, diffusion_2d, laplacian_1d};
+
+    #[test]
+    fn test_same_product_as_csr() {
+        let (values, col_indices, row_ptr) = coupled_2x2(&diffusion_2d(9, 7, &[1.0; 63]));
+        let a = Csr::new(&values, &col_indices, &row_ptr);
+        let x: Vec<f64> = (0..a.n()).map(|i| (i as f64 * 0.3).sin()).collect();
+        let mut expected = vec![0.0; a.n()];
+        a.spmv(&x, &mut expected);
+
+        let bsr = Bsr::from_csr(&a, 2).unwrap();
+        assert_eq!(4 * bsr.num_blocks(), values.len());
+        let mut y = vec![0.0; a.n()];
+        bsr.spmv(&x, &mut y);
+        assert!(y.iter().zip(&expected).all(|(u, v)| (u - v).abs() < 1e-12));
+        assert_eq!(bsr.to_csr().values, values);
+
+        // 3 x 3 and a generic block size store zeros inside the blocks
+        for bs in [3, 7] {
+            let bsr = Bsr::from_csr(&a, bs).unwrap();
+            bsr.spmv(&x, &mut y);
+            assert!(y.iter().zip(&expected).all(|(u, v)| (u - v).abs() < 1e-12));
+        }
+    }
+
+    #[test]
+    fn test_rejects_partial_blocks() {
+        let (values, col_indices, row_ptr) = laplacian_1d(5);
+        let a = Csr::new(&values, &col_indices, &row_ptr);
+        assert!(Bsr::from_csr(&a, 2).is_none());
+        assert!(Bsr::from_csr(&a, 0).is_none());
+        let bsr = Bsr::from_csr(&a, 5).unwrap();
+        assert_eq!(bsr.num_blocks(), 1);
+        assert_eq!(bsr.diagonal_block(0).unwrap()[..2], [2.0, -1.0]);
+    }
+}
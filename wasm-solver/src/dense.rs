@@ -0,0 +1,311 @@
+//! Small dense matrix helpers (row-major storage)
+
+use crate::kernels::Csr;
+use crate::precond::Footprint;
+
+/// In-place Cholesky factorization A = L L^T of an SPD matrix
+///
+/// On success the lower triangle of `a` holds L. Returns `false` if a
+/// non-positive pivot is met (matrix not SPD).
+pub(crate) fn cholesky_factor(a: &mut [f64], n: usize) -> bool {
+    for j in 0..n {
+        let mut d = a[j * n + j];
+        for k in 0..j {
+            d -= a[j * n + k] * a[j * n + k];
+        }
+        if d <= 0.0 || !d.is_finite() {
+            return false;
+        }
+        let d = d.sqrt();
+        a[j * n + j] = d;
+        for i in (j + 1)..n {
+            let mut s = a[i * n + j];
+            for k in 0..j {
+                s -= a[i * n + k] * a[j * n + k];
+            }
+            a[i * n + j] = s / d;
+        }
+    }
+    true
+}
+
+/// Solve L L^T x = b in place using a factor from `cholesky_factor`
+pub(crate) fn cholesky_solve(l: &[f64], n: usize, x: &mut [f64]) {
+    lower_solve(l, n, x);
+    lower_transpose_solve(l, n, x);
+}
+
+/// Solve L x = b in place (lower triangle of `l`)
+pub(crate) fn lower_solve(l: &[f64], n: usize, x: &mut [f64]) {
+    for i in 0..n {
+        let mut s = x[i];
+        for k in 0..i {
+            s -= l[i * n + k] * x[k];
+        }
+        x[i] = s / l[i * n + i];
+    }
+}
+
+/// Solve L^T x = b in place (lower triangle of `l`)
+pub(crate) fn lower_transpose_solve(l: &[f64], n: usize, x: &mut [f64]) {
+    for i in (0..n).rev() {
+        let mut s = x[i];
+        for k in (i + 1)..n {
+            s -= l[k * n + i] * x[k];
+        }
+        x[i] = s / l[i * n + i];
+    }
+}
+
+/// In-place LU factorization P A = L U with partial pivoting
+///
+/// On success `a` holds the unit lower L below the diagonal and U on and
+/// above it, and `perm[i]` is the original row now in position i. Returns
+/// `false` if a pivot is exactly zero or not finite (matrix singular).
+pub(crate) fn lu_factor(a: &mut [f64], n: usize, perm: &mut [usize]) -> bool {
+    for (i, p) in perm.iter_mut().enumerate().take(n) {
+        *p = i;
+    }
+    for j in 0..n {
+        let pivot_row = (j..n)
+            .max_by(|&p, &q| a[p * n + j].abs().total_cmp(&a[q * n + j].abs()))
+            .unwrap();
+        let pivot = a[pivot_row * n + j];
+        if pivot == 0.0 || !pivot.is_finite() {
+            return false;
+        }
+        if pivot_row != j {
+            for k in 0..n {
+                a.swap(j * n + k, pivot_row * n + k);
+            }
+            perm.swap(j, pivot_row);
+        }
+        for i in (j + 1)..n {
+            let l = a[i * n + j] / pivot;
+            a[i * n + j] = l;
+            for k in (j + 1)..n {
+                a[i * n + k] -= l * a[j * n + k];
+            }
+        }
+    }
+    true
+}
+
+/// Solve A x = b using a factor from `lu_factor`; `x` holds b on entry
+pub(crate) fn lu_solve(lu: &[f64], n: usize, perm: &[usize], x: &mut [f64]) {
+    let b: Vec<f64> = perm.iter().map(|&p| x[p]).collect();
+    x[..n].copy_from_slice(&b);
+    for i in 0..n {
+        let mut s = x[i];
+        for k in 0..i {
+            s -= lu[i * n + k] * x[k];
+        }
+        x[i] = s;
+    }
+    for i in (0..n).rev() {
+        let mut s = x[i];
+        for k in (i + 1)..n {
+            s -= lu[i * n + k] * x[k];
+        }
+        x[i] = s / lu[i * n + i];
+    }
+}
+
+/// Dense factorization of a small sparse matrix: Cholesky when it is
+/// symmetric positive definite, LU with partial pivoting otherwise
+pub(crate) enum DenseFactor {
+    Cholesky(Vec<f64>),
+    Lu(Vec<f64>, Vec<usize>),
+}
+
+impl DenseFactor {
+    /// Factorize `a` (both triangles stored); `None` if it is singular.
+    /// Costs n^2 storage and n^3 / 3 flops, so only for small n.
+    pub fn from_csr(a: &Csr) -> Option<Self> {
+        let n = a.n();
+        let mut dense = vec![0.0; n * n];
+        for i in 0..n {
+            for q in a.row_ptr[i] as usize..a.row_ptr[i + 1] as usize {
+                dense[i * n + a.col_indices[q] as usize] += a.values[q];
+            }
+        }
+        // `cholesky_factor` reads the lower triangle only
+        let symmetric = (0..n).all(|i| {
+            (0..i).all(|j| {
+                let (u, v) = (dense[i * n + j], dense[j * n + i]);
+                (u - v).abs() <= 1e-12 * u.abs().max(v.abs())
+            })
+        });
+        if symmetric {
+            let mut l = dense.clone();
+            if cholesky_factor(&mut l, n) {
+                return Some(DenseFactor::Cholesky(l));
+            }
+        }
+        let mut perm = vec![0usize; n];
+        lu_factor(&mut dense, n, &mut perm).then_some(DenseFactor::Lu(dense, perm))
+    }
+
+    /// Solve A x = b in place; `x` holds b on entry
+    pub fn solve(&self, x: &mut [f64]) {
+        match self {
+            DenseFactor::Cholesky(l) => cholesky_solve(l, x.len(), x),
+            DenseFactor::Lu(lu, perm) => lu_solve(lu, x.len(), perm, x),
+        }
+    }
+
+    pub fn footprint(&self) -> Footprint {
+        match self {
+            DenseFactor::Cholesky(l) => Footprint::values(l),
+            DenseFactor::Lu(lu, perm) => Footprint::values(lu) + Footprint::indices(perm),
+        }
+    }
+}
+
+/// Eigen-decomposition of a symmetric matrix by cyclic Jacobi rotations
+///
+/// Returns eigenvalues in ascending order and the matching eigenvectors,
+/// stored column-wise in a row-major n x n matrix.
+pub(crate) fn symmetric_eigen(a: &[f64], n: usize) -> (Vec<f64>, Vec<f64>) {
+    let mut m = a.to_vec();
+    let mut v = vec![0.0; n * n];
+    for i in 0..n {
+        v[i * n + i] = 1.0;
+    }
+
+    for _sweep in 0..100 {
+        let off: f64 = (0..n)
+            .flat_map(|i| (0..n).filter(move |&j| j != i).map(move |j| (i, j)))
+            .map(|(i, j)| m[i * n + j] * m[i * n + j])
+            .sum();
+        let scale: f64 = (0..n).map(|i| m[i * n + i] * m[i * n + i]).sum();
+        if off <= 1e-30 * scale.max(1e-300) {
+            break;
+        }
+        for p in 0..n {
+            for q in (p + 1)..n {
+                let apq = m[p * n + q];
+                if apq == 0.0 {
+                    continue;
+                }
+                let theta = (m[q * n + q] - m[p * n + p]) / (2.0 * apq);
+                let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+                let t = if theta == 0.0 { 1.0 } else { t };
+                let c = 1.0 / (t * t + 1.0).sqrt();
+                let s = t * c;
+                for k in 0..n {
+                    let mkp = m[k * n + p];
+                    let mkq = m[k * n + q];
+                    m[k * n + p] = c * mkp - s * mkq;
+                    m[k * n + q] = s * mkp + c * mkq;
+                }
+                for k in 0..n {
+                    let mpk = m[p * n + k];
+                    let mqk = m[q * n + k];
+                    m[p * n + k] = c * mpk - s * mqk;
+                    m[q * n + k] = s * mpk + c * mqk;
+                }
+                for k in 0..n {
+                    let vkp = v[k * n + p];
+                    let vkq = v[k * n + q];
+                    v[k * n + p] = c * vkp - s * vkq;
+                    v[k * n + q] = s * vkp + c * vkq;
+                }
+            }
+        }
+    }
+
+    let mut order: Vec<usize> = (0..n).collect();
+    order.sort_by(|&i, &j| m[i * n + i].total_cmp(&m[j * n + j]));
+    let values = order.iter().map(|&i| m[i * n + i]).collect();
+    let mut vectors = vec![0.0; n * n];
+    for (new, &old) in order.iter().enumerate() {
+        for k in 0..n {
+            vectors[k * n + new] = v[k * n + old];
+        }
+    }
+    (values, vectors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cholesky_roundtrip() {
+        let a = vec![4.0, 2.0, 0.4, 2.0, 5.0, 1.0, 0.4, 1.0, 3.0];
+        let mut l = a.clone();
+        assert!(cholesky_factor(&mut l, 3));
+
+        let mut x = vec![1.0, 2.0, 3.0];
+        cholesky_solve(&l, 3, &mut x);
+        for i in 0..3 {
+            let ax: f64 = (0..3).map(|j| a[i * 3 + j] * x[j]).sum();
+            assert!((ax - (i + 1) as f64).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_symmetric_eigen() {
+        // Eigenvalues of [2 -1 0; -1 2 -1; 0 -1 2] are 2 - sqrt(2), 2, 2 + sqrt(2)
+        let a = vec![2.0, -1.0, 0.0, -1.0, 2.0, -1.0, 0.0, -1.0, 2.0];
+        let (values, vectors) = symmetric_eigen(&a, 3);
+
+        let expected = [2.0 - 2f64.sqrt(), 2.0, 2.0 + 2f64.sqrt()];
+        for (k, lambda) in expected.iter().enumerate() {
+            assert!((values[k] - lambda).abs() < 1e-12);
+            // A v = lambda v
+            for i in 0..3 {
+                let av: f64 = (0..3).map(|j| a[i * 3 + j] * vectors[j * 3 + k]).sum();
+                assert!((av - lambda * vectors[i * 3 + k]).abs() < 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn test_cholesky_rejects_indefinite() {
+        let mut a = vec![1.0, 2.0, 2.0, 1.0];
+        assert!(!cholesky_factor(&mut a, 2));
+    }
+
+    #[test]
+    fn test_lu_with_pivoting() {
+        // Zero leading entry: fails without row exchanges
+        let a = vec![0.0, 2.0, 1.0, 1.0, 1.0, 0.0, 3.0, 0.0, 1.0];
+        let mut lu = a.clone();
+        let mut perm = vec![0; 3];
+        assert!(lu_factor(&mut lu, 3, &mut perm));
+
+        let mut x = vec![1.0, 2.0, 3.0];
+        lu_solve(&lu, 3, &perm, &mut x);
+        for i in 0..3 {
+            let ax: f64 = (0..3).map(|j| a[i * 3 + j] * x[j]).sum();
+            assert!((ax - (i + 1) as f64).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_dense_factor_of_csr() {
+        // SPD takes Cholesky; the indefinite permutation matrix and a
+        // nonsymmetric one with an SPD lower triangle take LU
+        let a = Csr::new(&[2.0, -1.0, -1.0, 2.0], &[0, 1, 0, 1], &[0, 2, 4]);
+        let spd = DenseFactor::from_csr(&a).unwrap();
+        assert!(matches!(spd, DenseFactor::Cholesky(_)));
+        let mut x = vec![1.0, 1.0];
+        spd.solve(&mut x);
+        assert!((x[0] - 1.0).abs() < 1e-14 && (x[1] - 1.0).abs() < 1e-14);
+
+        let p = Csr::new(&[1.0, 1.0], &[1, 0], &[0, 1, 2]);
+        let lu = DenseFactor::from_csr(&p).unwrap();
+        assert!(matches!(lu, DenseFactor::Lu(..)));
+        let mut x = vec![2.0, 3.0];
+        lu.solve(&mut x);
+        assert_eq!(x, vec![3.0, 2.0]);
+        let u = Csr::new(&[2.0, 1.0, -1.0, 2.0], &[0, 1, 0, 1], &[0, 2, 4]);
+        assert!(matches!(
+            DenseFactor::from_csr(&u),
+            Some(DenseFactor::Lu(..))
+        ));
+        assert!(DenseFactor::from_csr(&Csr::new(&[1.0], &[0], &[0, 1, 1])).is_none());
+    }
+}
@@ -0,0 +1,150 @@
+use crate::kernels::Csr;
+
+/// Largest max |i - j| over the stored entries of `a`
+pub(crate) fn half_bandwidth(a: &Csr) -> usize {
+    (0..a.n())
+        .flat_map(|i| {
+            (a.row_ptr[i] as usize..a.row_ptr[i + 1] as usize)
+                .map(move |q| i.abs_diff(a.col_indices[q] as usize))
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+/// Cholesky factor of a banded SPD matrix in band storage
+///
+/// L has the half-bandwidth b of A, so it fits in n (b + 1) entries with
+/// no index arrays: row i holds columns i - b ..= i, entry (i, j) at
+/// i (b + 1) + b + j - i (the slots left of column 0 stay zero). The
+/// factorization costs n b^2 flops and runs over contiguous memory, much
+/// faster than general sparse elimination for 1D and extruded meshes.
+pub(crate) struct BandCholesky {
+    n: usize,
+    bandwidth: usize,
+    l: Vec<f64>,
+}
+
+impl BandCholesky {
+    /// Factorize `a` (both triangles stored) whose entries all lie within
+    /// `bandwidth` of the diagonal. Returns `None` if A is not SPD.
+    pub fn new(a: &Csr, bandwidth: usize) -> Option<Self> {
+        let mut factor = BandCholesky {
+            n: a.n(),
+            bandwidth,
+            l: Vec::new(),
+        };
+        factor.refactor(a).then_some(factor)
+    }
+
+    fn at(&self, i: usize, j: usize) -> usize {
+        i * (self.bandwidth + 1) + self.bandwidth + j - i
+    }
+
+    /// Numeric factorization of new values within the same band. Returns
+    /// `false` and keeps the previous factor if A is not SPD.
+    pub fn refactor(&mut self, a: &Csr) -> bool {
+        let (n, b) = (self.n, self.bandwidth);
+        let mut l = vec![0.0; n * (b + 1)];
+        for i in 0..n {
+            for q in a.row_ptr[i] as usize..a.row_ptr[i + 1] as usize {
+                let j = a.col_indices[q] as usize;
+                if j <= i {
+                    l[self.at(i, j)] += a.values[q];
+                }
+            }
+        }
+        for i in 0..n {
+            let first = i.saturating_sub(b);
+            for j in first..=i {
+                let mut s = l[self.at(i, j)];
+                for k in first..j {
+                    s -= l[self.at(i, k)] * l[self.at(j, k)];
+                }
+                if j < i {
+                    l[self.at(i, j)] = s / l[self.at(j, j)];
+                } else if s <= 0.0 || !s.is_finite() {
+                    return false;
+                } else {
+                    l[self.at(i, i)] = s.sqrt();
+                }
+            }
+        }
+        self.l = l;
+        true
+    }
+
+    pub fn n(&self) -> usize {
+        self.n
+    }
+
+    /// Entries of the band storage
+    pub fn nnz(&self) -> usize {
+        self.l.len()
+    }
+
+    /// Memory of the band storage
+    pub fn bytes(&self) -> usize {
+        std::mem::size_of_val(self.l.as_slice())
+    }
+
+    /// Solve A x = b
+    pub fn solve(&self, b: &[f64]) -> Vec<f64> {
+        let (n, bw) = (self.n, self.bandwidth);
+        let mut x = b.to_vec();
+        for i in 0..n {
+            // Row i of L is contiguous, as is x over its columns
+            let first = i.saturating_sub(bw);
+            let row = &self.l[self.at(i, first)..self.at(i, i)];
+            let s: f64 = row.iter().zip(&x[first..i]).map(|(l, y)| l * y).sum();
+            x[i] = (x[i] - s) / self.l[self.at(i, i)];
+        }
+        for i in (0..n).rev() {
+            let s: f64 = (i + 1..(i + bw + 1).min(n))
+                .map(|k| self.l[self.at(k, i)] * x[k])
+                .sum();
+            x[i] = (x[i] - s) / self.l[self.at(i, i)];
+        }
+        x
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kernels::norm;
+    use crate::test_util::{dense_to_csr, diffusion_2d};
+
+    #[test]
+    fn test_extruded_strip() {
+        // Long strip three cells wide: half-bandwidth 3 in natural order
+        let (nx, ny) = (3, 200);
+        let kappa: Vec<f64> = (0..nx * ny).map(|c| 1.0 + (c % 4) as f64).collect();
+        let (values, col_indices, row_ptr) = diffusion_2d(nx, ny, &kappa);
+        let a = Csr::new(&values, &col_indices, &row_ptr);
+        assert_eq!(half_bandwidth(&a), nx);
+        let factor = BandCholesky::new(&a, nx).unwrap();
+        assert_eq!(factor.nnz(), (nx + 1) * nx * ny);
+
+        let x_true: Vec<f64> = (0..a.n()).map(|i| (i as f64 * 0.21).cos()).collect();
+        let mut b = vec![0.0; a.n()];
+        a.spmv(&x_true, &mut b);
+        let err: Vec<f64> = factor
+            .solve(&b)
+            .iter()
+            .zip(&x_true)
+            .map(|(x, t)| x - t)
+            .collect();
+        assert!(norm(&err) < 1e-12 * norm(&x_true));
+    }
+
+    #[test]
+    fn test_indefinite_keeps_previous_factor() {
+        let (values, col_indices, row_ptr) = dense_to_csr(&[4.0, 1.0, 1.0, 3.0], 2);
+        let mut factor = BandCholesky::new(&Csr::new(&values, &col_indices, &row_ptr), 1).unwrap();
+        let x = factor.solve(&[5.0, 4.0]);
+        let (values, col_indices, row_ptr) = dense_to_csr(&[1.0, 2.0, 2.0, 1.0], 2);
+        assert!(!factor.refactor(&Csr::new(&values, &col_indices, &row_ptr)));
+        assert_eq!(factor.solve(&[5.0, 4.0]), x);
+        assert!((x[0] - 1.0).abs() < 1e-15 && (x[1] - 1.0).abs() < 1e-15);
+    }
+}
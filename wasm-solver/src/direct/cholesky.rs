@@ -0,0 +1,317 @@
+use crate::kernels::{Csr, SparseMatrix};
+
+/// Elimination tree parent of every column; `usize::MAX` marks a root
+///
+/// Reads the strictly lower triangle of row k of `c` for column k of the
+/// upper triangle, so `c` must hold both triangles of a symmetric matrix.
+fn elimination_tree(c: &Csr) -> Vec<usize> {
+    let n = c.n();
+    let mut parent = vec![usize::MAX; n];
+    let mut ancestor = vec![usize::MAX; n];
+    for k in 0..n {
+        for q in c.row_ptr[k] as usize..c.row_ptr[k + 1] as usize {
+            let mut i = c.col_indices[q] as usize;
+            // Climb from i to the root of its subtree, compressing the path
+            while i < k {
+                let next = ancestor[i];
+                ancestor[i] = k;
+                if next == usize::MAX {
+                    parent[i] = k;
+                    break;
+                }
+                i = next;
+            }
+        }
+    }
+    parent
+}
+
+/// Nonzero columns of row k of L, left in `stack[top..]` in topological
+/// order; returns `top`. `mark` must hold values other than k on entry.
+fn row_pattern(
+    c: &Csr,
+    k: usize,
+    parent: &[usize],
+    stack: &mut [usize],
+    mark: &mut [usize],
+) -> usize {
+    let n = stack.len();
+    let mut top = n;
+    mark[k] = k;
+    for q in c.row_ptr[k] as usize..c.row_ptr[k + 1] as usize {
+        let mut i = c.col_indices[q] as usize;
+        if i > k {
+            continue;
+        }
+        // Walk up the tree to an already marked node, then push the path
+        let mut len = 0;
+        while mark[i] != k {
+            stack[len] = i;
+            len += 1;
+            mark[i] = k;
+            i = parent[i];
+        }
+        while len > 0 {
+            len -= 1;
+            top -= 1;
+            stack[top] = stack[len];
+        }
+    }
+    top
+}
+
+/// Symbolic analysis of a sparse Cholesky factorization: the ordering,
+/// the elimination tree and the column structure of L
+///
+/// Depends on the sparsity pattern of A only, so it is computed once and
+/// reused for every matrix with that pattern (`Cholesky::refactor`).
+pub(crate) struct Symbolic {
+    /// Original row placed at each position (fill-reducing ordering)
+    pub perm: Vec<usize>,
+    parent: Vec<usize>,
+    /// Start of every column of L, diagonal included
+    col_ptr: Vec<usize>,
+    /// Pattern of P A P^T
+    col_indices: Vec<u32>,
+    row_ptr: Vec<u32>,
+    /// Entry q of P A P^T is entry `source[q]` of the values of A
+    source: Vec<usize>,
+}
+
+impl Symbolic {
+    /// Analyse the pattern of P A P^T, with row k of it row `perm[k]` of
+    /// `a`
+    pub fn new(a: &Csr, perm: Vec<usize>) -> Self {
+        let permuted = SparseMatrix::permuted(a, &perm);
+        let c = permuted.csr();
+        let n = c.n();
+        let parent = elimination_tree(&c);
+        // Column counts from the row patterns (row k adds one entry to
+        // every column in its pattern)
+        let mut counts = vec![1usize; n];
+        let mut stack = vec![0usize; n];
+        let mut mark = vec![usize::MAX; n];
+        for k in 0..n {
+            let top = row_pattern(&c, k, &parent, &mut stack, &mut mark);
+            for &j in stack[top..].iter() {
+                counts[j] += 1;
+            }
+        }
+        let mut col_ptr = vec![0usize; n + 1];
+        for j in 0..n {
+            col_ptr[j + 1] = col_ptr[j] + counts[j];
+        }
+
+        // Same stable sort as `SparseMatrix::permuted`, on entry indices
+        let mut position = vec![0usize; n];
+        for (k, &i) in perm.iter().enumerate() {
+            position[i] = k;
+        }
+        let mut source = Vec::with_capacity(a.values.len());
+        for &i in perm.iter() {
+            let start = source.len();
+            source.extend(a.row_ptr[i] as usize..a.row_ptr[i + 1] as usize);
+            source[start..].sort_by_key(|&q| position[a.col_indices[q] as usize]);
+        }
+
+        Symbolic {
+            perm,
+            parent,
+            col_ptr,
+            col_indices: permuted.col_indices,
+            row_ptr: permuted.row_ptr,
+            source,
+        }
+    }
+
+    pub fn n(&self) -> usize {
+        self.parent.len()
+    }
+
+    /// Entries of L, diagonal included
+    pub fn nnz(&self) -> usize {
+        self.col_ptr[self.n()]
+    }
+
+    /// Stored entries of A the analysis was made for
+    pub fn input_nnz(&self) -> usize {
+        self.source.len()
+    }
+
+    /// Memory of the ordering, tree and kept pattern
+    pub fn bytes(&self) -> usize {
+        use std::mem::size_of_val;
+        size_of_val(self.perm.as_slice())
+            + size_of_val(self.parent.as_slice())
+            + size_of_val(self.col_ptr.as_slice())
+            + size_of_val(self.col_indices.as_slice())
+            + size_of_val(self.row_ptr.as_slice())
+            + size_of_val(self.source.as_slice())
+    }
+}
+
+/// Sparse Cholesky factor P A P^T = L L^T
+///
+/// Up-looking: row k of L solves a triangular system with the rows above
+/// it, its pattern given by the elimination tree. L is stored by columns
+/// with the diagonal first.
+pub(crate) struct Cholesky {
+    pub symbolic: Symbolic,
+    row_indices: Vec<usize>,
+    values: Vec<f64>,
+}
+
+impl Cholesky {
+    /// Factorize the matrix with the values `values`, stored in the CSR
+    /// pattern `symbolic` was computed for. Returns `None` if a pivot is
+    /// not positive, i.e. A is not SPD.
+    pub fn new(values: &[f64], symbolic: Symbolic) -> Option<Self> {
+        let mut factor = Cholesky {
+            symbolic,
+            row_indices: Vec::new(),
+            values: Vec::new(),
+        };
+        factor.refactor(values).then_some(factor)
+    }
+
+    /// Memory of L and of the symbolic analysis
+    pub fn bytes(&self) -> usize {
+        std::mem::size_of_val(self.row_indices.as_slice())
+            + std::mem::size_of_val(self.values.as_slice())
+            + self.symbolic.bytes()
+    }
+
+    /// Numeric factorization only, for new values in the same pattern.
+    /// Returns `false` and keeps the previous factor if A is not SPD.
+    pub fn refactor(&mut self, values: &[f64]) -> bool {
+        let symbolic = &self.symbolic;
+        let n = symbolic.n();
+        let permuted: Vec<f64> = symbolic.source.iter().map(|&q| values[q]).collect();
+        let c = Csr::new(&permuted, &symbolic.col_indices, &symbolic.row_ptr);
+        let mut row_indices = vec![0usize; symbolic.nnz()];
+        let mut l = vec![0.0; symbolic.nnz()];
+        let mut next = symbolic.col_ptr[..n].to_vec();
+        let mut x = vec![0.0; n];
+        let mut stack = vec![0usize; n];
+        let mut mark = vec![usize::MAX; n];
+
+        for k in 0..n {
+            let top = row_pattern(&c, k, &symbolic.parent, &mut stack, &mut mark);
+            for q in c.row_ptr[k] as usize..c.row_ptr[k + 1] as usize {
+                let i = c.col_indices[q] as usize;
+                if i <= k {
+                    x[i] += c.values[q];
+                }
+            }
+            let mut d = x[k];
+            x[k] = 0.0;
+            for &i in stack[top..].iter() {
+                let l_ki = x[i] / l[symbolic.col_ptr[i]];
+                x[i] = 0.0;
+                for q in symbolic.col_ptr[i] + 1..next[i] {
+                    x[row_indices[q]] -= l[q] * l_ki;
+                }
+                d -= l_ki * l_ki;
+                row_indices[next[i]] = k;
+                l[next[i]] = l_ki;
+                next[i] += 1;
+            }
+            if d <= 0.0 || !d.is_finite() {
+                return false;
+            }
+            row_indices[next[k]] = k;
+            l[next[k]] = d.sqrt();
+            next[k] += 1;
+        }
+
+        self.row_indices = row_indices;
+        self.values = l;
+        true
+    }
+
+    /// Solve A x = b
+    pub fn solve(&self, b: &[f64]) -> Vec<f64> {
+        let col_ptr = &self.symbolic.col_ptr;
+        let mut y: Vec<f64> = self.symbolic.perm.iter().map(|&i| b[i]).collect();
+        // L y = P b
+        for j in 0..y.len() {
+            y[j] /= self.values[col_ptr[j]];
+            for q in col_ptr[j] + 1..col_ptr[j + 1] {
+                y[self.row_indices[q]] -= self.values[q] * y[j];
+            }
+        }
+        // L^T z = y
+        for j in (0..y.len()).rev() {
+            let mut s = y[j];
+            for q in col_ptr[j] + 1..col_ptr[j + 1] {
+                s -= self.values[q] * y[self.row_indices[q]];
+            }
+            y[j] = s / self.values[col_ptr[j]];
+        }
+        let mut x = vec![0.0; y.len()];
+        for (&i, v) in self.symbolic.perm.iter().zip(&y) {
+            x[i] = *v;
+        }
+        x
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::reverse_cuthill_mckee;
+    use crate::kernels::norm;
+    use crate::test_util::{dense_to_csr, diffusion_2d};
+
+    #[test]
+    fn test_elimination_tree_of_arrow_matrix() {
+        // Dense last row and column: every column's parent is n - 1
+        let n = 5;
+        let mut dense = vec![0.0; n * n];
+        for i in 0..n {
+            dense[i * n + i] = 4.0;
+            dense[i * n + n - 1] = 1.0;
+            dense[(n - 1) * n + i] = 1.0;
+        }
+        let (values, col_indices, row_ptr) = dense_to_csr(&dense, n);
+        let a = Csr::new(&values, &col_indices, &row_ptr);
+        let parent = elimination_tree(&a);
+        assert_eq!(parent, vec![4, 4, 4, 4, usize::MAX]);
+        let symbolic = Symbolic::new(&a, (0..n).collect());
+        assert_eq!(symbolic.nnz(), 2 * n - 1);
+    }
+
+    #[test]
+    fn test_solves_to_rounding_error() {
+        let (nx, ny) = (20, 15);
+        let kappa: Vec<f64> = (0..nx * ny).map(|c| 1.0 + (c % 7) as f64).collect();
+        let (values, col_indices, row_ptr) = diffusion_2d(nx, ny, &kappa);
+        let a = Csr::new(&values, &col_indices, &row_ptr);
+        let symbolic = Symbolic::new(&a, (0..a.n()).collect());
+        let natural_nnz = symbolic.nnz();
+        let factor = Cholesky::new(a.values, symbolic).unwrap();
+
+        let x_true: Vec<f64> = (0..a.n()).map(|i| (i as f64 * 0.37).sin()).collect();
+        let mut b = vec![0.0; a.n()];
+        a.spmv(&x_true, &mut b);
+        let err: Vec<f64> = factor
+            .solve(&b)
+            .iter()
+            .zip(&x_true)
+            .map(|(x, t)| x - t)
+            .collect();
+        assert!(norm(&err) < 1e-12 * norm(&x_true));
+
+        // The same solution on another ordering
+        let reordered =
+            Cholesky::new(a.values, Symbolic::new(&a, reverse_cuthill_mckee(&a))).unwrap();
+        let err: Vec<f64> = reordered
+            .solve(&b)
+            .iter()
+            .zip(&x_true)
+            .map(|(x, t)| x - t)
+            .collect();
+        assert!(norm(&err) < 1e-12 * norm(&x_true));
+        assert!(reordered.symbolic.nnz() <= natural_nnz);
+    }
+}
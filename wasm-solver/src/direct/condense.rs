@@ -0,0 +1,218 @@
+use wasm_bindgen::prelude::*;
+
+use super::{Cholesky, Symbolic};
+use crate::graph::approximate_minimum_degree;
+use crate::kernels::{Csr, SparseMatrix};
+
+/// Static condensation of a set of interior DOFs
+///
+/// With A split into interior (I) and retained (R) DOFs, the Schur
+/// complement S = A_RR - A_RI A_II^{-1} A_IR is the stiffness of the
+/// retained DOFs alone, and the interior ones follow from them by
+/// x_I = A_II^{-1} (b_I - A_IR x_R). A_II is kept as a sparse Cholesky
+/// factor for the right-hand side reduction and the recovery.
+#[wasm_bindgen(js_name = Condensation)]
+pub struct CondensationHandle {
+    interior: Vec<usize>,
+    retained: Vec<usize>,
+    interior_factor: Cholesky,
+    /// A_IR, interior rows by retained columns
+    coupling: SparseMatrix,
+    schur: SparseMatrix,
+}
+
+/// Condense the DOFs `interior` out of the SPD matrix A
+///
+/// Storage as for `solve_pcg`. S is formed column by column with one
+/// solve with A_II per retained DOF coupled to the interior, so keep the
+/// retained set small (interface DOFs of a substructure, a handful of
+/// preview DOFs). Returns `undefined` if an index is out of range or A_II
+/// is not positive definite.
+#[wasm_bindgen]
+pub fn condense(
+    values: &[f64],
+    col_indices: &[u32],
+    row_ptr: &[u32],
+    interior: &[u32],
+) -> Option<CondensationHandle> {
+    let a = Csr::new(values, col_indices, row_ptr);
+    let n = a.n();
+    let mut is_interior = vec![false; n];
+    for &i in interior.iter() {
+        *is_interior.get_mut(i as usize)? = true;
+    }
+    let (interior, retained): (Vec<usize>, Vec<usize>) = (0..n).partition(|&i| is_interior[i]);
+
+    let a_ii = SparseMatrix::submatrix(&a, &interior);
+    let a_ii = a_ii.csr();
+    let symbolic = Symbolic::new(&a_ii, approximate_minimum_degree(&a_ii));
+    let interior_factor = Cholesky::new(a_ii.values, symbolic)?;
+    let coupling = SparseMatrix::block(&a, &interior, &retained);
+    let coupling_t = coupling.transpose();
+    let a_rr = SparseMatrix::submatrix(&a, &retained);
+
+    // Row j of S = row j of A_RR - A_RI A_II^{-1} (column j of A_IR), as S
+    // is symmetric
+    let nr = retained.len();
+    let mut schur = SparseMatrix {
+        row_ptr: vec![0],
+        col_indices: Vec::new(),
+        values: Vec::new(),
+        ncols: nr,
+    };
+    let mut row = vec![0.0; nr];
+    let mut column = vec![0.0; interior.len()];
+    for j in 0..nr {
+        row.iter_mut().for_each(|v| *v = 0.0);
+        for q in a_rr.row_ptr[j] as usize..a_rr.row_ptr[j + 1] as usize {
+            row[a_rr.col_indices[q] as usize] += a_rr.values[q];
+        }
+        let (start, end) = (
+            coupling_t.row_ptr[j] as usize,
+            coupling_t.row_ptr[j + 1] as usize,
+        );
+        if start < end {
+            column.iter_mut().for_each(|v| *v = 0.0);
+            for q in start..end {
+                column[coupling_t.col_indices[q] as usize] = coupling_t.values[q];
+            }
+            let y = interior_factor.solve(&column);
+            let mut correction = vec![0.0; nr];
+            coupling.csr().spmv_transpose(&y, &mut correction);
+            row.iter_mut().zip(&correction).for_each(|(s, c)| *s -= c);
+        }
+        for (k, &v) in row.iter().enumerate() {
+            if v != 0.0 {
+                schur.col_indices.push(k as u32);
+                schur.values.push(v);
+            }
+        }
+        schur.row_ptr.push(schur.col_indices.len() as u32);
+    }
+
+    Some(CondensationHandle {
+        interior,
+        retained,
+        interior_factor,
+        coupling,
+        schur,
+    })
+}
+
+#[wasm_bindgen(js_class = Condensation)]
+impl CondensationHandle {
+    /// Retained DOFs in ascending order; row k of S is DOF `retained[k]`
+    #[wasm_bindgen(getter)]
+    pub fn retained(&self) -> Vec<u32> {
+        self.retained.iter().map(|&i| i as u32).collect()
+    }
+
+    /// Values of S in CSR storage (both triangles), ready for the solvers
+    #[wasm_bindgen(getter)]
+    pub fn schur_values(&self) -> Vec<f64> {
+        self.schur.values.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn schur_col_indices(&self) -> Vec<u32> {
+        self.schur.col_indices.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn schur_row_ptr(&self) -> Vec<u32> {
+        self.schur.row_ptr.clone()
+    }
+
+    /// Condensed right-hand side g = b_R - A_RI A_II^{-1} b_I, so that
+    /// S x_R = g; `undefined` if `b` does not have one entry per DOF
+    pub fn condense_rhs(&self, b: &[f64]) -> Option<Vec<f64>> {
+        if b.len() != self.interior.len() + self.retained.len() {
+            return None;
+        }
+        let b_i: Vec<f64> = self.interior.iter().map(|&i| b[i]).collect();
+        let y = self.interior_factor.solve(&b_i);
+        let mut g = vec![0.0; self.retained.len()];
+        self.coupling.csr().spmv_transpose(&y, &mut g);
+        Some(
+            self.retained
+                .iter()
+                .zip(&g)
+                .map(|(&i, gi)| b[i] - gi)
+                .collect(),
+        )
+    }
+
+    /// Full solution from the retained part: x_R = `x_retained` and
+    /// x_I = A_II^{-1} (b_I - A_IR x_R); `undefined` on a length mismatch
+    pub fn recover(&self, x_retained: &[f64], b: &[f64]) -> Option<Vec<f64>> {
+        let n = self.interior.len() + self.retained.len();
+        if x_retained.len() != self.retained.len() || b.len() != n {
+            return None;
+        }
+        let mut coupled = vec![0.0; self.interior.len()];
+        self.coupling.csr().spmv(x_retained, &mut coupled);
+        let rhs: Vec<f64> = self
+            .interior
+            .iter()
+            .zip(&coupled)
+            .map(|(&i, c)| b[i] - c)
+            .collect();
+        let mut x = vec![0.0; n];
+        for (&i, v) in self.interior.iter().zip(self.interior_factor.solve(&rhs)) {
+            x[i] = v;
+        }
+        for (&i, &v) in self.retained.iter().zip(x_retained) {
+            x[i] = v;
+        }
+        Some(x)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::direct::factorize;
+    use crate::test_util::{dense_to_csr, diffusion_2d};
+
+    #[test]
+    fn test_condensed_chain() {
+        // Eliminating the middle of the 1D Laplacian [2 -1 0; -1 2 -1; 0 -1 2]
+        let dense = [2.0, -1.0, 0.0, -1.0, 2.0, -1.0, 0.0, -1.0, 2.0];
+        let (values, col_indices, row_ptr) = dense_to_csr(&dense, 3);
+        let condensed = condense(&values, &col_indices, &row_ptr, &[1]).unwrap();
+        assert_eq!(condensed.retained(), vec![0, 2]);
+        for (s, expected) in condensed.schur_values().iter().zip([1.5, -0.5, -0.5, 1.5]) {
+            assert!((s - expected).abs() < 1e-15);
+        }
+        assert_eq!(condensed.schur_row_ptr(), vec![0, 2, 4]);
+        assert!(condense(&values, &col_indices, &row_ptr, &[3]).is_none());
+    }
+
+    #[test]
+    fn test_solve_condensed_then_recover() {
+        let (nx, ny) = (12, 10);
+        let kappa: Vec<f64> = (0..nx * ny).map(|c| 1.0 + (c % 3) as f64).collect();
+        let (values, col_indices, row_ptr) = diffusion_2d(nx, ny, &kappa);
+        let b: Vec<f64> = (0..nx * ny).map(|i| (i as f64 * 0.3).sin()).collect();
+        // Keep the left and right columns of cells
+        let interior: Vec<u32> = (0..nx * ny)
+            .filter(|&c| c % nx != 0 && c % nx != nx - 1)
+            .map(|c| c as u32)
+            .collect();
+        let condensed = condense(&values, &col_indices, &row_ptr, &interior).unwrap();
+        assert_eq!(condensed.retained().len(), 2 * ny);
+
+        let s = factorize(
+            &condensed.schur_values(),
+            &condensed.schur_col_indices(),
+            &condensed.schur_row_ptr(),
+        )
+        .unwrap();
+        let x_r = s.solve(&condensed.condense_rhs(&b).unwrap()).unwrap();
+        let x = condensed.recover(&x_r, &b).unwrap();
+        let full = factorize(&values, &col_indices, &row_ptr).unwrap();
+        for (xi, yi) in x.iter().zip(full.solve(&b).unwrap()) {
+            assert!((xi - yi).abs() < 1e-12);
+        }
+    }
+}
@@ -0,0 +1,433 @@
+use wasm_bindgen::prelude::*;
+
+use super::{column_order, half_bandwidth, BandCholesky, Cholesky, Ldlt, SparseLu, Symbolic};
+use crate::graph::approximate_minimum_degree;
+use crate::kernels::Csr;
+use crate::precond::MemoryReport;
+
+/// Largest half-bandwidth factorized in band storage by `factorize`
+const MAX_BANDWIDTH: usize = 16;
+
+enum Factor {
+    Cholesky(Cholesky),
+    /// The pattern is kept to scatter new values into the band
+    Band {
+        factor: BandCholesky,
+        col_indices: Vec<u32>,
+        row_ptr: Vec<u32>,
+    },
+    /// Pivots depend on the values, so refactorizing repeats the pivoted
+    /// elimination in the kept ordering and pattern
+    Ldlt {
+        factor: Ldlt,
+        order: Vec<usize>,
+        col_indices: Vec<u32>,
+        row_ptr: Vec<u32>,
+    },
+    /// Row pivots depend on the values as for LDL^T; the column ordering
+    /// is kept in the factor
+    Lu {
+        factor: SparseLu,
+        col_indices: Vec<u32>,
+        row_ptr: Vec<u32>,
+    },
+}
+
+/// Sparse direct factorization, kept for repeated solves
+#[wasm_bindgen(js_name = Factor)]
+pub struct FactorHandle {
+    factor: Factor,
+}
+
+/// Factorize the SPD matrix A = L L^T for `Factor.solve`
+///
+/// Both triangles of A must be stored, as for the iterative solvers. A
+/// matrix whose entries all lie within `MAX_BANDWIDTH` (16) of the
+/// diagonal, as from 1D and extruded meshes, is factorized in band
+/// storage. Otherwise the rows are reordered by approximate minimum
+/// degree to limit fill, then the elimination tree gives the structure of
+/// L before the numeric phase. Returns `undefined` if A is not positive
+/// definite.
+#[wasm_bindgen]
+pub fn factorize(values: &[f64], col_indices: &[u32], row_ptr: &[u32]) -> Option<FactorHandle> {
+    let a = Csr::new(values, col_indices, row_ptr);
+    let bandwidth = half_bandwidth(&a);
+    if bandwidth <= MAX_BANDWIDTH {
+        return BandCholesky::new(&a, bandwidth).map(|factor| FactorHandle {
+            factor: Factor::Band {
+                factor,
+                col_indices: col_indices.to_vec(),
+                row_ptr: row_ptr.to_vec(),
+            },
+        });
+    }
+    let symbolic = Symbolic::new(&a, approximate_minimum_degree(&a));
+    Cholesky::new(values, symbolic).map(|factor| FactorHandle {
+        factor: Factor::Cholesky(factor),
+    })
+}
+
+/// Fill-reducing ordering of a symmetric sparsity pattern, by approximate
+/// minimum degree: entry k is the row eliminated k-th
+///
+/// The ordering depends on the pattern only; compute it once and pass it
+/// to `factorize_with_ordering` for every matrix with that pattern.
+#[wasm_bindgen]
+pub fn fill_reducing_ordering(col_indices: &[u32], row_ptr: &[u32]) -> Vec<u32> {
+    let pattern = Csr::<f64>::new(&[], col_indices, row_ptr);
+    approximate_minimum_degree(&pattern)
+        .into_iter()
+        .map(|i| i as u32)
+        .collect()
+}
+
+/// `factorize` with a given elimination order (a `Factor.permutation` or
+/// `fill_reducing_ordering`) instead of computing one, and always in
+/// sparse storage. Returns `undefined` if `permutation` is not a
+/// permutation of the rows or A is not positive definite.
+#[wasm_bindgen]
+pub fn factorize_with_ordering(
+    values: &[f64],
+    col_indices: &[u32],
+    row_ptr: &[u32],
+    permutation: &[u32],
+) -> Option<FactorHandle> {
+    let a = Csr::new(values, col_indices, row_ptr);
+    let n = a.n();
+    let mut seen = vec![false; n];
+    if permutation.len() != n {
+        return None;
+    }
+    for &i in permutation.iter() {
+        if std::mem::replace(seen.get_mut(i as usize)?, true) {
+            return None;
+        }
+    }
+    let perm = permutation.iter().map(|&i| i as usize).collect();
+    Cholesky::new(values, Symbolic::new(&a, perm)).map(|factor| FactorHandle {
+        factor: Factor::Cholesky(factor),
+    })
+}
+
+/// Factorize the symmetric, possibly indefinite matrix A = L D L^T with
+/// 1 x 1 and 2 x 2 pivots (Bunch–Kaufman), for systems with Lagrange
+/// multiplier constraints
+///
+/// Storage and ordering as for `factorize` (no band storage); pivoting
+/// may swap a row with a later one. Returns `undefined` if A is singular.
+#[wasm_bindgen]
+pub fn factorize_symmetric(
+    values: &[f64],
+    col_indices: &[u32],
+    row_ptr: &[u32],
+) -> Option<FactorHandle> {
+    let a = Csr::new(values, col_indices, row_ptr);
+    let order = approximate_minimum_degree(&a);
+    Ldlt::new(&a, &order).map(|factor| FactorHandle {
+        factor: Factor::Ldlt {
+            factor,
+            order,
+            col_indices: col_indices.to_vec(),
+            row_ptr: row_ptr.to_vec(),
+        },
+    })
+}
+
+/// Factorize a general square matrix P A Q = L U with partial pivoting
+///
+/// For nonsymmetric systems that are not definite either, next to the
+/// iterative GMRES and BiCGSTAB. The columns are pre-ordered by
+/// approximate minimum degree on the pattern of A + A^T to limit fill,
+/// the rows are exchanged as the elimination proceeds. Returns
+/// `undefined` if A is singular.
+#[wasm_bindgen]
+pub fn factorize_lu(values: &[f64], col_indices: &[u32], row_ptr: &[u32]) -> Option<FactorHandle> {
+    let a = Csr::new(values, col_indices, row_ptr);
+    SparseLu::new(&a, &column_order(&a)).map(|factor| FactorHandle {
+        factor: Factor::Lu {
+            factor,
+            col_indices: col_indices.to_vec(),
+            row_ptr: row_ptr.to_vec(),
+        },
+    })
+}
+
+#[wasm_bindgen(js_class = Factor)]
+impl FactorHandle {
+    /// Solve A x = b by forward and back substitution; `undefined` if `b`
+    /// does not have one entry per row
+    pub fn solve(&self, b: &[f64]) -> Option<Vec<f64>> {
+        (b.len() == self.size()).then(|| match &self.factor {
+            Factor::Cholesky(f) => f.solve(b),
+            Factor::Band { factor, .. } => factor.solve(b),
+            Factor::Ldlt { factor, .. } => factor.solve(b),
+            Factor::Lu { factor, .. } => factor.solve(b),
+        })
+    }
+
+    /// Solve A X = B for `num_rhs` right-hand sides in one call
+    ///
+    /// `rhs_flat` holds the columns of B one after another, each of length
+    /// n (load cases, adjoint right-hand sides); the solutions come back in
+    /// the same layout. Only the substitutions run per column. Returns
+    /// `undefined` if `rhs_flat` does not hold `num_rhs` columns.
+    pub fn solve_many(&self, rhs_flat: &[f64], num_rhs: u32) -> Option<Vec<f64>> {
+        let n = self.size();
+        if rhs_flat.len() != n * num_rhs as usize {
+            return None;
+        }
+        let mut x = Vec::with_capacity(rhs_flat.len());
+        for b in rhs_flat.chunks_exact(n.max(1)) {
+            x.extend(self.solve(b)?);
+        }
+        Some(x)
+    }
+
+    /// Factorize new values of a matrix with the same sparsity pattern
+    ///
+    /// `values` are stored in the pattern passed to `factorize`. The
+    /// ordering and, for Cholesky, the elimination tree and structure of L
+    /// are kept, so only the numeric phase runs: what a SIMP loop needs
+    /// when the densities change every iteration. Returns `false` and
+    /// keeps the previous factor if `values` has the wrong length or the
+    /// new matrix cannot be factorized.
+    pub fn refactorize(&mut self, values: &[f64]) -> bool {
+        match &mut self.factor {
+            Factor::Cholesky(f) => values.len() == f.symbolic.input_nnz() && f.refactor(values),
+            Factor::Band {
+                factor,
+                col_indices,
+                row_ptr,
+            } => {
+                values.len() == col_indices.len()
+                    && factor.refactor(&Csr::new(values, col_indices, row_ptr))
+            }
+            Factor::Ldlt {
+                factor,
+                order,
+                col_indices,
+                row_ptr,
+            } => {
+                if values.len() != col_indices.len() {
+                    return false;
+                }
+                match Ldlt::new(&Csr::new(values, col_indices, row_ptr), order) {
+                    Some(f) => {
+                        *factor = f;
+                        true
+                    }
+                    None => false,
+                }
+            }
+            Factor::Lu {
+                factor,
+                col_indices,
+                row_ptr,
+            } => {
+                if values.len() != col_indices.len() {
+                    return false;
+                }
+                let a = Csr::new(values, col_indices, row_ptr);
+                match SparseLu::new(&a, factor.order()) {
+                    Some(f) => {
+                        *factor = f;
+                        true
+                    }
+                    None => false,
+                }
+            }
+        }
+    }
+
+    /// Fill-reducing elimination order of the factor, entry k the row (the
+    /// column for LU) eliminated k-th; the natural order for band storage
+    #[wasm_bindgen(getter)]
+    pub fn permutation(&self) -> Vec<u32> {
+        let order: &[usize] = match &self.factor {
+            Factor::Cholesky(f) => &f.symbolic.perm,
+            Factor::Band { factor, .. } => return (0..factor.n() as u32).collect(),
+            Factor::Ldlt { order, .. } => order,
+            Factor::Lu { factor, .. } => factor.order(),
+        };
+        order.iter().map(|&i| i as u32).collect()
+    }
+
+    /// Number of rows
+    #[wasm_bindgen(getter)]
+    pub fn size(&self) -> usize {
+        match &self.factor {
+            Factor::Cholesky(f) => f.symbolic.n(),
+            Factor::Band { factor, .. } => factor.n(),
+            Factor::Ldlt { factor, .. } => factor.n(),
+            Factor::Lu { factor, .. } => factor.n(),
+        }
+    }
+
+    /// Entries of the factors (L, D for LDL^T, U for LU; the whole band
+    /// for band storage)
+    #[wasm_bindgen(getter)]
+    pub fn nnz(&self) -> usize {
+        match &self.factor {
+            Factor::Cholesky(f) => f.symbolic.nnz(),
+            Factor::Band { factor, .. } => factor.nnz(),
+            Factor::Ldlt { factor, .. } => factor.nnz(),
+            Factor::Lu { factor, .. } => factor.nnz(),
+        }
+    }
+
+    /// Memory of the factors and the pattern kept for `refactorize`; work
+    /// is the solution vector of one `solve`
+    pub fn memory_report(&self) -> MemoryReport {
+        let pattern = |col_indices: &[u32], row_ptr: &[u32]| {
+            std::mem::size_of_val(col_indices) + std::mem::size_of_val(row_ptr)
+        };
+        let (factors, indices) = match &self.factor {
+            Factor::Cholesky(f) => (f.bytes(), 0),
+            Factor::Band {
+                factor,
+                col_indices,
+                row_ptr,
+            } => (factor.bytes(), pattern(col_indices, row_ptr)),
+            Factor::Ldlt {
+                factor,
+                order,
+                col_indices,
+                row_ptr,
+            } => (
+                factor.bytes(),
+                pattern(col_indices, row_ptr) + std::mem::size_of_val(order.as_slice()),
+            ),
+            Factor::Lu {
+                factor,
+                col_indices,
+                row_ptr,
+            } => (factor.bytes(), pattern(col_indices, row_ptr)),
+        };
+        MemoryReport::default()
+            .with_indices(indices)
+            .with_factors(factors)
+            .with_work(self.size() * std::mem::size_of::<f64>())
+    }
+
+    /// Number of negative eigenvalues of A, from the signs of D; 0 for
+    /// Cholesky and LU factors. A saddle-point matrix with an SPD block and m
+    /// independent constraints has exactly m.
+    #[wasm_bindgen(getter)]
+    pub fn negative_eigenvalues(&self) -> usize {
+        match &self.factor {
+            Factor::Cholesky(_) | Factor::Band { .. } | Factor::Lu { .. } => 0,
+            Factor::Ldlt { factor, .. } => factor.negative_eigenvalues(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kernels::norm;
+    use crate::test_util::{convection_diffusion_1d, dense_to_csr, diffusion_2d};
+
+    #[test]
+    fn test_factor_once_solve_twice() {
+        let (nx, ny) = (40, 30);
+        let kappa: Vec<f64> = (0..nx * ny)
+            .map(|c| if (c / nx) % 10 < 5 { 1.0 } else { 1e-6 })
+            .collect();
+        let (values, col_indices, row_ptr) = diffusion_2d(nx, ny, &kappa);
+        let a = Csr::new(&values, &col_indices, &row_ptr);
+        let factor = factorize(&values, &col_indices, &row_ptr).unwrap();
+        assert_eq!(factor.size(), nx * ny);
+        assert!(factor.solve(&[1.0; 3]).is_none());
+
+        for load in [1.0, -2.5] {
+            let b: Vec<f64> = (0..nx * ny).map(|i| load * (i % 3) as f64).collect();
+            let x = factor.solve(&b).unwrap();
+            let mut r = vec![0.0; nx * ny];
+            a.residual(&b, &x, &mut r);
+            assert!(norm(&r) < 1e-10 * norm(&b));
+        }
+    }
+
+    #[test]
+    fn test_indefinite_matrix_needs_ldlt() {
+        let (values, col_indices, row_ptr) = dense_to_csr(&[1.0, 2.0, 2.0, 1.0], 2);
+        assert!(factorize(&values, &col_indices, &row_ptr).is_none());
+        let factor = factorize_symmetric(&values, &col_indices, &row_ptr).unwrap();
+        assert_eq!(factor.negative_eigenvalues(), 1);
+        let x = factor.solve(&[3.0, 3.0]).unwrap();
+        assert!((x[0] - 1.0).abs() < 1e-14 && (x[1] - 1.0).abs() < 1e-14);
+    }
+
+    #[test]
+    fn test_refactorize_matches_fresh_factor() {
+        let (nx, ny) = (24, 16);
+        let b: Vec<f64> = (0..nx * ny).map(|i| (i as f64 * 0.7).cos()).collect();
+        let (values, col_indices, row_ptr) = diffusion_2d(nx, ny, &vec![1.0; nx * ny]);
+        let mut factor = factorize(&values, &col_indices, &row_ptr).unwrap();
+
+        let kappa: Vec<f64> = (0..nx * ny).map(|c| 1e-3 + (c % 5) as f64).collect();
+        let (updated, _, _) = diffusion_2d(nx, ny, &kappa);
+        assert!(factor.refactorize(&updated));
+        let fresh = factorize(&updated, &col_indices, &row_ptr).unwrap();
+        let (x, y) = (factor.solve(&b).unwrap(), fresh.solve(&b).unwrap());
+        for (xi, yi) in x.iter().zip(&y) {
+            assert!((xi - yi).abs() < 1e-12 * yi.abs().max(1.0));
+        }
+
+        // Failures keep the previous factor
+        assert!(!factor.refactorize(&updated[1..]));
+        let negated: Vec<f64> = updated.iter().map(|v| -v).collect();
+        assert!(!factor.refactorize(&negated));
+        assert_eq!(factor.solve(&b).unwrap(), x);
+        let mut symmetric = factorize_symmetric(&values, &col_indices, &row_ptr).unwrap();
+        assert!(symmetric.refactorize(&negated));
+        assert_eq!(symmetric.negative_eigenvalues(), nx * ny);
+    }
+
+    #[test]
+    fn test_solve_many_matches_single_solves() {
+        let (values, col_indices, row_ptr) = diffusion_2d(10, 9, &[1.0; 90]);
+        let factor = factorize(&values, &col_indices, &row_ptr).unwrap();
+        let rhs: Vec<f64> = (0..3 * 90).map(|i| (i as f64 * 0.11).sin()).collect();
+        let x = factor.solve_many(&rhs, 3).unwrap();
+        for (b, xc) in rhs.chunks_exact(90).zip(x.chunks_exact(90)) {
+            assert_eq!(factor.solve(b).unwrap(), xc);
+        }
+        assert!(factor.solve_many(&rhs, 2).is_none());
+        assert_eq!(factor.solve_many(&[], 0), Some(vec![]));
+    }
+
+    #[test]
+    fn test_lu_for_nonsymmetric_matrix() {
+        let (values, col_indices, row_ptr) = convection_diffusion_1d(50, 1.5);
+        let a = Csr::new(&values, &col_indices, &row_ptr);
+        let mut factor = factorize_lu(&values, &col_indices, &row_ptr).unwrap();
+        let b: Vec<f64> = (0..50).map(|i| i as f64).collect();
+        let mut r = vec![0.0; 50];
+        a.residual(&b, &factor.solve(&b).unwrap(), &mut r);
+        assert!(norm(&r) < 1e-12 * norm(&b));
+
+        let scaled: Vec<f64> = values.iter().map(|v| 2.0 * v).collect();
+        assert!(factor.refactorize(&scaled));
+        let x = factor.solve(&b).unwrap();
+        Csr::new(&scaled, &col_indices, &row_ptr).residual(&b, &x, &mut r);
+        assert!(norm(&r) < 1e-12 * norm(&b));
+    }
+
+    #[test]
+    fn test_reuse_permutation() {
+        let (nx, ny) = (30, 20);
+        let (values, col_indices, row_ptr) = diffusion_2d(nx, ny, &vec![1.0; nx * ny]);
+        let factor = factorize(&values, &col_indices, &row_ptr).unwrap();
+        let permutation = factor.permutation();
+        assert_eq!(permutation, fill_reducing_ordering(&col_indices, &row_ptr));
+
+        let kappa: Vec<f64> = (0..nx * ny).map(|c| 1.0 + (c % 3) as f64).collect();
+        let (updated, _, _) = diffusion_2d(nx, ny, &kappa);
+        let reused = factorize_with_ordering(&updated, &col_indices, &row_ptr, &permutation);
+        assert_eq!(reused.unwrap().nnz(), factor.nnz());
+        let mut repeated = permutation.clone();
+        repeated[1] = repeated[0];
+        assert!(factorize_with_ordering(&values, &col_indices, &row_ptr, &repeated).is_none());
+    }
+}
@@ -0,0 +1,305 @@
+use std::collections::BTreeMap;
+
+use crate::kernels::Csr;
+
+/// Bunch–Kaufman growth constant (1 + sqrt(17)) / 8
+const ALPHA: f64 = 0.640_388_203_202_207_6;
+
+/// One elimination step: a 1 x 1 or 2 x 2 pivot block and its columns of L
+struct Step {
+    /// Pivot rows (original numbering); the second is `usize::MAX` for a
+    /// 1 x 1 pivot
+    pivots: [usize; 2],
+    /// Inverse of the pivot block D, row-major
+    d_inv: [f64; 4],
+    /// Rows i eliminated later with L(i, pivots)
+    l: Vec<(usize, [f64; 2])>,
+}
+
+/// Sparse symmetric indefinite factorization P A P^T = L D L^T
+///
+/// Columns are eliminated in a fill-reducing order, with the active
+/// submatrix held row by row so that fill can appear anywhere. At every
+/// step Bunch–Kaufman pivoting picks a 1 x 1 pivot on the next column, a
+/// 1 x 1 pivot on the row r holding its largest off-diagonal entry, or
+/// the 2 x 2 block on both; this bounds the growth of L without needing a
+/// positive diagonal, so saddle-point systems with zero blocks (Lagrange
+/// multipliers) factorize.
+pub(crate) struct Ldlt {
+    n: usize,
+    steps: Vec<Step>,
+    negative: usize,
+}
+
+impl Ldlt {
+    /// Factorize `a` (both triangles stored), eliminating in the order of
+    /// `order` where pivoting allows. Returns `None` if A is singular.
+    pub fn new(a: &Csr, order: &[usize]) -> Option<Self> {
+        let n = a.n();
+        let mut rows: Vec<BTreeMap<usize, f64>> = vec![BTreeMap::new(); n];
+        for (i, row) in rows.iter_mut().enumerate() {
+            for q in a.row_ptr[i] as usize..a.row_ptr[i + 1] as usize {
+                *row.entry(a.col_indices[q] as usize).or_insert(0.0) += a.values[q];
+            }
+        }
+        let scale = a.values.iter().fold(0.0f64, |acc, v| acc.max(v.abs()));
+        let tiny = f64::EPSILON * scale;
+        let mut eliminated = vec![false; n];
+        let mut steps = Vec::with_capacity(n);
+        let mut negative = 0;
+
+        let mut k = 0;
+        while k < n {
+            let c = order[k];
+            if eliminated[c] {
+                k += 1;
+                continue;
+            }
+            let diag =
+                |rows: &[BTreeMap<usize, f64>], i: usize| rows[i].get(&i).copied().unwrap_or(0.0);
+            // Largest off-diagonal of a row, with its column
+            let largest = |rows: &[BTreeMap<usize, f64>], i: usize| {
+                rows[i]
+                    .iter()
+                    .filter(|&(&j, _)| j != i)
+                    .map(|(&j, v)| (v.abs(), j))
+                    .fold(
+                        (0.0, usize::MAX),
+                        |best, x| if x.0 > best.0 { x } else { best },
+                    )
+            };
+            let a_cc = diag(&rows, c);
+            let (lambda, r) = largest(&rows, c);
+
+            let pivots = if a_cc.abs() >= ALPHA * lambda {
+                [c, usize::MAX]
+            } else {
+                let (sigma, _) = largest(&rows, r);
+                if a_cc.abs() * sigma >= ALPHA * lambda * lambda {
+                    [c, usize::MAX]
+                } else if diag(&rows, r).abs() >= ALPHA * sigma {
+                    [r, usize::MAX]
+                } else {
+                    [c, r]
+                }
+            };
+
+            let step = if pivots[1] == usize::MAX {
+                let p = pivots[0];
+                let d = diag(&rows, p);
+                if d.abs() <= tiny || !d.is_finite() {
+                    return None;
+                }
+                negative += usize::from(d < 0.0);
+                let column: Vec<(usize, f64)> = rows[p]
+                    .iter()
+                    .filter(|&(&i, _)| i != p)
+                    .map(|(&i, &v)| (i, v))
+                    .collect();
+                for &(i, a_ip) in column.iter() {
+                    rows[i].remove(&p);
+                    for &(j, a_jp) in column.iter() {
+                        *rows[i].entry(j).or_insert(0.0) -= a_ip * a_jp / d;
+                    }
+                }
+                Step {
+                    pivots,
+                    d_inv: [1.0 / d, 0.0, 0.0, 0.0],
+                    l: column.iter().map(|&(i, v)| (i, [v / d, 0.0])).collect(),
+                }
+            } else {
+                let [p, q] = pivots;
+                let (d11, d21, d22) = (diag(&rows, p), rows[p][&q], diag(&rows, q));
+                let det = d11 * d22 - d21 * d21;
+                if det.abs() <= f64::EPSILON * (d11 * d22).abs().max(d21 * d21) || !det.is_finite()
+                {
+                    return None;
+                }
+                // A 2 x 2 pivot of a Bunch–Kaufman step has det < 0: one
+                // negative and one positive eigenvalue
+                negative += if det < 0.0 {
+                    1
+                } else {
+                    2 * usize::from(d11 < 0.0)
+                };
+                let d_inv = [d22 / det, -d21 / det, -d21 / det, d11 / det];
+                let mut column: BTreeMap<usize, [f64; 2]> = BTreeMap::new();
+                for (&i, &v) in rows[p].iter() {
+                    if i != p && i != q {
+                        column.entry(i).or_insert([0.0; 2])[0] = v;
+                    }
+                }
+                for (&i, &v) in rows[q].iter() {
+                    if i != p && i != q {
+                        column.entry(i).or_insert([0.0; 2])[1] = v;
+                    }
+                }
+                let column: Vec<(usize, [f64; 2])> = column.into_iter().collect();
+                let l: Vec<(usize, [f64; 2])> = column
+                    .iter()
+                    .map(|&(i, [a_ip, a_iq])| {
+                        (
+                            i,
+                            [
+                                a_ip * d_inv[0] + a_iq * d_inv[2],
+                                a_ip * d_inv[1] + a_iq * d_inv[3],
+                            ],
+                        )
+                    })
+                    .collect();
+                for &(i, w) in l.iter() {
+                    rows[i].remove(&p);
+                    rows[i].remove(&q);
+                    for &(j, [a_jp, a_jq]) in column.iter() {
+                        *rows[i].entry(j).or_insert(0.0) -= w[0] * a_jp + w[1] * a_jq;
+                    }
+                }
+                Step { pivots, d_inv, l }
+            };
+
+            for &p in step.pivots.iter().filter(|&&p| p != usize::MAX) {
+                eliminated[p] = true;
+                rows[p].clear();
+            }
+            if step.pivots[0] == c {
+                k += 1;
+            }
+            steps.push(step);
+        }
+
+        Some(Ldlt { n, steps, negative })
+    }
+
+    pub fn n(&self) -> usize {
+        self.n
+    }
+
+    /// Entries of L and D
+    pub fn nnz(&self) -> usize {
+        self.steps
+            .iter()
+            .map(|s| {
+                if s.pivots[1] == usize::MAX {
+                    1 + s.l.len()
+                } else {
+                    3 + 2 * s.l.len()
+                }
+            })
+            .sum()
+    }
+
+    /// Memory of the pivot blocks and columns of L
+    pub fn bytes(&self) -> usize {
+        std::mem::size_of_val(self.steps.as_slice())
+            + self
+                .steps
+                .iter()
+                .map(|s| std::mem::size_of_val(s.l.as_slice()))
+                .sum::<usize>()
+    }
+
+    /// Number of negative eigenvalues of A (Sylvester's law of inertia)
+    pub fn negative_eigenvalues(&self) -> usize {
+        self.negative
+    }
+
+    /// Solve A x = b
+    pub fn solve(&self, b: &[f64]) -> Vec<f64> {
+        let mut x = b.to_vec();
+        let two = |s: &Step| s.pivots[1] != usize::MAX;
+        // L y = b, in elimination order
+        for s in self.steps.iter() {
+            let y0 = x[s.pivots[0]];
+            let y1 = if two(s) { x[s.pivots[1]] } else { 0.0 };
+            for &(i, l) in s.l.iter() {
+                x[i] -= l[0] * y0 + l[1] * y1;
+            }
+        }
+        // D z = y
+        for s in self.steps.iter() {
+            if two(s) {
+                let (y0, y1) = (x[s.pivots[0]], x[s.pivots[1]]);
+                x[s.pivots[0]] = s.d_inv[0] * y0 + s.d_inv[1] * y1;
+                x[s.pivots[1]] = s.d_inv[2] * y0 + s.d_inv[3] * y1;
+            } else {
+                x[s.pivots[0]] *= s.d_inv[0];
+            }
+        }
+        // L^T x = z, in reverse order
+        for s in self.steps.iter().rev() {
+            let (mut s0, mut s1) = (0.0, 0.0);
+            for &(i, l) in s.l.iter() {
+                s0 += l[0] * x[i];
+                s1 += l[1] * x[i];
+            }
+            x[s.pivots[0]] -= s0;
+            if two(s) {
+                x[s.pivots[1]] -= s1;
+            }
+        }
+        x
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kernels::norm;
+    use crate::test_util::{dense_to_csr, diffusion_2d, CsrParts};
+
+    /// [K B^T; B 0] with K a diffusion matrix and B pinning the averages
+    /// of `m` groups of unknowns
+    fn saddle_point(nx: usize, ny: usize, m: usize) -> CsrParts {
+        let n = nx * ny;
+        let (values, col_indices, row_ptr) = diffusion_2d(nx, ny, &vec![1.0; n]);
+        let k = Csr::new(&values, &col_indices, &row_ptr);
+        let mut dense = vec![0.0; (n + m) * (n + m)];
+        let size = n + m;
+        for i in 0..n {
+            for q in k.row_ptr[i] as usize..k.row_ptr[i + 1] as usize {
+                dense[i * size + k.col_indices[q] as usize] = k.values[q];
+            }
+            let g = n + i * m / n;
+            dense[g * size + i] = 1.0;
+            dense[i * size + g] = 1.0;
+        }
+        dense_to_csr(&dense, size)
+    }
+
+    #[test]
+    fn test_two_by_two_pivot() {
+        // [[0, 1], [1, 0]] has no usable 1 x 1 pivot
+        let (values, col_indices, row_ptr) = dense_to_csr(&[0.0, 1.0, 1.0, 0.0], 2);
+        let a = Csr::new(&values, &col_indices, &row_ptr);
+        let factor = Ldlt::new(&a, &[0, 1]).unwrap();
+        assert_eq!(factor.solve(&[2.0, 3.0]), vec![3.0, 2.0]);
+        assert_eq!(factor.negative_eigenvalues(), 1);
+    }
+
+    #[test]
+    fn test_saddle_point_system() {
+        let (nx, ny, m) = (8, 6, 4);
+        let (values, col_indices, row_ptr) = saddle_point(nx, ny, m);
+        let a = Csr::new(&values, &col_indices, &row_ptr);
+        let n = a.n();
+        let order: Vec<usize> = (0..n).rev().collect();
+        let factor = Ldlt::new(&a, &order).unwrap();
+        // K is SPD and B has full rank: exactly m negative eigenvalues
+        assert_eq!(factor.negative_eigenvalues(), m);
+
+        let x_true: Vec<f64> = (0..n).map(|i| (i as f64 * 0.3).cos()).collect();
+        let mut b = vec![0.0; n];
+        a.spmv(&x_true, &mut b);
+        let err: Vec<f64> = factor
+            .solve(&b)
+            .iter()
+            .zip(&x_true)
+            .map(|(x, t)| x - t)
+            .collect();
+        assert!(norm(&err) < 1e-10 * norm(&x_true));
+
+        // Singular: a multiplier row with no constraint entries
+        let (values, col_indices, row_ptr) = dense_to_csr(&[2.0, 0.0, 0.0, 0.0], 2);
+        assert!(Ldlt::new(&Csr::new(&values, &col_indices, &row_ptr), &[0, 1]).is_none());
+    }
+}
@@ -0,0 +1,30 @@
+//! Sparse direct solvers
+//!
+//! A factorization is computed once by `factorize` (SPD, Cholesky),
+//! `factorize_symmetric` (symmetric indefinite, LDL^T) or `factorize_lu`
+//! (general, LU) and kept in a `Factor` handle for any number of solves.
+//! Direct solves cost more memory than PCG but are exact up to rounding
+//! and reproducible, which pays off for 2D problems of moderate size.
+//! Tiny systems skip the sparse machinery altogether with `solve_dense`,
+//! and `condense` reduces a system to a subset of its DOFs (static
+//! condensation).
+
+mod band;
+mod cholesky;
+mod condense;
+mod handle;
+mod ldlt;
+mod lu;
+mod small;
+
+pub(crate) use band::{half_bandwidth, BandCholesky};
+pub(crate) use cholesky::{Cholesky, Symbolic};
+pub use condense::{condense, CondensationHandle};
+pub use handle::{
+    factorize, factorize_lu, factorize_symmetric, factorize_with_ordering, fill_reducing_ordering,
+    FactorHandle,
+};
+pub(crate) use ldlt::Ldlt;
+pub(crate) use lu::{column_order, SparseLu};
+pub use small::solve_dense;
+pub(crate) use small::solve_small;
@@ -0,0 +1,105 @@
+use wasm_bindgen::prelude::*;
+
+use crate::dense::DenseFactor;
+use crate::kernels::{criterion_threshold, norm, Csr};
+use crate::options::{ConvergenceCriterion, SolverOptions};
+use crate::SolveResult;
+
+/// Solve A x = b by a dense factorization of A
+///
+/// For small systems (a few hundred rows: coarse problems, unit tests),
+/// where an iterative solver is overkill. A is copied into a dense n x n
+/// array and factorized by Cholesky, or by LU with partial pivoting if it
+/// is not SPD. Storage as for `solve_pcg`. Returns `undefined` if A is
+/// singular or `b` has the wrong length.
+#[wasm_bindgen]
+pub fn solve_dense(
+    values: &[f64],
+    col_indices: &[u32],
+    row_ptr: &[u32],
+    b: &[f64],
+) -> Option<Vec<f64>> {
+    let a = Csr::new(values, col_indices, row_ptr);
+    if b.len() != a.n() {
+        return None;
+    }
+    let factor = DenseFactor::from_csr(&a)?;
+    let mut x = b.to_vec();
+    factor.solve(&mut x);
+    Some(x)
+}
+
+/// Solve a system below `SolverOptions::dense_threshold` directly
+///
+/// The result reports no iterations and the true residual, and carries the
+/// requested criterion when that residual meets it. `None` if A is
+/// singular, so that the caller can iterate instead.
+pub(crate) fn solve_small(
+    a: &Csr,
+    b: &[f64],
+    x0: &[f64],
+    options: &SolverOptions,
+) -> Option<SolveResult> {
+    let factor = DenseFactor::from_csr(a)?;
+    let mut x = b.to_vec();
+    factor.solve(&mut x);
+
+    let mut r = vec![0.0; b.len()];
+    let r0norm = if options.criterion == ConvergenceCriterion::RelativeToInitial {
+        a.residual(b, x0, &mut r);
+        norm(&r)
+    } else {
+        0.0
+    };
+    a.residual(b, &x, &mut r);
+    let residual = norm(&r);
+    // A direct solve has no energy-norm estimate; judge it by the residual
+    let criterion = match options.criterion {
+        ConvergenceCriterion::EnergyNorm => ConvergenceCriterion::RelativeToRhs,
+        other => other,
+    };
+    let result = SolveResult::new(x, 0, residual);
+    if residual <= criterion_threshold(criterion, norm(b), r0norm, options.tol) {
+        Some(result.with_criterion(criterion))
+    } else {
+        Some(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::krylov::solve_pcg_with_options;
+    use crate::test_util::{convection_diffusion_1d, diffusion_2d};
+
+    #[test]
+    fn test_nonsymmetric_system() {
+        let n = 30;
+        let (values, col_indices, row_ptr) = convection_diffusion_1d(n, 0.8);
+        let a = Csr::new(&values, &col_indices, &row_ptr);
+        let x_true: Vec<f64> = (0..n).map(|i| (i as f64 * 0.2).sin()).collect();
+        let mut b = vec![0.0; n];
+        a.spmv(&x_true, &mut b);
+        let x = solve_dense(&values, &col_indices, &row_ptr, &b).unwrap();
+        for (xi, ti) in x.iter().zip(&x_true) {
+            assert!((xi - ti).abs() < 1e-12);
+        }
+        assert!(solve_dense(&values, &col_indices, &row_ptr, &b[1..]).is_none());
+    }
+
+    #[test]
+    fn test_small_systems_skip_iterating() {
+        let (values, col_indices, row_ptr) = diffusion_2d(12, 10, &[1.0; 120]);
+        let b = vec![1.0; 120];
+        let x0 = vec![0.0; 120];
+        let mut options = SolverOptions::new();
+        options.dense_threshold = 200;
+        let direct = solve_pcg_with_options(&values, &col_indices, &row_ptr, &b, &x0, &options);
+        assert_eq!(direct.iterations, 0);
+        assert!(direct.criterion.is_some() && direct.residual < 1e-12);
+
+        options.dense_threshold = 120;
+        let iterative = solve_pcg_with_options(&values, &col_indices, &row_ptr, &b, &x0, &options);
+        assert!(iterative.iterations > 0 && iterative.criterion.is_some());
+    }
+}
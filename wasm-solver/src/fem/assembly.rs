@@ -0,0 +1,223 @@
+use wasm_bindgen::prelude::*;
+
+use super::q4::q4_stiffness;
+use super::{Formulation, Integration};
+use crate::grid::{node_count, node_index, ElementGrid};
+use crate::kernels::SparseMatrix;
+use crate::matrix::CsrMatrix;
+
+/// Sparsity pattern of the stiffness of a structured grid, values zero
+///
+/// Every DOF of a node couples to every DOF of the nodes sharing an
+/// element with it: 9 nodes in 2D, 27 in 3D. Columns come out sorted
+/// because node indices grow with z, then x, then y.
+pub(crate) fn grid_pattern(elements: [usize; 3], dofs_per_node: usize) -> SparseMatrix {
+    let nodes = elements.map(|e| e + 1);
+    let n = node_count(elements) * dofs_per_node;
+    let near = |c: usize, len: usize| c.saturating_sub(1)..(c + 2).min(len);
+    let mut row_ptr = Vec::with_capacity(n + 1);
+    let mut col_indices = Vec::new();
+    let mut cols = Vec::with_capacity(27 * dofs_per_node);
+    row_ptr.push(0u32);
+    for z in 0..nodes[2] {
+        for x in 0..nodes[0] {
+            for y in 0..nodes[1] {
+                cols.clear();
+                for zz in near(z, nodes[2]) {
+                    for xx in near(x, nodes[0]) {
+                        for yy in near(y, nodes[1]) {
+                            let node = node_index(elements, xx, yy, zz);
+                            cols.extend(
+                                (0..dofs_per_node).map(|d| (node * dofs_per_node + d) as u32),
+                            );
+                        }
+                    }
+                }
+                for _ in 0..dofs_per_node {
+                    col_indices.extend_from_slice(&cols);
+                    row_ptr.push(col_indices.len() as u32);
+                }
+            }
+        }
+    }
+    SparseMatrix {
+        values: vec![0.0; col_indices.len()],
+        col_indices,
+        row_ptr,
+        ncols: n,
+    }
+}
+
+/// k += s ke at the rows and columns `dofs`, skipping fixed ones; every
+/// pair of DOFs sharing an element must be in the pattern of k
+pub(super) fn add_element(
+    k: &mut SparseMatrix,
+    dofs: &[usize],
+    ke: &[f64],
+    s: f64,
+    fixed: impl Fn(usize) -> bool,
+) {
+    for (row, &i) in ke.chunks_exact(dofs.len()).zip(dofs) {
+        if fixed(i) {
+            continue;
+        }
+        let start = k.row_ptr[i] as usize;
+        let cols = &k.col_indices[start..k.row_ptr[i + 1] as usize];
+        for (&v, &j) in row.iter().zip(dofs) {
+            if !fixed(j) {
+                let q = start + cols.binary_search(&(j as u32)).unwrap();
+                k.values[q] += s * v;
+            }
+        }
+    }
+}
+
+/// Assembled K = sum_e s_e K_e of a grid operator, in `grid_pattern`
+///
+/// Fixed DOFs get a unit row and column, as the grid operator applies
+/// them.
+pub(crate) fn assemble_elements(grid: &ElementGrid) -> SparseMatrix {
+    let (elements, dofs_per_node) = grid.shape();
+    let mut k = grid_pattern(elements, dofs_per_node);
+    let mut dofs = Vec::with_capacity(grid.local_size());
+    for e in 0..grid.element_count() {
+        grid.element_dofs(e, &mut dofs);
+        add_element(&mut k, &dofs, grid.ke(), grid.scale(e), |i| {
+            grid.is_fixed(i)
+        });
+    }
+    for i in 0..grid.n() {
+        if grid.is_fixed(i) {
+            let start = k.row_ptr[i] as usize;
+            let cols = &k.col_indices[start..k.row_ptr[i + 1] as usize];
+            k.values[start + cols.binary_search(&(i as u32)).unwrap()] = 1.0;
+        }
+    }
+    k
+}
+
+/// SIMP moduli E_min + rho_e^p (E_0 - E_min) of the element densities
+pub(crate) fn simp_moduli(densities: &[f64], penal: f64, e0: f64, emin: f64) -> Vec<f64> {
+    densities
+        .iter()
+        .map(|&rho| emin + rho.powf(penal) * (e0 - emin))
+        .collect()
+}
+
+/// Global stiffness of a `nelx` x `nely` grid of unit Q4 elements with
+/// SIMP interpolation
+///
+/// Element e = x nely + y (as `getElementIndex`) has the modulus
+/// E_min + rho_e^p (E_0 - E_min) for its density `densities[e]`; the
+/// nodal DOF numbering is that of `getElementDOFs`. No DOF is fixed: the
+/// result is singular until boundary conditions are applied. Returns
+/// `undefined` if the grid is empty, `densities` does not hold one value
+/// per element or `nu` is not in (-1, 1).
+#[wasm_bindgen]
+pub fn assemble_simp(
+    nelx: usize,
+    nely: usize,
+    densities: &[f64],
+    penal: f64,
+    e0: f64,
+    emin: f64,
+    nu: f64,
+) -> Option<CsrMatrix> {
+    assemble_simp_with_formulation(
+        Formulation::PlaneStress,
+        nelx,
+        nely,
+        densities,
+        penal,
+        e0,
+        emin,
+        nu,
+    )
+}
+
+/// `assemble_simp` under plane stress, plane strain or axisymmetry
+///
+/// For `Axisymmetric` the grid is a half section: x is the radius from
+/// the axis at x = 0 and the stiffness is per radian. The x DOFs of the
+/// nodes on the axis should be fixed. Returns `undefined` as
+/// `assemble_simp`, and for nu >= 0.5 outside plane stress.
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn assemble_simp_with_formulation(
+    formulation: Formulation,
+    nelx: usize,
+    nely: usize,
+    densities: &[f64],
+    penal: f64,
+    e0: f64,
+    emin: f64,
+    nu: f64,
+) -> Option<CsrMatrix> {
+    if nelx == 0 || nely == 0 || densities.len() != nelx * nely || !formulation.admits(nu) {
+        return None;
+    }
+    let moduli = simp_moduli(densities, penal, e0, emin);
+    let elements = [nelx, nely, 0];
+    if formulation != Formulation::Axisymmetric {
+        let ke = q4_stiffness(formulation, Integration::Full, 1.0, nu, 1.0, 1.0, 1.0, 0.0);
+        let grid = ElementGrid::new(elements, 2, &ke, &moduli, &[]);
+        return Some(CsrMatrix::from_matrix(assemble_elements(&grid)));
+    }
+    // The element matrix changes with the radius, i.e. per column
+    let grid = ElementGrid::new(elements, 2, &[], &moduli, &[]);
+    let mut k = grid_pattern(elements, 2);
+    let mut dofs = Vec::with_capacity(8);
+    for (x, column) in moduli.chunks_exact(nely).enumerate() {
+        let ke = q4_stiffness(
+            formulation,
+            Integration::Full,
+            1.0,
+            nu,
+            1.0,
+            1.0,
+            1.0,
+            x as f64,
+        );
+        for (y, &s) in column.iter().enumerate() {
+            grid.element_dofs(x * nely + y, &mut dofs);
+            add_element(&mut k, &dofs, &ke, s, |_| false);
+        }
+    }
+    Some(CsrMatrix::from_matrix(k))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kernels::LinearOperator;
+    use crate::test_util::q4_stiffness;
+
+    #[test]
+    fn test_simp_matches_element_operator() {
+        let (nelx, nely) = (6, 4);
+        let densities: Vec<f64> = (0..nelx * nely)
+            .map(|e| 0.1 + (e % 9) as f64 / 10.0)
+            .collect();
+        let k = assemble_simp(nelx, nely, &densities, 3.0, 1.0, 1e-9, 0.3).unwrap();
+        let n = 2 * (nelx + 1) * (nely + 1);
+        assert_eq!(k.size(), n);
+        // Node pairs: 2 + 3 + ... + 3 + 2 neighbours per direction
+        assert_eq!(k.nnz(), 4 * 19 * 13);
+
+        let ke = q4_stiffness(0.3);
+        let moduli = simp_moduli(&densities, 3.0, 1.0, 1e-9);
+        let fixed = [0, 1, 2 * nely as u32 + 1];
+        let grid = ElementGrid::new([nelx, nely, 0], 2, &ke, &moduli, &fixed);
+        let x: Vec<f64> = (0..n).map(|i| (i as f64 * 0.4).cos()).collect();
+        let (mut y, mut expected) = (vec![0.0; n], vec![0.0; n]);
+        grid.spmv(&x, &mut expected);
+        assemble_elements(&grid).csr().spmv(&x, &mut y);
+        assert!(y.iter().zip(&expected).all(|(u, v)| (u - v).abs() < 1e-12));
+
+        // Rigid translations are in the null space of the unconstrained K
+        let ux: Vec<f64> = (0..n).map(|i| ((i + 1) % 2) as f64).collect();
+        k.csr().spmv(&ux, &mut y);
+        assert!(y.iter().all(|v| v.abs() < 1e-12));
+        assert!(assemble_simp(nelx, nely, &densities[1..], 3.0, 1.0, 1e-9, 0.3).is_none());
+    }
+}
@@ -0,0 +1,181 @@
+use wasm_bindgen::prelude::*;
+
+use crate::kernels::SparseMatrix;
+use crate::krylov::run_solver;
+use crate::matrix::CsrMatrix;
+use crate::options::{SolverKind, SolverOptions};
+use crate::SolveResult;
+
+/// Penalty stiffness of `DirichletMethod::Penalty`, relative to the
+/// largest |k_ii|; constrained values are met to about 1 / PENALTY relative
+const PENALTY: f64 = 1e8;
+
+/// How `apply_dirichlet` imposes prescribed DOF values
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DirichletMethod {
+    /// Zero the rows and columns of the fixed DOFs, keeping their diagonal,
+    /// and move the columns times the prescribed values to the right-hand
+    /// side. The system stays SPD and symmetric, and the fixed DOFs come
+    /// out exact.
+    Elimination = 0,
+    /// Add a large spring to the diagonal of every fixed DOF and the
+    /// matching force to the right-hand side; the matrix pattern and
+    /// off-diagonal values are untouched, the values are met approximately
+    Penalty = 1,
+}
+
+/// System K u = f with Dirichlet constraints applied, same size and
+/// pattern as the unconstrained one
+#[wasm_bindgen]
+pub struct ConstrainedSystem {
+    matrix: CsrMatrix,
+    rhs: Vec<f64>,
+}
+
+#[wasm_bindgen]
+impl ConstrainedSystem {
+    /// Copy of the constrained matrix
+    pub fn matrix(&self) -> CsrMatrix {
+        CsrMatrix::from_matrix(SparseMatrix::from_csr(&self.matrix.csr()))
+    }
+
+    /// Constrained right-hand side
+    #[wasm_bindgen(getter)]
+    pub fn rhs(&self) -> Vec<f64> {
+        self.rhs.clone()
+    }
+
+    /// `solve_pcg` on the constrained system
+    pub fn solve_pcg(&self, x0: &[f64], options: &SolverOptions) -> SolveResult {
+        run_solver(SolverKind::Pcg, &self.matrix.csr(), &self.rhs, x0, options)
+    }
+}
+
+/// Impose u_i = values[k] at the DOFs i = fixed_dofs[k] on K u = f
+///
+/// `values` is empty for homogeneous constraints (supports). Both
+/// triangles of K must be stored, each fixed DOF with its diagonal entry.
+/// Returns `undefined` if `f` does not have one entry per row, `values`
+/// has the wrong length, or a fixed DOF is out of range or has no stored
+/// diagonal.
+#[wasm_bindgen]
+pub fn apply_dirichlet(
+    k: &CsrMatrix,
+    f: &[f64],
+    fixed_dofs: &[u32],
+    values: &[f64],
+    method: DirichletMethod,
+) -> Option<ConstrainedSystem> {
+    let a = k.csr();
+    let n = a.n();
+    if f.len() != n || !(values.is_empty() || values.len() == fixed_dofs.len()) {
+        return None;
+    }
+    // Prescribed value of every DOF, NaN where free
+    let mut prescribed = vec![f64::NAN; n];
+    for (k, &i) in fixed_dofs.iter().enumerate() {
+        *prescribed.get_mut(i as usize)? = values.get(k).copied().unwrap_or(0.0);
+    }
+    let mut diagonal = Vec::with_capacity(fixed_dofs.len());
+    for &i in fixed_dofs {
+        let row = a.row_ptr[i as usize] as usize..a.row_ptr[i as usize + 1] as usize;
+        diagonal.push(row.clone().find(|&q| a.col_indices[q] == i)?);
+    }
+
+    let mut matrix = SparseMatrix::from_csr(&a);
+    let mut rhs = f.to_vec();
+    match method {
+        DirichletMethod::Elimination => {
+            for (i, r) in rhs.iter_mut().enumerate() {
+                let row_fixed = !prescribed[i].is_nan();
+                for q in a.row_ptr[i] as usize..a.row_ptr[i + 1] as usize {
+                    let j = a.col_indices[q] as usize;
+                    let col_fixed = !prescribed[j].is_nan();
+                    if i == j || !(row_fixed || col_fixed) {
+                        continue;
+                    }
+                    if !row_fixed {
+                        *r -= a.values[q] * prescribed[j];
+                    }
+                    matrix.values[q] = 0.0;
+                }
+            }
+            // Keeping k_ii preserves the scaling Jacobi-type
+            // preconditioners see
+            for &q in &diagonal {
+                if matrix.values[q] == 0.0 {
+                    matrix.values[q] = 1.0;
+                }
+            }
+            for (&i, &q) in fixed_dofs.iter().zip(&diagonal) {
+                rhs[i as usize] = matrix.values[q] * prescribed[i as usize];
+            }
+        }
+        DirichletMethod::Penalty => {
+            let scale = (0..n)
+                .flat_map(|i| {
+                    (a.row_ptr[i] as usize..a.row_ptr[i + 1] as usize)
+                        .filter(move |&q| a.col_indices[q] as usize == i)
+                })
+                .map(|q| a.values[q].abs())
+                .fold(0.0, f64::max);
+            let penalty = PENALTY * if scale > 0.0 { scale } else { 1.0 };
+            for (&i, &q) in fixed_dofs.iter().zip(&diagonal) {
+                matrix.values[q] += penalty;
+                rhs[i as usize] += penalty * prescribed[i as usize];
+            }
+        }
+    }
+    Some(ConstrainedSystem {
+        matrix: CsrMatrix::from_matrix(matrix),
+        rhs,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fem::assemble_simp;
+
+    #[test]
+    fn test_elimination_and_penalty_agree() {
+        let (nelx, nely) = (8, 4);
+        let k = assemble_simp(nelx, nely, &[1.0; 32], 3.0, 1.0, 1e-9, 0.3).unwrap();
+        let n = k.size();
+        // Clamped left edge, prescribed downward displacement at the top
+        // right corner
+        let mut fixed: Vec<u32> = (0..2 * (nely as u32 + 1)).collect();
+        fixed.push(n as u32 - 1);
+        let mut values = vec![0.0; fixed.len()];
+        *values.last_mut().unwrap() = -0.01;
+        let f = vec![0.0; n];
+        let mut options = SolverOptions::new();
+        options.tol = 1e-12;
+        options.max_iter = 2000;
+
+        let exact = apply_dirichlet(&k, &f, &fixed, &values, DirichletMethod::Elimination)
+            .unwrap()
+            .solve_pcg(&vec![0.0; n], &options);
+        assert!(exact.criterion.is_some());
+        for (&i, &v) in fixed.iter().zip(&values) {
+            assert!((exact.solution[i as usize] - v).abs() < 1e-12);
+        }
+
+        // The penalty forces dominate ||f||, so the relative tolerance has
+        // to be that much tighter
+        options.preconditioner = crate::options::PreconditionerKind::Ic0;
+        options.tol = 1e-16;
+        let penalty = apply_dirichlet(&k, &f, &fixed, &values, DirichletMethod::Penalty).unwrap();
+        assert!(penalty.matrix().same_pattern(&k));
+        let approx = penalty.solve_pcg(&vec![0.0; n], &options);
+        assert!(exact
+            .solution
+            .iter()
+            .zip(&approx.solution)
+            .all(|(u, v)| (u - v).abs() < 1e-6 * 0.01));
+
+        assert!(apply_dirichlet(&k, &f, &[n as u32], &[], DirichletMethod::Penalty).is_none());
+        assert!(apply_dirichlet(&k, &f[1..], &fixed, &[], DirichletMethod::Penalty).is_none());
+    }
+}
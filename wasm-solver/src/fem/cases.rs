@@ -0,0 +1,232 @@
+use wasm_bindgen::prelude::*;
+
+use crate::kernels::{dot, norm, threshold};
+use crate::krylov::{block_cg, run_solver};
+use crate::matrix::CsrMatrix;
+use crate::options::{SolverKind, SolverOptions};
+
+/// Several load cases on one structure, solved against the same stiffness
+///
+/// Each case has a force vector and a weight for the combined objective
+/// (all 1 for the plain sum). The displacements of the latest `solve` are
+/// kept per case and are the initial guesses of the next one, so in a
+/// design loop every case starts from the previous iteration's field.
+#[wasm_bindgen]
+pub struct LoadCases {
+    n: usize,
+    forces: Vec<Vec<f64>>,
+    weights: Vec<f64>,
+    displacements: Vec<Vec<f64>>,
+    iterations: Vec<u32>,
+}
+
+#[wasm_bindgen]
+impl LoadCases {
+    /// No cases, for a system of n DOFs
+    #[wasm_bindgen(constructor)]
+    pub fn new(n: usize) -> LoadCases {
+        LoadCases {
+            n,
+            forces: Vec::new(),
+            weights: Vec::new(),
+            displacements: Vec::new(),
+            iterations: Vec::new(),
+        }
+    }
+
+    /// Add a case with force vector `forces` (e.g. `LoadVector.forces`)
+    /// and objective weight `weight`. Returns `false`, adding nothing, if
+    /// `forces` does not have n entries.
+    pub fn add(&mut self, forces: &[f64], weight: f64) -> bool {
+        if forces.len() != self.n {
+            return false;
+        }
+        self.forces.push(forces.to_vec());
+        self.weights.push(weight);
+        self.displacements.push(vec![0.0; self.n]);
+        self.iterations.push(0);
+        true
+    }
+
+    /// Number of cases
+    #[wasm_bindgen(getter)]
+    pub fn count(&self) -> usize {
+        self.forces.len()
+    }
+
+    /// Solve K u_c = f_c for every case c, one PCG solve per case with
+    /// `options`, or all at once by block CG (Jacobi, `options.tol` and
+    /// `options.max_iter`) with `block`. Returns whether every case
+    /// converged; the displacements are updated either way. `k` must have
+    /// n rows and its boundary conditions applied.
+    pub fn solve(&mut self, k: &CsrMatrix, options: &SolverOptions, block: bool) -> bool {
+        let a = k.csr();
+        if a.n() != self.n {
+            return false;
+        }
+        if block && !self.forces.is_empty() {
+            let b = self.forces.concat();
+            let result = block_cg(
+                &a,
+                &b,
+                &self.displacements.concat(),
+                self.count(),
+                options.tol,
+                options.max_iter,
+            );
+            for (u, x) in self
+                .displacements
+                .iter_mut()
+                .zip(result.solution.chunks_exact(self.n))
+            {
+                u.copy_from_slice(x);
+            }
+            self.iterations.fill(result.iterations);
+            // Block CG stops on ||r_c|| < tol max(||f_c||, 1) for every column
+            let mut r = vec![0.0; self.n];
+            return self.forces.iter().zip(&self.displacements).all(|(f, u)| {
+                a.residual(f, u, &mut r);
+                norm(&r) < threshold(f, options.tol)
+            });
+        }
+        let mut converged = true;
+        for ((f, u), iterations) in self
+            .forces
+            .iter()
+            .zip(self.displacements.iter_mut())
+            .zip(self.iterations.iter_mut())
+        {
+            let result = run_solver(SolverKind::Pcg, &a, f, u, options);
+            converged &= result.criterion.is_some();
+            *iterations = result.iterations;
+            *u = result.solution;
+        }
+        converged
+    }
+
+    /// Displacements of case `case` from the latest solve; `undefined` if
+    /// there is no such case
+    pub fn displacement(&self, case: usize) -> Option<Vec<f64>> {
+        self.displacements.get(case).cloned()
+    }
+
+    /// Iterations of each case in the latest solve (block iterations, the
+    /// same for every case, for a block solve)
+    #[wasm_bindgen(getter)]
+    pub fn iterations(&self) -> Vec<u32> {
+        self.iterations.clone()
+    }
+
+    /// Weighted sum of the displacements, sum_c w_c u_c
+    pub fn combined(&self) -> Vec<f64> {
+        let mut u = vec![0.0; self.n];
+        for (uc, &w) in self.displacements.iter().zip(&self.weights) {
+            u.iter_mut().zip(uc).for_each(|(v, x)| *v += w * x);
+        }
+        u
+    }
+
+    /// Compliance f_c^T u_c of every case
+    pub fn compliances(&self) -> Vec<f64> {
+        self.forces
+            .iter()
+            .zip(&self.displacements)
+            .map(|(f, u)| dot(f, u))
+            .collect()
+    }
+
+    /// Weighted sum of the compliances, the usual multi-load objective
+    pub fn weighted_compliance(&self) -> f64 {
+        self.compliances()
+            .iter()
+            .zip(&self.weights)
+            .map(|(c, w)| c * w)
+            .sum()
+    }
+
+    /// Case with the largest weighted compliance, for worst-case designs;
+    /// `undefined` without cases
+    pub fn worst_case(&self) -> Option<usize> {
+        self.compliances()
+            .iter()
+            .zip(&self.weights)
+            .map(|(c, w)| c * w)
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(c, _)| c)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fem::{apply_dirichlet, assemble_simp, DirichletMethod, GridSide, LoadVector};
+
+    #[test]
+    fn test_block_and_separate_solves_agree() {
+        let (nelx, nely) = (12, 6);
+        let k = assemble_simp(nelx, nely, &vec![0.5; nelx * nely], 3.0, 1.0, 1e-9, 0.3).unwrap();
+        let fixed: Vec<u32> = (0..2 * (nely as u32 + 1)).collect();
+        let mut down = LoadVector::new(nelx, nely, 0, 1.0);
+        down.add_point_load(nelx, 0, 0, 0.0, -1.0, 0.0);
+        let mut side = LoadVector::new(nelx, nely, 0, 1.0);
+        side.add_edge_traction(GridSide::Right, 0, nely, 0.5, 0.0);
+        let k = apply_dirichlet(
+            &k,
+            &down.forces(),
+            &fixed,
+            &[],
+            DirichletMethod::Elimination,
+        )
+        .unwrap()
+        .matrix();
+
+        let mut options = SolverOptions::new();
+        options.tol = 1e-10;
+        options.max_iter = 5000;
+        let mut cases = LoadCases::new(k.size());
+        assert!(cases.add(&down.forces(), 1.0));
+        assert!(cases.add(&side.forces(), 2.0));
+        assert!(!cases.add(&[1.0], 1.0));
+        assert!(cases.solve(&k, &options, false));
+        let separate = [
+            cases.displacement(0).unwrap(),
+            cases.displacement(1).unwrap(),
+        ];
+        let worst = cases.worst_case().unwrap();
+        let objective = cases.weighted_compliance();
+        assert_eq!(
+            worst,
+            if 2.0 * cases.compliances()[1] > cases.compliances()[0] {
+                1
+            } else {
+                0
+            }
+        );
+
+        let mut blocked = LoadCases::new(k.size());
+        blocked.add(&down.forces(), 1.0);
+        blocked.add(&side.forces(), 2.0);
+        assert!(blocked.solve(&k, &options, true));
+        for (c, u) in separate.iter().enumerate() {
+            let v = blocked.displacement(c).unwrap();
+            assert!(u.iter().zip(&v).all(|(a, b)| (a - b).abs() < 1e-6));
+        }
+        assert!((blocked.weighted_compliance() - objective).abs() < 1e-6 * objective);
+
+        // The combined field solves the summed load
+        let sum: Vec<f64> = down
+            .forces()
+            .iter()
+            .zip(side.forces())
+            .map(|(a, b)| a + 2.0 * b)
+            .collect();
+        let direct = k.solve_pcg(&sum, &vec![0.0; k.size()], &options);
+        let combined = cases.combined();
+        assert!(direct
+            .solution
+            .iter()
+            .zip(&combined)
+            .all(|(a, b)| (a - b).abs() < 1e-6));
+    }
+}
@@ -0,0 +1,167 @@
+use wasm_bindgen::prelude::*;
+
+use super::{q4_element_stiffness, simp_moduli};
+use crate::grid::ElementGrid;
+use crate::kernels::dot;
+
+/// Compliance f^T u of a displacement field `u` under the loads `f`;
+/// `undefined` if the lengths differ
+#[wasm_bindgen]
+pub fn compliance(u: &[f64], f: &[f64]) -> Option<f64> {
+    (u.len() == f.len()).then(|| dot(u, f))
+}
+
+/// Per-element compliance of a SIMP design, from `element_compliance`
+#[wasm_bindgen]
+pub struct ElementCompliance {
+    energies: Vec<f64>,
+    sensitivities: Vec<f64>,
+    total: f64,
+}
+
+#[wasm_bindgen]
+impl ElementCompliance {
+    /// u_e^T K_0 u_e per element, K_0 the stiffness at unit modulus
+    #[wasm_bindgen(getter)]
+    pub fn energies(&self) -> Vec<f64> {
+        self.energies.clone()
+    }
+
+    /// dc / d rho_e = -p rho_e^(p - 1) (E_0 - E_min) u_e^T K_0 u_e, the
+    /// gradient the OC and MMA updates take
+    #[wasm_bindgen(getter)]
+    pub fn sensitivities(&self) -> Vec<f64> {
+        self.sensitivities.clone()
+    }
+
+    /// Compliance sum_e E_e u_e^T K_0 u_e, equal to f^T u at equilibrium
+    #[wasm_bindgen(getter)]
+    pub fn total(&self) -> f64 {
+        self.total
+    }
+}
+
+/// u_e^T K_e u_e of every element of a grid operator, with the reference
+/// `ke` (not scaled by the element factors)
+pub(crate) fn element_energies(grid: &ElementGrid, u: &[f64]) -> Vec<f64> {
+    let size = grid.local_size();
+    let mut dofs = Vec::with_capacity(size);
+    let mut ue = vec![0.0; size];
+    (0..grid.element_count())
+        .map(|e| {
+            grid.element_dofs(e, &mut dofs);
+            for (v, &i) in ue.iter_mut().zip(&dofs) {
+                *v = u[i];
+            }
+            grid.ke()
+                .chunks_exact(size)
+                .zip(&ue)
+                .map(|(row, ui)| ui * dot(row, &ue))
+                .sum()
+        })
+        .collect()
+}
+
+/// Element strain energies and compliance sensitivities of the
+/// `assemble_simp` design for the displacements `u`
+///
+/// Arguments are those of `assemble_simp`, so the element matrix and
+/// moduli match the stiffness `u` was solved with. Returns `undefined` if
+/// the grid is empty or `densities` or `u` have the wrong length.
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn element_compliance(
+    nelx: usize,
+    nely: usize,
+    u: &[f64],
+    densities: &[f64],
+    penal: f64,
+    e0: f64,
+    emin: f64,
+    nu: f64,
+) -> Option<ElementCompliance> {
+    if nelx == 0 || nely == 0 || densities.len() != nelx * nely {
+        return None;
+    }
+    let ke = q4_element_stiffness(1.0, nu, 1.0, 1.0, 1.0)?;
+    let moduli = simp_moduli(densities, penal, e0, emin);
+    let grid = ElementGrid::new([nelx, nely, 0], 2, &ke, &moduli, &[]);
+    if u.len() != grid.n() {
+        return None;
+    }
+    Some(simp_compliance(&grid, u, densities, penal, e0, emin))
+}
+
+/// `ElementCompliance` of a grid whose factors are the SIMP moduli of
+/// `densities`
+pub(super) fn simp_compliance(
+    grid: &ElementGrid,
+    u: &[f64],
+    densities: &[f64],
+    penal: f64,
+    e0: f64,
+    emin: f64,
+) -> ElementCompliance {
+    let energies = element_energies(grid, u);
+    let sensitivities = energies
+        .iter()
+        .zip(densities)
+        .map(|(ce, &rho)| -penal * rho.powf(penal - 1.0) * (e0 - emin) * ce)
+        .collect();
+    let total = energies
+        .iter()
+        .enumerate()
+        .map(|(e, ce)| grid.scale(e) * ce)
+        .sum();
+    ElementCompliance {
+        energies,
+        sensitivities,
+        total,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fem::{apply_dirichlet, assemble_simp, DirichletMethod, LoadVector};
+    use crate::options::SolverOptions;
+
+    #[test]
+    fn test_sensitivities_match_finite_differences() {
+        let (nelx, nely) = (8, 4);
+        let (penal, e0, emin, nu) = (3.0, 1.0, 1e-9, 0.3);
+        let mut densities: Vec<f64> = (0..nelx * nely)
+            .map(|e| 0.3 + (e % 5) as f64 / 10.0)
+            .collect();
+        let fixed: Vec<u32> = (0..2 * (nely as u32 + 1)).collect();
+        let mut loads = LoadVector::new(nelx, nely, 0, 1.0);
+        loads.add_point_load(nelx, 0, 0, 0.0, -1.0, 0.0);
+        let f = loads.forces();
+        let mut options = SolverOptions::new();
+        options.tol = 1e-12;
+        options.max_iter = 5000;
+        let solve = |densities: &[f64]| {
+            let k = assemble_simp(nelx, nely, densities, penal, e0, emin, nu).unwrap();
+            apply_dirichlet(&k, &f, &fixed, &[], DirichletMethod::Elimination)
+                .unwrap()
+                .solve_pcg(&vec![0.0; f.len()], &options)
+                .solution
+        };
+
+        let u = solve(&densities);
+        let c = element_compliance(nelx, nely, &u, &densities, penal, e0, emin, nu).unwrap();
+        let fu = compliance(&u, &f).unwrap();
+        assert!((c.total() - fu).abs() < 1e-8 * fu);
+        assert!(c.energies().iter().all(|&ce| ce >= 0.0));
+
+        // Central difference of f^T u(rho) in one element
+        let (e, h) = (13, 1e-5);
+        densities[e] += h;
+        let plus = compliance(&solve(&densities), &f).unwrap();
+        densities[e] -= 2.0 * h;
+        let minus = compliance(&solve(&densities), &f).unwrap();
+        let fd = (plus - minus) / (2.0 * h);
+        assert!((fd - c.sensitivities()[e]).abs() < 1e-4 * fd.abs());
+        assert!(compliance(&u, &f[1..]).is_none());
+    }
+}
@@ -0,0 +1,293 @@
+use wasm_bindgen::prelude::*;
+
+/// 2D idealization of a 3D solid
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Formulation {
+    /// Thin plate loaded in its plane: s_zz = 0
+    PlaneStress = 0,
+    /// Long prismatic body: e_zz = 0
+    PlaneStrain = 1,
+    /// Solid of revolution about the y axis, x the radius: strains
+    /// (e_rr, e_zz, g_rz) plus the hoop strain e_tt = u_r / r
+    Axisymmetric = 2,
+}
+
+/// How element stiffness matrices are integrated
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Integration {
+    /// Full Gauss integration of the whole material matrix
+    Full = 0,
+    /// Selective reduced integration: the volumetric part lambda m m^T of
+    /// D at the element centre only, the shear part 2 mu fully. Removes
+    /// volumetric locking of plane-strain, axisymmetric and solid elements
+    /// as nu approaches 0.5 (plane stress does not lock and is unchanged).
+    SelectiveReduced = 1,
+}
+
+/// Lame parameters (lambda, mu) of (E, nu)
+fn lame(e: f64, nu: f64) -> (f64, f64) {
+    (
+        e * nu / ((1.0 + nu) * (1.0 - 2.0 * nu)),
+        e / (2.0 * (1.0 + nu)),
+    )
+}
+
+/// Split D = D_mu + lambda m m^T over `strains` strain components, the
+/// normal ones marked in `normal`
+fn lame_split(e: f64, nu: f64, normal: &[bool]) -> (Vec<f64>, Vec<f64>) {
+    let (lambda, mu) = lame(e, nu);
+    let n = normal.len();
+    let (mut shear, mut volumetric) = (vec![0.0; n * n], vec![0.0; n * n]);
+    for (i, &ni) in normal.iter().enumerate() {
+        shear[i * n + i] = if ni { 2.0 * mu } else { mu };
+        for (j, &nj) in normal.iter().enumerate() {
+            if ni && nj {
+                volumetric[i * n + j] = lambda;
+            }
+        }
+    }
+    (shear, volumetric)
+}
+
+impl Formulation {
+    /// Number of strain components
+    pub(super) fn strains(self) -> usize {
+        match self {
+            Formulation::Axisymmetric => 4,
+            _ => 3,
+        }
+    }
+
+    /// Whether the material matrix exists for Poisson's ratio nu: plane
+    /// stress allows -1 < nu < 1, the others need nu < 0.5
+    pub(super) fn admits(self, nu: f64) -> bool {
+        match self {
+            Formulation::PlaneStress => nu.abs() < 1.0,
+            _ => nu > -1.0 && nu < 0.5,
+        }
+    }
+
+    /// D split into the shear part and the volumetric part that locks as
+    /// nu -> 0.5; all of plane-stress D is the first part
+    pub(super) fn split(self, e: f64, nu: f64) -> (Vec<f64>, Vec<f64>) {
+        match self {
+            Formulation::PlaneStress => (self.matrix(e, nu), vec![0.0; 9]),
+            Formulation::PlaneStrain => lame_split(e, nu, &[true, true, false]),
+            Formulation::Axisymmetric => lame_split(e, nu, &[true, true, false, true]),
+        }
+    }
+
+    /// Isotropic material matrix D, row-major, for the strains
+    /// (e_xx, e_yy, g_xy) and, axisymmetric, e_tt as the last
+    pub(super) fn matrix(self, e: f64, nu: f64) -> Vec<f64> {
+        match self {
+            Formulation::PlaneStress => {
+                let c = e / (1.0 - nu * nu);
+                vec![
+                    c,
+                    c * nu,
+                    0.0,
+                    c * nu,
+                    c,
+                    0.0,
+                    0.0,
+                    0.0,
+                    c * (1.0 - nu) / 2.0,
+                ]
+            }
+            Formulation::PlaneStrain => {
+                let c = e / ((1.0 + nu) * (1.0 - 2.0 * nu));
+                let (a, b, g) = (c * (1.0 - nu), c * nu, c * (1.0 - 2.0 * nu) / 2.0);
+                vec![a, b, 0.0, b, a, 0.0, 0.0, 0.0, g]
+            }
+            Formulation::Axisymmetric => {
+                let c = e / ((1.0 + nu) * (1.0 - 2.0 * nu));
+                let (a, b, g) = (c * (1.0 - nu), c * nu, c * (1.0 - 2.0 * nu) / 2.0);
+                #[rustfmt::skip]
+                let d = vec![
+                    a, b, 0.0, b,
+                    b, a, 0.0, b,
+                    0.0, 0.0, g, 0.0,
+                    b, b, 0.0, a,
+                ];
+                d
+            }
+        }
+    }
+}
+
+/// Isotropic elasticity matrix D for strains (e_xx, e_yy, e_zz, g_xy,
+/// g_yz, g_zx)
+pub(super) fn isotropic(e: f64, nu: f64) -> [f64; 36] {
+    let c = e / ((1.0 + nu) * (1.0 - 2.0 * nu));
+    let mut d = [0.0; 36];
+    for i in 0..3 {
+        for j in 0..3 {
+            d[i * 6 + j] = c * if i == j { 1.0 - nu } else { nu };
+        }
+        d[(i + 3) * 7] = c * (1.0 - 2.0 * nu) / 2.0;
+    }
+    d
+}
+
+/// `isotropic` split as `Formulation::split`
+pub(super) fn isotropic_split(e: f64, nu: f64) -> (Vec<f64>, Vec<f64>) {
+    lame_split(e, nu, &[true, true, true, false, false, false])
+}
+
+/// Material matrix D of `formulation` for Young's modulus `e` and
+/// Poisson's ratio `nu`, row-major (3 x 3, or 4 x 4 with the hoop strain
+/// last for `Axisymmetric`); `undefined` if nu is out of range
+#[wasm_bindgen]
+pub fn constitutive_matrix(formulation: Formulation, e: f64, nu: f64) -> Option<Vec<f64>> {
+    formulation.admits(nu).then(|| formulation.matrix(e, nu))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fem::{
+        assemble_simp_with_formulation, h8_integrated_stiffness, q4_formulation_stiffness,
+        q4_integrated_stiffness,
+    };
+
+    /// u^T K u / 2 of a dense K
+    fn energy(k: &[f64], u: &[f64]) -> f64 {
+        k.chunks_exact(u.len())
+            .zip(u)
+            .map(|(row, ui)| ui * row.iter().zip(u).map(|(a, uj)| a * uj).sum::<f64>())
+            .sum::<f64>()
+            / 2.0
+    }
+
+    #[test]
+    fn test_plane_strain_equivalent_material() {
+        // Plane strain with (E, nu) is plane stress with
+        // (E / (1 - nu^2), nu / (1 - nu))
+        let (e, nu) = (70.0, 0.33);
+        let strain =
+            q4_formulation_stiffness(Formulation::PlaneStrain, e, nu, 2.0, 1.5, 1.0, 0.0).unwrap();
+        let stress = q4_formulation_stiffness(
+            Formulation::PlaneStress,
+            e / (1.0 - nu * nu),
+            nu / (1.0 - nu),
+            2.0,
+            1.5,
+            1.0,
+            0.0,
+        )
+        .unwrap();
+        assert!(strain
+            .iter()
+            .zip(&stress)
+            .all(|(a, b)| (a - b).abs() < 1e-12));
+        assert_eq!(
+            constitutive_matrix(Formulation::Axisymmetric, e, nu)
+                .unwrap()
+                .len(),
+            16
+        );
+        assert!(constitutive_matrix(Formulation::PlaneStrain, e, 0.5).is_none());
+        assert!(constitutive_matrix(Formulation::PlaneStress, e, 0.5).is_some());
+    }
+
+    #[test]
+    fn test_axisymmetric_radial_expansion() {
+        // u_r = s r gives e_rr = e_tt = s, which the bilinear element
+        // reproduces exactly: energy per radian E s^2 / ((1 + nu)(1 - 2 nu))
+        // times the integral of r over the section
+        let (nelx, nely, nu, s) = (4, 3, 0.3, 1e-3);
+        let k = assemble_simp_with_formulation(
+            Formulation::Axisymmetric,
+            nelx,
+            nely,
+            &[1.0; 12],
+            3.0,
+            1.0,
+            1e-9,
+            nu,
+        )
+        .unwrap();
+        let u: Vec<f64> = (0..k.size())
+            .map(|i| {
+                if i % 2 == 0 {
+                    s * ((i / 2) / (nely + 1)) as f64
+                } else {
+                    0.0
+                }
+            })
+            .collect();
+        let dense = k.to_dense().unwrap();
+        let energy: f64 = dense
+            .chunks_exact(u.len())
+            .zip(&u)
+            .map(|(row, ui)| ui * row.iter().zip(&u).map(|(a, uj)| a * uj).sum::<f64>())
+            .sum::<f64>()
+            / 2.0;
+        let c = 1.0 / ((1.0 + nu) * (1.0 - 2.0 * nu));
+        let expected = c * s * s * (nely * nelx * nelx) as f64 / 2.0;
+        assert!((energy - expected).abs() < 1e-12 * expected.max(1.0) + 1e-15);
+    }
+
+    #[test]
+    fn test_selective_reduced_integration_unlocks() {
+        let nu = 0.4999;
+        // Corners of the unit square and cube, centred on the origin
+        let square = [(-0.5, -0.5), (0.5, -0.5), (0.5, 0.5), (-0.5, 0.5)];
+        let q4 = |integration| {
+            q4_integrated_stiffness(
+                Formulation::PlaneStrain,
+                integration,
+                1.0,
+                nu,
+                1.0,
+                1.0,
+                1.0,
+                0.0,
+            )
+            .unwrap()
+        };
+        let (full, reduced) = (q4(Integration::Full), q4(Integration::SelectiveReduced));
+        // Bending u_x = x y changes volume away from the centre only
+        let bending: Vec<f64> = square.iter().flat_map(|&(x, y)| [x * y, 0.0]).collect();
+        assert!(energy(&full, &bending) > 1000.0 * energy(&reduced, &bending));
+        // Constant strains are integrated exactly either way
+        let dilation: Vec<f64> = square.iter().flat_map(|&(x, y)| [x, y]).collect();
+        let (a, b) = (energy(&full, &dilation), energy(&reduced, &dilation));
+        assert!((a - b).abs() < 1e-9 * a);
+
+        let cube: Vec<[f64; 3]> = (0..8)
+            .map(|i| {
+                let (x, y) = square[i % 4];
+                [x, y, if i < 4 { -0.5 } else { 0.5 }]
+            })
+            .collect();
+        let h8 =
+            |integration| h8_integrated_stiffness(integration, 1.0, nu, 1.0, 1.0, 1.0).unwrap();
+        let (full, reduced) = (h8(Integration::Full), h8(Integration::SelectiveReduced));
+        let bending: Vec<f64> = cube.iter().flat_map(|p| [p[0] * p[1], 0.0, 0.0]).collect();
+        assert!(energy(&full, &bending) > 1000.0 * energy(&reduced, &bending));
+
+        // Plane stress does not lock and is left alone
+        let stress = |integration| {
+            q4_integrated_stiffness(
+                Formulation::PlaneStress,
+                integration,
+                1.0,
+                0.3,
+                1.0,
+                1.0,
+                1.0,
+                0.0,
+            )
+            .unwrap()
+        };
+        let (a, b) = (
+            stress(Integration::Full),
+            stress(Integration::SelectiveReduced),
+        );
+        assert!(a.iter().zip(&b).all(|(x, y)| (x - y).abs() < 1e-14));
+    }
+}
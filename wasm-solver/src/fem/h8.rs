@@ -0,0 +1,178 @@
+use wasm_bindgen::prelude::*;
+
+use super::constitutive::{isotropic, isotropic_split};
+use super::{add_btdb, solid_strain_displacement, Integration, GAUSS_2};
+
+/// Corners of the reference cube in local node order: the bottom face
+/// counter-clockwise, then the top face
+const CORNERS: [(f64, f64, f64); 8] = [
+    (-1.0, -1.0, -1.0),
+    (1.0, -1.0, -1.0),
+    (1.0, 1.0, -1.0),
+    (-1.0, 1.0, -1.0),
+    (-1.0, -1.0, 1.0),
+    (1.0, -1.0, 1.0),
+    (1.0, 1.0, 1.0),
+    (-1.0, 1.0, 1.0),
+];
+
+/// Trilinear shape functions of the local nodes at the reference point
+/// `point`
+pub(super) fn shape(point: [f64; 3]) -> [f64; 8] {
+    let [xi, eta, zeta] = point;
+    CORNERS.map(|(xi_i, eta_i, zeta_i)| {
+        (1.0 + xi * xi_i) * (1.0 + eta * eta_i) * (1.0 + zeta * zeta_i) / 8.0
+    })
+}
+
+/// Gradients (d/dx, d/dy, d/dz) of the shape functions of an element of
+/// edge lengths `size` at the reference point `point`
+pub(super) fn gradients(point: [f64; 3], size: [f64; 3]) -> [[f64; 3]; 8] {
+    let [xi, eta, zeta] = point;
+    CORNERS.map(|(xi_i, eta_i, zeta_i)| {
+        let (fx, fy, fz) = (1.0 + xi * xi_i, 1.0 + eta * eta_i, 1.0 + zeta * zeta_i);
+        [
+            xi_i * fy * fz / 4.0 / size[0],
+            eta_i * fx * fz / 4.0 / size[1],
+            zeta_i * fx * fy / 4.0 / size[2],
+        ]
+    })
+}
+
+/// Stiffness of the 8-node hexahedral (trilinear brick) element
+///
+/// The element is a `width` x `height` x `depth` box of an isotropic
+/// material with Young's modulus `e` and Poisson's ratio `nu`; DOFs are
+/// (u_x, u_y, u_z) per node, the nodes at z followed by those at z + 1 as
+/// in the 3D grid numbering. Returns the 24 x 24 matrix row-major from
+/// full 2 x 2 x 2 Gauss integration. Returns `undefined` unless the sizes
+/// are positive and -1 < nu < 0.5.
+#[wasm_bindgen]
+pub fn h8_element_stiffness(
+    e: f64,
+    nu: f64,
+    width: f64,
+    height: f64,
+    depth: f64,
+) -> Option<Vec<f64>> {
+    h8_integrated_stiffness(Integration::Full, e, nu, width, height, depth)
+}
+
+/// `h8_element_stiffness` with a choice of `integration`; selective
+/// reduced integration keeps nearly incompressible (rubber-like) bricks
+/// from locking
+#[wasm_bindgen]
+pub fn h8_integrated_stiffness(
+    integration: Integration,
+    e: f64,
+    nu: f64,
+    width: f64,
+    height: f64,
+    depth: f64,
+) -> Option<Vec<f64>> {
+    if !(width > 0.0 && height > 0.0 && depth > 0.0 && nu > -1.0 && nu < 0.5) {
+        return None;
+    }
+    let size = [width, height, depth];
+    let det = width * height * depth / 8.0;
+    let mut ke = vec![0.0; 24 * 24];
+    let mut b = [0.0; 6 * 24];
+    // Adds B^T D B at (xi, eta, zeta) with quadrature weight w
+    let mut add_point = |ke: &mut [f64], [xi, eta, zeta]: [f64; 3], d: &[f64], w: f64| {
+        let grads = gradients([xi, eta, zeta], size);
+        solid_strain_displacement(&grads, &mut b);
+        add_btdb(ke, &b, d, 6, w * det);
+    };
+    let (d, volumetric) = match integration {
+        Integration::Full => (isotropic(e, nu).to_vec(), None),
+        Integration::SelectiveReduced => {
+            let (shear, volumetric) = isotropic_split(e, nu);
+            (shear, Some(volumetric))
+        }
+    };
+    for &zeta in &GAUSS_2 {
+        for &eta in &GAUSS_2 {
+            for &xi in &GAUSS_2 {
+                add_point(&mut ke, [xi, eta, zeta], &d, 1.0);
+            }
+        }
+    }
+    if let Some(volumetric) = volumetric {
+        add_point(&mut ke, [0.0; 3], &volumetric, 8.0);
+    }
+    Some(ke)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rigid_body_modes_and_symmetry() {
+        let (w, h, t) = (1.0, 2.0, 0.5);
+        let ke = h8_element_stiffness(1.0, 0.3, w, h, t).unwrap();
+        let nodes: Vec<[f64; 3]> = CORNERS
+            .iter()
+            .map(|&(x, y, z)| {
+                [
+                    w * (1.0 + x) / 2.0,
+                    h * (1.0 + y) / 2.0,
+                    t * (1.0 + z) / 2.0,
+                ]
+            })
+            .collect();
+        // Three translations, then rotations about z, x and y
+        let mode = |k: usize, p: &[f64; 3]| match k {
+            0..=2 => {
+                let mut t = [0.0; 3];
+                t[k] = 1.0;
+                t
+            }
+            3 => [-p[1], p[0], 0.0],
+            4 => [0.0, -p[2], p[1]],
+            _ => [p[2], 0.0, -p[0]],
+        };
+        for k in 0..6 {
+            let u: Vec<f64> = nodes.iter().flat_map(|p| mode(k, p)).collect();
+            for row in ke.chunks_exact(24) {
+                let f: f64 = row.iter().zip(&u).map(|(k, u)| k * u).sum();
+                assert!(f.abs() < 1e-12);
+            }
+        }
+        for i in 0..24 {
+            assert!(ke[i * 24 + i] > 0.0);
+            for j in 0..i {
+                assert!((ke[i * 24 + j] - ke[j * 24 + i]).abs() < 1e-14);
+            }
+        }
+        assert!(h8_element_stiffness(1.0, 0.5, w, h, t).is_none());
+    }
+
+    #[test]
+    fn test_uniaxial_stress_energy() {
+        // u = (s x, -nu s y, -nu s z) is a uniaxial stress state, exact for
+        // the trilinear element: energy E s^2 V / 2
+        let (e, nu, s) = (7.0, 0.3, 1e-3);
+        let (w, h, t) = (0.5, 1.5, 2.0);
+        let ke = h8_element_stiffness(e, nu, w, h, t).unwrap();
+        let u: Vec<f64> = CORNERS
+            .iter()
+            .flat_map(|&(x, y, z)| {
+                let p = [
+                    w * (1.0 + x) / 2.0,
+                    h * (1.0 + y) / 2.0,
+                    t * (1.0 + z) / 2.0,
+                ];
+                [s * p[0], -nu * s * p[1], -nu * s * p[2]]
+            })
+            .collect();
+        let energy: f64 = ke
+            .chunks_exact(24)
+            .zip(&u)
+            .map(|(row, ui)| ui * row.iter().zip(&u).map(|(k, uj)| k * uj).sum::<f64>())
+            .sum::<f64>()
+            / 2.0;
+        let expected = e * s * s * w * h * t / 2.0;
+        assert!((energy - expected).abs() < 1e-12 * expected.max(1.0));
+    }
+}
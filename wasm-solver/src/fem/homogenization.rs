@@ -0,0 +1,252 @@
+use wasm_bindgen::prelude::*;
+
+use super::thermal::unit_conduction;
+use super::{
+    apply_periodic, assemble_elements, h8_element_stiffness, q4_element_stiffness, simp_moduli,
+};
+use crate::grid::ElementGrid;
+use crate::matrix::CsrMatrix;
+use crate::options::SolverOptions;
+
+/// Effective properties of a periodic unit cell, from
+/// `homogenize_elasticity` or `homogenize_conduction`
+#[wasm_bindgen]
+pub struct Homogenization {
+    cases: usize,
+    tensor: Vec<f64>,
+    fields: Vec<f64>,
+    sensitivities: Vec<f64>,
+    iterations: Vec<u32>,
+    converged: bool,
+}
+
+#[wasm_bindgen]
+impl Homogenization {
+    /// Number of unit load cases m: 3 (6 in 3D) strains for elasticity,
+    /// 2 (3) gradients for conduction
+    #[wasm_bindgen(getter)]
+    pub fn cases(&self) -> usize {
+        self.cases
+    }
+
+    /// Effective tensor, m x m row-major: the elasticity matrix in Voigt
+    /// notation (e_xx, e_yy, g_xy) or (e_xx, e_yy, e_zz, g_xy, g_yz,
+    /// g_zx), or the conductivity matrix
+    #[wasm_bindgen(getter)]
+    pub fn tensor(&self) -> Vec<f64> {
+        self.tensor.clone()
+    }
+
+    /// Cell field of the unit strain (gradient) analysis `case`, the
+    /// affine part included; `undefined` if there is no such case
+    pub fn field(&self, case: usize) -> Option<Vec<f64>> {
+        let n = self.fields.len() / self.cases;
+        (case < self.cases).then(|| self.fields[case * n..(case + 1) * n].to_vec())
+    }
+
+    /// Derivatives of the tensor entries by the element densities, m x m
+    /// row-major per element
+    #[wasm_bindgen(getter)]
+    pub fn sensitivities(&self) -> Vec<f64> {
+        self.sensitivities.clone()
+    }
+
+    /// PCG iterations of every unit case
+    #[wasm_bindgen(getter)]
+    pub fn iterations(&self) -> Vec<u32> {
+        self.iterations.clone()
+    }
+
+    /// Whether every unit case converged
+    #[wasm_bindgen(getter)]
+    pub fn converged(&self) -> bool {
+        self.converged
+    }
+}
+
+/// Solve the m unit cases of the cell `grid` (matrix `k`) and average
+/// their mutual energies over the cell volume
+fn homogenize(
+    grid: &ElementGrid,
+    k: &CsrMatrix,
+    cases: usize,
+    sensitivity: &[f64],
+    options: &SolverOptions,
+) -> Option<Homogenization> {
+    let ([nelx, nely, nelz], _) = grid.shape();
+    let n = grid.n();
+    let mut fields = Vec::with_capacity(cases * n);
+    let mut iterations = Vec::with_capacity(cases);
+    let mut converged = true;
+    for case in 0..cases {
+        let mut unit = vec![0.0; cases];
+        unit[case] = 1.0;
+        let system = apply_periodic(k, nelx, nely, nelz, &unit, &[])?;
+        let x0 = vec![0.0; system.rhs().len()];
+        let result = system.solve_pcg(&x0, options);
+        converged &= result.converged_by().is_some();
+        iterations.push(result.iterations());
+        fields.extend(system.expand(&result.solution())?);
+    }
+
+    // Q_e,ij = u_e,i^T K_e u_e,j
+    let volume = grid.element_count() as f64;
+    let size = grid.local_size();
+    let mut tensor = vec![0.0; cases * cases];
+    let mut sensitivities = vec![0.0; grid.element_count() * cases * cases];
+    let mut dofs = Vec::with_capacity(size);
+    let mut ku = vec![0.0; cases * size];
+    for (e, de) in sensitivities.chunks_exact_mut(cases * cases).enumerate() {
+        grid.element_dofs(e, &mut dofs);
+        for (j, kuj) in ku.chunks_exact_mut(size).enumerate() {
+            let uj = &fields[j * n..(j + 1) * n];
+            for (v, row) in kuj.iter_mut().zip(grid.ke().chunks_exact(size)) {
+                *v = row.iter().zip(&dofs).map(|(a, &d)| a * uj[d]).sum();
+            }
+        }
+        for (ij, d) in de.iter_mut().enumerate() {
+            let ui = &fields[(ij / cases) * n..(ij / cases + 1) * n];
+            let kuj = &ku[(ij % cases) * size..(ij % cases + 1) * size];
+            let q: f64 = dofs.iter().zip(kuj).map(|(&d, v)| ui[d] * v).sum();
+            tensor[ij] += grid.scale(e) * q / volume;
+            *d = sensitivity[e] * q / volume;
+        }
+    }
+    Some(Homogenization {
+        cases,
+        tensor,
+        fields,
+        sensitivities,
+        iterations,
+        converged,
+    })
+}
+
+/// d/drho of the SIMP interpolation P_min + rho^p (P_0 - P_min)
+fn simp_derivatives(densities: &[f64], penal: f64, p0: f64, pmin: f64) -> Vec<f64> {
+    densities
+        .iter()
+        .map(|&rho| penal * rho.powf(penal - 1.0) * (p0 - pmin))
+        .collect()
+}
+
+/// Effective elasticity matrix of a periodic unit cell of unit Q4 (`nelz`
+/// = 0, plane stress) or H8 elements with SIMP moduli
+///
+/// Solves the unit strain analyses of `apply_periodic`, one per Voigt
+/// component, and returns C^H_ij = u_i^T K u_j / |Y| with the density
+/// sensitivities for inverse homogenization. Returns `undefined` if the
+/// grid is empty, `densities` does not hold one value per element or nu
+/// is outside (-1, 1) in 2D and (-1, 0.5) in 3D.
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn homogenize_elasticity(
+    nelx: usize,
+    nely: usize,
+    nelz: usize,
+    densities: &[f64],
+    penal: f64,
+    e0: f64,
+    emin: f64,
+    nu: f64,
+    options: &SolverOptions,
+) -> Option<Homogenization> {
+    if nelx == 0 || nely == 0 || densities.len() != nelx * nely * nelz.max(1) {
+        return None;
+    }
+    let (dims, cases) = if nelz > 0 { (3, 6) } else { (2, 3) };
+    let ke = if nelz > 0 {
+        h8_element_stiffness(1.0, nu, 1.0, 1.0, 1.0)?
+    } else {
+        q4_element_stiffness(1.0, nu, 1.0, 1.0, 1.0)?
+    };
+    let moduli = simp_moduli(densities, penal, e0, emin);
+    let grid = ElementGrid::new([nelx, nely, nelz], dims, &ke, &moduli, &[]);
+    let k = CsrMatrix::from_matrix(assemble_elements(&grid));
+    let sensitivity = simp_derivatives(densities, penal, e0, emin);
+    homogenize(&grid, &k, cases, &sensitivity, options)
+}
+
+/// Effective conductivity matrix of a periodic unit cell with SIMP
+/// conductivities k_min + rho^p (k_0 - k_min), from one unit temperature
+/// gradient analysis per direction; grid and `undefined` as in
+/// `homogenize_elasticity`
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn homogenize_conduction(
+    nelx: usize,
+    nely: usize,
+    nelz: usize,
+    densities: &[f64],
+    penal: f64,
+    k0: f64,
+    kmin: f64,
+    options: &SolverOptions,
+) -> Option<Homogenization> {
+    if nelx == 0 || nely == 0 || densities.len() != nelx * nely * nelz.max(1) {
+        return None;
+    }
+    let cases = if nelz > 0 { 3 } else { 2 };
+    let ke = unit_conduction(nelz);
+    let moduli = simp_moduli(densities, penal, k0, kmin);
+    let grid = ElementGrid::new([nelx, nely, nelz], 1, &ke, &moduli, &[]);
+    let k = CsrMatrix::from_matrix(assemble_elements(&grid));
+    let sensitivity = simp_derivatives(densities, penal, k0, kmin);
+    homogenize(&grid, &k, cases, &sensitivity, options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fem::constitutive::isotropic;
+    use crate::fem::{constitutive_matrix, Formulation};
+
+    fn options() -> SolverOptions {
+        let mut options = SolverOptions::new();
+        options.tol = 1e-12;
+        options.max_iter = 2000;
+        options
+    }
+
+    #[test]
+    fn test_solid_cell_recovers_material() {
+        let h =
+            homogenize_elasticity(4, 4, 0, &[1.0; 16], 3.0, 2.0, 1e-9, 0.3, &options()).unwrap();
+        let d = constitutive_matrix(Formulation::PlaneStress, 2.0, 0.3).unwrap();
+        assert!(h.converged());
+        assert!(h.tensor().iter().zip(&d).all(|(a, b)| (a - b).abs() < 1e-8));
+        assert_eq!(h.sensitivities().len(), 16 * 9);
+
+        let h = homogenize_elasticity(2, 2, 2, &[1.0; 8], 3.0, 2.0, 1e-9, 0.3, &options()).unwrap();
+        let d = isotropic(2.0, 0.3);
+        assert_eq!(h.cases(), 6);
+        assert!(h.tensor().iter().zip(&d).all(|(a, b)| (a - b).abs() < 1e-8));
+        assert!(
+            homogenize_elasticity(2, 2, 2, &[1.0; 8], 3.0, 1.0, 0.0, 0.5, &options()).is_none()
+        );
+    }
+
+    #[test]
+    fn test_laminate_conductivity_bounds() {
+        // Vertical layers of k = 1 and k = 0.1: the arithmetic mean along
+        // the layers and the harmonic mean across them
+        let (nelx, nely) = (4, 3);
+        let densities: Vec<f64> = (0..nelx * nely)
+            .map(|e| if e / nely < 2 { 1.0 } else { 0.1 })
+            .collect();
+        let h =
+            homogenize_conduction(nelx, nely, 0, &densities, 1.0, 1.0, 0.0, &options()).unwrap();
+        let kappa = h.tensor();
+        assert!((kappa[0] - 2.0 / (1.0 + 10.0)).abs() < 1e-9);
+        assert!((kappa[3] - 0.55).abs() < 1e-9);
+        assert!(kappa[1].abs() < 1e-9 && kappa[2].abs() < 1e-9);
+
+        // Along the layers kappa_yy is linear in every density
+        let s = h.sensitivities();
+        assert!(s
+            .chunks_exact(4)
+            .all(|de| (de[3] - 1.0 / 12.0).abs() < 1e-9));
+        assert_eq!(h.field(1).unwrap().len(), 20);
+        assert!(h.field(2).is_none());
+    }
+}
@@ -0,0 +1,501 @@
+use wasm_bindgen::prelude::*;
+
+use crate::grid::{node_count, node_index, ElementGrid};
+
+/// Boundary of a structured grid, by the coordinate held fixed on it
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GridSide {
+    /// x = 0
+    Left = 0,
+    /// x = nelx
+    Right = 1,
+    /// y = 0
+    Bottom = 2,
+    /// y = nely
+    Top = 3,
+    /// z = 0 (3D only)
+    Back = 4,
+    /// z = nelz (3D only)
+    Front = 5,
+}
+
+impl GridSide {
+    /// Axis normal to the side and whether the outward normal points
+    /// along it
+    fn normal(self) -> (usize, bool) {
+        let side = self as usize;
+        (side / 2, side % 2 == 1)
+    }
+}
+
+/// Global force vector of a structured grid, accumulated load by load
+///
+/// The grid has square (cubic in 3D) elements of side `element_size` and
+/// the nodal DOF numbering of `assemble_simp`. Distributed loads are
+/// turned into consistent nodal forces of the bilinear (trilinear)
+/// elements: an edge traction gives each end node of an edge half the
+/// edge's force, a face pressure each corner of a face a quarter.
+#[wasm_bindgen]
+pub struct LoadVector {
+    elements: [usize; 3],
+    element_size: f64,
+    forces: Vec<f64>,
+}
+
+#[wasm_bindgen]
+impl LoadVector {
+    /// Zero loads on a `nelx` x `nely` grid (2 DOFs per node), or a
+    /// `nelx` x `nely` x `nelz` one (3 DOFs per node) if `nelz` > 0
+    #[wasm_bindgen(constructor)]
+    pub fn new(nelx: usize, nely: usize, nelz: usize, element_size: f64) -> LoadVector {
+        let elements = [nelx, nely, nelz];
+        let dofs_per_node = if nelz > 0 { 3 } else { 2 };
+        LoadVector {
+            elements,
+            element_size,
+            forces: vec![0.0; node_count(elements) * dofs_per_node],
+        }
+    }
+
+    fn dimension(&self) -> usize {
+        if self.elements[2] > 0 {
+            3
+        } else {
+            2
+        }
+    }
+
+    /// Add `force` at `node`, one component per DOF
+    fn add_at(&mut self, node: [usize; 3], force: &[f64]) {
+        let dim = self.dimension();
+        let i = node_index(self.elements, node[0], node[1], node[2]) * dim;
+        for (f, &v) in self.forces[i..i + dim].iter_mut().zip(force) {
+            *f += v;
+        }
+    }
+
+    /// Add the force (fx, fy, fz) at node (x, y, z); fz and z are ignored
+    /// in 2D. Returns `false`, adding nothing, if the node is outside the
+    /// grid.
+    pub fn add_point_load(
+        &mut self,
+        x: usize,
+        y: usize,
+        z: usize,
+        fx: f64,
+        fy: f64,
+        fz: f64,
+    ) -> bool {
+        let z = if self.dimension() == 3 { z } else { 0 };
+        if [x, y, z].iter().zip(&self.elements).any(|(&c, &e)| c > e) {
+            return false;
+        }
+        self.add_at([x, y, z], &[fx, fy, fz]);
+        true
+    }
+
+    /// Add a traction (tx, ty), force per unit length, on the element
+    /// edges `from..to` along `side` of a 2D grid, counted from the
+    /// origin. Returns `false`, adding nothing, on a 3D grid, a z side or
+    /// a range past the end of the side.
+    pub fn add_edge_traction(
+        &mut self,
+        side: GridSide,
+        from: usize,
+        to: usize,
+        tx: f64,
+        ty: f64,
+    ) -> bool {
+        let (axis, far) = side.normal();
+        if self.dimension() != 2 || axis > 1 || from > to || to > self.elements[1 - axis] {
+            return false;
+        }
+        let half = [tx, ty].map(|t| t * self.element_size / 2.0);
+        for k in from..to {
+            for along in [k, k + 1] {
+                let mut node = [0; 3];
+                node[axis] = if far { self.elements[axis] } else { 0 };
+                node[1 - axis] = along;
+                self.add_at(node, &half);
+            }
+        }
+        true
+    }
+
+    /// Add a pressure `p` (force per unit area, pushing into the body)
+    /// on the element faces `a0..a1` x `b0..b1` of `side` of a 3D grid,
+    /// a and b being the two in-plane axes in x, y, z order. Returns
+    /// `false`, adding nothing, on a 2D grid or a range past the face.
+    pub fn add_face_pressure(
+        &mut self,
+        side: GridSide,
+        p: f64,
+        a0: usize,
+        a1: usize,
+        b0: usize,
+        b1: usize,
+    ) -> bool {
+        let (axis, far) = side.normal();
+        let [a, b] = match axis {
+            0 => [1, 2],
+            1 => [0, 2],
+            _ => [0, 1],
+        };
+        if self.dimension() != 3
+            || a0 > a1
+            || b0 > b1
+            || a1 > self.elements[a]
+            || b1 > self.elements[b]
+        {
+            return false;
+        }
+        // Inward along the normal axis
+        let mut quarter = [0.0; 3];
+        quarter[axis] = if far { -p } else { p } * self.element_size * self.element_size / 4.0;
+        for i in a0..a1 {
+            for j in b0..b1 {
+                for (da, db) in [(0, 0), (1, 0), (1, 1), (0, 1)] {
+                    let mut node = [0; 3];
+                    node[axis] = if far { self.elements[axis] } else { 0 };
+                    node[a] = i + da;
+                    node[b] = j + db;
+                    self.add_at(node, &quarter);
+                }
+            }
+        }
+        true
+    }
+
+    /// Set every force back to zero
+    pub fn clear(&mut self) {
+        self.forces.iter_mut().for_each(|f| *f = 0.0);
+    }
+
+    /// Number of DOFs
+    #[wasm_bindgen(getter)]
+    pub fn size(&self) -> usize {
+        self.forces.len()
+    }
+
+    /// The force vector, for `solve_pcg` and `apply_dirichlet`
+    #[wasm_bindgen(getter)]
+    pub fn forces(&self) -> Vec<f64> {
+        self.forces.clone()
+    }
+}
+
+/// Grid of `nelx` x `nely` (x `nelz`) elements for the self-weight
+/// functions, its DOFs per node and the share of its element weight each
+/// node carries; `None` unless the arguments match
+fn self_weight_grid(
+    elements: [usize; 3],
+    element_size: f64,
+    densities: &[f64],
+    mass_density: f64,
+) -> Option<(ElementGrid<'static>, usize, f64)> {
+    let [nelx, nely, nelz] = elements;
+    if nelx == 0 || nely == 0 || densities.len() != nelx * nely * nelz.max(1) {
+        return None;
+    }
+    let (dims, nodes) = if nelz > 0 { (3, 8.0) } else { (2, 4.0) };
+    let volume = element_size.powi(dims as i32);
+    let grid = ElementGrid::new(elements, dims, &[], &[], &[]);
+    Some((grid, dims, mass_density * volume / nodes))
+}
+
+/// Consistent nodal loads of the self-weight of a density design
+///
+/// Element e of the grid of `LoadVector` (square or cubic elements of
+/// side `element_size`) weighs rho_e `mass_density` V_e times the gravity
+/// (gx, gy, gz), gz ignored in 2D; bilinear (trilinear) elements give
+/// every node an equal share. The load changes with the design, so add it
+/// to the fixed loads anew every iteration. It grows linearly in rho_e
+/// while SIMP stiffness grows as rho_e^p, which is why self-weight
+/// problems are usually run with a RAMP or modified low-density
+/// interpolation. Returns `undefined` if the grid is empty or `densities`
+/// does not hold one value per element.
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn self_weight_load(
+    nelx: usize,
+    nely: usize,
+    nelz: usize,
+    element_size: f64,
+    densities: &[f64],
+    mass_density: f64,
+    gx: f64,
+    gy: f64,
+    gz: f64,
+) -> Option<Vec<f64>> {
+    let (grid, dims, share) =
+        self_weight_grid([nelx, nely, nelz], element_size, densities, mass_density)?;
+    let gravity = [gx, gy, gz];
+    let mut forces = vec![0.0; grid.n()];
+    let mut dofs = Vec::with_capacity(grid.local_size());
+    for (e, rho) in densities.iter().enumerate() {
+        grid.element_dofs(e, &mut dofs);
+        for (&i, g) in dofs.iter().zip(gravity[..dims].iter().cycle()) {
+            forces[i] += rho * share * g;
+        }
+    }
+    Some(forces)
+}
+
+/// Load term 2 u_e^T df_e/drho_e of the compliance sensitivities under
+/// `self_weight_load`, one value per element for the displacements `u`
+///
+/// dc/drho_e is this term minus the stiffness term of
+/// `element_compliance`. Arguments and `undefined` as
+/// `self_weight_load`, and if `u` does not have one entry per DOF.
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn self_weight_sensitivities(
+    nelx: usize,
+    nely: usize,
+    nelz: usize,
+    element_size: f64,
+    u: &[f64],
+    densities: &[f64],
+    mass_density: f64,
+    gx: f64,
+    gy: f64,
+    gz: f64,
+) -> Option<Vec<f64>> {
+    let (grid, dims, share) =
+        self_weight_grid([nelx, nely, nelz], element_size, densities, mass_density)?;
+    if u.len() != grid.n() {
+        return None;
+    }
+    let gravity = [gx, gy, gz];
+    let mut dofs = Vec::with_capacity(grid.local_size());
+    let sensitivities = (0..grid.element_count())
+        .map(|e| {
+            grid.element_dofs(e, &mut dofs);
+            let work: f64 = dofs
+                .iter()
+                .zip(gravity[..dims].iter().cycle())
+                .map(|(&i, g)| u[i] * g)
+                .sum();
+            2.0 * share * work
+        })
+        .collect();
+    Some(sensitivities)
+}
+
+/// Nodal loads of a fluid pressure `p` acting on the evolving boundary
+/// of a density design
+///
+/// The fluid fills the space beyond `side` and every element with
+/// rho_e < `threshold` it reaches through void elements sharing an edge
+/// (face in 3D); enclosed voids stay dry. Every edge (face) between the
+/// fluid and a solid element (rho_e >= threshold) is loaded with p times
+/// its length (area), pushing into the solid, split equally between its
+/// end (corner) nodes. The grid and DOFs are those of `LoadVector`. The
+/// load is recomputed every iteration; it jumps as the boundary moves
+/// between elements, so it adds no term to the sensitivities. Returns
+/// `undefined` if the grid is empty, `densities` does not hold one
+/// value per element or `side` is a z side of a 2D grid.
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn design_pressure_load(
+    nelx: usize,
+    nely: usize,
+    nelz: usize,
+    element_size: f64,
+    densities: &[f64],
+    threshold: f64,
+    side: GridSide,
+    p: f64,
+) -> Option<Vec<f64>> {
+    let mut loads = LoadVector::new(nelx, nely, nelz, element_size);
+    let elements = loads.elements;
+    let dims = loads.dimension();
+    let (axis, far) = side.normal();
+    if nelx == 0 || nely == 0 || densities.len() != nelx * nely * nelz.max(1) || axis >= dims {
+        return None;
+    }
+    let cell = |e: usize| [e % (nelx * nely) / nely, e % nely, e / (nelx * nely)];
+    let index = |c: [usize; 3]| c[2] * nelx * nely + c[0] * nely + c[1];
+    // Neighbour of element c across its face (a, positive side), `None`
+    // outside the grid
+    let neighbour = |c: [usize; 3], a: usize, positive: bool| {
+        let mut nb = c;
+        if positive {
+            nb[a] += 1;
+            (nb[a] < elements[a]).then(|| index(nb))
+        } else {
+            nb[a] = c[a].checked_sub(1)?;
+            Some(index(nb))
+        }
+    };
+    let solid = |e: usize| densities[e] >= threshold;
+
+    // Flood the voids from the open side
+    let mut wet = vec![false; densities.len()];
+    let mut stack: Vec<usize> = (0..densities.len())
+        .filter(|&e| !solid(e) && neighbour(cell(e), axis, far).is_none())
+        .collect();
+    while let Some(e) = stack.pop() {
+        if std::mem::replace(&mut wet[e], true) {
+            continue;
+        }
+        for a in 0..dims {
+            for positive in [false, true] {
+                if let Some(nb) = neighbour(cell(e), a, positive) {
+                    if !solid(nb) && !wet[nb] {
+                        stack.push(nb);
+                    }
+                }
+            }
+        }
+    }
+
+    let corners: &[(usize, usize)] = if dims == 3 {
+        &[(0, 0), (1, 0), (1, 1), (0, 1)]
+    } else {
+        &[(0, 0), (1, 0)]
+    };
+    let share = p * element_size.powi(dims as i32 - 1) / corners.len() as f64;
+    for e in (0..densities.len()).filter(|&e| solid(e)) {
+        let c = cell(e);
+        for a in 0..dims {
+            for positive in [false, true] {
+                let fluid = match neighbour(c, a, positive) {
+                    Some(nb) => wet[nb],
+                    None => a == axis && positive == far,
+                };
+                if !fluid {
+                    continue;
+                }
+                let mut force = [0.0; 3];
+                force[a] = if positive { -share } else { share };
+                // The other axes of the face, in x, y, z order
+                let mut others = (0..dims).filter(|&b| b != a);
+                let (b0, b1) = (others.next().unwrap(), others.next());
+                for &(d0, d1) in corners {
+                    let mut node = c;
+                    node[a] += positive as usize;
+                    node[b0] += d0;
+                    if let Some(b1) = b1 {
+                        node[b1] += d1;
+                    }
+                    loads.add_at(node, &force);
+                }
+            }
+        }
+    }
+    Some(loads.forces)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_consistent_edge_and_point_loads() {
+        let (nelx, nely) = (6, 3);
+        let mut loads = LoadVector::new(nelx, nely, 0, 0.5);
+        assert_eq!(loads.size(), 2 * 7 * 4);
+        // Downward traction of 2 per length over the whole top edge
+        assert!(loads.add_edge_traction(GridSide::Top, 0, nelx, 0.0, -2.0));
+        let f = loads.forces();
+        let fy: Vec<f64> = (0..=nelx)
+            .map(|x| f[2 * (x * (nely + 1) + nely) + 1])
+            .collect();
+        assert_eq!(fy, [-0.5, -1.0, -1.0, -1.0, -1.0, -1.0, -0.5]);
+        assert_eq!(f.iter().sum::<f64>(), -2.0 * 0.5 * nelx as f64);
+
+        // The classic MBB load: one point force on the top left node
+        loads.clear();
+        assert!(loads.add_point_load(0, nely, 9, 0.0, -1.0, 0.0));
+        assert_eq!(loads.forces()[2 * nely + 1], -1.0);
+        assert!(!loads.add_point_load(nelx + 1, 0, 0, 1.0, 0.0, 0.0));
+        assert!(!loads.add_edge_traction(GridSide::Right, 0, nely + 1, 1.0, 0.0));
+        assert!(!loads.add_face_pressure(GridSide::Front, 1.0, 0, 1, 0, 1));
+    }
+
+    #[test]
+    fn test_face_pressure_pushes_inward() {
+        let mut loads = LoadVector::new(4, 2, 3, 1.0);
+        assert!(loads.add_face_pressure(GridSide::Front, 3.0, 0, 4, 0, 2));
+        let f = loads.forces();
+        // Total force p A, towards -z on the z = nelz face
+        assert_eq!(f.iter().skip(2).step_by(3).sum::<f64>(), -3.0 * 8.0);
+        assert_eq!(f.iter().map(|v| v.abs()).sum::<f64>(), 3.0 * 8.0);
+        // An interior node of the face carries four quarters
+        let centre = node_index([4, 2, 3], 2, 1, 3);
+        assert_eq!(f[3 * centre + 2], -3.0);
+
+        let mut left = LoadVector::new(4, 2, 3, 1.0);
+        assert!(left.add_face_pressure(GridSide::Left, 1.0, 0, 1, 0, 1));
+        assert_eq!(left.forces().iter().sum::<f64>(), 1.0);
+        assert!(!left.add_edge_traction(GridSide::Left, 0, 1, 1.0, 0.0));
+    }
+
+    #[test]
+    fn test_self_weight() {
+        let (nelx, nely, nelz) = (3, 2, 0);
+        let densities = [1.0, 0.5, 0.0, 1.0, 1.0, 0.25];
+        let f = self_weight_load(nelx, nely, nelz, 0.5, &densities, 8.0, 0.0, -10.0, 0.0).unwrap();
+        // Total weight sum rho_e rho V g, all of it along -y
+        let weight = 3.75 * 8.0 * 0.25 * 10.0;
+        assert!((f.iter().skip(1).step_by(2).sum::<f64>() + weight).abs() < 1e-12);
+        assert!(f.iter().step_by(2).all(|&v| v == 0.0));
+        // The bottom left node carries a quarter of element 0
+        assert_eq!(f[1], -8.0 * 0.25 * 10.0 / 4.0);
+
+        // The load term is the derivative of 2 u^T f by each density
+        let u: Vec<f64> = (0..f.len()).map(|i| (i as f64 * 0.7).cos()).collect();
+        let dc =
+            self_weight_sensitivities(nelx, nely, nelz, 0.5, &u, &densities, 8.0, 0.0, -10.0, 0.0)
+                .unwrap();
+        let work = |rho: &[f64]| {
+            let f = self_weight_load(nelx, nely, nelz, 0.5, rho, 8.0, 0.0, -10.0, 0.0).unwrap();
+            2.0 * f.iter().zip(&u).map(|(a, b)| a * b).sum::<f64>()
+        };
+        let mut bumped = densities;
+        bumped[4] += 1.0;
+        assert!((work(&bumped) - work(&densities) - dc[4]).abs() < 1e-12);
+
+        let f3 = self_weight_load(2, 2, 2, 1.0, &[1.0; 8], 1.0, 0.0, 0.0, -1.0).unwrap();
+        assert!((f3.iter().skip(2).step_by(3).sum::<f64>() + 8.0).abs() < 1e-12);
+        assert!(self_weight_load(2, 2, 2, 1.0, &[1.0; 4], 1.0, 0.0, 0.0, -1.0).is_none());
+    }
+
+    #[test]
+    fn test_pressure_follows_boundary() {
+        let (nelx, nely) = (6, 4);
+        // A solid block is loaded as by a traction on its top edge
+        let solid = vec![1.0; nelx * nely];
+        let f = design_pressure_load(nelx, nely, 0, 0.5, &solid, 0.5, GridSide::Top, 2.0).unwrap();
+        let mut top = LoadVector::new(nelx, nely, 0, 0.5);
+        top.add_edge_traction(GridSide::Top, 0, nelx, 0.0, -2.0);
+        assert_eq!(f, top.forces());
+
+        // A slot open to the top is wetted down its walls and bottom; an
+        // enclosed void stays dry
+        let mut densities = solid.clone();
+        for y in 2..nely {
+            densities[2 * nely + y] = 0.0;
+        }
+        densities[4 * nely + 1] = 0.0;
+        let f =
+            design_pressure_load(nelx, nely, 0, 0.5, &densities, 0.5, GridSide::Top, 2.0).unwrap();
+        let fx: Vec<f64> = f.iter().step_by(2).copied().collect();
+        assert!(fx.iter().sum::<f64>().abs() < 1e-12);
+        // Walls of the slot at x = 2 and x = 3 pushed apart
+        assert_eq!(fx[node_index([nelx, nely, 0], 2, 3, 0)], -1.0);
+        assert_eq!(fx[node_index([nelx, nely, 0], 3, 3, 0)], 1.0);
+        // The vertical resultant is p times the projected width
+        let fy: f64 = f.iter().skip(1).step_by(2).sum();
+        assert!((fy + 2.0 * 0.5 * nelx as f64).abs() < 1e-12);
+        let dry = node_index([nelx, nely, 0], 5, 1, 0);
+        assert_eq!((f[2 * dry], f[2 * dry + 1]), (0.0, 0.0));
+
+        // In 3D the faces of a solid block under the front side
+        let f = design_pressure_load(2, 2, 2, 1.0, &[1.0; 8], 0.5, GridSide::Front, 1.0).unwrap();
+        assert_eq!(f.iter().skip(2).step_by(3).sum::<f64>(), -4.0);
+        assert!(design_pressure_load(2, 2, 0, 1.0, &[1.0; 4], 0.5, GridSide::Back, 1.0).is_none());
+    }
+}
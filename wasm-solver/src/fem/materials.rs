@@ -0,0 +1,264 @@
+use wasm_bindgen::prelude::*;
+
+use super::assembly::{add_element, grid_pattern};
+use super::q4::q4_stiffness;
+use super::thermal::unit_conduction;
+use super::{assemble_elements, h8_element_stiffness, Formulation, Integration};
+use crate::grid::ElementGrid;
+use crate::matrix::CsrMatrix;
+
+/// Isotropic material of a `MaterialTable`
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Material {
+    e: f64,
+    nu: f64,
+    density: f64,
+    conductivity: f64,
+}
+
+/// Materials referenced by id from per-element material ids
+///
+/// A grid mixes materials by giving every element a material id next to
+/// its density: element e is material `ids[e]` interpolated by SIMP as
+/// P_min + rho_e^p (P_m - P_min) for its modulus and conductivity. A
+/// non-design region is a block of elements of its own material kept at
+/// density 1. Grids, element numbering and DOFs are those of
+/// `assemble_simp` (2D, `nelz` = 0, plane stress) and its 3D H8
+/// counterpart.
+#[wasm_bindgen]
+#[derive(Default)]
+pub struct MaterialTable {
+    materials: Vec<Material>,
+}
+
+#[wasm_bindgen]
+impl MaterialTable {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> MaterialTable {
+        MaterialTable::default()
+    }
+
+    /// Add a material of Young's modulus `e`, Poisson's ratio `nu`, mass
+    /// density `density` and thermal conductivity `conductivity`, returning
+    /// its id (ids count from 0 in order of addition). Returns `undefined`,
+    /// adding nothing, unless e > 0, -1 < nu < 0.5 and the density and
+    /// conductivity are non-negative.
+    pub fn add(&mut self, e: f64, nu: f64, density: f64, conductivity: f64) -> Option<u32> {
+        if !(e > 0.0 && nu > -1.0 && nu < 0.5 && density >= 0.0 && conductivity >= 0.0) {
+            return None;
+        }
+        self.materials.push(Material {
+            e,
+            nu,
+            density,
+            conductivity,
+        });
+        Some(self.materials.len() as u32 - 1)
+    }
+
+    /// Number of materials
+    pub fn count(&self) -> usize {
+        self.materials.len()
+    }
+
+    /// Young's modulus of material `id`, `undefined` if there is none
+    pub fn young_modulus(&self, id: u32) -> Option<f64> {
+        self.materials.get(id as usize).map(|m| m.e)
+    }
+
+    pub fn poisson_ratio(&self, id: u32) -> Option<f64> {
+        self.materials.get(id as usize).map(|m| m.nu)
+    }
+
+    pub fn mass_density(&self, id: u32) -> Option<f64> {
+        self.materials.get(id as usize).map(|m| m.density)
+    }
+
+    pub fn conductivity(&self, id: u32) -> Option<f64> {
+        self.materials.get(id as usize).map(|m| m.conductivity)
+    }
+
+    /// Global stiffness of a grid of unit elements of mixed materials
+    ///
+    /// Element e has the modulus E_min + rho_e^p (E_m - E_min) of its
+    /// material m = `ids[e]` and that material's Poisson's ratio. No DOF
+    /// is fixed. Returns `undefined` if the grid is empty, `ids` or
+    /// `densities` do not hold one value per element or an id is not in
+    /// the table.
+    #[allow(clippy::too_many_arguments)]
+    pub fn assemble_stiffness(
+        &self,
+        nelx: usize,
+        nely: usize,
+        nelz: usize,
+        ids: &[u32],
+        densities: &[f64],
+        penal: f64,
+        emin: f64,
+    ) -> Option<CsrMatrix> {
+        let elements = [nelx, nely, nelz];
+        if !self.valid(elements, ids, densities) {
+            return None;
+        }
+        let dims = if nelz > 0 { 3 } else { 2 };
+        // Unit-modulus element matrix of every material
+        let kes: Vec<Vec<f64>> = self
+            .materials
+            .iter()
+            .map(|m| {
+                if dims == 3 {
+                    h8_element_stiffness(1.0, m.nu, 1.0, 1.0, 1.0).unwrap()
+                } else {
+                    let plane = Formulation::PlaneStress;
+                    q4_stiffness(plane, Integration::Full, 1.0, m.nu, 1.0, 1.0, 1.0, 0.0)
+                }
+            })
+            .collect();
+        let grid = ElementGrid::new(elements, dims, &[], &[], &[]);
+        let mut k = grid_pattern(elements, dims);
+        let mut dofs = Vec::with_capacity(grid.local_size());
+        for (el, (&id, &rho)) in ids.iter().zip(densities).enumerate() {
+            let e = self.materials[id as usize].e;
+            grid.element_dofs(el, &mut dofs);
+            let modulus = emin + rho.powf(penal) * (e - emin);
+            add_element(&mut k, &dofs, &kes[id as usize], modulus, |_| false);
+        }
+        Some(CsrMatrix::from_matrix(k))
+    }
+
+    /// Global conductivity matrix of a grid of mixed materials, element e
+    /// with k_min + rho_e^p (k_m - k_min) for its material m; arguments
+    /// and `undefined` as `assemble_stiffness`
+    #[allow(clippy::too_many_arguments)]
+    pub fn assemble_conduction(
+        &self,
+        nelx: usize,
+        nely: usize,
+        nelz: usize,
+        ids: &[u32],
+        densities: &[f64],
+        penal: f64,
+        kmin: f64,
+    ) -> Option<CsrMatrix> {
+        let elements = [nelx, nely, nelz];
+        if !self.valid(elements, ids, densities) {
+            return None;
+        }
+        let ke = unit_conduction(nelz);
+        let scales: Vec<f64> = ids
+            .iter()
+            .zip(densities)
+            .map(|(&id, &rho)| {
+                let k = self.materials[id as usize].conductivity;
+                kmin + rho.powf(penal) * (k - kmin)
+            })
+            .collect();
+        let grid = ElementGrid::new(elements, 1, &ke, &scales, &[]);
+        Some(CsrMatrix::from_matrix(assemble_elements(&grid)))
+    }
+
+    /// Mass sum_e rho_e density_m of a design of unit elements (area in
+    /// 2D, volume in 3D), `undefined` as `assemble_stiffness`
+    pub fn mass(
+        &self,
+        nelx: usize,
+        nely: usize,
+        nelz: usize,
+        ids: &[u32],
+        densities: &[f64],
+    ) -> Option<f64> {
+        self.valid([nelx, nely, nelz], ids, densities).then(|| {
+            ids.iter()
+                .zip(densities)
+                .map(|(&id, rho)| rho * self.materials[id as usize].density)
+                .sum()
+        })
+    }
+}
+
+impl MaterialTable {
+    /// Whether the grid is non-empty and `ids` and `densities` give every
+    /// element a material of the table and a density
+    fn valid(&self, elements: [usize; 3], ids: &[u32], densities: &[f64]) -> bool {
+        let count = elements[0] * elements[1] * elements[2].max(1);
+        count > 0
+            && ids.len() == count
+            && densities.len() == count
+            && ids.iter().all(|&id| (id as usize) < self.materials.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fem::{assemble_conduction_simp, assemble_simp};
+
+    #[test]
+    fn test_single_material_matches_simp() {
+        let (nelx, nely) = (6, 4);
+        let densities: Vec<f64> = (0..nelx * nely)
+            .map(|e| 0.2 + (e % 7) as f64 / 9.0)
+            .collect();
+        let mut table = MaterialTable::new();
+        let steel = table.add(210.0, 0.3, 7.8, 50.0).unwrap();
+        assert!(table.add(1.0, 0.5, 1.0, 1.0).is_none());
+        assert_eq!(table.count(), 1);
+
+        let ids = vec![steel; nelx * nely];
+        let k = table
+            .assemble_stiffness(nelx, nely, 0, &ids, &densities, 3.0, 1e-6)
+            .unwrap();
+        let expected = assemble_simp(nelx, nely, &densities, 3.0, 210.0, 1e-6, 0.3).unwrap();
+        assert!(k.same_pattern(&expected));
+        assert!(k
+            .values()
+            .iter()
+            .zip(expected.values())
+            .all(|(a, b)| (a - b).abs() < 1e-9));
+
+        let kt = table
+            .assemble_conduction(nelx, nely, 0, &ids, &densities, 3.0, 1e-3)
+            .unwrap();
+        let expected =
+            assemble_conduction_simp(nelx, nely, 0, &densities, 3.0, 50.0, 1e-3).unwrap();
+        assert!(kt
+            .values()
+            .iter()
+            .zip(expected.values())
+            .all(|(a, b)| (a - b).abs() < 1e-12));
+        let mass = table.mass(nelx, nely, 0, &ids, &densities).unwrap();
+        assert!((mass - 7.8 * densities.iter().sum::<f64>()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_mixed_materials() {
+        // A stiff bottom layer as a non-design region under a design region
+        let (nelx, nely, nelz) = (3, 2, 2);
+        let mut table = MaterialTable::new();
+        let soft = table.add(1.0, 0.45, 1.0, 0.1).unwrap();
+        let stiff = table.add(100.0, 0.2, 3.0, 10.0).unwrap();
+        let ids: Vec<u32> = (0..nelx * nely * nelz)
+            .map(|e| if e % nely == 0 { stiff } else { soft })
+            .collect();
+        let densities = vec![1.0; ids.len()];
+        let k = table
+            .assemble_stiffness(nelx, nely, nelz, &ids, &densities, 3.0, 1e-9)
+            .unwrap();
+        assert_eq!(k.size(), 3 * 4 * 3 * 3);
+        // Rigid translations stay free
+        let ux: Vec<f64> = (0..k.size())
+            .map(|i| if i % 3 == 0 { 1.0 } else { 0.0 })
+            .collect();
+        assert!(k.spmv(&ux).iter().all(|v| v.abs() < 1e-9));
+        // A bottom-row node is stiffer than a top-row one
+        let dense = k.to_dense().unwrap();
+        let (bottom, top) = (3 * 3, 3 * 5);
+        assert!(dense[bottom * k.size() + bottom] > 10.0 * dense[top * k.size() + top]);
+
+        let mass = table.mass(nelx, nely, nelz, &ids, &densities).unwrap();
+        assert!((mass - (6.0 * 3.0 + 6.0)).abs() < 1e-12);
+        assert!(table
+            .assemble_conduction(nelx, nely, nelz, &[2; 12], &densities, 3.0, 0.0)
+            .is_none());
+    }
+}
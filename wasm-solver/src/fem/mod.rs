@@ -0,0 +1,90 @@
+//! Finite elements for structured-grid topology optimization: element
+//! stiffness matrices, global assembly, boundary conditions, loads and
+//! post-processing
+//!
+//! Elements are numbered as in `grid`: the local nodes of an element are
+//! (x, y), (x + 1, y), (x + 1, y + 1), (x, y + 1), with y pointing up as in
+//! `getElementDOFs` (in 3D the same four at z, then at z + 1), and DOFs are
+//! ordered node by node. Element matrices are row-major and integrated
+//! with Gauss quadrature, so they can be passed straight to the
+//! matrix-free grid operator or to element assembly.
+
+mod assembly;
+mod boundary;
+mod cases;
+mod compliance;
+mod constitutive;
+mod h8;
+mod homogenization;
+mod loads;
+mod materials;
+mod periodic;
+mod q4;
+mod quadratic;
+mod stress;
+mod tet;
+mod thermal;
+mod thermoelastic;
+
+pub(crate) use assembly::{assemble_elements, simp_moduli};
+pub use assembly::{assemble_simp, assemble_simp_with_formulation};
+pub use boundary::*;
+pub use cases::*;
+pub use compliance::*;
+pub use constitutive::*;
+pub use h8::*;
+pub use homogenization::*;
+pub use loads::*;
+pub use materials::*;
+pub use periodic::*;
+pub use q4::*;
+pub use quadratic::*;
+pub use stress::*;
+pub(crate) use stress::{centre_stress_matrix, von_mises};
+pub use tet::*;
+pub use thermal::*;
+pub use thermoelastic::*;
+
+/// Gauss-Legendre points on [-1, 1] for two points per direction, exact
+/// for the stiffness of bilinear and trilinear elements; both weights are 1
+const GAUSS_2: [f64; 2] = [-0.577_350_269_189_625_8, 0.577_350_269_189_625_8];
+
+/// Strain-displacement matrix B (6 x 3m, row-major) of a solid element
+/// from the gradients (d/dx, d/dy, d/dz) of its m shape functions, for
+/// strains (e_xx, e_yy, e_zz, g_xy, g_yz, g_zx)
+fn solid_strain_displacement(grads: &[[f64; 3]], b: &mut [f64]) {
+    let n = 3 * grads.len();
+    b.fill(0.0);
+    for (i, grad) in grads.iter().enumerate() {
+        let c = 3 * i;
+        for k in 0..3 {
+            b[k * n + c + k] = grad[k];
+        }
+        // Shear rows: (x, y), (y, z), (z, x)
+        for (row, (p, q)) in [(0, 1), (1, 2), (2, 0)].into_iter().enumerate() {
+            b[(3 + row) * n + c + p] = grad[q];
+            b[(3 + row) * n + c + q] = grad[p];
+        }
+    }
+}
+
+/// ke += scale B^T D B, with B row-major `strains` x n and D row-major
+/// `strains` x `strains`
+fn add_btdb(ke: &mut [f64], b: &[f64], d: &[f64], strains: usize, scale: f64) {
+    let n = b.len() / strains;
+    // D B, row-major strains x n
+    let mut db = vec![0.0; b.len()];
+    for (i, row) in db.chunks_exact_mut(n).enumerate() {
+        for (k, bk) in b.chunks_exact(n).enumerate() {
+            let dik = d[i * strains + k];
+            row.iter_mut().zip(bk).for_each(|(v, &bkj)| *v += dik * bkj);
+        }
+    }
+    for (k, bk) in b.chunks_exact(n).enumerate() {
+        let dbk = &db[k * n..(k + 1) * n];
+        for (i, row) in ke.chunks_exact_mut(n).enumerate() {
+            let bki = scale * bk[i];
+            row.iter_mut().zip(dbk).for_each(|(v, &w)| *v += bki * w);
+        }
+    }
+}
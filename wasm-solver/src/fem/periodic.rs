@@ -0,0 +1,253 @@
+use wasm_bindgen::prelude::*;
+
+use crate::grid::node_count;
+use crate::kernels::SparseMatrix;
+use crate::krylov::run_solver;
+use crate::matrix::CsrMatrix;
+use crate::options::{SolverKind, SolverOptions};
+use crate::SolveResult;
+
+/// Unit-cell system K~ u~ = f~ with periodic boundary conditions, from
+/// `apply_periodic`
+///
+/// The field of the cell is split as u = E x + u~ into the affine part of
+/// the macroscopic strain (or temperature gradient) E and a periodic
+/// fluctuation u~. Only the fluctuation of the master nodes, those with
+/// x < nelx, y < nely and z < nelz, is unknown: a slave node on the right,
+/// top or front face takes the fluctuation of its master on the opposite
+/// face, so u_slave - u_master = E (x_slave - x_master). The fluctuation
+/// of the corner node 0 is fixed to remove the rigid translations.
+#[wasm_bindgen]
+pub struct PeriodicSystem {
+    matrix: CsrMatrix,
+    rhs: Vec<f64>,
+    /// Reduced DOF of every DOF of the cell
+    master: Vec<usize>,
+    /// Affine field E x at every DOF of the cell
+    affine: Vec<f64>,
+}
+
+#[wasm_bindgen]
+impl PeriodicSystem {
+    /// Copy of the reduced matrix T^T K T over the master DOFs
+    pub fn matrix(&self) -> CsrMatrix {
+        CsrMatrix::from_matrix(SparseMatrix::from_csr(&self.matrix.csr()))
+    }
+
+    /// Reduced right-hand side T^T (f - K E x)
+    #[wasm_bindgen(getter)]
+    pub fn rhs(&self) -> Vec<f64> {
+        self.rhs.clone()
+    }
+
+    /// Affine field E x of the macroscopic strain at every DOF of the cell
+    #[wasm_bindgen(getter)]
+    pub fn affine(&self) -> Vec<f64> {
+        self.affine.clone()
+    }
+
+    /// `solve_pcg` on the reduced system; `x0` and the solution hold the
+    /// fluctuation of the master DOFs, turned into the cell field by
+    /// `expand`
+    pub fn solve_pcg(&self, x0: &[f64], options: &SolverOptions) -> SolveResult {
+        run_solver(SolverKind::Pcg, &self.matrix.csr(), &self.rhs, x0, options)
+    }
+
+    /// Field E x + u~ at every DOF of the cell from the fluctuation of the
+    /// master DOFs, `undefined` if `reduced` has the wrong length
+    pub fn expand(&self, reduced: &[f64]) -> Option<Vec<f64>> {
+        (reduced.len() == self.rhs.len()).then(|| {
+            self.master
+                .iter()
+                .zip(&self.affine)
+                .map(|(&m, a)| a + reduced[m])
+                .collect()
+        })
+    }
+}
+
+/// Reduced DOF of every DOF of a periodic cell, and their number
+fn periodic_masters(elements: [usize; 3], dofs_per_node: usize) -> (Vec<usize>, usize) {
+    let [nelx, nely, nelz] = elements;
+    let (periods, masters) = ([nelx, nely, nelz.max(1)], nelx * nely * nelz.max(1));
+    let mut master = Vec::with_capacity(node_count(elements) * dofs_per_node);
+    for z in 0..=nelz {
+        for x in 0..=nelx {
+            for y in 0..=nely {
+                let node =
+                    (z % periods[2]) * nelx * nely + (x % periods[0]) * nely + y % periods[1];
+                master.extend((0..dofs_per_node).map(|d| node * dofs_per_node + d));
+            }
+        }
+    }
+    (master, masters * dofs_per_node)
+}
+
+/// Macroscopic gradient matrix (row-major `dofs_per_node` x dims) of a
+/// Voigt strain, with engineering shears, or of a temperature gradient
+fn macro_gradient(macro_strain: &[f64], dims: usize, dofs_per_node: usize) -> Option<Vec<f64>> {
+    match (dims, dofs_per_node, macro_strain) {
+        (_, 1, g) if g.len() == dims => Some(g.to_vec()),
+        (2, 2, &[exx, eyy, gxy]) => Some(vec![exx, gxy / 2.0, gxy / 2.0, eyy]),
+        (3, 3, &[exx, eyy, ezz, gxy, gyz, gzx]) => Some(vec![
+            exx,
+            gxy / 2.0,
+            gzx / 2.0,
+            gxy / 2.0,
+            eyy,
+            gyz / 2.0,
+            gzx / 2.0,
+            gyz / 2.0,
+            ezz,
+        ]),
+        _ => None,
+    }
+}
+
+/// Tie the opposite faces of the unit cell `nelx` x `nely` (x `nelz`) of
+/// K u = f periodically under the prescribed macroscopic strain
+///
+/// K is the grid matrix of the cell, e.g. from `assemble_simp`, with 2 (3
+/// in 3D, `nelz` > 0) DOFs per node for elasticity or 1 for conduction.
+/// `macro_strain` is (e_xx, e_yy, g_xy) in 2D or (e_xx, e_yy, e_zz, g_xy,
+/// g_yz, g_zx) in 3D, with engineering shear strains, or the temperature
+/// gradient for conduction. `f` holds external nodal loads, or is empty
+/// for none. Returns `undefined` if K does not match the grid, or
+/// `macro_strain` or `f` have the wrong length.
+#[wasm_bindgen]
+pub fn apply_periodic(
+    k: &CsrMatrix,
+    nelx: usize,
+    nely: usize,
+    nelz: usize,
+    macro_strain: &[f64],
+    f: &[f64],
+) -> Option<PeriodicSystem> {
+    let elements = [nelx, nely, nelz];
+    let a = k.csr();
+    let (n, nodes) = (a.n(), node_count(elements));
+    let dims = if nelz > 0 { 3 } else { 2 };
+    if nelx == 0 || nely == 0 || !n.is_multiple_of(nodes) || !(f.is_empty() || f.len() == n) {
+        return None;
+    }
+    let dofs_per_node = n / nodes;
+    let gradient = macro_gradient(macro_strain, dims, dofs_per_node)?;
+
+    let mut affine = Vec::with_capacity(n);
+    for z in 0..=nelz {
+        for x in 0..=nelx {
+            for y in 0..=nely {
+                let coords = [x as f64, y as f64, z as f64];
+                affine.extend(
+                    gradient
+                        .chunks_exact(dims)
+                        .map(|row| row.iter().zip(&coords).map(|(g, c)| g * c).sum::<f64>()),
+                );
+            }
+        }
+    }
+    let mut rhs_full = vec![0.0; n];
+    a.spmv(&affine, &mut rhs_full);
+    for (i, r) in rhs_full.iter_mut().enumerate() {
+        *r = f.get(i).copied().unwrap_or(0.0) - *r;
+    }
+
+    // T^T K T and T^T (f - K E x), the DOFs of master node 0 fixed
+    let (master, size) = periodic_masters(elements, dofs_per_node);
+    let fixed = |i: usize| i < dofs_per_node;
+    let mut entries = Vec::with_capacity(a.values.len());
+    let mut rhs = vec![0.0; size];
+    for (i, &mi) in master.iter().enumerate() {
+        if fixed(mi) {
+            continue;
+        }
+        rhs[mi] += rhs_full[i];
+        for q in a.row_ptr[i] as usize..a.row_ptr[i + 1] as usize {
+            let mj = master[a.col_indices[q] as usize];
+            if !fixed(mj) {
+                entries.push((mi, mj as u32, a.values[q]));
+            }
+        }
+    }
+    entries.extend((0..dofs_per_node).map(|d| (d, d as u32, 1.0)));
+    Some(PeriodicSystem {
+        matrix: CsrMatrix::from_matrix(SparseMatrix::from_triplets(size, size, entries)),
+        rhs,
+        master,
+        affine,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fem::{assemble_conduction_simp, assemble_simp};
+    use crate::grid::node_index;
+
+    fn options() -> SolverOptions {
+        let mut options = SolverOptions::new();
+        options.tol = 1e-12;
+        options.max_iter = 2000;
+        options
+    }
+
+    #[test]
+    fn test_homogeneous_cell_deforms_affinely() {
+        let (nelx, nely) = (4, 3);
+        let k = assemble_simp(nelx, nely, &[1.0; 12], 3.0, 1.0, 1e-9, 0.3).unwrap();
+        let system = apply_periodic(&k, nelx, nely, 0, &[0.01, -0.02, 0.005], &[]).unwrap();
+        assert_eq!(system.rhs().len(), 2 * nelx * nely);
+        let result = system.solve_pcg(&vec![0.0; system.rhs().len()], &options());
+        assert!(result.solution().iter().all(|v| v.abs() < 1e-10));
+        let u = system.expand(&result.solution()).unwrap();
+        assert!(u
+            .iter()
+            .zip(system.affine())
+            .all(|(a, b)| (a - b).abs() < 1e-10));
+
+        let kt = assemble_conduction_simp(nelx, nely, 0, &[1.0; 12], 3.0, 1.0, 1e-3).unwrap();
+        assert!(apply_periodic(&kt, nelx, nely, 0, &[1.0, 0.0], &[]).is_some());
+        assert!(apply_periodic(&kt, nelx, nely, 0, &[1.0, 0.0, 0.0], &[]).is_none());
+        assert!(apply_periodic(&k, nelx + 1, nely, 0, &[0.0; 3], &[]).is_none());
+    }
+
+    #[test]
+    fn test_heterogeneous_cell_is_periodic() {
+        // Soft inclusion in the middle of a stiff 6 x 6 cell under shear
+        let (nelx, nely) = (6, 6);
+        let densities: Vec<f64> = (0..nelx * nely)
+            .map(|e| {
+                let (x, y) = (e / nely, e % nely);
+                if (2..4).contains(&x) && (2..4).contains(&y) {
+                    0.1
+                } else {
+                    1.0
+                }
+            })
+            .collect();
+        let k = assemble_simp(nelx, nely, &densities, 3.0, 1.0, 1e-9, 0.3).unwrap();
+        let strain = [0.0, 0.0, 0.01];
+        let system = apply_periodic(&k, nelx, nely, 0, &strain, &[]).unwrap();
+        let result = system.solve_pcg(&vec![0.0; system.rhs().len()], &options());
+        assert!(result.converged_by().is_some());
+        let fluctuation = result.solution();
+        assert!(fluctuation.iter().any(|v| v.abs() > 1e-6));
+        let u = system.expand(&fluctuation).unwrap();
+
+        // Opposite faces differ by the macroscopic jump E (x_s - x_m)
+        let elements = [nelx, nely, 0];
+        for y in 0..=nely {
+            let (left, right) = (
+                node_index(elements, 0, y, 0),
+                node_index(elements, nelx, y, 0),
+            );
+            assert!((u[2 * right] - u[2 * left]).abs() < 1e-12);
+            assert!((u[2 * right + 1] - u[2 * left + 1] - 0.005 * nelx as f64).abs() < 1e-12);
+        }
+        // The cell field is in equilibrium away from the tied faces
+        let r = k.spmv(&u);
+        let inner = node_index(elements, 3, 3, 0);
+        assert!(r[2 * inner].abs() < 1e-9 && r[2 * inner + 1].abs() < 1e-9);
+        assert_eq!(system.matrix().size(), fluctuation.len());
+    }
+}
@@ -0,0 +1,229 @@
+use wasm_bindgen::prelude::*;
+
+use super::{add_btdb, Formulation, Integration, GAUSS_2};
+
+/// Corners of the reference square in local node order
+const CORNERS: [(f64, f64); 4] = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)];
+
+/// Bilinear shape functions of the local nodes at (xi, eta)
+pub(super) fn shape(xi: f64, eta: f64) -> [f64; 4] {
+    CORNERS.map(|(xi_i, eta_i)| (1.0 + xi * xi_i) * (1.0 + eta * eta_i) / 4.0)
+}
+
+/// Gradients (d/dx, d/dy) of the shape functions of a `width` x `height`
+/// element at the reference point (xi, eta)
+pub(super) fn gradients(xi: f64, eta: f64, width: f64, height: f64) -> [[f64; 2]; 4] {
+    // Jacobian of the map from the reference square: x = width (1 + xi) / 2
+    CORNERS.map(|(xi_i, eta_i)| {
+        [
+            xi_i * (1.0 + eta * eta_i) / 2.0 / width,
+            eta_i * (1.0 + xi * xi_i) / 2.0 / height,
+        ]
+    })
+}
+
+/// Strain-displacement matrix B (3 x 8, row-major) of a `width` x
+/// `height` element at the reference point (xi, eta), mapping the nodal
+/// displacements to (e_xx, e_yy, g_xy)
+pub(super) fn strain_displacement(xi: f64, eta: f64, width: f64, height: f64, b: &mut [f64; 24]) {
+    for (i, [dx, dy]) in gradients(xi, eta, width, height).into_iter().enumerate() {
+        b[2 * i] = dx;
+        b[8 + 2 * i + 1] = dy;
+        b[16 + 2 * i] = dy;
+        b[16 + 2 * i + 1] = dx;
+    }
+}
+
+/// Q4 stiffness of `formulation`, arguments as in
+/// `q4_integrated_stiffness`
+#[allow(clippy::too_many_arguments)]
+pub(super) fn q4_stiffness(
+    formulation: Formulation,
+    integration: Integration,
+    e: f64,
+    nu: f64,
+    thickness: f64,
+    width: f64,
+    height: f64,
+    radius: f64,
+) -> Vec<f64> {
+    let strains = formulation.strains();
+    let det = width * height / 4.0;
+    let mut ke = vec![0.0; 64];
+    let mut plane = [0.0; 24];
+    let mut b = vec![0.0; 8 * strains];
+    // Adds B^T D B at (xi, eta) with quadrature weight w
+    let mut add_point = |ke: &mut [f64], xi: f64, eta: f64, d: &[f64], w: f64| {
+        strain_displacement(xi, eta, width, height, &mut plane);
+        b[..24].copy_from_slice(&plane);
+        let weight = if formulation == Formulation::Axisymmetric {
+            // Hoop strain u_r / r; the volume element is r dr dz per radian
+            let r = radius + width * (1.0 + xi) / 2.0;
+            for (i, n) in shape(xi, eta).iter().enumerate() {
+                b[24 + 2 * i] = n / r;
+            }
+            r
+        } else {
+            thickness
+        };
+        add_btdb(ke, &b, d, strains, w * weight * det);
+    };
+    let (d, volumetric) = match integration {
+        Integration::Full => (formulation.matrix(e, nu), None),
+        Integration::SelectiveReduced => {
+            let (shear, volumetric) = formulation.split(e, nu);
+            (shear, Some(volumetric))
+        }
+    };
+    for &eta in &GAUSS_2 {
+        for &xi in &GAUSS_2 {
+            add_point(&mut ke, xi, eta, &d, 1.0);
+        }
+    }
+    if let Some(volumetric) = volumetric {
+        add_point(&mut ke, 0.0, 0.0, &volumetric, 4.0);
+    }
+    ke
+}
+
+/// Stiffness of the plane-stress Q4 (bilinear quadrilateral) element
+///
+/// The element is a `width` x `height` rectangle of the given
+/// `thickness`, made of an isotropic material with Young's modulus `e` and
+/// Poisson's ratio `nu`; DOFs are (u_x, u_y) per node, nodes counter-
+/// clockwise from the bottom left. Returns the 8 x 8 matrix row-major from
+/// 2 x 2 Gauss integration, which for a unit square with E = 1 is the
+/// `KE` of the 88-line code. Returns `undefined` unless the sizes are
+/// positive and -1 < nu < 1.
+#[wasm_bindgen]
+pub fn q4_element_stiffness(
+    e: f64,
+    nu: f64,
+    thickness: f64,
+    width: f64,
+    height: f64,
+) -> Option<Vec<f64>> {
+    q4_formulation_stiffness(
+        Formulation::PlaneStress,
+        e,
+        nu,
+        thickness,
+        width,
+        height,
+        0.0,
+    )
+}
+
+/// Stiffness of the Q4 element under plane stress, plane strain or
+/// axisymmetry
+///
+/// As `q4_element_stiffness`, with `formulation` choosing the material
+/// matrix. An axisymmetric element spans the radii `radius` to
+/// `radius + width` (x is r, y the axis direction z), DOFs (u_r, u_z);
+/// its stiffness is per radian, so 2 pi times it is that of the full
+/// ring, and `thickness` is not used. Returns `undefined` unless the sizes
+/// are positive, `radius` is not negative and `nu` is admissible (below
+/// 0.5 except in plane stress).
+#[wasm_bindgen]
+pub fn q4_formulation_stiffness(
+    formulation: Formulation,
+    e: f64,
+    nu: f64,
+    thickness: f64,
+    width: f64,
+    height: f64,
+    radius: f64,
+) -> Option<Vec<f64>> {
+    q4_integrated_stiffness(
+        formulation,
+        Integration::Full,
+        e,
+        nu,
+        thickness,
+        width,
+        height,
+        radius,
+    )
+}
+
+/// `q4_formulation_stiffness` with a choice of `integration`, selective
+/// reduced integration avoiding the locking of nearly incompressible
+/// materials in plane strain and axisymmetry
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn q4_integrated_stiffness(
+    formulation: Formulation,
+    integration: Integration,
+    e: f64,
+    nu: f64,
+    thickness: f64,
+    width: f64,
+    height: f64,
+    radius: f64,
+) -> Option<Vec<f64>> {
+    let thickness = match formulation {
+        Formulation::Axisymmetric => 1.0,
+        _ => thickness,
+    };
+    let valid = width > 0.0 && height > 0.0 && thickness > 0.0 && radius >= 0.0;
+    (valid && formulation.admits(nu)).then(|| {
+        q4_stiffness(
+            formulation,
+            integration,
+            e,
+            nu,
+            thickness,
+            width,
+            height,
+            radius,
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::q4_stiffness;
+
+    #[test]
+    fn test_unit_square_matches_top88() {
+        let nu = 0.3;
+        let ke = q4_element_stiffness(1.0, nu, 1.0, 1.0, 1.0).unwrap();
+        let expected = q4_stiffness(nu);
+        assert!(ke.iter().zip(&expected).all(|(u, v)| (u - v).abs() < 1e-14));
+
+        // In 2D a square's stiffness does not depend on its size, and is
+        // linear in E and the thickness
+        let scaled = q4_element_stiffness(210e3, nu, 0.5, 4.0, 4.0).unwrap();
+        assert!(scaled
+            .iter()
+            .zip(&expected)
+            .all(|(u, v)| (u - 105e3 * v).abs() < 1e-9));
+        assert!(q4_element_stiffness(1.0, 1.0, 1.0, 1.0, 1.0).is_none());
+        assert!(q4_element_stiffness(1.0, nu, 1.0, 0.0, 1.0).is_none());
+    }
+
+    #[test]
+    fn test_rectangle_rigid_body_modes() {
+        let (w, h) = (2.0, 0.5);
+        let ke = q4_element_stiffness(1.0, 0.25, 1.0, w, h).unwrap();
+        let corners = [(0.0, 0.0), (w, 0.0), (w, h), (0.0, h)];
+        let modes: [Vec<f64>; 3] = [
+            corners.iter().flat_map(|_| [1.0, 0.0]).collect(),
+            corners.iter().flat_map(|_| [0.0, 1.0]).collect(),
+            corners.iter().flat_map(|&(x, y)| [-y, x]).collect(),
+        ];
+        for mode in &modes {
+            for row in ke.chunks_exact(8) {
+                let f: f64 = row.iter().zip(mode).map(|(k, u)| k * u).sum();
+                assert!(f.abs() < 1e-12);
+            }
+        }
+        for i in 0..8 {
+            assert!(ke[i * 8 + i] > 0.0);
+            for j in 0..8 {
+                assert!((ke[i * 8 + j] - ke[j * 8 + i]).abs() < 1e-14);
+            }
+        }
+    }
+}
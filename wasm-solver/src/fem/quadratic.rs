@@ -0,0 +1,349 @@
+use wasm_bindgen::prelude::*;
+
+use super::assembly::simp_moduli;
+use super::{add_btdb, Formulation};
+use crate::kernels::SparseMatrix;
+use crate::matrix::CsrMatrix;
+
+/// Reference coordinates of the local nodes: corners counter-clockwise
+/// from the bottom left, the mid-side nodes of the bottom, right, top and
+/// left sides, then the Q9 centre
+const NODES: [(f64, f64); 9] = [
+    (-1.0, -1.0),
+    (1.0, -1.0),
+    (1.0, 1.0),
+    (-1.0, 1.0),
+    (0.0, -1.0),
+    (1.0, 0.0),
+    (0.0, 1.0),
+    (-1.0, 0.0),
+    (0.0, 0.0),
+];
+
+/// Three-point Gauss-Legendre rule on [-1, 1], exact for the biquadratic
+/// elements' stiffness: (point, weight)
+const GAUSS_3: [(f64, f64); 3] = [
+    (-0.774_596_669_241_483_4, 5.0 / 9.0),
+    (0.0, 8.0 / 9.0),
+    (0.774_596_669_241_483_4, 5.0 / 9.0),
+];
+
+/// 1D quadratic Lagrange polynomial of the node at c in {-1, 0, 1} and its
+/// derivative, at t
+fn lagrange(c: f64, t: f64) -> (f64, f64) {
+    if c == 0.0 {
+        (1.0 - t * t, -2.0 * t)
+    } else {
+        (t * (t + c) / 2.0, t + c / 2.0)
+    }
+}
+
+/// Shape functions of the Q8 (`nodes` = 8) or Q9 element at (xi, eta),
+/// with their derivatives in xi and eta
+fn shape(nodes: usize, xi: f64, eta: f64, n: &mut [f64], dn: &mut [[f64; 2]]) {
+    for (i, &(a, b)) in NODES[..nodes].iter().enumerate() {
+        (n[i], dn[i]) = if nodes == 9 {
+            let ((lx, dx), (ly, dy)) = (lagrange(a, xi), lagrange(b, eta));
+            (lx * ly, [dx * ly, lx * dy])
+        } else if a == 0.0 {
+            let (p, q) = (1.0 - xi * xi, 1.0 + eta * b);
+            (p * q / 2.0, [-xi * q, p * b / 2.0])
+        } else if b == 0.0 {
+            let (p, q) = (1.0 + xi * a, 1.0 - eta * eta);
+            (p * q / 2.0, [a * q / 2.0, -eta * p])
+        } else {
+            let (p, q, s) = (1.0 + xi * a, 1.0 + eta * b, xi * a + eta * b - 1.0);
+            (
+                p * q * s / 4.0,
+                [a * q * (s + p) / 4.0, b * p * (s + q) / 4.0],
+            )
+        };
+    }
+}
+
+/// Q8 or Q9 stiffness, arguments as in `q8_element_stiffness`
+#[allow(clippy::too_many_arguments)]
+fn quadratic_stiffness(
+    nodes: usize,
+    formulation: Formulation,
+    e: f64,
+    nu: f64,
+    thickness: f64,
+    width: f64,
+    height: f64,
+    radius: f64,
+) -> Vec<f64> {
+    let d = formulation.matrix(e, nu);
+    let strains = formulation.strains();
+    let size = 2 * nodes;
+    let det = width * height / 4.0;
+    let mut ke = vec![0.0; size * size];
+    let mut b = vec![0.0; strains * size];
+    let (mut n, mut dn) = ([0.0; 9], [[0.0; 2]; 9]);
+    for &(eta, weta) in &GAUSS_3 {
+        for &(xi, wxi) in &GAUSS_3 {
+            shape(nodes, xi, eta, &mut n, &mut dn);
+            for (i, g) in dn[..nodes].iter().enumerate() {
+                let (dx, dy) = (g[0] * 2.0 / width, g[1] * 2.0 / height);
+                b[2 * i] = dx;
+                b[size + 2 * i + 1] = dy;
+                b[2 * size + 2 * i] = dy;
+                b[2 * size + 2 * i + 1] = dx;
+            }
+            let weight = if formulation == Formulation::Axisymmetric {
+                let r = radius + width * (1.0 + xi) / 2.0;
+                for (i, ni) in n[..nodes].iter().enumerate() {
+                    b[3 * size + 2 * i] = ni / r;
+                }
+                r
+            } else {
+                thickness
+            };
+            add_btdb(&mut ke, &b, &d, strains, weight * det * wxi * weta);
+        }
+    }
+    ke
+}
+
+/// Checked `quadratic_stiffness`
+#[allow(clippy::too_many_arguments)]
+fn checked_stiffness(
+    nodes: usize,
+    formulation: Formulation,
+    e: f64,
+    nu: f64,
+    thickness: f64,
+    width: f64,
+    height: f64,
+    radius: f64,
+) -> Option<Vec<f64>> {
+    let thickness = match formulation {
+        Formulation::Axisymmetric => 1.0,
+        _ => thickness,
+    };
+    let valid = width > 0.0 && height > 0.0 && thickness > 0.0 && radius >= 0.0;
+    (valid && formulation.admits(nu))
+        .then(|| quadratic_stiffness(nodes, formulation, e, nu, thickness, width, height, radius))
+}
+
+/// Stiffness of the 8-node serendipity quadrilateral (Q8)
+///
+/// Arguments as in `q4_formulation_stiffness`. The local nodes are the
+/// corners counter-clockwise from the bottom left, then the mid-side nodes
+/// of the bottom, right, top and left sides; the 16 x 16 matrix is
+/// integrated with 3 x 3 Gauss points. Quadratic displacement fields, such
+/// as pure bending, are represented exactly, so stresses converge much
+/// faster than with Q4.
+#[wasm_bindgen]
+pub fn q8_element_stiffness(
+    formulation: Formulation,
+    e: f64,
+    nu: f64,
+    thickness: f64,
+    width: f64,
+    height: f64,
+    radius: f64,
+) -> Option<Vec<f64>> {
+    checked_stiffness(8, formulation, e, nu, thickness, width, height, radius)
+}
+
+/// Stiffness of the 9-node Lagrangian quadrilateral (Q9): the Q8 nodes
+/// followed by the centre node, 18 x 18, as `q8_element_stiffness`
+#[wasm_bindgen]
+pub fn q9_element_stiffness(
+    formulation: Formulation,
+    e: f64,
+    nu: f64,
+    thickness: f64,
+    width: f64,
+    height: f64,
+    radius: f64,
+) -> Option<Vec<f64>> {
+    checked_stiffness(9, formulation, e, nu, thickness, width, height, radius)
+}
+
+/// Node number of lattice point (i, j), 0 <= i <= 2 nelx and
+/// 0 <= j <= 2 nely, on a grid of quadratic elements
+///
+/// Nodes are numbered column by column from the bottom, like the corner
+/// nodes of Q4 grids; Q8 grids have no node at the element centres (i and
+/// j both odd), which are skipped. `undefined` for such a point or a
+/// `nodes_per_element` other than 8 or 9.
+#[wasm_bindgen]
+pub fn quadratic_node_index(
+    nodes_per_element: usize,
+    nely: usize,
+    i: usize,
+    j: usize,
+) -> Option<usize> {
+    let column = 2 * nely + 1;
+    match nodes_per_element {
+        9 => Some(i * column + j),
+        8 if i % 2 == 1 && j % 2 == 1 => None,
+        8 => {
+            // Even columns hold every point, odd ones only the even j
+            let start = i.div_ceil(2) * column + (i / 2) * (nely + 1);
+            Some(start + if i % 2 == 1 { j / 2 } else { j })
+        }
+        _ => None,
+    }
+}
+
+/// Global stiffness of a `nelx` x `nely` grid of unit Q8 or Q9 elements
+/// with SIMP interpolation
+///
+/// As `assemble_simp_with_formulation`, on the grid of quadratic elements
+/// (`nodes_per_element` 8 or 9) numbered by `quadratic_node_index`, two
+/// DOFs per node. Returns `undefined` in the same cases, or for another
+/// number of nodes.
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn assemble_simp_quadratic(
+    formulation: Formulation,
+    nodes_per_element: usize,
+    nelx: usize,
+    nely: usize,
+    densities: &[f64],
+    penal: f64,
+    e0: f64,
+    emin: f64,
+    nu: f64,
+) -> Option<CsrMatrix> {
+    if !(nodes_per_element == 8 || nodes_per_element == 9)
+        || nelx == 0
+        || nely == 0
+        || densities.len() != nelx * nely
+        || !formulation.admits(nu)
+    {
+        return None;
+    }
+    let moduli = simp_moduli(densities, penal, e0, emin);
+    let size = 2 * nodes_per_element;
+    let nodes = quadratic_node_index(nodes_per_element, nely, 2 * nelx, 2 * nely)? + 1;
+    let mut entries = Vec::with_capacity(nelx * nely * size * size);
+    let mut dofs = Vec::with_capacity(size);
+    let mut ke = Vec::new();
+    for (x, column) in moduli.chunks_exact(nely).enumerate() {
+        // Axisymmetric elements change with the radius
+        if x == 0 || formulation == Formulation::Axisymmetric {
+            ke = quadratic_stiffness(
+                nodes_per_element,
+                formulation,
+                1.0,
+                nu,
+                1.0,
+                1.0,
+                1.0,
+                x as f64,
+            );
+        }
+        for (y, &s) in column.iter().enumerate() {
+            dofs.clear();
+            for &(a, b) in &NODES[..nodes_per_element] {
+                let i = (2 * x + 1) as isize + a as isize;
+                let j = (2 * y + 1) as isize + b as isize;
+                let node = quadratic_node_index(nodes_per_element, nely, i as usize, j as usize)?;
+                dofs.extend([2 * node, 2 * node + 1]);
+            }
+            for (row, &i) in ke.chunks_exact(size).zip(&dofs) {
+                entries.extend(row.iter().zip(&dofs).map(|(&v, &j)| (i, j as u32, s * v)));
+            }
+        }
+    }
+    let n = 2 * nodes;
+    Some(CsrMatrix::from_matrix(SparseMatrix::from_triplets(
+        n, n, entries,
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fem::assemble_simp;
+
+    /// u^T K u / 2 of a dense K
+    fn energy(k: &[f64], u: &[f64]) -> f64 {
+        k.chunks_exact(u.len())
+            .zip(u)
+            .map(|(row, ui)| ui * row.iter().zip(u).map(|(a, uj)| a * uj).sum::<f64>())
+            .sum::<f64>()
+            / 2.0
+    }
+
+    #[test]
+    fn test_pure_bending_is_exact() {
+        // u = (c x y, -c (x^2 + nu y^2) / 2) is pure bending in plane
+        // stress: s_xx = E c y, energy E c^2 / 2 times the integral of y^2
+        let (nelx, nely, nu, c) = (3, 2, 0.3, 1e-2);
+        let bending = |x: f64, y: f64| [c * x * y, -c * (x * x + nu * y * y) / 2.0];
+        let y0 = nely as f64 / 2.0;
+        let expected = c * c / 2.0 * nelx as f64 * 2.0 * y0.powi(3) / 3.0;
+        let densities = vec![1.0; nelx * nely];
+        for nodes in [8, 9] {
+            let k = assemble_simp_quadratic(
+                Formulation::PlaneStress,
+                nodes,
+                nelx,
+                nely,
+                &densities,
+                3.0,
+                1.0,
+                1e-9,
+                nu,
+            )
+            .unwrap();
+            let mut u = vec![0.0; k.size()];
+            for i in 0..=2 * nelx {
+                for j in 0..=2 * nely {
+                    if let Some(node) = quadratic_node_index(nodes, nely, i, j) {
+                        let [ux, uy] = bending(i as f64 / 2.0, j as f64 / 2.0 - y0);
+                        u[2 * node..2 * node + 2].copy_from_slice(&[ux, uy]);
+                    }
+                }
+            }
+            let e = energy(&k.to_dense().unwrap(), &u);
+            assert!((e - expected).abs() < 1e-12 * expected);
+        }
+
+        // Bilinear elements are too stiff in bending (shear locking)
+        let k = assemble_simp(nelx, nely, &densities, 3.0, 1.0, 1e-9, nu).unwrap();
+        let u: Vec<f64> = (0..k.size() / 2)
+            .flat_map(|node| bending((node / (nely + 1)) as f64, (node % (nely + 1)) as f64 - y0))
+            .collect();
+        assert!(energy(&k.to_dense().unwrap(), &u) > 1.1 * expected);
+    }
+
+    #[test]
+    fn test_element_properties() {
+        let ke =
+            q8_element_stiffness(Formulation::PlaneStrain, 1.0, 0.3, 1.0, 2.0, 1.0, 0.0).unwrap();
+        assert_eq!(ke.len(), 256);
+        let q9 =
+            q9_element_stiffness(Formulation::PlaneStress, 1.0, 0.3, 1.0, 2.0, 1.0, 0.0).unwrap();
+        assert_eq!(q9.len(), 324);
+        for (k, nodes) in [(&ke, 8), (&q9, 9)] {
+            let size = 2 * nodes;
+            // Rotation about the centre, nodes at (x, y) = (a, b / 2)
+            let rotation: Vec<f64> = NODES[..nodes]
+                .iter()
+                .flat_map(|&(a, b)| [-b / 2.0, a])
+                .collect();
+            for row in k.chunks_exact(size) {
+                assert!(
+                    row.iter()
+                        .zip(&rotation)
+                        .map(|(v, u)| v * u)
+                        .sum::<f64>()
+                        .abs()
+                        < 1e-12
+                );
+            }
+        }
+        assert_eq!(quadratic_node_index(8, 2, 1, 1), None);
+        assert_eq!(quadratic_node_index(8, 2, 2, 0), Some(8));
+        assert_eq!(quadratic_node_index(9, 2, 1, 1), Some(6));
+        assert!(
+            q8_element_stiffness(Formulation::PlaneStrain, 1.0, 0.5, 1.0, 1.0, 1.0, 0.0).is_none()
+        );
+    }
+}
@@ -0,0 +1,177 @@
+use wasm_bindgen::prelude::*;
+
+use super::q4::strain_displacement;
+use super::{Formulation, GAUSS_2};
+use crate::grid::{node_count, ElementGrid};
+
+/// von Mises stress of a plane stress state (s_xx, s_yy, t_xy)
+pub(crate) fn von_mises(s: &[f64]) -> f64 {
+    (s[0] * s[0] - s[0] * s[1] + s[1] * s[1] + 3.0 * s[2] * s[2]).sqrt()
+}
+
+/// D B at the centre of a unit Q4 element of a plane-stress material (E,
+/// nu), 3 x 8 row-major: the element centre stresses (s_xx, s_yy, t_xy)
+/// of its nodal displacements, the mean of those at the Gauss points
+pub(crate) fn centre_stress_matrix(e: f64, nu: f64) -> Vec<f64> {
+    let d = Formulation::PlaneStress.matrix(e, nu);
+    let mut b = [0.0; 24];
+    strain_displacement(0.0, 0.0, 1.0, 1.0, &mut b);
+    (0..24)
+        .map(|i| {
+            let (k, j) = (i / 8, i % 8);
+            (0..3).map(|l| d[k * 3 + l] * b[l * 8 + j]).sum()
+        })
+        .collect()
+}
+
+/// Stresses of a 2D displacement field, from `element_stresses`
+#[wasm_bindgen]
+pub struct StressField {
+    gauss: Vec<f64>,
+    von_mises: Vec<f64>,
+    nodal_von_mises: Vec<f64>,
+}
+
+#[wasm_bindgen]
+impl StressField {
+    /// (s_xx, s_yy, t_xy) at the 2 x 2 Gauss points of every element, 12
+    /// values per element; points ordered (-, -), (+, -), (-, +), (+, +)
+    /// in (xi, eta)
+    #[wasm_bindgen(getter)]
+    pub fn gauss_stresses(&self) -> Vec<f64> {
+        self.gauss.clone()
+    }
+
+    /// von Mises stress per element, of the mean of its Gauss point
+    /// stresses (the stress at the element centre)
+    #[wasm_bindgen(getter)]
+    pub fn von_mises(&self) -> Vec<f64> {
+        self.von_mises.clone()
+    }
+
+    /// von Mises stress per node, of the element centre stresses averaged
+    /// over the elements sharing the node, for smooth contour plots
+    #[wasm_bindgen(getter)]
+    pub fn nodal_von_mises(&self) -> Vec<f64> {
+        self.nodal_von_mises.clone()
+    }
+}
+
+/// Plane-stress stresses of the displacements `u` of a `nelx` x `nely`
+/// grid of unit Q4 elements of a solid material (E, nu)
+///
+/// These are the stresses of the solid phase; in a SIMP design the
+/// relaxed stress of element e is rho_e^q times its value, q chosen by
+/// the stress-constrained formulation. Returns `undefined` if the grid is
+/// empty or `u` does not have one entry per DOF.
+#[wasm_bindgen]
+pub fn element_stresses(
+    nelx: usize,
+    nely: usize,
+    u: &[f64],
+    e: f64,
+    nu: f64,
+) -> Option<StressField> {
+    let elements = [nelx, nely, 0];
+    let grid = ElementGrid::new(elements, 2, &[], &[], &[]);
+    if nelx == 0 || nely == 0 || u.len() != grid.n() {
+        return None;
+    }
+    let d = Formulation::PlaneStress.matrix(e, nu);
+    let mut gauss = Vec::with_capacity(12 * grid.element_count());
+    let mut centre = Vec::with_capacity(3 * grid.element_count());
+    let mut dofs = Vec::with_capacity(8);
+    let mut b = [0.0; 24];
+    for el in 0..grid.element_count() {
+        grid.element_dofs(el, &mut dofs);
+        let mut mean = [0.0; 3];
+        for &eta in &GAUSS_2 {
+            for &xi in &GAUSS_2 {
+                strain_displacement(xi, eta, 1.0, 1.0, &mut b);
+                let strain: Vec<f64> = b
+                    .chunks_exact(8)
+                    .map(|row| row.iter().zip(&dofs).map(|(bi, &i)| bi * u[i]).sum())
+                    .collect();
+                for (k, m) in mean.iter_mut().enumerate() {
+                    let s: f64 = (0..3).map(|l| d[k * 3 + l] * strain[l]).sum();
+                    gauss.push(s);
+                    *m += s / 4.0;
+                }
+            }
+        }
+        centre.extend_from_slice(&mean);
+    }
+
+    let mut nodal = vec![0.0; 3 * node_count(elements)];
+    let mut shared = vec![0u32; node_count(elements)];
+    for (el, s) in centre.chunks_exact(3).enumerate() {
+        grid.element_dofs(el, &mut dofs);
+        for node in dofs.iter().step_by(2).map(|i| i / 2) {
+            nodal[3 * node..3 * node + 3]
+                .iter_mut()
+                .zip(s)
+                .for_each(|(v, x)| *v += x);
+            shared[node] += 1;
+        }
+    }
+    let nodal_von_mises = nodal
+        .chunks_exact(3)
+        .zip(&shared)
+        .map(|(s, &count)| von_mises(s) / count as f64)
+        .collect();
+    Some(StressField {
+        gauss,
+        von_mises: centre.chunks_exact(3).map(von_mises).collect(),
+        nodal_von_mises,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::node_index;
+
+    #[test]
+    fn test_uniaxial_stress_field() {
+        // u = (s x, -nu s y) is uniaxial stress E s along x in plane stress
+        let (nelx, nely, e, nu, s) = (5, 3, 200.0, 0.3, 1e-3);
+        let mut u = vec![0.0; 2 * (nelx + 1) * (nely + 1)];
+        for x in 0..=nelx {
+            for y in 0..=nely {
+                let node = node_index([nelx, nely, 0], x, y, 0);
+                u[2 * node] = s * x as f64;
+                u[2 * node + 1] = -nu * s * y as f64;
+            }
+        }
+        let field = element_stresses(nelx, nely, &u, e, nu).unwrap();
+        let gauss = field.gauss_stresses();
+        assert_eq!(gauss.len(), 12 * nelx * nely);
+        for point in gauss.chunks_exact(3) {
+            assert!((point[0] - e * s).abs() < 1e-12);
+            assert!(point[1].abs() < 1e-12 && point[2].abs() < 1e-12);
+        }
+        assert!(field.von_mises().iter().all(|v| (v - e * s).abs() < 1e-12));
+        assert!(field
+            .nodal_von_mises()
+            .iter()
+            .all(|v| (v - e * s).abs() < 1e-12));
+
+        // Pure shear t: von Mises sqrt(3) t
+        let shear: Vec<f64> = (0..u.len())
+            .map(|i| {
+                if i % 2 == 0 {
+                    s * ((i / 2) % (nely + 1)) as f64
+                } else {
+                    0.0
+                }
+            })
+            .collect();
+        let field = element_stresses(nelx, nely, &shear, e, nu).unwrap();
+        let t = e / (2.0 * (1.0 + nu)) * s;
+        assert!(field
+            .von_mises()
+            .iter()
+            .all(|v| (v - 3f64.sqrt() * t).abs() < 1e-12));
+        assert!(element_stresses(nelx, nely, &u[1..], e, nu).is_none());
+    }
+}
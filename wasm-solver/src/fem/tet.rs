@@ -0,0 +1,258 @@
+use wasm_bindgen::prelude::*;
+
+use super::constitutive::isotropic;
+use super::{add_btdb, solid_strain_displacement};
+use crate::kernels::SparseMatrix;
+use crate::matrix::CsrMatrix;
+
+/// Edges of the Tet10 mid-side nodes 4..9, by their corner nodes (the
+/// VTK and Abaqus order)
+const TET10_EDGES: [(usize, usize); 6] = [(0, 1), (1, 2), (0, 2), (0, 3), (1, 3), (2, 3)];
+
+/// Four-point rule on the reference tetrahedron, exact for the quadratic
+/// products of Tet10 gradients: points (b, b, b), (a, b, b), (b, a, b),
+/// (b, b, a), weights 1/24
+const TET_GAUSS_A: f64 = 0.585_410_196_624_968_5;
+const TET_GAUSS_B: f64 = 0.138_196_601_125_010_5;
+
+/// Gradients in the reference coordinates (xi, eta, zeta) of the shape
+/// functions of a Tet4 (`nodes` = 4) or Tet10 element at `point`
+fn reference_gradients(nodes: usize, point: [f64; 3], grads: &mut [[f64; 3]]) {
+    // Barycentric coordinates L_0 = 1 - xi - eta - zeta, L_k = point[k - 1]
+    let l = [
+        1.0 - point[0] - point[1] - point[2],
+        point[0],
+        point[1],
+        point[2],
+    ];
+    let dl = |i: usize| -> [f64; 3] {
+        match i {
+            0 => [-1.0; 3],
+            _ => {
+                let mut d = [0.0; 3];
+                d[i - 1] = 1.0;
+                d
+            }
+        }
+    };
+    if nodes == 4 {
+        for (i, g) in grads.iter_mut().enumerate() {
+            *g = dl(i);
+        }
+        return;
+    }
+    for i in 0..4 {
+        grads[i] = dl(i).map(|d| (4.0 * l[i] - 1.0) * d);
+    }
+    for (k, &(a, b)) in TET10_EDGES.iter().enumerate() {
+        let (da, db) = (dl(a), dl(b));
+        grads[4 + k] = [0, 1, 2].map(|c| 4.0 * (l[a] * db[c] + l[b] * da[c]));
+    }
+}
+
+/// Stiffness of a Tet4 or Tet10 element with node coordinates `coords`
+/// (x, y, z per node), or `None` if it is degenerate
+fn tet_stiffness(coords: &[f64], d: &[f64; 36]) -> Option<Vec<f64>> {
+    let nodes = coords.len() / 3;
+    let (points, weight): (&[[f64; 3]], f64) = if nodes == 4 {
+        (&[[0.25; 3]], 1.0 / 6.0)
+    } else {
+        let (a, b) = (TET_GAUSS_A, TET_GAUSS_B);
+        (&[[b, b, b], [a, b, b], [b, a, b], [b, b, a]], 1.0 / 24.0)
+    };
+    let size = 3 * nodes;
+    let mut ke = vec![0.0; size * size];
+    let mut reference = vec![[0.0; 3]; nodes];
+    let mut grads = vec![[0.0; 3]; nodes];
+    let mut b = vec![0.0; 6 * size];
+    for &point in points {
+        reference_gradients(nodes, point, &mut reference);
+        // J[r][c] = d x_r / d xi_c
+        let mut j = [[0.0; 3]; 3];
+        for (x, g) in coords.chunks_exact(3).zip(&reference) {
+            for r in 0..3 {
+                for c in 0..3 {
+                    j[r][c] += x[r] * g[c];
+                }
+            }
+        }
+        let det = j[0][0] * (j[1][1] * j[2][2] - j[1][2] * j[2][1])
+            - j[0][1] * (j[1][0] * j[2][2] - j[1][2] * j[2][0])
+            + j[0][2] * (j[1][0] * j[2][1] - j[1][1] * j[2][0]);
+        let scale = j.iter().flatten().map(|v| v.abs()).fold(0.0, f64::max);
+        if det.abs() <= 1e-12 * scale * scale * scale {
+            return None;
+        }
+        // Physical gradients g = J^-T g_ref, with J^-1 = adj(J) / det
+        let adj = |r: usize, c: usize| {
+            let (r1, r2) = ((r + 1) % 3, (r + 2) % 3);
+            let (c1, c2) = ((c + 1) % 3, (c + 2) % 3);
+            j[c1][r1] * j[c2][r2] - j[c1][r2] * j[c2][r1]
+        };
+        for (g, gr) in grads.iter_mut().zip(&reference) {
+            // (J^-T g_ref)_k = sum_c (J^-1)[c][k] g_ref[c]
+            *g = [0, 1, 2].map(|k| (0..3).map(|c| adj(c, k) * gr[c]).sum::<f64>() / det);
+        }
+        solid_strain_displacement(&grads, &mut b);
+        // Either node orientation gives the same element
+        add_btdb(&mut ke, &b, d, 6, weight * det.abs());
+    }
+    Some(ke)
+}
+
+/// Stiffness of a linear (Tet4) or quadratic (Tet10) tetrahedron
+///
+/// `coords` holds x, y, z of the 4 corner nodes, then for Tet10 of the 6
+/// mid-side nodes in the order of the edges 01, 12, 02, 03, 13, 23 (VTK,
+/// Abaqus). DOFs are (u_x, u_y, u_z) per node; the 12 x 12 or 30 x 30
+/// matrix is row-major, integrated with 1 point (Tet4) or 4 points
+/// (Tet10). Returns `undefined` for another number of coordinates, a flat
+/// element or nu outside (-1, 0.5).
+#[wasm_bindgen]
+pub fn tet_element_stiffness(coords: &[f64], e: f64, nu: f64) -> Option<Vec<f64>> {
+    if !(coords.len() == 12 || coords.len() == 30) || nu <= -1.0 || nu >= 0.5 {
+        return None;
+    }
+    tet_stiffness(coords, &isotropic(e, nu))
+}
+
+/// Global stiffness of an unstructured tetrahedral mesh
+///
+/// `coords` holds x, y, z per node and `connectivity` the nodes of every
+/// element, `nodes_per_element` (4 or 10) each in the order of
+/// `tet_element_stiffness`; node i has the DOFs 3i, 3i + 1, 3i + 2.
+/// Element e has the modulus `moduli[e] * e`, or `e` when `moduli` is
+/// empty, so SIMP designs pass their interpolated densities. Returns
+/// `undefined` if the arrays do not match, a node is out of range, an
+/// element is flat or nu is outside (-1, 0.5).
+#[wasm_bindgen]
+pub fn assemble_tet(
+    coords: &[f64],
+    connectivity: &[u32],
+    nodes_per_element: usize,
+    e: f64,
+    nu: f64,
+    moduli: &[f64],
+) -> Option<CsrMatrix> {
+    let nodes = coords.len() / 3;
+    if !(nodes_per_element == 4 || nodes_per_element == 10)
+        || !coords.len().is_multiple_of(3)
+        || !connectivity.len().is_multiple_of(nodes_per_element)
+        || !(moduli.is_empty() || moduli.len() * nodes_per_element == connectivity.len())
+        || connectivity.iter().any(|&i| i as usize >= nodes)
+        || nu <= -1.0
+        || nu >= 0.5
+    {
+        return None;
+    }
+    let d = isotropic(e, nu);
+    let size = 3 * nodes_per_element;
+    let mut entries = Vec::with_capacity(connectivity.len() / nodes_per_element * size * size);
+    let mut xe = vec![0.0; size];
+    let mut dofs = Vec::with_capacity(size);
+    for (el, element) in connectivity.chunks_exact(nodes_per_element).enumerate() {
+        dofs.clear();
+        for (x, &node) in xe.chunks_exact_mut(3).zip(element) {
+            let node = node as usize;
+            x.copy_from_slice(&coords[3 * node..3 * node + 3]);
+            dofs.extend(3 * node..3 * node + 3);
+        }
+        let ke = tet_stiffness(&xe, &d)?;
+        let s = moduli.get(el).copied().unwrap_or(1.0);
+        for (row, &i) in ke.chunks_exact(size).zip(&dofs) {
+            entries.extend(row.iter().zip(&dofs).map(|(&v, &j)| (i, j as u32, s * v)));
+        }
+    }
+    let n = 3 * nodes;
+    Some(CsrMatrix::from_matrix(SparseMatrix::from_triplets(
+        n, n, entries,
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Energy u^T K u / 2 of the uniaxial stress field (s x, -nu s y,
+    /// -nu s z) sampled at the nodes
+    fn uniaxial_energy(ke: &[f64], coords: &[f64], nu: f64, s: f64) -> f64 {
+        let u: Vec<f64> = coords
+            .chunks_exact(3)
+            .flat_map(|p| [s * p[0], -nu * s * p[1], -nu * s * p[2]])
+            .collect();
+        let n = u.len();
+        ke.chunks_exact(n)
+            .zip(&u)
+            .map(|(row, ui)| ui * row.iter().zip(&u).map(|(k, uj)| k * uj).sum::<f64>())
+            .sum::<f64>()
+            / 2.0
+    }
+
+    #[test]
+    fn test_linear_and_quadratic_elements() {
+        let (e, nu, s) = (3.0, 0.25, 1e-2);
+        let corners = [0.0, 0.0, 0.0, 2.0, 0.0, 0.0, 0.3, 1.5, 0.0, 0.4, 0.2, 1.2];
+        let volume = 2.0 * 1.5 * 1.2 / 6.0;
+        let tet4 = tet_element_stiffness(&corners, e, nu).unwrap();
+        let expected = e * s * s * volume / 2.0;
+        assert!((uniaxial_energy(&tet4, &corners, nu, s) - expected).abs() < 1e-12);
+
+        // Straight-sided Tet10: mid-side nodes at the edge midpoints
+        let mut coords = corners.to_vec();
+        for &(a, b) in &TET10_EDGES {
+            coords.extend((0..3).map(|c| (corners[3 * a + c] + corners[3 * b + c]) / 2.0));
+        }
+        let tet10 = tet_element_stiffness(&coords, e, nu).unwrap();
+        assert_eq!(tet10.len(), 900);
+        assert!((uniaxial_energy(&tet10, &coords, nu, s) - expected).abs() < 1e-12);
+        for i in 0..30 {
+            assert!(tet10[i * 30 + i] > 0.0);
+            assert!((0..i).all(|j| (tet10[i * 30 + j] - tet10[j * 30 + i]).abs() < 1e-12));
+        }
+        // Rotation about z is a zero-energy mode
+        let rotation: Vec<f64> = coords
+            .chunks_exact(3)
+            .flat_map(|p| [-p[1], p[0], 0.0])
+            .collect();
+        for row in tet10.chunks_exact(30) {
+            assert!(
+                row.iter()
+                    .zip(&rotation)
+                    .map(|(k, u)| k * u)
+                    .sum::<f64>()
+                    .abs()
+                    < 1e-12
+            );
+        }
+        let flat = [0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 1.0, 1.0, 0.0];
+        assert!(tet_element_stiffness(&flat, e, nu).is_none());
+    }
+
+    #[test]
+    fn test_assembled_cube() {
+        // Unit cube as six tetrahedra around the diagonal 0-7, mixing both
+        // node orientations
+        let coords: Vec<f64> = (0..8)
+            .flat_map(|v| [(v & 1) as f64, ((v >> 1) & 1) as f64, ((v >> 2) & 1) as f64])
+            .collect();
+        let connectivity = [
+            0, 1, 3, 7, 0, 1, 5, 7, 0, 2, 3, 7, 0, 2, 6, 7, 0, 4, 5, 7, 0, 4, 6, 7,
+        ];
+        let (e, nu, s) = (1.0, 0.3, 1e-2);
+        let k = assemble_tet(&coords, &connectivity, 4, e, nu, &[]).unwrap();
+        assert_eq!(k.size(), 24);
+        let dense = k.to_dense().unwrap();
+        assert!((uniaxial_energy(&dense, &coords, nu, s) - e * s * s / 2.0).abs() < 1e-12);
+
+        let half = assemble_tet(&coords, &connectivity, 4, e, nu, &[0.5; 6]).unwrap();
+        assert!(half
+            .to_dense()
+            .unwrap()
+            .iter()
+            .zip(&dense)
+            .all(|(h, f)| (2.0 * h - f).abs() < 1e-12));
+        assert!(assemble_tet(&coords, &connectivity, 4, e, nu, &[1.0]).is_none());
+        assert!(assemble_tet(&coords, &[0, 1, 2, 8], 4, e, nu, &[]).is_none());
+        assert!(assemble_tet(&coords, &connectivity, 10, e, nu, &[]).is_none());
+    }
+}
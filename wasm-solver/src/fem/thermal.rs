@@ -0,0 +1,205 @@
+use wasm_bindgen::prelude::*;
+
+use super::compliance::simp_compliance;
+use super::{add_btdb, assemble_elements, h8, q4, simp_moduli, ElementCompliance, GAUSS_2};
+use crate::grid::ElementGrid;
+use crate::matrix::CsrMatrix;
+
+/// Conductivity matrix of the Q4 element for heat conduction, 1 DOF (the
+/// temperature) per node
+///
+/// k integral of grad N^T grad N over a `width` x `height` rectangle of
+/// the given `thickness`, 4 x 4 row-major from 2 x 2 Gauss points; nodes
+/// as in `q4_element_stiffness`. Returns `undefined` unless the sizes are
+/// positive.
+#[wasm_bindgen]
+pub fn q4_conduction_stiffness(
+    k: f64,
+    thickness: f64,
+    width: f64,
+    height: f64,
+) -> Option<Vec<f64>> {
+    if !(width > 0.0 && height > 0.0 && thickness > 0.0) {
+        return None;
+    }
+    let det = width * height / 4.0;
+    let mut ke = vec![0.0; 16];
+    let mut g = [0.0; 8];
+    for &eta in &GAUSS_2 {
+        for &xi in &GAUSS_2 {
+            for (i, [dx, dy]) in q4::gradients(xi, eta, width, height)
+                .into_iter()
+                .enumerate()
+            {
+                (g[i], g[4 + i]) = (dx, dy);
+            }
+            add_btdb(&mut ke, &g, &[k, 0.0, 0.0, k], 2, thickness * det);
+        }
+    }
+    Some(ke)
+}
+
+/// Conductivity matrix of the H8 element, 8 x 8, nodes as in
+/// `h8_element_stiffness`; `undefined` unless the sizes are positive
+#[wasm_bindgen]
+pub fn h8_conduction_stiffness(k: f64, width: f64, height: f64, depth: f64) -> Option<Vec<f64>> {
+    if !(width > 0.0 && height > 0.0 && depth > 0.0) {
+        return None;
+    }
+    let det = width * height * depth / 8.0;
+    let d = [k, 0.0, 0.0, 0.0, k, 0.0, 0.0, 0.0, k];
+    let mut ke = vec![0.0; 64];
+    let mut g = [0.0; 24];
+    for &zeta in &GAUSS_2 {
+        for &eta in &GAUSS_2 {
+            for &xi in &GAUSS_2 {
+                let grads = h8::gradients([xi, eta, zeta], [width, height, depth]);
+                for (i, grad) in grads.iter().enumerate() {
+                    for (c, &v) in grad.iter().enumerate() {
+                        g[8 * c + i] = v;
+                    }
+                }
+                add_btdb(&mut ke, &g, &d, 3, det);
+            }
+        }
+    }
+    Some(ke)
+}
+
+/// Conductivity grid of unit elements with SIMP moduli as factors
+fn conduction_grid<'a>(elements: [usize; 3], ke: &'a [f64], moduli: &'a [f64]) -> ElementGrid<'a> {
+    ElementGrid::new(elements, 1, ke, moduli, &[])
+}
+
+/// Unit-element conductivity matrix of a 2D (`nelz` = 0) or 3D grid
+pub(super) fn unit_conduction(nelz: usize) -> Vec<f64> {
+    if nelz > 0 {
+        h8_conduction_stiffness(1.0, 1.0, 1.0, 1.0)
+    } else {
+        q4_conduction_stiffness(1.0, 1.0, 1.0, 1.0)
+    }
+    .unwrap()
+}
+
+/// Global conductivity matrix of a grid of unit Q4 (`nelz` = 0) or H8
+/// elements with SIMP interpolation k_min + rho_e^p (k_0 - k_min)
+///
+/// One temperature DOF per node, nodes and elements numbered as in
+/// `assemble_simp`. No temperature is fixed: apply the heat sinks with
+/// `apply_dirichlet`. Returns `undefined` if the grid is empty or
+/// `densities` does not have one value per element.
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn assemble_conduction_simp(
+    nelx: usize,
+    nely: usize,
+    nelz: usize,
+    densities: &[f64],
+    penal: f64,
+    k0: f64,
+    kmin: f64,
+) -> Option<CsrMatrix> {
+    if nelx == 0 || nely == 0 || densities.len() != nelx * nely * nelz.max(1) {
+        return None;
+    }
+    let ke = unit_conduction(nelz);
+    let moduli = simp_moduli(densities, penal, k0, kmin);
+    let grid = conduction_grid([nelx, nely, nelz], &ke, &moduli);
+    Some(CsrMatrix::from_matrix(assemble_elements(&grid)))
+}
+
+/// Thermal compliance T^T K T and its element sensitivities for the
+/// temperatures `t` of an `assemble_conduction_simp` design
+///
+/// Arguments are those of `assemble_conduction_simp`; the result reads
+/// as `element_compliance` with temperatures for displacements. Returns
+/// `undefined` if the grid is empty or `densities` or `t` have the wrong
+/// length.
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn element_thermal_compliance(
+    nelx: usize,
+    nely: usize,
+    nelz: usize,
+    t: &[f64],
+    densities: &[f64],
+    penal: f64,
+    k0: f64,
+    kmin: f64,
+) -> Option<ElementCompliance> {
+    if nelx == 0 || nely == 0 || densities.len() != nelx * nely * nelz.max(1) {
+        return None;
+    }
+    let ke = unit_conduction(nelz);
+    let moduli = simp_moduli(densities, penal, k0, kmin);
+    let grid = conduction_grid([nelx, nely, nelz], &ke, &moduli);
+    (t.len() == grid.n()).then(|| simp_compliance(&grid, t, densities, penal, k0, kmin))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fem::{apply_dirichlet, compliance, DirichletMethod};
+    use crate::grid::node_index;
+    use crate::options::SolverOptions;
+
+    #[test]
+    fn test_element_matrices() {
+        let ke = q4_conduction_stiffness(6.0, 1.0, 1.0, 1.0).unwrap();
+        #[rustfmt::skip]
+        let expected = [
+            4.0, -1.0, -2.0, -1.0,
+            -1.0, 4.0, -1.0, -2.0,
+            -2.0, -1.0, 4.0, -1.0,
+            -1.0, -2.0, -1.0, 4.0,
+        ];
+        assert!(ke.iter().zip(&expected).all(|(a, b)| (a - b).abs() < 1e-12));
+
+        // A linear field T = g . x stores k |g|^2 V / 2
+        let (k, size) = (2.0, [0.5, 1.0, 2.0]);
+        let ke = h8_conduction_stiffness(k, size[0], size[1], size[2]).unwrap();
+        let g = [1.0, -2.0, 0.5];
+        let t: Vec<f64> = (0..8)
+            .map(|i| {
+                let corner = [i % 4 == 1 || i % 4 == 2, i % 4 >= 2, i >= 4];
+                (0..3)
+                    .map(|c| if corner[c] { g[c] * size[c] } else { 0.0 })
+                    .sum()
+            })
+            .collect();
+        let energy: f64 = ke
+            .chunks_exact(8)
+            .zip(&t)
+            .map(|(row, ti)| ti * row.iter().zip(&t).map(|(a, tj)| a * tj).sum::<f64>())
+            .sum::<f64>()
+            / 2.0;
+        let expected =
+            k * g.iter().map(|v| v * v).sum::<f64>() * size.iter().product::<f64>() / 2.0;
+        assert!((energy - expected).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_heat_sink_compliance() {
+        let (nelx, nely) = (10, 10);
+        let densities: Vec<f64> = (0..nelx * nely)
+            .map(|e| 0.2 + (e % 7) as f64 / 10.0)
+            .collect();
+        let k = assemble_conduction_simp(nelx, nely, 0, &densities, 3.0, 1.0, 1e-3).unwrap();
+        assert_eq!(k.size(), 121);
+        // Uniform heating, sink at the middle of the left edge
+        let q = vec![0.01; k.size()];
+        let sink = [node_index([nelx, nely, 0], 0, 5, 0) as u32];
+        let system = apply_dirichlet(&k, &q, &sink, &[], DirichletMethod::Elimination).unwrap();
+        let mut options = SolverOptions::new();
+        options.tol = 1e-12;
+        options.max_iter = 2000;
+        let t = system.solve_pcg(&vec![0.0; k.size()], &options).solution;
+        assert!(t.iter().all(|&v| v >= 0.0));
+
+        let c = element_thermal_compliance(nelx, nely, 0, &t, &densities, 3.0, 1.0, 1e-3).unwrap();
+        let qt = compliance(&t, &q).unwrap();
+        assert!((c.total() - qt).abs() < 1e-8 * qt);
+        assert!(c.sensitivities().iter().all(|&s| s <= 0.0));
+        assert!(assemble_conduction_simp(nelx, nely, 2, &densities, 3.0, 1.0, 1e-3).is_none());
+    }
+}
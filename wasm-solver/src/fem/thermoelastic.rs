@@ -0,0 +1,198 @@
+use wasm_bindgen::prelude::*;
+
+use super::constitutive::isotropic;
+use super::{h8, q4, simp_moduli, solid_strain_displacement, Formulation, GAUSS_2};
+use crate::grid::{node_count, ElementGrid};
+
+/// g += scale B^T D m N^T, the nodal forces of the thermal stress
+/// D m alpha dT per nodal temperature change: B row-major `dm.len()` x n,
+/// N the values of the m shape functions and g row-major n x m
+fn add_thermal_coupling(g: &mut [f64], b: &[f64], dm: &[f64], n: &[f64], scale: f64) {
+    let dofs = b.len() / dm.len();
+    for (j, row) in g.chunks_exact_mut(n.len()).enumerate() {
+        let f: f64 = b.chunks_exact(dofs).zip(dm).map(|(bk, d)| bk[j] * d).sum();
+        row.iter_mut()
+            .zip(n)
+            .for_each(|(v, ni)| *v += scale * f * ni);
+    }
+}
+
+/// Thermal coupling matrix of the unit Q4 (plane stress, `dims` = 2) or
+/// H8 element of modulus 1 and expansion coefficient 1: element forces
+/// per unit temperature change of each node
+fn unit_thermal_coupling(dims: usize, nu: f64) -> Vec<f64> {
+    let nodes = if dims == 3 { 8 } else { 4 };
+    let mut g = vec![0.0; dims * nodes * nodes];
+    if dims == 3 {
+        let d = isotropic(1.0, nu);
+        // D m for the thermal strain m = (1, 1, 1, 0, 0, 0)
+        let dm: Vec<f64> = d.chunks_exact(6).map(|row| row[..3].iter().sum()).collect();
+        let mut b = [0.0; 144];
+        for &zeta in &GAUSS_2 {
+            for &eta in &GAUSS_2 {
+                for &xi in &GAUSS_2 {
+                    let point = [xi, eta, zeta];
+                    solid_strain_displacement(&h8::gradients(point, [1.0; 3]), &mut b);
+                    add_thermal_coupling(&mut g, &b, &dm, &h8::shape(point), 1.0 / 8.0);
+                }
+            }
+        }
+    } else {
+        let d = Formulation::PlaneStress.matrix(1.0, nu);
+        // D m for the thermal strain m = (1, 1, 0)
+        let dm: Vec<f64> = d.chunks_exact(3).map(|row| row[0] + row[1]).collect();
+        let mut b = [0.0; 24];
+        for &eta in &GAUSS_2 {
+            for &xi in &GAUSS_2 {
+                q4::strain_displacement(xi, eta, 1.0, 1.0, &mut b);
+                add_thermal_coupling(&mut g, &b, &dm, &q4::shape(xi, eta), 1.0 / 4.0);
+            }
+        }
+    }
+    g
+}
+
+/// Equivalent nodal forces of the thermal strain alpha (T - T_ref) of a
+/// grid of unit Q4 (`nelx` x `nely`, `nelz` = 0, plane stress) or H8
+/// elements with SIMP moduli
+///
+/// `temperatures` holds one value per node, e.g. the solution of an
+/// `assemble_conduction_simp` system on the same grid, and `reference` is
+/// the stress-free temperature; the result has the DOF numbering of
+/// `assemble_simp` and is added to the mechanical loads before the
+/// elastic solve. The load depends on the design through the modulus of
+/// each element, so compliance sensitivities need the extra term
+/// 2 u^T df/drho_e. Returns `undefined` if the grid is empty,
+/// `densities` or `temperatures` have the wrong length or nu is outside
+/// (-1, 1) in 2D and (-1, 0.5) in 3D.
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn assemble_thermal_load(
+    nelx: usize,
+    nely: usize,
+    nelz: usize,
+    temperatures: &[f64],
+    reference: f64,
+    alpha: f64,
+    densities: &[f64],
+    penal: f64,
+    e0: f64,
+    emin: f64,
+    nu: f64,
+) -> Option<Vec<f64>> {
+    let elements = [nelx, nely, nelz];
+    let dims = if nelz > 0 { 3 } else { 2 };
+    let admits = if dims == 3 {
+        nu > -1.0 && nu < 0.5
+    } else {
+        Formulation::PlaneStress.admits(nu)
+    };
+    if nelx == 0
+        || nely == 0
+        || densities.len() != nelx * nely * nelz.max(1)
+        || temperatures.len() != node_count(elements)
+        || !admits
+    {
+        return None;
+    }
+    let g = unit_thermal_coupling(dims, nu);
+    let moduli = simp_moduli(densities, penal, e0, emin);
+    let grid = ElementGrid::new(elements, dims, &[], &moduli, &[]);
+    let mut forces = vec![0.0; grid.n()];
+    let mut dofs = Vec::with_capacity(grid.local_size());
+    let mut dt = Vec::with_capacity(grid.local_size() / dims);
+    for el in 0..grid.element_count() {
+        grid.element_dofs(el, &mut dofs);
+        dt.clear();
+        dt.extend(
+            dofs.iter()
+                .step_by(dims)
+                .map(|&i| alpha * (temperatures[i / dims] - reference)),
+        );
+        for (row, &i) in g.chunks_exact(dt.len()).zip(&dofs) {
+            forces[i] += grid.scale(el) * row.iter().zip(&dt).map(|(a, t)| a * t).sum::<f64>();
+        }
+    }
+    Some(forces)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fem::{apply_dirichlet, assemble_conduction_simp, assemble_elements};
+    use crate::fem::{assemble_simp, h8_element_stiffness, DirichletMethod};
+    use crate::grid::node_index;
+    use crate::matrix::CsrMatrix;
+    use crate::options::SolverOptions;
+
+    #[test]
+    fn test_free_expansion_is_stress_free() {
+        // Uniform heating expands freely as u = alpha dT x, so the thermal
+        // load is exactly K u
+        let (alpha, dt, nu) = (1e-5, 40.0, 0.3);
+        for nelz in [0, 2] {
+            let elements = [4, 3, nelz];
+            let dims = if nelz > 0 { 3 } else { 2 };
+            let count = 12 * nelz.max(1);
+            let densities: Vec<f64> = (0..count).map(|e| 0.3 + (e % 5) as f64 / 8.0).collect();
+            let t = vec![20.0 + dt; node_count(elements)];
+            let f =
+                assemble_thermal_load(4, 3, nelz, &t, 20.0, alpha, &densities, 3.0, 1.0, 1e-9, nu)
+                    .unwrap();
+            let k = if nelz > 0 {
+                let ke = h8_element_stiffness(1.0, nu, 1.0, 1.0, 1.0).unwrap();
+                let moduli = simp_moduli(&densities, 3.0, 1.0, 1e-9);
+                let grid = ElementGrid::new(elements, 3, &ke, &moduli, &[]);
+                CsrMatrix::from_matrix(assemble_elements(&grid))
+            } else {
+                assemble_simp(4, 3, &densities, 3.0, 1.0, 1e-9, nu).unwrap()
+            };
+            let mut u = vec![0.0; f.len()];
+            for z in 0..=nelz {
+                for x in 0..=4 {
+                    for y in 0..=3 {
+                        let node = node_index(elements, x, y, z);
+                        let coords = [x, y, z];
+                        for (c, &xc) in coords[..dims].iter().enumerate() {
+                            u[dims * node + c] = alpha * dt * xc as f64;
+                        }
+                    }
+                }
+            }
+            let ku = k.spmv(&u);
+            assert!(ku.iter().zip(&f).all(|(a, b)| (a - b).abs() < 1e-12));
+            assert!(f.iter().any(|v| v.abs() > 1e-6));
+        }
+        assert!(assemble_thermal_load(
+            4, 3, 0, &[0.0; 19], 0.0, 1.0, &[1.0; 12], 3.0, 1.0, 0.0, 0.3
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn test_sequential_coupling() {
+        // Heated plate, sink on the left edge, clamped on the left edge
+        let (nelx, nely) = (8, 4);
+        let densities = vec![1.0; nelx * nely];
+        let kt = assemble_conduction_simp(nelx, nely, 0, &densities, 3.0, 1.0, 1e-3).unwrap();
+        let left: Vec<u32> = (0..=nely).map(|y| y as u32).collect();
+        let q = vec![0.1; kt.size()];
+        let thermal = apply_dirichlet(&kt, &q, &left, &[], DirichletMethod::Elimination).unwrap();
+        let mut options = SolverOptions::new();
+        options.tol = 1e-12;
+        let t = thermal.solve_pcg(&vec![0.0; kt.size()], &options).solution;
+
+        let f = assemble_thermal_load(
+            nelx, nely, 0, &t, 0.0, 1e-3, &densities, 3.0, 1.0, 1e-9, 0.3,
+        )
+        .unwrap();
+        let k = assemble_simp(nelx, nely, &densities, 3.0, 1.0, 1e-9, 0.3).unwrap();
+        let clamped: Vec<u32> = (0..2 * (nely as u32 + 1)).collect();
+        let elastic = apply_dirichlet(&k, &f, &clamped, &[], DirichletMethod::Elimination).unwrap();
+        let result = elastic.solve_pcg(&vec![0.0; k.size()], &options);
+        assert!(result.converged_by().is_some());
+        // The hot free end moves away from the clamp
+        let tip = node_index([nelx, nely, 0], nelx, nely / 2, 0);
+        assert!(result.solution()[2 * tip] > 0.0);
+    }
+}
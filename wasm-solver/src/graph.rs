@@ -0,0 +1,492 @@
+//! Adjacency graph of a sparse matrix: row i is a vertex, coupled to the
+//! columns of its off-diagonal entries
+
+use std::collections::BTreeSet;
+
+use wasm_bindgen::prelude::*;
+
+use crate::kernels::Csr;
+
+/// Breadth-first order of the vertices reachable from `start` that have
+/// no `level` yet (`usize::MAX`), appended to `order`; sets their distance
+/// from `start` and returns the number of levels
+fn bfs(a: &Csr, start: usize, level: &mut [usize], order: &mut Vec<usize>) -> usize {
+    let mut head = order.len();
+    level[start] = 0;
+    order.push(start);
+    while head < order.len() {
+        let i = order[head];
+        head += 1;
+        for &j in a.col_indices[a.row_ptr[i] as usize..a.row_ptr[i + 1] as usize].iter() {
+            let j = j as usize;
+            if level[j] == usize::MAX {
+                level[j] = level[i] + 1;
+                order.push(j);
+            }
+        }
+    }
+    level[order[order.len() - 1]] + 1
+}
+
+fn degree(a: &Csr, i: usize) -> usize {
+    (a.row_ptr[i + 1] - a.row_ptr[i]) as usize
+}
+
+/// Vertex of (nearly) largest eccentricity in the component of `start`,
+/// by the George–Liu search: restart from a lowest-degree vertex of the
+/// last level for as long as the level structure gets deeper
+pub(crate) fn pseudo_peripheral(a: &Csr, start: usize) -> usize {
+    let mut level = vec![usize::MAX; a.n()];
+    let mut order = Vec::new();
+    let mut root = start;
+    let mut depth = bfs(a, root, &mut level, &mut order);
+    loop {
+        let candidate = order
+            .iter()
+            .copied()
+            .filter(|&i| level[i] + 1 == depth)
+            .min_by_key(|&i| degree(a, i))
+            .unwrap_or(root);
+        for &i in order.iter() {
+            level[i] = usize::MAX;
+        }
+        order.clear();
+        let candidate_depth = bfs(a, candidate, &mut level, &mut order);
+        if candidate_depth <= depth {
+            return root;
+        }
+        root = candidate;
+        depth = candidate_depth;
+    }
+}
+
+/// Breadth-first order of all vertices, each component started from a
+/// pseudo-peripheral vertex so that consecutive vertices stay close
+pub(crate) fn level_order(a: &Csr) -> Vec<usize> {
+    let n = a.n();
+    let mut level = vec![usize::MAX; n];
+    let mut order = Vec::with_capacity(n);
+    for i in 0..n {
+        if level[i] == usize::MAX {
+            let root = pseudo_peripheral(a, i);
+            bfs(a, root, &mut level, &mut order);
+        }
+    }
+    order
+}
+
+/// Reverse Cuthill–McKee ordering: breadth-first from a pseudo-peripheral
+/// vertex of each component, neighbours in increasing degree, reversed.
+/// Entry k is the vertex placed at position k; the result has a small
+/// bandwidth and profile, which makes incomplete factorizations of the
+/// permuted matrix stronger.
+pub(crate) fn reverse_cuthill_mckee(a: &Csr) -> Vec<usize> {
+    let n = a.n();
+    let mut placed = vec![false; n];
+    let mut order = Vec::with_capacity(n);
+    let mut neighbours = Vec::new();
+    for i in 0..n {
+        if placed[i] {
+            continue;
+        }
+        let root = pseudo_peripheral(a, i);
+        placed[root] = true;
+        let mut head = order.len();
+        order.push(root);
+        while head < order.len() {
+            let v = order[head];
+            head += 1;
+            neighbours.clear();
+            neighbours.extend(
+                a.col_indices[a.row_ptr[v] as usize..a.row_ptr[v + 1] as usize]
+                    .iter()
+                    .map(|&j| j as usize)
+                    .filter(|&j| !placed[j]),
+            );
+            neighbours.sort_by_key(|&j| degree(a, j));
+            for &j in neighbours.iter() {
+                if !placed[j] {
+                    placed[j] = true;
+                    order.push(j);
+                }
+            }
+        }
+    }
+    order.reverse();
+    order
+}
+
+/// Approximate minimum degree ordering: entry k is the vertex eliminated
+/// at step k
+///
+/// Elimination runs on the quotient graph: eliminating p turns it into an
+/// element whose variables L_p are its uneliminated neighbours, directly
+/// or through earlier elements, which it absorbs. The degrees of the
+/// variables in L_p are then bounded from above, as in Amestoy, Davis and
+/// Duff, by |A_i| + |L_p \ i| + sum over their other elements e of
+/// |L_e \ L_p|, which is cheap to update and close to the true degree.
+/// The vertex of smallest bound goes next. Gives much less Cholesky fill
+/// than bandwidth orderings on 2D and 3D meshes.
+pub(crate) fn approximate_minimum_degree(a: &Csr) -> Vec<usize> {
+    let n = a.n();
+    // Neighbouring variables A_i and elements E_i of every variable
+    let mut variables: Vec<Vec<usize>> = (0..n)
+        .map(|i| {
+            let mut row: Vec<usize> = a.col_indices
+                [a.row_ptr[i] as usize..a.row_ptr[i + 1] as usize]
+                .iter()
+                .map(|&j| j as usize)
+                .filter(|&j| j != i)
+                .collect();
+            row.sort_unstable();
+            row.dedup();
+            row
+        })
+        .collect();
+    let mut elements: Vec<Vec<usize>> = vec![Vec::new(); n];
+    // Variables L_e of element e (named after its pivot)
+    let mut members: Vec<Vec<usize>> = vec![Vec::new(); n];
+    let mut eliminated = vec![false; n];
+    let mut absorbed = vec![false; n];
+    let mut degree: Vec<usize> = variables.iter().map(Vec::len).collect();
+    let mut queue: BTreeSet<(usize, usize)> = (0..n).map(|i| (degree[i], i)).collect();
+    let mut in_pivot = vec![false; n];
+    let mut outside = vec![usize::MAX; n];
+    let mut order = Vec::with_capacity(n);
+
+    while let Some((_, p)) = queue.pop_first() {
+        eliminated[p] = true;
+        order.push(p);
+
+        // L_p = (A_p ∪ L_e for e in E_p) \ p, absorbing E_p
+        let mut element = Vec::new();
+        let sources = std::mem::take(&mut variables[p]).into_iter().chain(
+            std::mem::take(&mut elements[p]).into_iter().flat_map(|e| {
+                absorbed[e] = true;
+                std::mem::take(&mut members[e])
+            }),
+        );
+        for i in sources {
+            if !eliminated[i] && !in_pivot[i] {
+                in_pivot[i] = true;
+                element.push(i);
+            }
+        }
+
+        // |L_e \ L_p| for the other elements of the variables in L_p
+        for &i in element.iter() {
+            elements[i].retain(|&e| !absorbed[e]);
+            for &e in elements[i].iter() {
+                if outside[e] == usize::MAX {
+                    outside[e] = members[e].iter().filter(|&&j| !eliminated[j]).count();
+                }
+                outside[e] -= 1;
+            }
+        }
+
+        let remaining = n - order.len();
+        for &i in element.iter() {
+            // Entries of A_i inside L_p are covered by the new element
+            variables[i].retain(|&j| !eliminated[j] && !in_pivot[j]);
+            let external: usize = elements[i].iter().map(|&e| outside[e]).sum();
+            let bound = variables[i].len() + element.len() - 1 + external;
+            queue.remove(&(degree[i], i));
+            degree[i] = bound.min(remaining.saturating_sub(1));
+            queue.insert((degree[i], i));
+            elements[i].push(p);
+        }
+        for &i in element.iter() {
+            in_pivot[i] = false;
+            for &e in elements[i].iter() {
+                outside[e] = usize::MAX;
+            }
+        }
+        members[p] = element;
+    }
+    order
+}
+
+/// Split the vertices into `parts` sets of nearly equal size, as
+/// consecutive pieces of `level_order`: slabs across the mesh, which are
+/// connected for the usual FEM graphs. Each set is sorted.
+pub(crate) fn partition(a: &Csr, parts: usize) -> Vec<Vec<usize>> {
+    let order = level_order(a);
+    let parts = parts.clamp(1, order.len().max(1));
+    let mut sets: Vec<Vec<usize>> = (0..parts)
+        .map(|p| order[p * order.len() / parts..(p + 1) * order.len() / parts].to_vec())
+        .collect();
+    for set in sets.iter_mut() {
+        set.sort_unstable();
+    }
+    sets
+}
+
+/// Allowed deviation of a part from the average size in
+/// `balanced_partition`
+const IMBALANCE: f64 = 0.03;
+/// Greedy refinement sweeps of `balanced_partition`
+const REFINE_SWEEPS: usize = 8;
+
+/// Rows split into balanced parts with few couplings between them: the
+/// data layout of a partitioned SpMV or a domain decomposition
+#[wasm_bindgen]
+pub struct Partition {
+    part: Vec<u32>,
+    parts: usize,
+    edge_cut: usize,
+}
+
+#[wasm_bindgen]
+impl Partition {
+    /// Part of each row
+    #[wasm_bindgen(getter)]
+    pub fn part(&self) -> Vec<u32> {
+        self.part.clone()
+    }
+
+    /// Rows of part 0, then part 1, ...; each part in increasing order
+    #[wasm_bindgen(getter)]
+    pub fn permutation(&self) -> Vec<u32> {
+        let mut rows: Vec<u32> = (0..self.part.len() as u32).collect();
+        rows.sort_by_key(|&i| self.part[i as usize]);
+        rows
+    }
+
+    /// Part p holds `permutation[offsets[p]..offsets[p + 1]]`
+    #[wasm_bindgen(getter)]
+    pub fn offsets(&self) -> Vec<u32> {
+        let mut offsets = vec![0u32; self.parts + 1];
+        for &p in self.part.iter() {
+            offsets[p as usize + 1] += 1;
+        }
+        for p in 0..self.parts {
+            offsets[p + 1] += offsets[p];
+        }
+        offsets
+    }
+
+    /// Stored off-diagonal entries whose row and column lie in different
+    /// parts: the values a partitioned SpMV exchanges
+    #[wasm_bindgen(getter)]
+    pub fn edge_cut(&self) -> usize {
+        self.edge_cut
+    }
+}
+
+/// Split the rows of a matrix (pattern only; use both triangles of
+/// symmetric matrices) into `parts` parts of nearly equal size with a
+/// small edge cut, see `balanced_partition`
+#[wasm_bindgen]
+pub fn partition_matrix(col_indices: &[u32], row_ptr: &[u32], parts: u32) -> Partition {
+    let a = Csr::<f64>::new(&[], col_indices, row_ptr);
+    let parts = (parts as usize).clamp(1, a.n().max(1));
+    let part = balanced_partition(&a, parts);
+    Partition {
+        edge_cut: edge_cut(&a, &part),
+        part: part.iter().map(|&p| p as u32).collect(),
+        parts,
+    }
+}
+
+/// Part of each vertex for `parts` parts: the slabs of `partition`, then
+/// greedy refinement that moves boundary vertices to the neighbouring part
+/// holding most of their neighbours while every part stays within
+/// `IMBALANCE` of the average size. Cheap, and on meshes it smooths the
+/// jagged slab boundaries a breadth-first order leaves.
+pub(crate) fn balanced_partition(a: &Csr, parts: usize) -> Vec<usize> {
+    let n = a.n();
+    let mut part = vec![0usize; n];
+    for (p, set) in partition(a, parts).iter().enumerate() {
+        for &i in set.iter() {
+            part[i] = p;
+        }
+    }
+    let parts = parts.clamp(1, n.max(1));
+    let average = n as f64 / parts as f64;
+    let max_size = (average * (1.0 + IMBALANCE)).ceil() as usize;
+    let min_size = (average * (1.0 - IMBALANCE)).floor() as usize;
+    let mut size = vec![0usize; parts];
+    part.iter().for_each(|&p| size[p] += 1);
+
+    let mut links: Vec<(usize, usize)> = Vec::new();
+    for _ in 0..REFINE_SWEEPS {
+        let mut moved = false;
+        for i in 0..n {
+            // Neighbours of i per part
+            links.clear();
+            for &j in a.col_indices[a.row_ptr[i] as usize..a.row_ptr[i + 1] as usize].iter() {
+                let (j, q) = (j as usize, part[j as usize]);
+                if j == i {
+                    continue;
+                }
+                match links.iter_mut().find(|(p, _)| *p == q) {
+                    Some(link) => link.1 += 1,
+                    None => links.push((q, 1)),
+                }
+            }
+            let own = part[i];
+            let internal = links.iter().find(|(p, _)| *p == own).map_or(0, |l| l.1);
+            let best = links
+                .iter()
+                .filter(|&&(q, count)| q != own && count > internal && size[q] < max_size)
+                .max_by_key(|&&(_, count)| count);
+            if let Some(&(q, _)) = best {
+                if size[own] > min_size {
+                    size[own] -= 1;
+                    size[q] += 1;
+                    part[i] = q;
+                    moved = true;
+                }
+            }
+        }
+        if !moved {
+            break;
+        }
+    }
+    part
+}
+
+/// Stored off-diagonal entries between different parts
+pub(crate) fn edge_cut(a: &Csr, part: &[usize]) -> usize {
+    (0..a.n())
+        .map(|i| {
+            a.col_indices[a.row_ptr[i] as usize..a.row_ptr[i + 1] as usize]
+                .iter()
+                .filter(|&&j| part[j as usize] != part[i])
+                .count()
+        })
+        .sum()
+}
+
+/// Grow a sorted vertex set by `layers` rings of neighbours
+pub(crate) fn expand(a: &Csr, set: &[usize], layers: usize) -> Vec<usize> {
+    let mut inside = vec![false; a.n()];
+    let mut members = set.to_vec();
+    let mut frontier = set.to_vec();
+    for &i in set.iter() {
+        inside[i] = true;
+    }
+    for _ in 0..layers {
+        let mut next = Vec::new();
+        for &i in frontier.iter() {
+            for &j in a.col_indices[a.row_ptr[i] as usize..a.row_ptr[i + 1] as usize].iter() {
+                let j = j as usize;
+                if !inside[j] {
+                    inside[j] = true;
+                    next.push(j);
+                }
+            }
+        }
+        members.extend_from_slice(&next);
+        frontier = next;
+    }
+    members.sort_unstable();
+    members
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::direct::Symbolic;
+    use crate::kernels::SparseMatrix;
+    use crate::test_util::diffusion_2d;
+
+    #[test]
+    fn test_peripheral_vertex_of_grid_is_a_corner() {
+        let (nx, ny) = (9, 5);
+        let (values, col_indices, row_ptr) = diffusion_2d(nx, ny, &[1.0; 45]);
+        let a = Csr::new(&values, &col_indices, &row_ptr);
+        let corner = pseudo_peripheral(&a, nx * (ny / 2) + nx / 2);
+        let (x, y) = (corner % nx, corner / nx);
+        assert!((x == 0 || x == nx - 1) && (y == 0 || y == ny - 1));
+    }
+
+    #[test]
+    fn test_partition_covers_rows_once() {
+        let (nx, ny) = (12, 10);
+        let (values, col_indices, row_ptr) = diffusion_2d(nx, ny, &[1.0; 120]);
+        let a = Csr::new(&values, &col_indices, &row_ptr);
+        let sets = partition(&a, 4);
+        assert_eq!(sets.len(), 4);
+        let mut count = vec![0; nx * ny];
+        for set in sets.iter() {
+            assert_eq!(set.len(), 30);
+            set.iter().for_each(|&i| count[i] += 1);
+        }
+        assert!(count.iter().all(|&c| c == 1));
+
+        let grown = expand(&a, &sets[0], 1);
+        assert!(grown.len() > sets[0].len());
+        assert!(sets[0].iter().all(|i| grown.binary_search(i).is_ok()));
+    }
+
+    #[test]
+    fn test_rcm_restores_small_bandwidth() {
+        // A grid numbered with the stride 7 (coprime to 60) between
+        // neighbours' numbers
+        let (nx, ny) = (10, 6);
+        let (values, col_indices, row_ptr) = diffusion_2d(nx, ny, &[1.0; 60]);
+        let grid = Csr::new(&values, &col_indices, &row_ptr);
+        let scrambled: Vec<usize> = (0..60).map(|k| (7 * k) % 60).collect();
+        let matrix = SparseMatrix::permuted(&grid, &scrambled);
+        let a = matrix.csr();
+        let bandwidth = |m: &Csr| {
+            (0..m.n())
+                .flat_map(|i| {
+                    (m.row_ptr[i]..m.row_ptr[i + 1])
+                        .map(move |q| i.abs_diff(m.col_indices[q as usize] as usize))
+                })
+                .max()
+                .unwrap()
+        };
+
+        let perm = reverse_cuthill_mckee(&a);
+        let mut sorted = perm.clone();
+        sorted.sort_unstable();
+        assert_eq!(sorted, (0..60).collect::<Vec<_>>());
+        assert!(bandwidth(&a) > 30);
+        assert!(bandwidth(&SparseMatrix::permuted(&a, &perm).csr()) <= ny + 1);
+    }
+
+    #[test]
+    fn test_amd_reduces_cholesky_fill() {
+        let (nx, ny) = (60, 60);
+        let (values, col_indices, row_ptr) = diffusion_2d(nx, ny, &vec![1.0; nx * ny]);
+        let a = Csr::new(&values, &col_indices, &row_ptr);
+        let amd = approximate_minimum_degree(&a);
+        let mut seen = amd.clone();
+        seen.sort_unstable();
+        assert_eq!(seen, (0..nx * ny).collect::<Vec<_>>());
+
+        let fill = |perm: Vec<usize>| Symbolic::new(&a, perm).nnz();
+        let (amd, rcm) = (fill(amd), fill(reverse_cuthill_mckee(&a)));
+        assert!(2 * amd < rcm);
+    }
+
+    #[test]
+    fn test_balanced_partition_lowers_cut() {
+        let (nx, ny) = (40, 40);
+        let (values, col_indices, row_ptr) = diffusion_2d(nx, ny, &vec![1.0; nx * ny]);
+        let a = Csr::new(&values, &col_indices, &row_ptr);
+        let mut slabs = vec![0usize; nx * ny];
+        for (p, set) in partition(&a, 4).iter().enumerate() {
+            set.iter().for_each(|&i| slabs[i] = p);
+        }
+
+        let result = partition_matrix(&col_indices, &row_ptr, 4);
+        let offsets = result.offsets();
+        assert_eq!((offsets.len(), offsets[4] as usize), (5, nx * ny));
+        for p in 0..4 {
+            let size = (offsets[p + 1] - offsets[p]) as f64;
+            assert!((size - 400.0).abs() <= 400.0 * IMBALANCE + 1.0);
+        }
+        let perm = result.permutation();
+        let part = result.part();
+        assert!(perm
+            .windows(2)
+            .all(|w| part[w[0] as usize] <= part[w[1] as usize]));
+        let refined: Vec<usize> = part.iter().map(|&p| p as usize).collect();
+        assert_eq!(result.edge_cut(), edge_cut(&a, &refined));
+        assert!(result.edge_cut() < edge_cut(&a, &slabs));
+    }
+}
@@ -0,0 +1,195 @@
+//! Structured element grids and their matrix-free stiffness operator
+//!
+//! Nodes and elements follow the numbering of the JavaScript FEM code:
+//! node (x, y, z) is z (nelx + 1)(nely + 1) + x (nely + 1) + y and element
+//! (x, y, z) is z nelx nely + x nely + y; 2D grids have nelz = 0.
+
+use crate::kernels::LinearOperator;
+
+/// Number of nodes of a grid with `elements` elements per direction
+pub(crate) fn node_count(elements: [usize; 3]) -> usize {
+    elements.iter().map(|e| e + 1).product()
+}
+
+/// Node index of (x, y, z) on a grid with `elements` elements per direction
+pub(crate) fn node_index(elements: [usize; 3], x: usize, y: usize, z: usize) -> usize {
+    let (nx, ny) = (elements[0] + 1, elements[1] + 1);
+    z * nx * ny + x * ny + y
+}
+
+/// Stiffness K = sum_e s_e K_e of a structured grid, applied element by
+/// element without assembly
+///
+/// Every element shares the reference matrix `ke` (row-major, local DOFs
+/// ordered node by node, `dofs_per_node` per node) scaled by its factor
+/// s_e, e.g. the SIMP modulus of its density. The local nodes of element
+/// (x, y) are (x, y), (x + 1, y), (x + 1, y + 1), (x, y + 1) as in
+/// `getElementDOFs`; in 3D the same four at z are followed by those at
+/// z + 1. Fixed DOFs get a unit row and column, as when a Dirichlet
+/// condition is imposed on the assembled matrix.
+pub(crate) struct ElementGrid<'a> {
+    elements: [usize; 3],
+    dofs_per_node: usize,
+    ke: &'a [f64],
+    scales: &'a [f64],
+    fixed: Vec<bool>,
+}
+
+impl<'a> ElementGrid<'a> {
+    pub fn new(
+        elements: [usize; 3],
+        dofs_per_node: usize,
+        ke: &'a [f64],
+        scales: &'a [f64],
+        fixed_dofs: &[u32],
+    ) -> Self {
+        let dofs_per_node = dofs_per_node.max(1);
+        let mut fixed = vec![false; node_count(elements) * dofs_per_node];
+        for &i in fixed_dofs {
+            if let Some(f) = fixed.get_mut(i as usize) {
+                *f = true;
+            }
+        }
+        ElementGrid {
+            elements,
+            dofs_per_node,
+            ke,
+            scales,
+            fixed,
+        }
+    }
+
+    /// Number of DOFs
+    pub fn n(&self) -> usize {
+        self.fixed.len()
+    }
+
+    pub fn element_count(&self) -> usize {
+        self.elements.iter().map(|&e| e.max(1)).product()
+    }
+
+    /// Size of the element matrix
+    pub fn local_size(&self) -> usize {
+        let nodes = if self.elements[2] > 0 { 8 } else { 4 };
+        nodes * self.dofs_per_node
+    }
+
+    /// Elements per direction and DOFs per node
+    pub fn shape(&self) -> ([usize; 3], usize) {
+        (self.elements, self.dofs_per_node)
+    }
+
+    pub fn ke(&self) -> &[f64] {
+        self.ke
+    }
+
+    pub fn scale(&self, e: usize) -> f64 {
+        self.scales[e]
+    }
+
+    pub fn is_fixed(&self, i: usize) -> bool {
+        self.fixed[i]
+    }
+
+    /// Global DOFs of element `e` in local order
+    pub fn element_dofs(&self, e: usize, dofs: &mut Vec<usize>) {
+        let [nelx, nely, _] = self.elements;
+        let (z, rest) = (e / (nelx * nely), e % (nelx * nely));
+        let (x, y) = (rest / nely, rest % nely);
+        let layers: &[usize] = if self.elements[2] > 0 { &[0, 1] } else { &[0] };
+
+        dofs.clear();
+        for &dz in layers {
+            for (dx, dy) in [(0, 0), (1, 0), (1, 1), (0, 1)] {
+                let node = node_index(self.elements, x + dx, y + dy, z + dz);
+                dofs.extend((0..self.dofs_per_node).map(|d| node * self.dofs_per_node + d));
+            }
+        }
+    }
+
+    /// Diagonal of K, summed element by element (1 at fixed DOFs)
+    pub fn diagonal(&self) -> Vec<f64> {
+        let size = self.local_size();
+        let mut diag = vec![0.0; self.n()];
+        let mut dofs = Vec::with_capacity(size);
+        for e in 0..self.element_count() {
+            self.element_dofs(e, &mut dofs);
+            for (l, &i) in dofs.iter().enumerate() {
+                diag[i] += self.scales[e] * self.ke[l * size + l];
+            }
+        }
+        for (d, &f) in diag.iter_mut().zip(&self.fixed) {
+            if f || d.abs() <= 1e-30 {
+                *d = 1.0;
+            }
+        }
+        diag
+    }
+}
+
+impl LinearOperator for ElementGrid<'_> {
+    fn spmv(&self, x: &[f64], y: &mut [f64]) {
+        let size = self.local_size();
+        let mut dofs = Vec::with_capacity(size);
+        let mut xe = vec![0.0; size];
+        y.iter_mut().for_each(|v| *v = 0.0);
+
+        for e in 0..self.element_count() {
+            self.element_dofs(e, &mut dofs);
+            for (xl, &i) in xe.iter_mut().zip(&dofs) {
+                *xl = if self.fixed[i] { 0.0 } else { x[i] };
+            }
+            let s = self.scales[e];
+            for (row, &i) in self.ke.chunks_exact(size).zip(&dofs) {
+                if !self.fixed[i] {
+                    y[i] += s * row.iter().zip(&xe).map(|(k, v)| k * v).sum::<f64>();
+                }
+            }
+        }
+        for (i, yi) in y.iter_mut().enumerate() {
+            if self.fixed[i] {
+                *yi = x[i];
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{assemble_grid, q4_stiffness};
+
+    #[test]
+    fn test_matches_assembled_matrix() {
+        let ke = q4_stiffness(0.3);
+        let scales: Vec<f64> = (0..12).map(|e| 1e-3 + (e % 5) as f64).collect();
+        let grid = ElementGrid::new([4, 3, 0], 2, &ke, &scales, &[0, 1, 3, 40]);
+        let n = grid.n();
+        assert_eq!(n, 40);
+
+        let (values, col_indices, row_ptr) = assemble_grid(&grid);
+        let a = crate::kernels::Csr::new(&values, &col_indices, &row_ptr);
+        let x: Vec<f64> = (0..n).map(|i| (i as f64 * 0.7).sin()).collect();
+        let (mut free, mut assembled) = (vec![0.0; n], vec![0.0; n]);
+        grid.spmv(&x, &mut free);
+        a.spmv(&x, &mut assembled);
+        for (f, s) in free.iter().zip(&assembled) {
+            assert!((f - s).abs() < 1e-12);
+        }
+        for (d, s) in grid.diagonal().iter().zip(a.diagonal()) {
+            assert!((d - s).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_element_dofs_3d() {
+        let ke = vec![0.0; 24 * 24];
+        let grid = ElementGrid::new([2, 2, 2], 3, &ke, &[], &[]);
+        assert_eq!(grid.element_count(), 8);
+        let mut dofs = Vec::new();
+        grid.element_dofs(7, &mut dofs);
+        // Element (1, 1, 1): nodes (1, 1, 1), (2, 1, 1), (2, 2, 1), (1, 2, 1), ...
+        let first: Vec<usize> = dofs.iter().step_by(3).map(|d| d / 3).collect();
+        assert_eq!(first, vec![13, 16, 17, 14, 22, 25, 26, 23]);
+    }
+}
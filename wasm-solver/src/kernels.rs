@@ -0,0 +1,517 @@
+//! Shared sparse/dense kernels used by every solver
+//!
+//! Matrices arrive from JavaScript as three flat CSR arrays; `Csr` bundles
+//! them so the solvers can share SpMV and diagonal extraction.
+
+use crate::options::ConvergenceCriterion;
+
+/// Stored value type of a matrix: f64, or f32 to halve the memory of the
+/// values when single precision suffices. Products still accumulate in f64.
+pub(crate) trait Scalar: Copy + 'static {
+    fn to_f64(self) -> f64;
+    fn from_f64(v: f64) -> Self;
+}
+
+impl Scalar for f64 {
+    #[inline]
+    fn to_f64(self) -> f64 {
+        self
+    }
+
+    #[inline]
+    fn from_f64(v: f64) -> Self {
+        v
+    }
+}
+
+impl Scalar for f32 {
+    #[inline]
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+
+    #[inline]
+    fn from_f64(v: f64) -> Self {
+        v as f32
+    }
+}
+
+/// Borrowed CSR (Compressed Sparse Row) matrix
+#[derive(Clone, Copy)]
+pub(crate) struct Csr<'a, T: Scalar = f64> {
+    pub values: &'a [T],
+    pub col_indices: &'a [u32],
+    pub row_ptr: &'a [u32],
+}
+
+impl<'a, T: Scalar> Csr<'a, T> {
+    pub fn new(values: &'a [T], col_indices: &'a [u32], row_ptr: &'a [u32]) -> Self {
+        Csr {
+            values,
+            col_indices,
+            row_ptr,
+        }
+    }
+
+    /// Number of rows
+    #[inline]
+    pub fn n(&self) -> usize {
+        self.row_ptr.len().saturating_sub(1)
+    }
+
+    /// Sparse matrix-vector multiplication: y = A * x
+    #[inline]
+    pub fn spmv(&self, x: &[f64], y: &mut [f64]) {
+        for (i, yi) in y.iter_mut().enumerate() {
+            let row_start = self.row_ptr[i] as usize;
+            let row_end = self.row_ptr[i + 1] as usize;
+            let mut sum = 0.0;
+            for j in row_start..row_end {
+                sum += self.values[j].to_f64() * x[self.col_indices[j] as usize];
+            }
+            *yi = sum;
+        }
+    }
+
+    /// Transposed product y = A^T * x (y has one entry per column)
+    pub fn spmv_transpose(&self, x: &[f64], y: &mut [f64]) {
+        y.iter_mut().for_each(|v| *v = 0.0);
+        for (i, xi) in x.iter().enumerate().take(self.n()) {
+            for j in self.row_ptr[i] as usize..self.row_ptr[i + 1] as usize {
+                y[self.col_indices[j] as usize] += self.values[j].to_f64() * xi;
+            }
+        }
+    }
+
+    /// Multi-vector product Y = A * X in a single pass over the matrix
+    ///
+    /// X and Y hold `s` vectors interleaved row by row: x[i * s + c].
+    pub fn spmm(&self, x: &[f64], y: &mut [f64], s: usize) {
+        for i in 0..self.n() {
+            let yi = &mut y[i * s..(i + 1) * s];
+            yi.iter_mut().for_each(|v| *v = 0.0);
+            for j in self.row_ptr[i] as usize..self.row_ptr[i + 1] as usize {
+                let a = self.values[j].to_f64();
+                let col = self.col_indices[j] as usize;
+                for (yc, xc) in yi.iter_mut().zip(&x[col * s..(col + 1) * s]) {
+                    *yc += a * xc;
+                }
+            }
+        }
+    }
+
+    /// Residual r = b - A * x
+    pub fn residual(&self, b: &[f64], x: &[f64], r: &mut [f64]) {
+        self.spmv(x, r);
+        for (ri, bi) in r.iter_mut().zip(b) {
+            *ri = bi - *ri;
+        }
+    }
+
+    /// Extract diagonal elements (for Jacobi preconditioner); missing or
+    /// tiny entries are replaced with 1, see `invalid_diagonal_rows`
+    pub fn diagonal(&self) -> Vec<f64> {
+        (0..self.n())
+            .map(|i| self.usable_diagonal(i).unwrap_or(1.0))
+            .collect()
+    }
+
+    /// Rows whose diagonal entry is missing or tiny (|a_ii| <= 1e-30)
+    pub fn invalid_diagonal_rows(&self) -> Vec<u32> {
+        (0..self.n())
+            .filter(|&i| self.usable_diagonal(i).is_none())
+            .map(|i| i as u32)
+            .collect()
+    }
+
+    pub(crate) fn usable_diagonal(&self, i: usize) -> Option<f64> {
+        (self.row_ptr[i] as usize..self.row_ptr[i + 1] as usize)
+            .find(|&j| self.col_indices[j] as usize == i)
+            .map(|j| self.values[j].to_f64())
+            .filter(|val| val.abs() > 1e-30)
+    }
+}
+
+/// Operator y = A x that PCG can run against without a stored matrix
+pub(crate) trait LinearOperator {
+    /// y = A * x
+    fn spmv(&self, x: &[f64], y: &mut [f64]);
+
+    /// Residual r = b - A * x
+    fn residual(&self, b: &[f64], x: &[f64], r: &mut [f64]) {
+        self.spmv(x, r);
+        for (ri, bi) in r.iter_mut().zip(b) {
+            *ri = bi - *ri;
+        }
+    }
+}
+
+impl<T: Scalar> LinearOperator for Csr<'_, T> {
+    fn spmv(&self, x: &[f64], y: &mut [f64]) {
+        Csr::spmv(self, x, y)
+    }
+}
+
+/// Symmetric matrix stored as its upper triangle, diagonal included
+///
+/// Every stored off-diagonal a_ij also stands for a_ji, so the matrix and
+/// its SpMV take about half the memory and bandwidth of full storage.
+#[derive(Clone, Copy)]
+pub(crate) struct SymmetricCsr<'a> {
+    pub upper: Csr<'a>,
+}
+
+impl<'a> SymmetricCsr<'a> {
+    pub fn new(upper: Csr<'a>) -> Self {
+        SymmetricCsr { upper }
+    }
+
+    /// Lower triangle, the transpose of the stored one
+    pub fn lower(&self) -> SparseMatrix {
+        SparseMatrix::from_csr(&self.upper).transpose()
+    }
+
+    /// Both triangles, for code that needs full rows
+    pub fn full(&self) -> SparseMatrix {
+        let a = &self.upper;
+        let mut entries = Vec::with_capacity(2 * a.values.len());
+        for i in 0..a.n() {
+            for q in a.row_ptr[i] as usize..a.row_ptr[i + 1] as usize {
+                let j = a.col_indices[q];
+                entries.push((i, j, a.values[q]));
+                if j as usize != i {
+                    entries.push((j as usize, i as u32, a.values[q]));
+                }
+            }
+        }
+        SparseMatrix::from_triplets(a.n(), a.n(), entries)
+    }
+}
+
+impl LinearOperator for SymmetricCsr<'_> {
+    /// y = A x, each stored entry applied to both of its positions
+    fn spmv(&self, x: &[f64], y: &mut [f64]) {
+        let a = &self.upper;
+        y.iter_mut().for_each(|v| *v = 0.0);
+        for i in 0..a.n() {
+            let mut sum = 0.0;
+            for q in a.row_ptr[i] as usize..a.row_ptr[i + 1] as usize {
+                let (j, v) = (a.col_indices[q] as usize, a.values[q]);
+                sum += v * x[j];
+                if j != i {
+                    y[j] += v * x[i];
+                }
+            }
+            y[i] += sum;
+        }
+    }
+}
+
+/// Owned CSR matrix with `ncols` columns, for operators built inside the
+/// solver (multigrid hierarchies, prolongators)
+pub(crate) struct SparseMatrix {
+    pub row_ptr: Vec<u32>,
+    pub col_indices: Vec<u32>,
+    pub values: Vec<f64>,
+    pub ncols: usize,
+}
+
+impl SparseMatrix {
+    pub fn csr(&self) -> Csr<'_> {
+        Csr::new(&self.values, &self.col_indices, &self.row_ptr)
+    }
+
+    pub fn from_csr(a: &Csr) -> Self {
+        SparseMatrix {
+            row_ptr: a.row_ptr.to_vec(),
+            col_indices: a.col_indices.to_vec(),
+            values: a.values.to_vec(),
+            ncols: a.n(),
+        }
+    }
+
+    /// `a` with every row sorted by column and duplicate entries summed;
+    /// with `drop_zeros` entries that are (or sum to) exactly zero are
+    /// removed as well
+    pub fn canonical(a: &Csr, drop_zeros: bool) -> Self {
+        let mut out = SparseMatrix {
+            row_ptr: vec![0],
+            col_indices: Vec::with_capacity(a.values.len()),
+            values: Vec::with_capacity(a.values.len()),
+            ncols: a.n(),
+        };
+        let mut row: Vec<(u32, f64)> = Vec::new();
+        for i in 0..a.n() {
+            let start = out.col_indices.len();
+            row.clear();
+            row.extend(
+                (a.row_ptr[i] as usize..a.row_ptr[i + 1] as usize)
+                    .map(|q| (a.col_indices[q], a.values[q])),
+            );
+            row.sort_by_key(|&(j, _)| j);
+            for &(j, v) in row.iter() {
+                if out.col_indices.len() > start && out.col_indices.last() == Some(&j) {
+                    *out.values.last_mut().unwrap() += v;
+                } else {
+                    out.col_indices.push(j);
+                    out.values.push(v);
+                }
+            }
+            if drop_zeros {
+                let mut kept = start;
+                for q in start..out.col_indices.len() {
+                    if out.values[q] != 0.0 {
+                        out.col_indices[kept] = out.col_indices[q];
+                        out.values[kept] = out.values[q];
+                        kept += 1;
+                    }
+                }
+                out.col_indices.truncate(kept);
+                out.values.truncate(kept);
+            }
+            out.row_ptr.push(out.col_indices.len() as u32);
+        }
+        out
+    }
+
+    /// Principal submatrix A(rows, rows) for sorted `rows`, renumbered
+    /// 0..rows.len()
+    pub fn submatrix(a: &Csr, rows: &[usize]) -> Self {
+        Self::block(a, rows, rows)
+    }
+
+    /// Block A(rows, cols) for sorted `rows` and `cols`, renumbered
+    /// 0..rows.len() by 0..cols.len()
+    pub fn block(a: &Csr, rows: &[usize], cols: &[usize]) -> Self {
+        let mut local = vec![u32::MAX; a.n()];
+        for (l, &j) in cols.iter().enumerate() {
+            local[j] = l as u32;
+        }
+        let mut out = SparseMatrix {
+            row_ptr: vec![0],
+            col_indices: Vec::new(),
+            values: Vec::new(),
+            ncols: cols.len(),
+        };
+        for &i in rows.iter() {
+            for k in a.row_ptr[i] as usize..a.row_ptr[i + 1] as usize {
+                let j = local[a.col_indices[k] as usize];
+                if j != u32::MAX {
+                    out.col_indices.push(j);
+                    out.values.push(a.values[k]);
+                }
+            }
+            out.row_ptr.push(out.col_indices.len() as u32);
+        }
+        out
+    }
+
+    /// Symmetric permutation P A P^T: row and column k of the result are
+    /// row and column `perm[k]` of A
+    pub fn permuted(a: &Csr, perm: &[usize]) -> Self {
+        let mut position = vec![0u32; perm.len()];
+        for (k, &i) in perm.iter().enumerate() {
+            position[i] = k as u32;
+        }
+        let mut out = SparseMatrix {
+            row_ptr: vec![0],
+            col_indices: Vec::with_capacity(a.values.len()),
+            values: Vec::with_capacity(a.values.len()),
+            ncols: a.n(),
+        };
+        let mut row = Vec::new();
+        for &i in perm.iter() {
+            row.clear();
+            row.extend(
+                (a.row_ptr[i] as usize..a.row_ptr[i + 1] as usize)
+                    .map(|q| (position[a.col_indices[q] as usize], a.values[q])),
+            );
+            row.sort_by_key(|&(j, _)| j);
+            for &(j, v) in row.iter() {
+                out.col_indices.push(j);
+                out.values.push(v);
+            }
+            out.row_ptr.push(out.col_indices.len() as u32);
+        }
+        out
+    }
+
+    /// Sparse product A * B, one dense accumulator row at a time
+    pub fn multiply(a: &Csr, b: &SparseMatrix) -> SparseMatrix {
+        let mut out = SparseMatrix {
+            row_ptr: vec![0],
+            col_indices: Vec::new(),
+            values: Vec::new(),
+            ncols: b.ncols,
+        };
+        let mut acc = vec![0.0; b.ncols];
+        let mut used = vec![false; b.ncols];
+        let mut pattern = Vec::new();
+        for i in 0..a.n() {
+            for k in a.row_ptr[i] as usize..a.row_ptr[i + 1] as usize {
+                let (j, v) = (a.col_indices[k] as usize, a.values[k]);
+                for q in b.row_ptr[j] as usize..b.row_ptr[j + 1] as usize {
+                    let c = b.col_indices[q] as usize;
+                    if !used[c] {
+                        used[c] = true;
+                        pattern.push(c);
+                    }
+                    acc[c] += v * b.values[q];
+                }
+            }
+            pattern.sort_unstable();
+            for &c in pattern.iter() {
+                out.col_indices.push(c as u32);
+                out.values.push(acc[c]);
+                acc[c] = 0.0;
+                used[c] = false;
+            }
+            pattern.clear();
+            out.row_ptr.push(out.col_indices.len() as u32);
+        }
+        out
+    }
+
+    pub fn transpose(&self) -> SparseMatrix {
+        let mut count = vec![0u32; self.ncols + 1];
+        for &c in self.col_indices.iter() {
+            count[c as usize + 1] += 1;
+        }
+        for c in 0..self.ncols {
+            count[c + 1] += count[c];
+        }
+        let mut next = count.clone();
+        let mut col_indices = vec![0u32; self.values.len()];
+        let mut values = vec![0.0; self.values.len()];
+        for i in 0..self.row_ptr.len() - 1 {
+            for k in self.row_ptr[i] as usize..self.row_ptr[i + 1] as usize {
+                let c = self.col_indices[k] as usize;
+                let dst = next[c] as usize;
+                col_indices[dst] = i as u32;
+                values[dst] = self.values[k];
+                next[c] += 1;
+            }
+        }
+        SparseMatrix {
+            row_ptr: count,
+            col_indices,
+            values,
+            ncols: self.row_ptr.len() - 1,
+        }
+    }
+
+    /// X + Y for matrices of the same shape
+    pub fn add(x: &SparseMatrix, y: &SparseMatrix) -> SparseMatrix {
+        let rows = x.row_ptr.len() - 1;
+        let mut out = SparseMatrix {
+            row_ptr: vec![0],
+            col_indices: Vec::new(),
+            values: Vec::new(),
+            ncols: x.ncols,
+        };
+        for i in 0..rows {
+            let mut row: Vec<(u32, f64)> = (x.row_ptr[i] as usize..x.row_ptr[i + 1] as usize)
+                .map(|q| (x.col_indices[q], x.values[q]))
+                .chain(
+                    (y.row_ptr[i] as usize..y.row_ptr[i + 1] as usize)
+                        .map(|q| (y.col_indices[q], y.values[q])),
+                )
+                .collect();
+            row.sort_by_key(|&(j, _)| j);
+            for (j, v) in row {
+                if out.col_indices.len() > out.row_ptr[i] as usize
+                    && out.col_indices.last() == Some(&j)
+                {
+                    *out.values.last_mut().unwrap() += v;
+                } else {
+                    out.col_indices.push(j);
+                    out.values.push(v);
+                }
+            }
+            out.row_ptr.push(out.col_indices.len() as u32);
+        }
+        out
+    }
+
+    /// Matrix with `nrows` rows from (row, column, value) triplets; repeated
+    /// positions are summed
+    pub fn from_triplets(nrows: usize, ncols: usize, mut entries: Vec<(usize, u32, f64)>) -> Self {
+        entries.sort_by_key(|&(i, j, _)| (i, j));
+        let mut out = SparseMatrix {
+            row_ptr: vec![0u32; nrows + 1],
+            col_indices: Vec::with_capacity(entries.len()),
+            values: Vec::with_capacity(entries.len()),
+            ncols,
+        };
+        let mut last = None;
+        for &(i, j, v) in entries.iter() {
+            if last == Some((i, j)) {
+                *out.values.last_mut().unwrap() += v;
+                continue;
+            }
+            last = Some((i, j));
+            out.row_ptr[i + 1] += 1;
+            out.col_indices.push(j);
+            out.values.push(v);
+        }
+        for i in 0..nrows {
+            out.row_ptr[i + 1] += out.row_ptr[i];
+        }
+        out
+    }
+}
+
+/// Compute dot product of two vectors
+#[inline]
+pub(crate) fn dot(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b.iter()).map(|(x, y)| x * y).sum()
+}
+
+/// Compute vector L2 norm
+#[inline]
+pub(crate) fn norm(v: &[f64]) -> f64 {
+    dot(v, v).sqrt()
+}
+
+/// Absolute residual threshold for a relative tolerance `tol`
+#[inline]
+pub(crate) fn threshold(b: &[f64], tol: f64) -> f64 {
+    tol * norm(b).max(1.0)
+}
+
+/// Absolute residual threshold of a residual-based `criterion`
+///
+/// `bnorm` and `r0norm` are the norms of b and of the initial residual, in
+/// the norm the solver monitors. `EnergyNorm` has no residual threshold; it
+/// maps to the smallest positive value so that only an exactly zero
+/// residual stops on it.
+pub(crate) fn criterion_threshold(
+    criterion: ConvergenceCriterion,
+    bnorm: f64,
+    r0norm: f64,
+    tol: f64,
+) -> f64 {
+    match criterion {
+        ConvergenceCriterion::RelativeToRhs => tol * bnorm.max(1.0),
+        ConvergenceCriterion::Absolute => tol,
+        ConvergenceCriterion::RelativeToInitial => tol * r0norm,
+        ConvergenceCriterion::EnergyNorm => f64::MIN_POSITIVE,
+    }
+}
+
+/// y = y + alpha * x
+#[inline]
+pub(crate) fn axpy(alpha: f64, x: &[f64], y: &mut [f64]) {
+    for (yi, xi) in y.iter_mut().zip(x) {
+        *yi += alpha * xi;
+    }
+}
+
+/// Apply Jacobi preconditioner: z = M^{-1} * r
+/// where M = diag(A)
+#[inline]
+pub(crate) fn apply_jacobi(diag: &[f64], r: &[f64], z: &mut [f64]) {
+    for ((zi, ri), di) in z.iter_mut().zip(r).zip(diag) {
+        *zi = ri / di;
+    }
+}
@@ -0,0 +1,110 @@
+use wasm_bindgen::prelude::*;
+
+use super::fallback;
+use crate::analysis::{analyze, MatrixInfo};
+use crate::kernels::Csr;
+use crate::options::{SolverKind, SolverOptions};
+use crate::SolveResult;
+
+/// Systems up to this size are solved by unrestarted GMRES, which
+/// terminates in at most n steps whatever the matrix
+const SMALL_SYSTEM: u32 = 100;
+
+/// Solve A x = b with a solver picked from the properties of A
+///
+/// * n <= 100: full GMRES
+/// * symmetric with a positive diagonal (stiffness matrices): PCG, falling
+///   back to MINRES and GMRES if it breaks down
+/// * other symmetric matrices: MINRES, then GMRES
+/// * nonsymmetric and diagonally dominant: BiCGSTAB, then GMRES
+/// * other nonsymmetric matrices: GMRES
+///
+/// PCG, BiCGSTAB and GMRES use the preconditioner from `options`, MINRES
+/// Jacobi.
+/// Tolerance, iteration limit and the other settings also come from
+/// `options`; the `solver` field of the result reports which solver
+/// produced the solution.
+#[wasm_bindgen]
+pub fn solve_auto(
+    values: &[f64],
+    col_indices: &[u32],
+    row_ptr: &[u32],
+    b: &[f64],
+    x0: &[f64],
+    options: &SolverOptions,
+) -> SolveResult {
+    let a = Csr::new(values, col_indices, row_ptr);
+    auto(&a, b, x0, options)
+}
+
+pub(crate) fn auto(a: &Csr, b: &[f64], x0: &[f64], options: &SolverOptions) -> SolveResult {
+    let info = analyze(a);
+    let mut options = *options;
+    if info.n() <= SMALL_SYSTEM {
+        options.gmres_restart = info.n().max(1);
+    }
+    fallback(a, b, x0, &choose_chain(&info), &options)
+}
+
+/// Solvers to try, in order, for a matrix with the given properties
+pub(crate) fn choose_chain(info: &MatrixInfo) -> Vec<SolverKind> {
+    if info.n() <= SMALL_SYSTEM {
+        vec![SolverKind::Gmres]
+    } else if info.symmetric() && info.positive_diagonal() {
+        vec![SolverKind::Pcg, SolverKind::Minres, SolverKind::Gmres]
+    } else if info.symmetric() {
+        vec![SolverKind::Minres, SolverKind::Gmres]
+    } else if info.diagonally_dominant() {
+        vec![SolverKind::Bicgstab, SolverKind::Gmres]
+    } else {
+        vec![SolverKind::Gmres]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kernels::norm;
+    use crate::test_util::{convection_diffusion_1d, laplacian_1d};
+
+    #[test]
+    fn test_picks_cg_for_stiffness_like_matrix() {
+        let n = 200;
+        let (values, col_indices, row_ptr) = laplacian_1d(n);
+        let b = vec![1.0; n];
+
+        let result = solve_auto(
+            &values,
+            &col_indices,
+            &row_ptr,
+            &b,
+            &vec![0.0; n],
+            &SolverOptions::new(),
+        );
+
+        assert_eq!(result.solver, Some(SolverKind::Pcg));
+        assert!(result.residual < 1e-8 * norm(&b));
+    }
+
+    #[test]
+    fn test_picks_nonsymmetric_solver() {
+        let n = 300;
+        let (values, col_indices, row_ptr) = convection_diffusion_1d(n, 0.6);
+        let a = Csr::new(&values, &col_indices, &row_ptr);
+        let b: Vec<f64> = (0..n).map(|i| (i as f64 * 0.05).sin()).collect();
+
+        let result = auto(&a, &b, &vec![0.0; n], &SolverOptions::new());
+
+        assert_eq!(result.solver, Some(SolverKind::Bicgstab));
+        let mut r = vec![0.0; n];
+        a.residual(&b, &result.solution, &mut r);
+        assert!(norm(&r) < 1e-8 * norm(&b).max(1.0));
+
+        // Small systems go straight to full GMRES
+        let (values, col_indices, row_ptr) = laplacian_1d(20);
+        let small = Csr::new(&values, &col_indices, &row_ptr);
+        let result = auto(&small, &[1.0; 20], &[0.0; 20], &SolverOptions::new());
+        assert_eq!(result.solver, Some(SolverKind::Gmres));
+        assert!(result.iterations <= 20);
+    }
+}
@@ -0,0 +1,183 @@
+use wasm_bindgen::prelude::*;
+
+use crate::kernels::{axpy, dot, norm, threshold, Csr};
+use crate::precond::{Jacobi, Preconditioner};
+use crate::SolveResult;
+
+/// Jacobi-preconditioned BiCGSTAB solver for nonsymmetric systems
+///
+/// Takes the same arguments as `solve_pcg`; the matrix only needs to be
+/// nonsingular, not symmetric positive definite.
+#[wasm_bindgen]
+pub fn solve_bicgstab(
+    values: &[f64],
+    col_indices: &[u32],
+    row_ptr: &[u32],
+    b: &[f64],
+    x0: &[f64],
+    tol: f64,
+    max_iter: u32,
+) -> SolveResult {
+    let a = Csr::new(values, col_indices, row_ptr);
+    bicgstab(&a, b, x0, tol, max_iter)
+}
+
+pub(crate) fn bicgstab(a: &Csr, b: &[f64], x0: &[f64], tol: f64, max_iter: u32) -> SolveResult {
+    let m = Jacobi::new(a);
+    bicgstab_preconditioned(a, &m, b, x0, tol, max_iter)
+}
+
+/// BiCGSTAB with right preconditioner `m`
+pub(crate) fn bicgstab_preconditioned(
+    a: &Csr,
+    m: &dyn Preconditioner,
+    b: &[f64],
+    x0: &[f64],
+    tol: f64,
+    max_iter: u32,
+) -> SolveResult {
+    let n = b.len();
+    let mut x: Vec<f64> = x0.to_vec();
+
+    // Work vectors
+    let mut r = vec![0.0; n]; // Residual
+    let mut p = vec![0.0; n]; // Search direction
+    let mut v = vec![0.0; n]; // A * M^{-1} p
+    let mut y = vec![0.0; n]; // M^{-1} p
+    let mut s = vec![0.0; n]; // Intermediate residual
+    let mut z = vec![0.0; n]; // M^{-1} s
+    let mut t = vec![0.0; n]; // A * M^{-1} s
+
+    a.residual(b, &x, &mut r);
+    let threshold = threshold(b, tol);
+
+    let mut rnorm = norm(&r);
+    if rnorm < threshold {
+        return SolveResult::new(x, 0, rnorm);
+    }
+
+    // Shadow residual stays fixed for the whole run
+    let r_hat = r.clone();
+
+    let mut rho = 1.0;
+    let mut alpha = 1.0;
+    let mut omega = 1.0;
+
+    let mut iter = 0u32;
+    for i in 0..max_iter {
+        iter = i + 1;
+
+        let rho_new = dot(&r_hat, &r);
+        if rho_new.abs() < 1e-30 {
+            // r is orthogonal to the shadow residual: breakdown
+            break;
+        }
+
+        // p = r + beta * (p - omega * v)
+        let beta = (rho_new / rho) * (alpha / omega);
+        for j in 0..n {
+            p[j] = r[j] + beta * (p[j] - omega * v[j]);
+        }
+        rho = rho_new;
+
+        // v = A * M^{-1} p
+        m.apply(&p, &mut y);
+        a.spmv(&y, &mut v);
+
+        let rv = dot(&r_hat, &v);
+        if rv.abs() < 1e-30 {
+            break;
+        }
+        alpha = rho / rv;
+
+        // s = r - alpha * v
+        s.copy_from_slice(&r);
+        axpy(-alpha, &v, &mut s);
+
+        // Half-step convergence: x = x + alpha * M^{-1} p
+        let snorm = norm(&s);
+        if snorm < threshold {
+            axpy(alpha, &y, &mut x);
+            rnorm = snorm;
+            break;
+        }
+
+        // t = A * M^{-1} s
+        m.apply(&s, &mut z);
+        a.spmv(&z, &mut t);
+
+        // omega = (t^T * s) / (t^T * t)
+        let tt = dot(&t, &t);
+        omega = if tt > 1e-30 { dot(&t, &s) / tt } else { 0.0 };
+
+        // x = x + alpha * M^{-1} p + omega * M^{-1} s
+        axpy(alpha, &y, &mut x);
+        axpy(omega, &z, &mut x);
+
+        // r = s - omega * t
+        r.copy_from_slice(&s);
+        axpy(-omega, &t, &mut r);
+
+        rnorm = norm(&r);
+        if rnorm < threshold {
+            break;
+        }
+
+        if omega.abs() < 1e-30 {
+            // Stabilization step stagnated; cannot continue
+            break;
+        }
+    }
+
+    SolveResult::new(x, iter, rnorm)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::convection_diffusion_1d;
+
+    #[test]
+    fn test_nonsymmetric_3x3() {
+        // [4, 1, 0; -1, 4, 1; 0, -2, 4] * x = b with x = [1, 2, 3]
+        let values = vec![4.0, 1.0, -1.0, 4.0, 1.0, -2.0, 4.0];
+        let col_indices = vec![0u32, 1, 0, 1, 2, 1, 2];
+        let row_ptr = vec![0u32, 2, 5, 7];
+        let b = vec![6.0, 10.0, 8.0];
+        let x0 = vec![0.0; 3];
+
+        let result = solve_bicgstab(&values, &col_indices, &row_ptr, &b, &x0, 1e-12, 100);
+
+        for (i, xi) in result.solution.iter().enumerate() {
+            assert!((xi - (i + 1) as f64).abs() < 1e-8);
+        }
+    }
+
+    #[test]
+    fn test_convection_diffusion() {
+        let n = 50;
+        let (values, col_indices, row_ptr) = convection_diffusion_1d(n, 0.8);
+        let a = Csr::new(&values, &col_indices, &row_ptr);
+        let b = vec![1.0; n];
+        let x0 = vec![0.0; n];
+
+        let result = bicgstab(&a, &b, &x0, 1e-10, 500);
+
+        let mut r = vec![0.0; n];
+        a.residual(&b, &result.solution, &mut r);
+        assert!(norm(&r) < 1e-8);
+        assert!(result.iterations < 500);
+    }
+
+    #[test]
+    fn test_zero_rhs_returns_initial_guess() {
+        let (values, col_indices, row_ptr) = convection_diffusion_1d(10, 0.5);
+        let b = vec![0.0; 10];
+        let x0 = vec![0.0; 10];
+
+        let result = solve_bicgstab(&values, &col_indices, &row_ptr, &b, &x0, 1e-10, 100);
+
+        assert_eq!(result.iterations, 0);
+        assert!(result.solution.iter().all(|&v| v == 0.0));
+    }
+}
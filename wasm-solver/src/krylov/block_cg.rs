@@ -0,0 +1,259 @@
+use wasm_bindgen::prelude::*;
+
+use super::pcg;
+use crate::dense::{cholesky_factor, cholesky_solve};
+use crate::kernels::{norm, threshold, Csr};
+use crate::SolveResult;
+
+/// Block PCG solver for several right-hand sides against the same matrix
+///
+/// # Arguments
+/// * `values`, `col_indices`, `row_ptr` - SPD matrix in CSR format
+/// * `b` - `num_rhs` right-hand sides of length n, stored one after another
+/// * `x0` - Initial guesses, same layout as `b`
+/// * `num_rhs` - Number of right-hand sides
+/// * `tol`, `max_iter` - As in `solve_pcg`, applied to every column
+///
+/// # Returns
+/// SolveResult whose `solution` holds all solutions in the layout of `b`,
+/// `iterations` counts block iterations and `residual` is the largest
+/// residual norm over the columns
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn solve_block_cg(
+    values: &[f64],
+    col_indices: &[u32],
+    row_ptr: &[u32],
+    b: &[f64],
+    x0: &[f64],
+    num_rhs: u32,
+    tol: f64,
+    max_iter: u32,
+) -> SolveResult {
+    let a = Csr::new(values, col_indices, row_ptr);
+    block_cg(&a, b, x0, num_rhs as usize, tol, max_iter)
+}
+
+pub(crate) fn block_cg(
+    a: &Csr,
+    b: &[f64],
+    x0: &[f64],
+    num_rhs: usize,
+    tol: f64,
+    max_iter: u32,
+) -> SolveResult {
+    let n = a.n();
+    let diag = a.diagonal();
+
+    let mut x: Vec<Vec<f64>> = x0
+        .chunks_exact(n)
+        .take(num_rhs)
+        .map(<[f64]>::to_vec)
+        .collect();
+    let rhs: Vec<&[f64]> = b.chunks_exact(n).take(num_rhs).collect();
+    let thresholds: Vec<f64> = rhs.iter().map(|bc| threshold(bc, tol)).collect();
+    let mut r: Vec<Vec<f64>> = rhs
+        .iter()
+        .zip(&x)
+        .map(|(bc, xc)| {
+            let mut rc = vec![0.0; n];
+            a.residual(bc, xc, &mut rc);
+            rc
+        })
+        .collect();
+    let mut rnorms: Vec<f64> = r.iter().map(|rc| norm(rc)).collect();
+
+    let mut iter = 0u32;
+
+    // Each pass restarts the block recurrence on the columns that have not
+    // converged yet; converged columns would make P^T A P singular
+    'restart: while iter < max_iter {
+        let active: Vec<usize> = (0..num_rhs)
+            .filter(|&c| rnorms[c] >= thresholds[c])
+            .collect();
+        let s = active.len();
+        if s == 0 {
+            break;
+        }
+
+        // Pack active columns interleaved: v[i * s + k]
+        let pack = |cols: &[Vec<f64>]| -> Vec<f64> {
+            let mut out = vec![0.0; n * s];
+            for (k, &c) in active.iter().enumerate() {
+                for i in 0..n {
+                    out[i * s + k] = cols[c][i];
+                }
+            }
+            out
+        };
+        let mut xb = pack(&x);
+        let mut rb = pack(&r);
+        let mut zb = vec![0.0; n * s];
+        let mut qb = vec![0.0; n * s];
+
+        precondition(&diag, &rb, &mut zb, s);
+        let mut pb = zb.clone();
+        let mut fresh = true;
+
+        loop {
+            a.spmm(&pb, &mut qb, s);
+
+            // P^T Q is SPD as long as the columns of P stay independent
+            let mut ptq = gram(&pb, &qb, n, s);
+            if !cholesky_factor(&mut ptq, s) {
+                unpack(&xb, &mut x, &active, n);
+                unpack(&rb, &mut r, &active, n);
+                if fresh {
+                    // Block is rank deficient from the start: solve the
+                    // remaining columns one at a time
+                    for &c in &active {
+                        let single = pcg(a, rhs[c], &x[c], tol, max_iter - iter);
+                        x[c] = single.solution;
+                        rnorms[c] = single.residual;
+                    }
+                    break 'restart;
+                }
+                continue 'restart;
+            }
+            fresh = false;
+
+            // alpha = (P^T Q)^{-1} P^T R
+            let alpha = solve_block(&ptq, &gram(&pb, &rb, n, s), s);
+            add_product(&mut xb, &pb, &alpha, 1.0, n, s);
+            add_product(&mut rb, &qb, &alpha, -1.0, n, s);
+            iter += 1;
+
+            let mut any_converged = false;
+            for (k, &c) in active.iter().enumerate() {
+                rnorms[c] = (0..n).map(|i| rb[i * s + k].powi(2)).sum::<f64>().sqrt();
+                any_converged |= rnorms[c] < thresholds[c];
+            }
+            if any_converged || iter >= max_iter {
+                unpack(&xb, &mut x, &active, n);
+                unpack(&rb, &mut r, &active, n);
+                continue 'restart;
+            }
+
+            // beta = -(P^T Q)^{-1} Q^T Z, P = Z + P beta
+            precondition(&diag, &rb, &mut zb, s);
+            let beta = solve_block(&ptq, &gram(&qb, &zb, n, s), s);
+            let mut pnew = zb.clone();
+            add_product(&mut pnew, &pb, &beta, -1.0, n, s);
+            pb = pnew;
+        }
+    }
+
+    let residual = rnorms.iter().fold(0.0, |acc: f64, &v| acc.max(v));
+    SolveResult::new(x.concat(), iter, residual)
+}
+
+/// Jacobi on every column of an interleaved block
+fn precondition(diag: &[f64], r: &[f64], z: &mut [f64], s: usize) {
+    for (i, d) in diag.iter().enumerate() {
+        for k in 0..s {
+            z[i * s + k] = r[i * s + k] / d;
+        }
+    }
+}
+
+/// s x s product U^T V of two interleaved blocks
+fn gram(u: &[f64], v: &[f64], n: usize, s: usize) -> Vec<f64> {
+    let mut g = vec![0.0; s * s];
+    for i in 0..n {
+        let ui = &u[i * s..(i + 1) * s];
+        let vi = &v[i * s..(i + 1) * s];
+        for (k, uk) in ui.iter().enumerate() {
+            for (l, vl) in vi.iter().enumerate() {
+                g[k * s + l] += uk * vl;
+            }
+        }
+    }
+    g
+}
+
+/// Solve (L L^T) C = G column by column for an s x s right-hand side
+fn solve_block(l: &[f64], g: &[f64], s: usize) -> Vec<f64> {
+    let mut c = vec![0.0; s * s];
+    let mut col = vec![0.0; s];
+    for j in 0..s {
+        for k in 0..s {
+            col[k] = g[k * s + j];
+        }
+        cholesky_solve(l, s, &mut col);
+        for k in 0..s {
+            c[k * s + j] = col[k];
+        }
+    }
+    c
+}
+
+/// Y = Y + sign * U C for an interleaved block U and s x s matrix C
+fn add_product(y: &mut [f64], u: &[f64], c: &[f64], sign: f64, n: usize, s: usize) {
+    for i in 0..n {
+        for k in 0..s {
+            let uik = sign * u[i * s + k];
+            for l in 0..s {
+                y[i * s + l] += uik * c[k * s + l];
+            }
+        }
+    }
+}
+
+fn unpack(block: &[f64], cols: &mut [Vec<f64>], active: &[usize], n: usize) {
+    let s = active.len();
+    for (k, &c) in active.iter().enumerate() {
+        for i in 0..n {
+            cols[c][i] = block[i * s + k];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::laplacian_1d;
+
+    #[test]
+    fn test_three_rhs() {
+        let n = 40;
+        let (values, col_indices, row_ptr) = laplacian_1d(n);
+        let a = Csr::new(&values, &col_indices, &row_ptr);
+        let mut b = vec![1.0; n];
+        b.extend((0..n).map(|i| i as f64));
+        b.extend((0..n).map(|i| ((i * 3) % 7) as f64));
+        let x0 = vec![0.0; 3 * n];
+
+        let result = solve_block_cg(&values, &col_indices, &row_ptr, &b, &x0, 3, 1e-10, 500);
+
+        let mut r = vec![0.0; n];
+        for c in 0..3 {
+            let bc = &b[c * n..(c + 1) * n];
+            a.residual(bc, &result.solution[c * n..(c + 1) * n], &mut r);
+            assert!(norm(&r) < 1e-8 * norm(bc));
+        }
+
+        // Shared Krylov information: never slower than the hardest single solve
+        let worst_single = (0..3)
+            .map(|c| pcg(&a, &b[c * n..(c + 1) * n], &x0[..n], 1e-10, 500).iterations)
+            .max()
+            .unwrap();
+        assert!(result.iterations <= worst_single);
+    }
+
+    #[test]
+    fn test_dependent_rhs_falls_back() {
+        // Two identical right-hand sides make P^T A P singular immediately
+        let n = 20;
+        let (values, col_indices, row_ptr) = laplacian_1d(n);
+        let a = Csr::new(&values, &col_indices, &row_ptr);
+        let b = vec![1.0; 2 * n];
+        let x0 = vec![0.0; 2 * n];
+
+        let result = block_cg(&a, &b, &x0, 2, 1e-10, 500);
+
+        assert!(result.residual < 1e-8);
+        for i in 0..n {
+            assert!((result.solution[i] - result.solution[n + i]).abs() < 1e-8);
+        }
+    }
+}
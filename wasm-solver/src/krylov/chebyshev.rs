@@ -0,0 +1,247 @@
+use wasm_bindgen::prelude::*;
+
+use crate::kernels::{apply_jacobi, axpy, dot, norm, threshold, Csr};
+use crate::SolveResult;
+
+/// Residual norm is only checked this often, so Chebyshev iterations in
+/// between need no inner products at all
+const CHECK_INTERVAL: u32 = 10;
+
+/// Jacobi-preconditioned Chebyshev iteration
+///
+/// # Arguments
+/// * `values`, `col_indices`, `row_ptr`, `b`, `x0` - As in `solve_pcg`
+/// * `lambda_min`, `lambda_max` - Bounds on the spectrum of D^{-1} A,
+///   e.g. from `estimate_spectral_bounds`, with 0 < lambda_min <
+///   lambda_max; other bounds return `x0` unconverged with an infinite
+///   residual
+/// * `tol` - Convergence tolerance, checked every few iterations; with
+///   `tol = 0` exactly `max_iter` iterations are run (smoother use)
+/// * `max_iter` - Maximum number of iterations
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn solve_chebyshev(
+    values: &[f64],
+    col_indices: &[u32],
+    row_ptr: &[u32],
+    b: &[f64],
+    x0: &[f64],
+    lambda_min: f64,
+    lambda_max: f64,
+    tol: f64,
+    max_iter: u32,
+) -> SolveResult {
+    let a = Csr::new(values, col_indices, row_ptr);
+    chebyshev(&a, b, x0, lambda_min, lambda_max, tol, max_iter)
+}
+
+/// Estimate [lambda_min, lambda_max] of D^{-1} A by power iteration
+///
+/// lambda_max comes from power iteration on D^{-1} A, lambda_min from power
+/// iteration on the shifted operator lambda_max I - D^{-1} A. Power iteration
+/// approaches the extreme eigenvalues from inside, so callers usually widen
+/// the interval by a few percent before using it.
+#[wasm_bindgen]
+pub fn estimate_spectral_bounds(
+    values: &[f64],
+    col_indices: &[u32],
+    row_ptr: &[u32],
+    iterations: u32,
+) -> Vec<f64> {
+    let a = Csr::new(values, col_indices, row_ptr);
+    let (lmin, lmax) = spectral_bounds(&a, iterations);
+    vec![lmin, lmax]
+}
+
+pub(crate) fn spectral_bounds(a: &Csr, iterations: u32) -> (f64, f64) {
+    let n = a.n();
+    let diag = a.diagonal();
+    let mut av = vec![0.0; n];
+
+    // y = D^{-1} A v - shift * v
+    let mut apply = |v: &[f64], y: &mut [f64], shift: f64| {
+        a.spmv(v, &mut av);
+        apply_jacobi(&diag, &av, y);
+        axpy(-shift, v, y);
+    };
+
+    let power = |apply: &mut dyn FnMut(&[f64], &mut [f64])| -> f64 {
+        // Deterministic, non-smooth start vector
+        let mut v: Vec<f64> = (0..n)
+            .map(|i| 1.0 + ((i * 7919) % 101) as f64 / 101.0)
+            .collect();
+        let vnorm = norm(&v);
+        v.iter_mut().for_each(|vi| *vi /= vnorm);
+        let mut y = vec![0.0; n];
+        let mut lambda = 0.0;
+        for _ in 0..iterations.max(1) {
+            apply(&v, &mut y);
+            // Rayleigh quotient with the normalized iterate
+            lambda = dot(&v, &y);
+            let ynorm = norm(&y);
+            if ynorm < 1e-300 {
+                break;
+            }
+            for (vi, yi) in v.iter_mut().zip(&y) {
+                *vi = yi / ynorm;
+            }
+        }
+        lambda
+    };
+
+    let lmax = power(&mut |v, y| apply(v, y, 0.0));
+    let spread = power(&mut |v, y| {
+        apply(v, y, lmax);
+        y.iter_mut().for_each(|yi| *yi = -*yi);
+    });
+    let lmin = (lmax - spread).max(0.0);
+    (lmin, lmax)
+}
+
+pub(crate) fn chebyshev(
+    a: &Csr,
+    b: &[f64],
+    x0: &[f64],
+    lambda_min: f64,
+    lambda_max: f64,
+    tol: f64,
+    max_iter: u32,
+) -> SolveResult {
+    // Equal bounds divide by delta = 0, reversed or non-positive ones
+    // diverge
+    if !(lambda_min > 0.0 && lambda_min < lambda_max && lambda_max.is_finite()) {
+        return SolveResult::new(x0.to_vec(), 0, f64::INFINITY);
+    }
+    let n = b.len();
+    let mut x: Vec<f64> = x0.to_vec();
+
+    let diag = a.diagonal();
+    let threshold = threshold(b, tol);
+
+    // Ellipse parameters of the spectral interval
+    let theta = 0.5 * (lambda_max + lambda_min);
+    let delta = 0.5 * (lambda_max - lambda_min);
+    let sigma = theta / delta;
+    let mut rho = 1.0 / sigma;
+
+    let mut r = vec![0.0; n];
+    a.residual(b, &x, &mut r);
+    let mut rnorm = norm(&r);
+    if tol > 0.0 && rnorm < threshold {
+        return SolveResult::new(x, 0, rnorm);
+    }
+
+    let mut z = vec![0.0; n];
+    let mut d = vec![0.0; n];
+    let mut ad = vec![0.0; n];
+
+    // d = M^{-1} r / theta
+    apply_jacobi(&diag, &r, &mut z);
+    for (di, zi) in d.iter_mut().zip(&z) {
+        *di = zi / theta;
+    }
+
+    let mut iter = 0u32;
+    for i in 0..max_iter {
+        iter = i + 1;
+
+        // x = x + d, r = r - A d
+        axpy(1.0, &d, &mut x);
+        a.spmv(&d, &mut ad);
+        axpy(-1.0, &ad, &mut r);
+
+        if tol > 0.0 && iter.is_multiple_of(CHECK_INTERVAL) {
+            rnorm = norm(&r);
+            if rnorm < threshold {
+                break;
+            }
+        }
+
+        // d = rho_new * rho * d + 2 * rho_new / delta * M^{-1} r
+        let rho_new = 1.0 / (2.0 * sigma - rho);
+        apply_jacobi(&diag, &r, &mut z);
+        let c1 = rho_new * rho;
+        let c2 = 2.0 * rho_new / delta;
+        for (di, zi) in d.iter_mut().zip(&z) {
+            *di = c1 * *di + c2 * zi;
+        }
+        rho = rho_new;
+    }
+
+    SolveResult::new(x, iter, norm(&r))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::laplacian_1d;
+    use std::f64::consts::PI;
+
+    #[test]
+    fn test_spectral_bounds_laplacian() {
+        // D^{-1} A = A / 2 has eigenvalues 1 - cos(k pi / (n + 1))
+        let n = 20;
+        let (values, col_indices, row_ptr) = laplacian_1d(n);
+        let bounds = estimate_spectral_bounds(&values, &col_indices, &row_ptr, 200);
+
+        let exact_max = 1.0 - (n as f64 * PI / (n as f64 + 1.0)).cos();
+        assert!(bounds[1] <= exact_max + 1e-12);
+        assert!(bounds[1] > 0.95 * exact_max);
+        // Exact minimum is ~0.011; the shifted power method converges slowly
+        assert!(bounds[0] >= 0.0 && bounds[0] < 0.2);
+    }
+
+    #[test]
+    fn test_chebyshev_with_exact_bounds() {
+        let n = 20;
+        let (values, col_indices, row_ptr) = laplacian_1d(n);
+        let a = Csr::new(&values, &col_indices, &row_ptr);
+        let lmax = 1.0 - (n as f64 * PI / (n as f64 + 1.0)).cos();
+        let lmin = 1.0 - (PI / (n as f64 + 1.0)).cos();
+        let b = vec![1.0; n];
+
+        let result = chebyshev(&a, &b, &vec![0.0; n], lmin, lmax, 1e-10, 2000);
+
+        let mut r = vec![0.0; n];
+        a.residual(&b, &result.solution, &mut r);
+        assert!(norm(&r) < 1e-9 * norm(&b).max(1.0));
+        assert!((result.residual - norm(&r)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_fixed_sweeps_reduce_residual() {
+        // Smoother use: tol = 0 runs exactly max_iter steps
+        let n = 30;
+        let (values, col_indices, row_ptr) = laplacian_1d(n);
+        let b: Vec<f64> = (0..n).map(|i| (i as f64 * 2.0).sin()).collect();
+
+        let result = solve_chebyshev(
+            &values,
+            &col_indices,
+            &row_ptr,
+            &b,
+            &vec![0.0; n],
+            0.3,
+            2.0,
+            0.0,
+            7,
+        );
+
+        assert_eq!(result.iterations, 7);
+        assert!(result.residual < norm(&b));
+    }
+
+    #[test]
+    fn test_invalid_bounds() {
+        let n = 10;
+        let (values, col_indices, row_ptr) = laplacian_1d(n);
+        let a = Csr::new(&values, &col_indices, &row_ptr);
+        let (b, x0) = (vec![1.0; n], vec![0.0; n]);
+        for (lmin, lmax) in [(0.5, 0.5), (1.5, 0.2), (0.0, 2.0), (-0.1, 2.0)] {
+            let result = chebyshev(&a, &b, &x0, lmin, lmax, 1e-10, 100);
+            assert_eq!(result.iterations, 0);
+            assert!(result.residual.is_infinite() && result.criterion.is_none());
+            assert_eq!(result.solution, x0);
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use wasm_bindgen::prelude::*;
+
+use crate::kernels::{apply_jacobi, axpy, dot, norm, threshold, Csr};
+use crate::SolveResult;
+
+/// Jacobi-preconditioned Conjugate Residual solver for symmetric systems
+///
+/// Takes the same arguments as `solve_pcg`. CR minimizes the residual in
+/// the M^{-1} norm over the Krylov space, so that norm never increases from
+/// one iteration to the next. The reported `residual` is the 2-norm, which
+/// is only monotone as well for a constant diagonal; use
+/// `solve_cr_unpreconditioned` when it must be.
+#[wasm_bindgen]
+pub fn solve_cr(
+    values: &[f64],
+    col_indices: &[u32],
+    row_ptr: &[u32],
+    b: &[f64],
+    x0: &[f64],
+    tol: f64,
+    max_iter: u32,
+) -> SolveResult {
+    let a = Csr::new(values, col_indices, row_ptr);
+    cr(&a, b, x0, tol, max_iter, &a.diagonal())
+}
+
+/// Conjugate Residual solver without preconditioning, whose residual
+/// 2-norm, the reported `residual`, never increases from one iteration to
+/// the next
+///
+/// Takes the same arguments as `solve_cr`; slower to converge on badly
+/// scaled systems.
+#[wasm_bindgen]
+pub fn solve_cr_unpreconditioned(
+    values: &[f64],
+    col_indices: &[u32],
+    row_ptr: &[u32],
+    b: &[f64],
+    x0: &[f64],
+    tol: f64,
+    max_iter: u32,
+) -> SolveResult {
+    let a = Csr::new(values, col_indices, row_ptr);
+    cr(&a, b, x0, tol, max_iter, &vec![1.0; b.len()])
+}
+
+/// CR with the Jacobi preconditioner of diagonal `diag`, all ones for
+/// none
+pub(crate) fn cr(
+    a: &Csr,
+    b: &[f64],
+    x0: &[f64],
+    tol: f64,
+    max_iter: u32,
+    diag: &[f64],
+) -> SolveResult {
+    let n = b.len();
+    let mut x: Vec<f64> = x0.to_vec();
+
+    let mut r = vec![0.0; n]; // Residual
+    let mut z = vec![0.0; n]; // Preconditioned residual
+    let mut az = vec![0.0; n]; // A * z
+    let mut p = vec![0.0; n]; // Search direction
+    let mut ap = vec![0.0; n]; // A * p
+    let mut q = vec![0.0; n]; // M^{-1} * A*p
+
+    a.residual(b, &x, &mut r);
+    let threshold = threshold(b, tol);
+
+    let mut rnorm = norm(&r);
+    if rnorm < threshold {
+        return SolveResult::new(x, 0, rnorm);
+    }
+
+    // z = M^{-1} r, p = z, A*p = A*z
+    apply_jacobi(diag, &r, &mut z);
+    a.spmv(&z, &mut az);
+    p.copy_from_slice(&z);
+    ap.copy_from_slice(&az);
+
+    let mut zaz = dot(&z, &az);
+
+    let mut iter = 0u32;
+    for i in 0..max_iter {
+        iter = i + 1;
+
+        // q = M^{-1} * A*p
+        apply_jacobi(diag, &ap, &mut q);
+
+        // alpha = (z^T * A*z) / (A*p^T * M^{-1} * A*p)
+        let apq = dot(&ap, &q);
+        if apq.abs() < 1e-30 {
+            break;
+        }
+        let alpha = zaz / apq;
+
+        axpy(alpha, &p, &mut x);
+        axpy(-alpha, &ap, &mut r);
+        axpy(-alpha, &q, &mut z);
+
+        rnorm = norm(&r);
+        if rnorm < threshold {
+            break;
+        }
+
+        a.spmv(&z, &mut az);
+        let zaz_new = dot(&z, &az);
+        if zaz.abs() < 1e-30 {
+            break;
+        }
+        let beta = zaz_new / zaz;
+        zaz = zaz_new;
+
+        // p = z + beta * p, A*p = A*z + beta * A*p
+        for j in 0..n {
+            p[j] = z[j] + beta * p[j];
+            ap[j] = az[j] + beta * ap[j];
+        }
+    }
+
+    SolveResult::new(x, iter, rnorm)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::laplacian_1d;
+
+    #[test]
+    fn test_3x3_symmetric() {
+        // [4, 1, 1; 1, 4, 1; 1, 1, 4] * x = [6; 6; 6], x = [1, 1, 1]
+        let values = vec![4.0, 1.0, 1.0, 1.0, 4.0, 1.0, 1.0, 1.0, 4.0];
+        let col_indices = vec![0u32, 1, 2, 0, 1, 2, 0, 1, 2];
+        let row_ptr = vec![0u32, 3, 6, 9];
+        let b = vec![6.0, 6.0, 6.0];
+
+        let result = solve_cr(&values, &col_indices, &row_ptr, &b, &[0.0; 3], 1e-10, 100);
+
+        for xi in &result.solution {
+            assert!((xi - 1.0).abs() < 1e-8);
+        }
+    }
+
+    #[test]
+    fn test_residual_is_monotone() {
+        let n = 40;
+        let (values, col_indices, row_ptr) = laplacian_1d(n);
+        let a = Csr::new(&values, &col_indices, &row_ptr);
+        let b: Vec<f64> = (0..n).map(|i| (i as f64).cos()).collect();
+        let x0 = vec![0.0; n];
+
+        let mut previous = norm(&b);
+        for k in 1..=n as u32 {
+            let result = cr(&a, &b, &x0, 1e-14, k, &a.diagonal());
+            assert!(result.residual <= previous * (1.0 + 1e-12));
+            previous = result.residual;
+        }
+        assert!(previous < 1e-8);
+    }
+
+    #[test]
+    fn test_unpreconditioned_residual_is_monotone() {
+        // Tridiagonal [-1, d_i, -1] with d_i from 2.5 to 42, where only the
+        // M^{-1} norm of the Jacobi preconditioned residual is monotone
+        let n: usize = 30;
+        let (mut values, mut col_indices, mut row_ptr) = (Vec::new(), Vec::new(), vec![0u32]);
+        for i in 0..n {
+            for j in i.saturating_sub(1)..(i + 2).min(n) {
+                values.push(if i == j {
+                    2.5 + (i * i) as f64 / 20.0
+                } else {
+                    -1.0
+                });
+                col_indices.push(j as u32);
+            }
+            row_ptr.push(values.len() as u32);
+        }
+        let a = Csr::new(&values, &col_indices, &row_ptr);
+        let b: Vec<f64> = (0..n).map(|i| (3.0 * i as f64).sin()).collect();
+        let x0 = vec![0.0; n];
+
+        let mut previous = norm(&b);
+        for k in 1..=2 * n as u32 {
+            let result = cr(&a, &b, &x0, 1e-14, k, &vec![1.0; n]);
+            assert!(result.residual <= previous * (1.0 + 1e-12));
+            previous = result.residual;
+        }
+        assert!(previous < 1e-8);
+        let result =
+            solve_cr_unpreconditioned(&values, &col_indices, &row_ptr, &b, &x0, 1e-10, 100);
+        let preconditioned = solve_cr(&values, &col_indices, &row_ptr, &b, &x0, 1e-10, 100);
+        for (x, y) in result.solution.iter().zip(&preconditioned.solution) {
+            assert!((x - y).abs() < 1e-8);
+        }
+    }
+}
@@ -0,0 +1,253 @@
+use wasm_bindgen::prelude::*;
+
+use crate::dense::{cholesky_factor, cholesky_solve};
+use crate::kernels::{apply_jacobi, axpy, dot, norm, threshold, Csr};
+use crate::SolveResult;
+
+/// Deflated PCG solver
+///
+/// Components of the solution in the span of the deflation vectors (e.g.
+/// rigid body modes of weakly connected regions) are solved for directly
+/// and projected out of every search direction.
+///
+/// # Arguments
+/// * `values`, `col_indices`, `row_ptr`, `b`, `x0`, `tol`, `max_iter` - As in `solve_pcg`
+/// * `deflation` - `num_vectors` vectors of length n, stored one after another
+/// * `num_vectors` - Number of deflation vectors
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn solve_deflated_cg(
+    values: &[f64],
+    col_indices: &[u32],
+    row_ptr: &[u32],
+    b: &[f64],
+    x0: &[f64],
+    tol: f64,
+    max_iter: u32,
+    deflation: &[f64],
+    num_vectors: u32,
+) -> SolveResult {
+    let a = Csr::new(values, col_indices, row_ptr);
+    let space = DeflationSpace::new(&a, deflation, num_vectors as usize);
+    deflated_cg(&a, &space, b, x0, tol, max_iter)
+}
+
+/// Deflation subspace W together with A*W and the Cholesky factor of
+/// the coarse matrix E = W^T A W
+pub(crate) struct DeflationSpace {
+    w: Vec<Vec<f64>>,
+    aw: Vec<Vec<f64>>,
+    e_factor: Vec<f64>,
+}
+
+impl DeflationSpace {
+    /// Build from `k` flat vectors. The vectors are orthonormalized first and
+    /// numerically dependent ones are dropped. If E is not SPD (A not SPD on
+    /// the subspace) the space is left empty, which reduces to plain PCG.
+    pub fn new(a: &Csr, vectors: &[f64], k: usize) -> Self {
+        let n = a.n();
+        let mut w: Vec<Vec<f64>> = Vec::with_capacity(k);
+        for chunk in vectors.chunks_exact(n).take(k) {
+            let mut v = chunk.to_vec();
+            let original = norm(&v);
+            // Modified Gram-Schmidt against the accepted vectors
+            for u in &w {
+                let c = dot(&v, u);
+                axpy(-c, u, &mut v);
+            }
+            let vnorm = norm(&v);
+            if vnorm > 1e-10 * original && vnorm > 0.0 {
+                v.iter_mut().for_each(|vi| *vi /= vnorm);
+                w.push(v);
+            }
+        }
+
+        let aw: Vec<Vec<f64>> = w
+            .iter()
+            .map(|v| {
+                let mut av = vec![0.0; n];
+                a.spmv(v, &mut av);
+                av
+            })
+            .collect();
+
+        let k = w.len();
+        let mut e_factor = vec![0.0; k * k];
+        for i in 0..k {
+            for j in 0..k {
+                e_factor[i * k + j] = dot(&w[i], &aw[j]);
+            }
+        }
+        if !cholesky_factor(&mut e_factor, k) {
+            return DeflationSpace {
+                w: Vec::new(),
+                aw: Vec::new(),
+                e_factor: Vec::new(),
+            };
+        }
+
+        DeflationSpace { w, aw, e_factor }
+    }
+
+    pub fn dim(&self) -> usize {
+        self.w.len()
+    }
+
+    /// Solve E c = (basis)^T v for the coarse coefficients
+    fn coefficients(&self, basis: &[Vec<f64>], v: &[f64]) -> Vec<f64> {
+        let mut c: Vec<f64> = basis.iter().map(|u| dot(u, v)).collect();
+        cholesky_solve(&self.e_factor, self.dim(), &mut c);
+        c
+    }
+
+    /// Coarse-grid correction: x = x + W E^{-1} W^T r
+    pub fn correct(&self, r: &[f64], x: &mut [f64]) {
+        let c = self.coefficients(&self.w, r);
+        for (ci, wi) in c.iter().zip(&self.w) {
+            axpy(*ci, wi, x);
+        }
+    }
+
+    /// Make p A-orthogonal to W: p = p - W E^{-1} (A W)^T z
+    pub fn project(&self, z: &[f64], p: &mut [f64]) {
+        let c = self.coefficients(&self.aw, z);
+        for (ci, wi) in c.iter().zip(&self.w) {
+            axpy(-ci, wi, p);
+        }
+    }
+}
+
+pub(crate) fn deflated_cg(
+    a: &Csr,
+    space: &DeflationSpace,
+    b: &[f64],
+    x0: &[f64],
+    tol: f64,
+    max_iter: u32,
+) -> SolveResult {
+    let n = b.len();
+    let mut x: Vec<f64> = x0.to_vec();
+
+    let mut r = vec![0.0; n];
+    let mut z = vec![0.0; n];
+    let mut p = vec![0.0; n];
+    let mut ap = vec![0.0; n];
+
+    let diag = a.diagonal();
+    let threshold = threshold(b, tol);
+
+    // Start from an initial guess whose residual is orthogonal to W
+    a.residual(b, &x, &mut r);
+    if space.dim() > 0 {
+        space.correct(&r, &mut x);
+        a.residual(b, &x, &mut r);
+    }
+
+    let mut rnorm = norm(&r);
+    if rnorm < threshold {
+        return SolveResult::new(x, 0, rnorm);
+    }
+
+    apply_jacobi(&diag, &r, &mut z);
+    p.copy_from_slice(&z);
+    space.project(&z, &mut p);
+    let mut rz = dot(&r, &z);
+
+    let mut iter = 0u32;
+    for i in 0..max_iter {
+        iter = i + 1;
+
+        a.spmv(&p, &mut ap);
+        let pap = dot(&p, &ap);
+        if pap.abs() < 1e-30 {
+            break;
+        }
+        let alpha = rz / pap;
+
+        axpy(alpha, &p, &mut x);
+        axpy(-alpha, &ap, &mut r);
+
+        rnorm = norm(&r);
+        if rnorm < threshold {
+            break;
+        }
+
+        apply_jacobi(&diag, &r, &mut z);
+        let rz_new = dot(&r, &z);
+        let beta = rz_new / rz;
+        rz = rz_new;
+
+        // p = z + beta * p, then project out the deflation space
+        for j in 0..n {
+            p[j] = z[j] + beta * p[j];
+        }
+        space.project(&z, &mut p);
+    }
+
+    SolveResult::new(x, iter, rnorm)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::dense_to_csr;
+
+    /// Chain of springs fixed at node 0, with one nearly broken spring at
+    /// `weak`: everything past it is an almost-free rigid body
+    fn weak_chain(n: usize, weak: usize) -> (Vec<f64>, Vec<u32>, Vec<u32>) {
+        let mut dense = vec![0.0; n * n];
+        dense[0] = 1.0;
+        for e in 0..n - 1 {
+            let k = if e == weak { 1e-8 } else { 1.0 };
+            dense[e * n + e] += k;
+            dense[(e + 1) * n + e + 1] += k;
+            dense[e * n + e + 1] -= k;
+            dense[(e + 1) * n + e] -= k;
+        }
+        dense_to_csr(&dense, n)
+    }
+
+    #[test]
+    fn test_rigid_mode_deflation() {
+        let n = 60;
+        let weak = 30;
+        let (values, col_indices, row_ptr) = weak_chain(n, weak);
+        let a = Csr::new(&values, &col_indices, &row_ptr);
+        let b: Vec<f64> = (0..n).map(|i| if i > weak { 1e-8 } else { 1.0 }).collect();
+        let x0 = vec![0.0; n];
+
+        // Indicator of the floating part
+        let mode: Vec<f64> = (0..n).map(|i| if i > weak { 1.0 } else { 0.0 }).collect();
+
+        let deflated = solve_deflated_cg(
+            &values,
+            &col_indices,
+            &row_ptr,
+            &b,
+            &x0,
+            1e-10,
+            1000,
+            &mode,
+            1,
+        );
+        let plain = crate::solve_pcg(&values, &col_indices, &row_ptr, &b, &x0, 1e-10, 1000);
+
+        let mut r = vec![0.0; n];
+        a.residual(&b, &deflated.solution, &mut r);
+        assert!(norm(&r) < 1e-9);
+        assert!(deflated.iterations < plain.iterations);
+    }
+
+    #[test]
+    fn test_dependent_vectors_are_dropped() {
+        let n = 10;
+        let (values, col_indices, row_ptr) = weak_chain(n, 5);
+        let a = Csr::new(&values, &col_indices, &row_ptr);
+        let v: Vec<f64> = (0..n).map(|i| i as f64).collect();
+        let mut vectors = v.clone();
+        vectors.extend(v.iter().map(|x| 2.0 * x));
+
+        let space = DeflationSpace::new(&a, &vectors, 2);
+        assert_eq!(space.dim(), 1);
+    }
+}
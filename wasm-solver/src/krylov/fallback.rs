@@ -0,0 +1,432 @@
+use wasm_bindgen::prelude::*;
+
+use super::{
+    bicgstab_preconditioned, gmres_preconditioned, minres, pcg_preconditioned, pipelined_cg,
+    sstep_cg,
+};
+use crate::analysis::validate_system;
+use crate::direct::solve_small;
+use crate::kernels::{criterion_threshold, norm, Csr, SparseMatrix};
+use crate::options::{CgVariant, ConvergenceCriterion, DiagonalPolicy, SolverKind, SolverOptions};
+use crate::precond::{setup, timed, Jacobi, Preconditioner, PreconditionerHandle};
+use crate::SolveResult;
+
+/// Chain used when `solve_with_fallback` is given an empty one
+pub(crate) const DEFAULT_CHAIN: [SolverKind; 3] =
+    [SolverKind::Pcg, SolverKind::Minres, SolverKind::Gmres];
+
+/// Try a chain of solvers until one converges
+///
+/// A stage that breaks down (e.g. PCG on an indefinite matrix) or runs out
+/// of iterations leaves a residual above the tolerance; the next stage then
+/// restarts from `x0`. The result reports the solver that produced it and
+/// the iterations of all stages together. If no stage converges, the result
+/// with the smallest residual is returned.
+///
+/// # Arguments
+/// * `values`, `col_indices`, `row_ptr`, `b`, `x0` - As in `solve_pcg`
+/// * `chain` - `SolverKind` values in the order to try them; empty means
+///   PCG -> MINRES -> GMRES. Unknown values are skipped.
+/// * `options` - Tolerance, iteration limit per stage, CG kernel and GMRES restart
+#[wasm_bindgen]
+pub fn solve_with_fallback(
+    values: &[f64],
+    col_indices: &[u32],
+    row_ptr: &[u32],
+    b: &[f64],
+    x0: &[f64],
+    chain: &[u32],
+    options: &SolverOptions,
+) -> SolveResult {
+    let a = Csr::new(values, col_indices, row_ptr);
+    let chain: Vec<SolverKind> = if chain.is_empty() {
+        DEFAULT_CHAIN.to_vec()
+    } else {
+        chain
+            .iter()
+            .filter_map(|&c| SolverKind::from_code(c))
+            .collect()
+    };
+    fallback(&a, b, x0, &chain, options)
+}
+
+/// Solve with a preconditioner set up earlier
+///
+/// Runs `kind` as in `solve_with_fallback` with a one-solver chain, but
+/// applies `preconditioner` instead of setting up `options.preconditioner`
+/// (MINRES keeps its own Jacobi diagonal, and `options.equilibrate` is
+/// ignored). If the preconditioner was set up for a matrix of another
+/// size, `x0` is returned unchanged with an infinite residual.
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn solve_with_preconditioner(
+    values: &[f64],
+    col_indices: &[u32],
+    row_ptr: &[u32],
+    b: &[f64],
+    x0: &[f64],
+    kind: SolverKind,
+    preconditioner: &PreconditionerHandle,
+    options: &SolverOptions,
+) -> SolveResult {
+    let a = Csr::new(values, col_indices, row_ptr);
+    if preconditioner.size() != a.n() {
+        return SolveResult::new(x0.to_vec(), 0, f64::INFINITY);
+    }
+    run_preconditioned(kind, &a, preconditioner.preconditioner(), b, x0, options)
+}
+
+pub(crate) fn fallback(
+    a: &Csr,
+    b: &[f64],
+    x0: &[f64],
+    chain: &[SolverKind],
+    options: &SolverOptions,
+) -> SolveResult {
+    let mut total_iter = 0u32;
+    let mut best: Option<SolveResult> = None;
+
+    for &kind in chain {
+        let result = run_solver(kind, a, b, x0, options);
+        total_iter += result.iterations;
+        let converged = result.criterion.is_some();
+        let better = match &best {
+            Some(prev) => result.residual < prev.residual || !prev.residual.is_finite(),
+            None => true,
+        };
+        if better {
+            best = Some(result);
+        }
+        if converged {
+            break;
+        }
+    }
+
+    match best {
+        Some(mut result) => {
+            result.iterations = total_iter;
+            result
+        }
+        None => SolveResult::new(x0.to_vec(), 0, f64::INFINITY),
+    }
+}
+
+/// Run one solver with the settings from `options`
+pub(crate) fn run_solver(
+    kind: SolverKind,
+    a: &Csr,
+    b: &[f64],
+    x0: &[f64],
+    options: &SolverOptions,
+) -> SolveResult {
+    if options.validate {
+        let report = validate_system(a, b, x0);
+        if !report.valid() {
+            return SolveResult::new(x0.to_vec(), 0, f64::INFINITY).with_validation(report);
+        }
+    }
+    if options.diagonal_policy != DiagonalPolicy::Substitute {
+        let rows = a.invalid_diagonal_rows();
+        if !rows.is_empty() {
+            return match options.diagonal_policy {
+                DiagonalPolicy::Reject => SolveResult::new(x0.to_vec(), 0, f64::INFINITY),
+                _ => regularized(kind, a, &rows, b, x0, options),
+            }
+            .with_invalid_diagonal(rows);
+        }
+    }
+    if a.n() < options.dense_threshold as usize {
+        if let Some(result) = solve_small(a, b, x0, options) {
+            return result;
+        }
+    }
+    if options.equilibrate {
+        return equilibrated(kind, a, b, x0, options);
+    }
+    if kind == SolverKind::Minres {
+        // MINRES needs an SPD preconditioner and sets up |diag(A)| itself
+        let (m, report) = timed(|| Jacobi::new(a));
+        return run_preconditioned(kind, a, &m, b, x0, options).with_setup(report);
+    }
+    let (m, report) = timed(|| setup(options, a));
+    run_preconditioned(kind, a, m.as_ref(), b, x0, options).with_setup(report)
+}
+
+/// `run_solver` with an already set up preconditioner `m` (not used by
+/// MINRES); `options.equilibrate` is ignored
+pub(crate) fn run_preconditioned(
+    kind: SolverKind,
+    a: &Csr,
+    m: &dyn Preconditioner,
+    b: &[f64],
+    x0: &[f64],
+    options: &SolverOptions,
+) -> SolveResult {
+    let max_iter = options.max_iter;
+    if kind == SolverKind::Pcg && options.cg_variant == CgVariant::Classic {
+        return pcg_preconditioned(a, m, b, x0, options).with_solver(kind);
+    }
+
+    // The other solvers test ||r|| < tol * max(||b||, 1); rescale `tol` so
+    // that this matches the requested criterion
+    let criterion = match options.criterion {
+        ConvergenceCriterion::EnergyNorm => ConvergenceCriterion::RelativeToRhs,
+        other => other,
+    };
+    let r0norm = if criterion == ConvergenceCriterion::RelativeToInitial {
+        let mut r = vec![0.0; b.len()];
+        a.residual(b, x0, &mut r);
+        norm(&r)
+    } else {
+        0.0
+    };
+    let target = criterion_threshold(criterion, norm(b), r0norm, options.tol);
+    let tol = target / norm(b).max(1.0);
+
+    let result = match kind {
+        SolverKind::Pcg => match options.cg_variant {
+            CgVariant::SStep => sstep_cg(a, m, b, x0, tol, max_iter, options.s_step),
+            _ => pipelined_cg(a, m, b, x0, tol, max_iter),
+        },
+        SolverKind::Minres => minres(a, b, x0, tol, max_iter),
+        SolverKind::Gmres => {
+            gmres_preconditioned(a, m, b, x0, tol, max_iter, options.gmres_restart)
+        }
+        SolverKind::Bicgstab => bicgstab_preconditioned(a, m, b, x0, tol, max_iter),
+    }
+    .with_solver(kind);
+    if result.residual < target {
+        result.with_criterion(criterion)
+    } else {
+        result
+    }
+}
+
+/// `run_solver` on A with a shift added at the diagonal of `rows`, see
+/// `DiagonalPolicy::Regularize`
+fn regularized(
+    kind: SolverKind,
+    a: &Csr,
+    rows: &[u32],
+    b: &[f64],
+    x0: &[f64],
+    options: &SolverOptions,
+) -> SolveResult {
+    let largest = a.diagonal().iter().fold(0.0f64, |acc, d| acc.max(d.abs()));
+    let shift = options.diagonal_regularization * largest;
+    let n = a.n();
+    let diagonal =
+        SparseMatrix::from_triplets(n, n, rows.iter().map(|&i| (i as usize, i, shift)).collect());
+    let shifted = SparseMatrix::add(&SparseMatrix::from_csr(a), &diagonal);
+    let inner = SolverOptions {
+        diagonal_policy: DiagonalPolicy::Substitute,
+        ..*options
+    };
+    run_solver(kind, &shifted.csr(), b, x0, &inner)
+}
+
+/// `run_solver` on the symmetrically scaled system, see
+/// `SolverOptions::equilibrate`
+fn equilibrated(
+    kind: SolverKind,
+    a: &Csr,
+    b: &[f64],
+    x0: &[f64],
+    options: &SolverOptions,
+) -> SolveResult {
+    // Rows without a usable diagonal keep scale 1, as they do in Jacobi
+    let scale: Vec<f64> = (0..a.n())
+        .map(|i| a.usable_diagonal(i).map_or(1.0, |d| 1.0 / d.abs().sqrt()))
+        .collect();
+    let mut scaled = SparseMatrix::from_csr(a);
+    for (i, &si) in scale.iter().enumerate() {
+        for q in scaled.row_ptr[i] as usize..scaled.row_ptr[i + 1] as usize {
+            scaled.values[q] *= si * scale[scaled.col_indices[q] as usize];
+        }
+    }
+    let scaled_b: Vec<f64> = b.iter().zip(&scale).map(|(bi, s)| bi * s).collect();
+    let scaled_x0: Vec<f64> = x0.iter().zip(&scale).map(|(xi, s)| xi / s).collect();
+
+    let inner = SolverOptions {
+        equilibrate: false,
+        ..*options
+    };
+    let mut result = run_solver(kind, &scaled.csr(), &scaled_b, &scaled_x0, &inner);
+    for (xi, s) in result.solution.iter_mut().zip(&scale) {
+        *xi *= s;
+    }
+    let mut r = vec![0.0; b.len()];
+    a.residual(b, &result.solution, &mut r);
+    result.residual = norm(&r);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::CsrError;
+    use crate::krylov::solve_pcg_with_options;
+    use crate::options::PreconditionerKind;
+    use crate::test_util::{convection_diffusion_1d, dense_to_csr, diffusion_2d};
+
+    #[test]
+    fn test_pcg_breakdown_falls_back_to_minres() {
+        // diag(1, -1): the first PCG step has p^T A p = 0
+        let (values, col_indices, row_ptr) = dense_to_csr(&[1.0, 0.0, 0.0, -1.0], 2);
+        let b = vec![1.0, 1.0];
+
+        let result = solve_with_fallback(
+            &values,
+            &col_indices,
+            &row_ptr,
+            &b,
+            &[0.0; 2],
+            &[],
+            &SolverOptions::new(),
+        );
+
+        assert_eq!(result.solver, Some(SolverKind::Minres));
+        assert!((result.solution[0] - 1.0).abs() < 1e-8);
+        assert!((result.solution[1] + 1.0).abs() < 1e-8);
+    }
+
+    #[test]
+    fn test_nonsymmetric_reaches_gmres() {
+        let n = 60;
+        let (values, col_indices, row_ptr) = convection_diffusion_1d(n, 0.9);
+        let a = Csr::new(&values, &col_indices, &row_ptr);
+        let b: Vec<f64> = (0..n).map(|i| (i as f64 * 0.2).cos()).collect();
+        let mut options = SolverOptions::new();
+        options.max_iter = 200;
+        options.tol = 1e-10;
+
+        let chain = [SolverKind::Minres, SolverKind::Gmres];
+        let result = fallback(&a, &b, &vec![0.0; n], &chain, &options);
+
+        let mut r = vec![0.0; n];
+        a.residual(&b, &result.solution, &mut r);
+        assert_eq!(result.solver, Some(SolverKind::Gmres));
+        assert!(norm(&r) < 1e-10 * norm(&b).max(1.0));
+        assert!(result.iterations > 200);
+    }
+
+    #[test]
+    fn test_equilibration_of_mixed_units() {
+        // Diffusion with rows and columns scaled by 1e4 or 1e-2 in bands
+        let (nx, ny) = (20, 20);
+        let (mut values, col_indices, row_ptr) = diffusion_2d(nx, ny, &vec![1.0; nx * ny]);
+        let n = nx * ny;
+        let s: Vec<f64> = (0..n)
+            .map(|i| if (i / 7) % 2 == 0 { 1e4 } else { 1e-2 })
+            .collect();
+        for i in 0..n {
+            for q in row_ptr[i] as usize..row_ptr[i + 1] as usize {
+                values[q] *= s[i] * s[col_indices[q] as usize];
+            }
+        }
+        let a = Csr::new(&values, &col_indices, &row_ptr);
+        let b: Vec<f64> = (0..n)
+            .map(|i| s[i] * (1.0 + (i as f64 * 0.3).sin()))
+            .collect();
+        let x0 = vec![0.0; n];
+
+        // SPAI is not invariant under diagonal scaling
+        let mut options = SolverOptions::new();
+        options.preconditioner = PreconditionerKind::Spai;
+        options.max_iter = 500;
+        let plain = run_solver(SolverKind::Gmres, &a, &b, &x0, &options);
+        options.equilibrate = true;
+        let scaled = run_solver(SolverKind::Gmres, &a, &b, &x0, &options);
+        assert!(plain.criterion.is_none());
+        assert!(scaled.criterion.is_some() && scaled.iterations < 100);
+
+        let mut r = vec![0.0; n];
+        a.residual(&b, &scaled.solution, &mut r);
+        assert_eq!(scaled.residual, norm(&r));
+        assert!(norm(&r) < 1e-6 * norm(&b));
+    }
+
+    /// 6 x 6 diffusion whose node 5 is touched by no element (empty row
+    /// and column) and whose row 17 stores an explicit zero diagonal, with
+    /// a load vanishing on both
+    fn broken_diffusion() -> (SparseMatrix, Vec<f64>) {
+        let (values, col_indices, row_ptr) = diffusion_2d(6, 6, &[1.0; 36]);
+        let a = Csr::new(&values, &col_indices, &row_ptr);
+        let entries = (0..36)
+            .flat_map(|i| (a.row_ptr[i]..a.row_ptr[i + 1]).map(move |q| (i, q as usize)))
+            .map(|(i, q)| (i, a.col_indices[q], a.values[q]))
+            .filter(|&(i, j, _)| i != 5 && j != 5 && i != 17 && j != 17)
+            .chain(std::iter::once((17, 17, 0.0)))
+            .collect();
+        let broken = SparseMatrix::from_triplets(36, 36, entries);
+        let mut b = vec![1.0; 36];
+        b[5] = 0.0;
+        b[17] = 0.0;
+        (broken, b)
+    }
+
+    #[test]
+    fn test_diagonal_policies() {
+        let (broken, b) = broken_diffusion();
+        let x0 = vec![0.0; 36];
+        assert_eq!(broken.csr().invalid_diagonal_rows(), vec![5, 17]);
+
+        let mut options = SolverOptions::new();
+        let substituted = run_solver(SolverKind::Pcg, &broken.csr(), &b, &x0, &options);
+        assert!(substituted.invalid_diagonal.is_empty());
+
+        options.diagonal_policy = DiagonalPolicy::Reject;
+        let rejected = run_solver(SolverKind::Pcg, &broken.csr(), &b, &x0, &options);
+        assert_eq!(rejected.invalid_diagonal, vec![5, 17]);
+        assert_eq!(rejected.iterations, 0);
+        assert!(rejected.residual.is_infinite() && rejected.criterion.is_none());
+
+        options.diagonal_policy = DiagonalPolicy::Regularize;
+        let result = fallback(&broken.csr(), &b, &x0, &DEFAULT_CHAIN, &options);
+        assert_eq!(result.invalid_diagonal, vec![5, 17]);
+        assert_eq!(result.solver, Some(SolverKind::Pcg));
+        assert!(result.criterion.is_some());
+        assert_eq!((result.solution[5], result.solution[17]), (0.0, 0.0));
+    }
+
+    #[test]
+    fn test_equilibration_keeps_unusable_diagonal() {
+        // The default Substitute policy reaches the scaling with the zero
+        // and missing diagonals of rows 5 and 17
+        let (broken, b) = broken_diffusion();
+        let options = SolverOptions {
+            equilibrate: true,
+            ..SolverOptions::new()
+        };
+        let result = run_solver(SolverKind::Pcg, &broken.csr(), &b, &[0.0; 36], &options);
+        assert!(result.solution.iter().all(|x| x.is_finite()));
+        assert!(result.criterion.is_some() && result.residual < 1e-6 * norm(&b));
+        assert_eq!((result.solution[5], result.solution[17]), (0.0, 0.0));
+    }
+
+    #[test]
+    fn test_validation_before_solving() {
+        let (values, mut col_indices, row_ptr) = diffusion_2d(6, 6, &[1.0; 36]);
+        let (b, x0) = (vec![1.0; 36], vec![0.0; 36]);
+        let options = SolverOptions {
+            validate: true,
+            ..SolverOptions::new()
+        };
+        let valid = solve_pcg_with_options(&values, &col_indices, &row_ptr, &b, &x0, &options);
+        assert!(valid.criterion.is_some() && valid.validation.is_none());
+
+        let short = solve_pcg_with_options(&values, &col_indices, &row_ptr, &b[1..], &x0, &options);
+        assert_eq!(
+            short.validation.unwrap().error(),
+            Some(CsrError::VectorLength)
+        );
+
+        col_indices[10] = 36;
+        let result = solve_with_fallback(&values, &col_indices, &row_ptr, &b, &x0, &[], &options);
+        let report = result.validation.unwrap();
+        assert_eq!(report.error(), Some(CsrError::ColumnOutOfRange));
+        assert_eq!(report.index(), Some(10));
+        assert!(result.residual.is_infinite());
+        assert_eq!(result.solution, x0);
+    }
+}
@@ -0,0 +1,144 @@
+use wasm_bindgen::prelude::*;
+
+use super::pcg;
+use crate::kernels::{apply_jacobi, axpy, dot, norm, threshold, Csr};
+use crate::SolveResult;
+
+/// Flexible Conjugate Gradient solver
+///
+/// Tolerates a preconditioner that changes from one iteration to the next.
+/// The preconditioner applied here is `inner_iter` Jacobi-PCG iterations on
+/// A*z = r; with `inner_iter = 0` it reduces to plain Jacobi.
+///
+/// Other arguments are the same as `solve_pcg`.
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn solve_fcg(
+    values: &[f64],
+    col_indices: &[u32],
+    row_ptr: &[u32],
+    b: &[f64],
+    x0: &[f64],
+    tol: f64,
+    max_iter: u32,
+    inner_iter: u32,
+) -> SolveResult {
+    let a = Csr::new(values, col_indices, row_ptr);
+    if inner_iter == 0 {
+        let diag = a.diagonal();
+        fcg(&a, b, x0, tol, max_iter, &mut |r, z| {
+            apply_jacobi(&diag, r, z)
+        })
+    } else {
+        let zero = vec![0.0; b.len()];
+        fcg(&a, b, x0, tol, max_iter, &mut |r, z| {
+            let inner = pcg(&a, r, &zero, 0.0, inner_iter);
+            z.copy_from_slice(&inner.solution);
+        })
+    }
+}
+
+/// FCG(1): each new direction is explicitly A-orthogonalized against the
+/// previous one, so the recurrence does not rely on M being fixed
+pub(crate) fn fcg(
+    a: &Csr,
+    b: &[f64],
+    x0: &[f64],
+    tol: f64,
+    max_iter: u32,
+    precond: &mut dyn FnMut(&[f64], &mut [f64]),
+) -> SolveResult {
+    let n = b.len();
+    let mut x: Vec<f64> = x0.to_vec();
+
+    let mut r = vec![0.0; n];
+    let mut z = vec![0.0; n];
+    let mut p = vec![0.0; n];
+    let mut ap = vec![0.0; n];
+
+    a.residual(b, &x, &mut r);
+    let threshold = threshold(b, tol);
+
+    let mut rnorm = norm(&r);
+    if rnorm < threshold {
+        return SolveResult::new(x, 0, rnorm);
+    }
+
+    // Previous direction's p^T * A*p (zero until the first step is taken)
+    let mut pap_old = 0.0;
+
+    let mut iter = 0u32;
+    for i in 0..max_iter {
+        iter = i + 1;
+
+        // z = M_i^{-1} * r
+        precond(&r, &mut z);
+
+        // p = z - (z^T * A*p_old) / (p_old^T * A*p_old) * p_old
+        if pap_old > 0.0 {
+            let beta = -dot(&z, &ap) / pap_old;
+            for j in 0..n {
+                p[j] = z[j] + beta * p[j];
+            }
+        } else {
+            p.copy_from_slice(&z);
+        }
+
+        a.spmv(&p, &mut ap);
+        let pap = dot(&p, &ap);
+        if pap.abs() < 1e-30 {
+            break;
+        }
+
+        // alpha = (p^T * r) / (p^T * A*p)
+        let alpha = dot(&p, &r) / pap;
+        axpy(alpha, &p, &mut x);
+        axpy(-alpha, &ap, &mut r);
+        pap_old = pap;
+
+        rnorm = norm(&r);
+        if rnorm < threshold {
+            break;
+        }
+    }
+
+    SolveResult::new(x, iter, rnorm)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::laplacian_1d;
+
+    #[test]
+    fn test_matches_pcg_with_fixed_preconditioner() {
+        let n = 30;
+        let (values, col_indices, row_ptr) = laplacian_1d(n);
+        let b = vec![1.0; n];
+        let x0 = vec![0.0; n];
+
+        let flexible = solve_fcg(&values, &col_indices, &row_ptr, &b, &x0, 1e-10, 200, 0);
+        let standard = crate::solve_pcg(&values, &col_indices, &row_ptr, &b, &x0, 1e-10, 200);
+
+        for (xf, xs) in flexible.solution.iter().zip(&standard.solution) {
+            assert!((xf - xs).abs() < 1e-7);
+        }
+    }
+
+    #[test]
+    fn test_inner_cg_preconditioner() {
+        let n = 200;
+        let (values, col_indices, row_ptr) = laplacian_1d(n);
+        let a = Csr::new(&values, &col_indices, &row_ptr);
+        let b: Vec<f64> = (0..n).map(|i| ((i * 7) % 11) as f64 - 5.0).collect();
+        let x0 = vec![0.0; n];
+
+        let result = solve_fcg(&values, &col_indices, &row_ptr, &b, &x0, 1e-10, 500, 10);
+
+        let mut r = vec![0.0; n];
+        a.residual(&b, &result.solution, &mut r);
+        assert!(norm(&r) < 1e-8);
+        // Ten inner iterations per step should cut outer iterations well below n
+        assert!(result.iterations < 100);
+    }
+}
@@ -0,0 +1,210 @@
+use wasm_bindgen::prelude::*;
+
+use crate::kernels::{axpy, dot, norm, threshold, Csr};
+use crate::precond::{Jacobi, Preconditioner};
+use crate::SolveResult;
+
+/// Restarted GMRES(m) solver with right Jacobi preconditioning
+///
+/// # Arguments
+/// * `values`, `col_indices`, `row_ptr` - Sparse matrix in CSR format
+/// * `b` - Right-hand side vector
+/// * `x0` - Initial guess
+/// * `tol` - Convergence tolerance
+/// * `max_iter` - Maximum number of inner (Arnoldi) iterations in total
+/// * `restart` - Krylov subspace size before restarting (m)
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn solve_gmres(
+    values: &[f64],
+    col_indices: &[u32],
+    row_ptr: &[u32],
+    b: &[f64],
+    x0: &[f64],
+    tol: f64,
+    max_iter: u32,
+    restart: u32,
+) -> SolveResult {
+    let a = Csr::new(values, col_indices, row_ptr);
+    gmres(&a, b, x0, tol, max_iter, restart)
+}
+
+pub(crate) fn gmres(
+    a: &Csr,
+    b: &[f64],
+    x0: &[f64],
+    tol: f64,
+    max_iter: u32,
+    restart: u32,
+) -> SolveResult {
+    let precond = Jacobi::new(a);
+    gmres_preconditioned(a, &precond, b, x0, tol, max_iter, restart)
+}
+
+/// GMRES(m) with right preconditioner `precond`
+pub(crate) fn gmres_preconditioned(
+    a: &Csr,
+    precond: &dyn Preconditioner,
+    b: &[f64],
+    x0: &[f64],
+    tol: f64,
+    max_iter: u32,
+    restart: u32,
+) -> SolveResult {
+    let n = b.len();
+    let m = (restart.max(1) as usize).min(n.max(1));
+    let mut x: Vec<f64> = x0.to_vec();
+
+    let threshold = threshold(b, tol);
+
+    // Arnoldi basis V (m + 1 vectors) and Hessenberg matrix H, column-major
+    let mut basis: Vec<Vec<f64>> = vec![vec![0.0; n]; m + 1];
+    let mut h = vec![0.0; (m + 1) * m];
+    // Givens rotations and rotated right-hand side
+    let mut cs = vec![0.0; m];
+    let mut sn = vec![0.0; m];
+    let mut g = vec![0.0; m + 1];
+
+    let mut r = vec![0.0; n];
+    let mut z = vec![0.0; n];
+    let mut w = vec![0.0; n];
+
+    a.residual(b, &x, &mut r);
+    let mut rnorm = norm(&r);
+    let mut iter = 0u32;
+
+    while rnorm >= threshold && iter < max_iter {
+        // v_0 = r / ||r||
+        for (vi, ri) in basis[0].iter_mut().zip(&r) {
+            *vi = ri / rnorm;
+        }
+        g.iter_mut().for_each(|gi| *gi = 0.0);
+        g[0] = rnorm;
+
+        let mut k = 0;
+        let mut breakdown = false;
+        while k < m && iter < max_iter {
+            iter += 1;
+
+            // w = A * M^{-1} v_k
+            precond.apply(&basis[k], &mut z);
+            a.spmv(&z, &mut w);
+
+            // Modified Gram-Schmidt orthogonalization
+            for j in 0..=k {
+                let hjk = dot(&w, &basis[j]);
+                h[k * (m + 1) + j] = hjk;
+                axpy(-hjk, &basis[j], &mut w);
+            }
+            let wnorm = norm(&w);
+            h[k * (m + 1) + k + 1] = wnorm;
+
+            // Apply previous rotations to the new column
+            for j in 0..k {
+                let hj = h[k * (m + 1) + j];
+                let hj1 = h[k * (m + 1) + j + 1];
+                h[k * (m + 1) + j] = cs[j] * hj + sn[j] * hj1;
+                h[k * (m + 1) + j + 1] = -sn[j] * hj + cs[j] * hj1;
+            }
+
+            // New rotation eliminating H[k+1, k]
+            let hkk = h[k * (m + 1) + k];
+            let denom = hkk.hypot(wnorm);
+            if denom < 1e-300 {
+                (cs[k], sn[k]) = (1.0, 0.0);
+            } else {
+                (cs[k], sn[k]) = (hkk / denom, wnorm / denom);
+            }
+            h[k * (m + 1) + k] = cs[k] * hkk + sn[k] * wnorm;
+            h[k * (m + 1) + k + 1] = 0.0;
+            g[k + 1] = -sn[k] * g[k];
+            g[k] *= cs[k];
+
+            k += 1;
+            rnorm = g[k].abs();
+
+            if rnorm < threshold {
+                break;
+            }
+            if wnorm < 1e-30 {
+                // Invariant subspace found; the restart below will tell
+                // whether this was a lucky breakdown or stagnation
+                breakdown = true;
+                break;
+            }
+            for (vi, wi) in basis[k].iter_mut().zip(&w) {
+                *vi = wi / wnorm;
+            }
+        }
+
+        // Solve the upper triangular system H y = g
+        let mut y = vec![0.0; k];
+        for i in (0..k).rev() {
+            let mut sum = g[i];
+            for j in (i + 1)..k {
+                sum -= h[j * (m + 1) + i] * y[j];
+            }
+            let hii = h[i * (m + 1) + i];
+            y[i] = if hii.abs() > 1e-300 { sum / hii } else { 0.0 };
+        }
+
+        // x = x + M^{-1} V y
+        w.iter_mut().for_each(|wi| *wi = 0.0);
+        for (j, yj) in y.iter().enumerate() {
+            axpy(*yj, &basis[j], &mut w);
+        }
+        precond.apply(&w, &mut z);
+        axpy(1.0, &z, &mut x);
+
+        // Recompute the true residual at each restart
+        a.residual(b, &x, &mut r);
+        rnorm = norm(&r);
+        if breakdown && rnorm >= threshold {
+            // Krylov space exhausted without converging
+            break;
+        }
+    }
+
+    SolveResult::new(x, iter, rnorm)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::convection_diffusion_1d;
+
+    #[test]
+    fn test_nonsymmetric_3x3() {
+        // [4, 1, 0; -1, 4, 1; 0, -2, 4] * x = b with x = [1, 2, 3]
+        let values = vec![4.0, 1.0, -1.0, 4.0, 1.0, -2.0, 4.0];
+        let col_indices = vec![0u32, 1, 0, 1, 2, 1, 2];
+        let row_ptr = vec![0u32, 2, 5, 7];
+        let b = vec![6.0, 10.0, 8.0];
+        let x0 = vec![0.0; 3];
+
+        let result = solve_gmres(&values, &col_indices, &row_ptr, &b, &x0, 1e-12, 100, 10);
+
+        for (i, xi) in result.solution.iter().enumerate() {
+            assert!((xi - (i + 1) as f64).abs() < 1e-8);
+        }
+        // Full GMRES terminates in at most n steps
+        assert!(result.iterations <= 3);
+    }
+
+    #[test]
+    fn test_restarted_convection_diffusion() {
+        let n = 80;
+        let (values, col_indices, row_ptr) = convection_diffusion_1d(n, 0.7);
+        let a = Csr::new(&values, &col_indices, &row_ptr);
+        let b: Vec<f64> = (0..n).map(|i| (i as f64 * 0.1).sin()).collect();
+        let x0 = vec![0.0; n];
+
+        let result = gmres(&a, &b, &x0, 1e-10, 1000, 5);
+
+        let mut r = vec![0.0; n];
+        a.residual(&b, &result.solution, &mut r);
+        assert!(norm(&r) < 1e-8);
+        assert!((result.residual - norm(&r)).abs() < 1e-12);
+        assert!(result.iterations > 5);
+    }
+}
@@ -0,0 +1,222 @@
+use wasm_bindgen::prelude::*;
+
+use crate::kernels::{norm, threshold, Csr};
+
+/// Result of a least-squares solve
+#[wasm_bindgen]
+pub struct LeastSquaresResult {
+    solution: Vec<f64>,
+    iterations: u32,
+    residual_norm: f64,
+    normal_residual_norm: f64,
+    solution_norm: f64,
+}
+
+#[wasm_bindgen]
+impl LeastSquaresResult {
+    #[wasm_bindgen(getter)]
+    pub fn solution(&self) -> Vec<f64> {
+        self.solution.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn iterations(&self) -> u32 {
+        self.iterations
+    }
+
+    /// ||b - A x||
+    #[wasm_bindgen(getter)]
+    pub fn residual_norm(&self) -> f64 {
+        self.residual_norm
+    }
+
+    /// ||A^T (b - A x)||, zero at a least-squares minimizer
+    #[wasm_bindgen(getter)]
+    pub fn normal_residual_norm(&self) -> f64 {
+        self.normal_residual_norm
+    }
+
+    /// ||x||
+    #[wasm_bindgen(getter)]
+    pub fn solution_norm(&self) -> f64 {
+        self.solution_norm
+    }
+}
+
+/// LSQR solver for min ||b - A x|| with a rectangular or rank-deficient A
+///
+/// # Arguments
+/// * `values`, `col_indices`, `row_ptr` - m x n matrix in CSR format (m rows)
+/// * `num_cols` - Number of columns n
+/// * `b` - Right-hand side vector (length m)
+/// * `x0` - Initial guess (length n)
+/// * `tol` - Stops when ||r|| < tol * max(||b||, 1) (consistent systems) or
+///   ||A^T r|| < tol * ||A|| * ||r|| (least-squares systems)
+/// * `max_iter` - Maximum number of iterations
+///
+/// Started from `x0 = 0`, a rank-deficient problem converges to the
+/// minimum-norm least-squares solution.
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn solve_lsqr(
+    values: &[f64],
+    col_indices: &[u32],
+    row_ptr: &[u32],
+    num_cols: u32,
+    b: &[f64],
+    x0: &[f64],
+    tol: f64,
+    max_iter: u32,
+) -> LeastSquaresResult {
+    let a = Csr::new(values, col_indices, row_ptr);
+    lsqr(&a, num_cols as usize, b, x0, tol, max_iter)
+}
+
+pub(crate) fn lsqr(
+    a: &Csr,
+    num_cols: usize,
+    b: &[f64],
+    x0: &[f64],
+    tol: f64,
+    max_iter: u32,
+) -> LeastSquaresResult {
+    let m = b.len();
+    let threshold = threshold(b, tol);
+    let mut x: Vec<f64> = x0.to_vec();
+
+    // Golub-Kahan bidiagonalization: beta u = b - A x, alpha v = A^T u
+    let mut u = vec![0.0; m];
+    a.residual(b, &x, &mut u);
+    let mut beta = norm(&u);
+    let mut v = vec![0.0; num_cols];
+    let mut alpha = 0.0;
+    if beta > 0.0 {
+        u.iter_mut().for_each(|ui| *ui /= beta);
+        a.spmv_transpose(&u, &mut v);
+        alpha = norm(&v);
+    }
+    if alpha > 0.0 {
+        v.iter_mut().for_each(|vi| *vi /= alpha);
+    }
+
+    let mut rnorm = beta;
+    let mut arnorm = alpha * beta;
+    if rnorm < threshold || arnorm == 0.0 {
+        let solution_norm = norm(&x);
+        return LeastSquaresResult {
+            solution: x,
+            iterations: 0,
+            residual_norm: rnorm,
+            normal_residual_norm: arnorm,
+            solution_norm,
+        };
+    }
+
+    let mut w = v.clone();
+    let mut av = vec![0.0; m];
+    let mut atu = vec![0.0; num_cols];
+    let mut phibar = beta;
+    let mut rhobar = alpha;
+    // Frobenius-norm estimate of A from the bidiagonal entries
+    let mut anorm_sq = alpha * alpha;
+
+    let mut iter = 0u32;
+    for i in 0..max_iter {
+        iter = i + 1;
+
+        // beta u = A v - alpha u
+        a.spmv(&v, &mut av);
+        for (ui, avi) in u.iter_mut().zip(&av) {
+            *ui = avi - alpha * *ui;
+        }
+        beta = norm(&u);
+        if beta > 0.0 {
+            u.iter_mut().for_each(|ui| *ui /= beta);
+            // alpha v = A^T u - beta v
+            a.spmv_transpose(&u, &mut atu);
+            for (vi, atui) in v.iter_mut().zip(&atu) {
+                *vi = atui - beta * *vi;
+            }
+            alpha = norm(&v);
+            if alpha > 0.0 {
+                v.iter_mut().for_each(|vi| *vi /= alpha);
+            }
+        } else {
+            alpha = 0.0;
+        }
+        anorm_sq += alpha * alpha + beta * beta;
+
+        // Plane rotation eliminating the subdiagonal beta
+        let rho = rhobar.hypot(beta);
+        let c = rhobar / rho;
+        let s = beta / rho;
+        let theta = s * alpha;
+        rhobar = -c * alpha;
+        let phi = c * phibar;
+        phibar *= s;
+
+        // x = x + (phi / rho) w, w = v - (theta / rho) w
+        let step = phi / rho;
+        let ratio = theta / rho;
+        for j in 0..num_cols {
+            x[j] += step * w[j];
+            w[j] = v[j] - ratio * w[j];
+        }
+
+        rnorm = phibar;
+        arnorm = phibar * alpha * c.abs();
+        if rnorm < threshold || arnorm <= tol * anorm_sq.sqrt() * rnorm {
+            break;
+        }
+    }
+
+    let solution_norm = norm(&x);
+    LeastSquaresResult {
+        solution: x,
+        iterations: iter,
+        residual_norm: rnorm,
+        normal_residual_norm: arnorm,
+        solution_norm,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_fit() {
+        // Fit y = c0 + c1 t to (0, 1), (1, 3), (2, 4), (3, 8)
+        let values = vec![1.0, 0.0, 1.0, 1.0, 1.0, 2.0, 1.0, 3.0];
+        let col_indices = vec![0u32, 1, 0, 1, 0, 1, 0, 1];
+        let row_ptr = vec![0u32, 2, 4, 6, 8];
+        let b = vec![1.0, 3.0, 4.0, 8.0];
+
+        let result = solve_lsqr(&values, &col_indices, &row_ptr, 2, &b, &[0.0; 2], 1e-12, 50);
+
+        // Normal equations: [4 6; 6 14] c = [16; 35] -> c = [0.7, 2.2]
+        assert!((result.solution[0] - 0.7).abs() < 1e-9);
+        assert!((result.solution[1] - 2.2).abs() < 1e-9);
+        assert!(result.normal_residual_norm < 1e-8);
+
+        let a = Csr::new(&values, &col_indices, &row_ptr);
+        let mut r = vec![0.0; 4];
+        a.residual(&b, &result.solution, &mut r);
+        assert!((result.residual_norm - norm(&r)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_rank_deficient_minimum_norm() {
+        // Two identical columns: x0 + x1 = rhs, minimum norm splits it evenly
+        let values = vec![1.0, 1.0, 2.0, 2.0, 1.0, 1.0];
+        let col_indices = vec![0u32, 1, 0, 1, 0, 1];
+        let row_ptr = vec![0u32, 2, 4, 6];
+        let b = vec![2.0, 4.0, 2.0];
+
+        let result = solve_lsqr(&values, &col_indices, &row_ptr, 2, &b, &[0.0; 2], 1e-12, 50);
+
+        assert!((result.solution[0] - 1.0).abs() < 1e-9);
+        assert!((result.solution[1] - 1.0).abs() < 1e-9);
+        assert!((result.solution_norm - 2f64.sqrt()).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,177 @@
+use wasm_bindgen::prelude::*;
+
+use crate::kernels::{apply_jacobi, dot, norm, threshold, Csr};
+use crate::SolveResult;
+
+/// MINRES solver for symmetric (possibly indefinite) systems
+///
+/// Takes the same arguments as `solve_pcg`. MINRES needs an SPD
+/// preconditioner, so the Jacobi diagonal is taken in absolute value;
+/// zero diagonal entries (e.g. Lagrange multiplier rows) fall back to 1.
+#[wasm_bindgen]
+pub fn solve_minres(
+    values: &[f64],
+    col_indices: &[u32],
+    row_ptr: &[u32],
+    b: &[f64],
+    x0: &[f64],
+    tol: f64,
+    max_iter: u32,
+) -> SolveResult {
+    let a = Csr::new(values, col_indices, row_ptr);
+    minres(&a, b, x0, tol, max_iter)
+}
+
+pub(crate) fn minres(a: &Csr, b: &[f64], x0: &[f64], tol: f64, max_iter: u32) -> SolveResult {
+    let n = b.len();
+    let mut x: Vec<f64> = x0.to_vec();
+
+    let diag: Vec<f64> = a.diagonal().iter().map(|d| d.abs()).collect();
+    let threshold = threshold(b, tol);
+
+    // ||r||_2 <= sqrt(max diag) * ||r||_{M^{-1}}, so the recurrence estimate
+    // can be tested against the unpreconditioned threshold
+    let scale = diag.iter().fold(0.0f64, |acc, &d| acc.max(d)).sqrt();
+
+    // Lanczos vectors: r1 = previous, r2 = current (unnormalized)
+    let mut r1 = vec![0.0; n];
+    a.residual(b, &x, &mut r1);
+    let mut r2 = r1.clone();
+    let mut y = vec![0.0; n];
+    apply_jacobi(&diag, &r1, &mut y);
+
+    let beta1 = dot(&r1, &y).max(0.0).sqrt();
+    let rnorm0 = norm(&r1);
+    if rnorm0 < threshold || beta1 == 0.0 {
+        return SolveResult::new(x, 0, rnorm0);
+    }
+
+    // Search directions
+    let mut v = vec![0.0; n];
+    let mut w = vec![0.0; n];
+    let mut w1 = vec![0.0; n];
+    let mut w2 = vec![0.0; n];
+
+    let mut oldb = 0.0;
+    let mut beta = beta1;
+    let mut dbar = 0.0;
+    let mut epsln = 0.0;
+    let mut phibar = beta1;
+    let mut cs = -1.0;
+    let mut sn = 0.0;
+
+    let mut iter = 0u32;
+    for i in 0..max_iter {
+        iter = i + 1;
+
+        // Lanczos step: v = y / beta, y = A v - (beta / oldb) r1 - (alfa / beta) r2
+        let s = 1.0 / beta;
+        for (vj, yj) in v.iter_mut().zip(&y) {
+            *vj = s * yj;
+        }
+        a.spmv(&v, &mut y);
+        if i > 0 {
+            let c = beta / oldb;
+            for (yj, r1j) in y.iter_mut().zip(&r1) {
+                *yj -= c * r1j;
+            }
+        }
+        let alfa = dot(&v, &y);
+        let c = alfa / beta;
+        for (yj, r2j) in y.iter_mut().zip(&r2) {
+            *yj -= c * r2j;
+        }
+        std::mem::swap(&mut r1, &mut r2);
+        r2.copy_from_slice(&y);
+        apply_jacobi(&diag, &r2, &mut y);
+        oldb = beta;
+        let beta_sq = dot(&r2, &y);
+        if beta_sq < 0.0 {
+            // Preconditioner is not positive definite
+            break;
+        }
+        beta = beta_sq.sqrt();
+
+        // Apply previous rotation, then compute the next one
+        let oldeps = epsln;
+        let delta = cs * dbar + sn * alfa;
+        let gbar = sn * dbar - cs * alfa;
+        epsln = sn * beta;
+        dbar = -cs * beta;
+
+        let gamma = gbar.hypot(beta).max(f64::EPSILON);
+        cs = gbar / gamma;
+        sn = beta / gamma;
+        let phi = cs * phibar;
+        phibar *= sn;
+
+        // Update search direction and solution
+        std::mem::swap(&mut w1, &mut w2);
+        std::mem::swap(&mut w2, &mut w);
+        let denom = 1.0 / gamma;
+        for j in 0..n {
+            w[j] = (v[j] - oldeps * w1[j] - delta * w2[j]) * denom;
+            x[j] += phi * w[j];
+        }
+
+        if phibar * scale < threshold || beta < 1e-30 {
+            break;
+        }
+    }
+
+    // Recurrence only tracks the preconditioned norm; report the true residual
+    a.residual(b, &x, &mut y);
+
+    SolveResult::new(x, iter, norm(&y))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{dense_to_csr, laplacian_1d};
+
+    #[test]
+    fn test_indefinite_diagonal_dominant() {
+        // Eigenvalues of both signs
+        let dense = vec![4.0, 1.0, 0.0, 1.0, -3.0, 1.0, 0.0, 1.0, 2.0];
+        let (values, col_indices, row_ptr) = dense_to_csr(&dense, 3);
+        let x_true = [1.0, -1.0, 2.0];
+        let b: Vec<f64> = (0..3)
+            .map(|i| (0..3).map(|j| dense[i * 3 + j] * x_true[j]).sum())
+            .collect();
+
+        let result = solve_minres(&values, &col_indices, &row_ptr, &b, &[0.0; 3], 1e-12, 100);
+
+        for (xi, ti) in result.solution.iter().zip(&x_true) {
+            assert!((xi - ti).abs() < 1e-8);
+        }
+    }
+
+    #[test]
+    fn test_saddle_point_system() {
+        // [K  1; 1^T  0] [u; lambda] = [f; 1]: Laplacian with a sum constraint
+        let n = 12;
+        let (kv, kc, kr) = laplacian_1d(n);
+        let mut dense = vec![0.0; (n + 1) * (n + 1)];
+        for i in 0..n {
+            for j in kr[i] as usize..kr[i + 1] as usize {
+                dense[i * (n + 1) + kc[j] as usize] = kv[j];
+            }
+            dense[i * (n + 1) + n] = 1.0;
+            dense[n * (n + 1) + i] = 1.0;
+        }
+        let (values, col_indices, row_ptr) = dense_to_csr(&dense, n + 1);
+        let a = Csr::new(&values, &col_indices, &row_ptr);
+        let mut b = vec![0.1; n + 1];
+        b[n] = 1.0;
+
+        let result = minres(&a, &b, &vec![0.0; n + 1], 1e-10, 500);
+
+        let mut r = vec![0.0; n + 1];
+        a.residual(&b, &result.solution, &mut r);
+        assert!(norm(&r) < 1e-8);
+        assert!((result.residual - norm(&r)).abs() < 1e-14);
+        let sum: f64 = result.solution[..n].iter().sum();
+        assert!((sum - 1.0).abs() < 1e-8);
+    }
+}
@@ -0,0 +1,41 @@
+//! Krylov subspace solvers
+//!
+//! Every solver takes the matrix in CSR form and returns a `SolveResult`.
+//! Unless documented otherwise they use the Jacobi (diagonal) preconditioner.
+
+mod auto;
+mod bicgstab;
+mod block_cg;
+mod chebyshev;
+mod cr;
+mod deflated;
+mod fallback;
+mod fcg;
+mod gmres;
+mod lsqr;
+mod minres;
+mod pcg;
+mod pipelined_cg;
+mod recycling;
+mod refine;
+mod sstep_cg;
+mod symmlq;
+
+pub use auto::*;
+pub use bicgstab::*;
+pub use block_cg::*;
+pub use chebyshev::*;
+pub use cr::*;
+pub use deflated::*;
+pub use fallback::*;
+pub use fcg::*;
+pub use gmres::*;
+pub use lsqr::*;
+pub use minres::*;
+pub use pcg::*;
+pub use recycling::*;
+pub use refine::*;
+pub use symmlq::*;
+
+pub(crate) use pipelined_cg::pipelined_cg;
+pub(crate) use sstep_cg::sstep_cg;
@@ -0,0 +1,697 @@
+use wasm_bindgen::prelude::*;
+
+use super::run_solver;
+use crate::grid::ElementGrid;
+use crate::kernels::{axpy, criterion_threshold, dot, norm, Csr, LinearOperator, SymmetricCsr};
+use crate::options::{ConvergenceCriterion, ResidualNorm, SolverKind, SolverOptions};
+use crate::precond::{
+    setup, setup_single, setup_symmetric, smoother, timed, Amg, Ebe, Gmg, Preconditioner, TwoLevel,
+};
+use crate::SolveResult;
+
+/// Preconditioned Conjugate Gradient solver
+///
+/// # Arguments
+/// * `values` - Non-zero values of the sparse matrix (CSR format)
+/// * `col_indices` - Column indices for each value
+/// * `row_ptr` - Row pointers (index into values for each row start)
+/// * `b` - Right-hand side vector
+/// * `x0` - Initial guess
+/// * `tol` - Convergence tolerance
+/// * `max_iter` - Maximum number of iterations
+///
+/// # Returns
+/// SolveResult containing the solution vector, iteration count, and final residual
+#[wasm_bindgen]
+pub fn solve_pcg(
+    values: &[f64],
+    col_indices: &[u32],
+    row_ptr: &[u32],
+    b: &[f64],
+    x0: &[f64],
+    tol: f64,
+    max_iter: u32,
+) -> SolveResult {
+    let a = Csr::new(values, col_indices, row_ptr);
+    pcg(&a, b, x0, tol, max_iter)
+}
+
+/// Preconditioned Conjugate Gradient solver configured by `SolverOptions`
+///
+/// Same as `solve_pcg`, with the tolerance, iteration limit, CG kernel and
+/// stopping criterion taken from `options`.
+#[wasm_bindgen]
+pub fn solve_pcg_with_options(
+    values: &[f64],
+    col_indices: &[u32],
+    row_ptr: &[u32],
+    b: &[f64],
+    x0: &[f64],
+    options: &SolverOptions,
+) -> SolveResult {
+    let a = Csr::new(values, col_indices, row_ptr);
+    run_solver(SolverKind::Pcg, &a, b, x0, options)
+}
+
+/// PCG preconditioned by smoothed-aggregation AMG with a given near-null
+/// space
+///
+/// `near_null_space` holds the vectors one after another, each of length
+/// n; for elasticity pass `rigid_body_modes` of the nodal coordinates and
+/// set `options.block_size` to the DOFs per node. An empty slice uses the
+/// constant vector of each DOF component. The other settings come from
+/// `options` as in `solve_pcg_with_options` (classic kernel only).
+#[wasm_bindgen]
+pub fn solve_pcg_amg(
+    values: &[f64],
+    col_indices: &[u32],
+    row_ptr: &[u32],
+    b: &[f64],
+    x0: &[f64],
+    near_null_space: &[f64],
+    options: &SolverOptions,
+) -> SolveResult {
+    let a = Csr::new(values, col_indices, row_ptr);
+    let (m, report) = timed(|| Amg::new(&a, near_null_space, options.block_size as usize));
+    pcg_preconditioned(&a, &m, b, x0, options).with_setup(report)
+}
+
+/// PCG with a two-level preconditioner whose coarse space is spanned by
+/// `near_null_space` (k vectors of length n one after another, e.g. from
+/// `rigid_body_modes`) restricted to each of `options.subdomains`
+/// subdomains, smoothed by `options.smoother`. Other settings come from
+/// `options` as in `solve_pcg_amg`.
+#[wasm_bindgen]
+pub fn solve_pcg_two_level(
+    values: &[f64],
+    col_indices: &[u32],
+    row_ptr: &[u32],
+    b: &[f64],
+    x0: &[f64],
+    near_null_space: &[f64],
+    options: &SolverOptions,
+) -> SolveResult {
+    let a = Csr::new(values, col_indices, row_ptr);
+    let (m, report) = timed(|| {
+        TwoLevel::new(
+            &a,
+            near_null_space,
+            options.block_size as usize,
+            options.subdomains as usize,
+            smoother(options, &a),
+        )
+    });
+    pcg_preconditioned(&a, &m, b, x0, options).with_setup(report)
+}
+
+/// PCG preconditioned by geometric multigrid on a structured grid
+///
+/// The matrix must hold the nodal DOFs of an `nelx` x `nely` (x `nelz`)
+/// element grid, node (x, y, z) numbered z (nelx + 1)(nely + 1) +
+/// x (nely + 1) + y with `options.block_size` DOFs per node; use
+/// `nelz = 0` for 2D. Directions with an even element count are halved on
+/// each level. Other settings come from `options` as in `solve_pcg_amg`.
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn solve_pcg_gmg(
+    values: &[f64],
+    col_indices: &[u32],
+    row_ptr: &[u32],
+    b: &[f64],
+    x0: &[f64],
+    nelx: u32,
+    nely: u32,
+    nelz: u32,
+    options: &SolverOptions,
+) -> SolveResult {
+    let a = Csr::new(values, col_indices, row_ptr);
+    let elements = [nelx as usize, nely as usize, nelz as usize];
+    let (m, report) = timed(|| Gmg::new(&a, elements, options.block_size as usize));
+    pcg_preconditioned(&a, &m, b, x0, options).with_setup(report)
+}
+
+/// Matrix-free PCG on a structured grid, preconditioned element by element
+///
+/// K = sum_e scales[e] K_e is never assembled: `ke` is the row-major
+/// reference element matrix (8 x 8 for Q4, 24 x 24 for H8 with
+/// `options.block_size` DOFs per node), `scales` the per-element factors
+/// such as E_min + rho_e^p (E_0 - E_min), and `fixed_dofs` the DOFs held
+/// at zero. Nodes, elements and element DOFs are numbered as in
+/// `getElementDOFs`; use `nelz = 0` for 2D. Other settings come from
+/// `options` as in `solve_pcg_amg`.
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn solve_pcg_ebe(
+    ke: &[f64],
+    scales: &[f64],
+    fixed_dofs: &[u32],
+    nelx: u32,
+    nely: u32,
+    nelz: u32,
+    b: &[f64],
+    x0: &[f64],
+    options: &SolverOptions,
+) -> SolveResult {
+    let elements = [nelx as usize, nely as usize, nelz as usize];
+    let grid = ElementGrid::new(
+        elements,
+        options.block_size as usize,
+        ke,
+        scales,
+        fixed_dofs,
+    );
+    let (m, report) = timed(|| Ebe::new(&grid));
+    pcg_preconditioned(&grid, &m, b, x0, options).with_setup(report)
+}
+
+/// PCG on a symmetric matrix stored as its upper triangle
+///
+/// The CSR arrays hold only entries with column >= row, the diagonal
+/// included; each off-diagonal entry stands for both a_ij and a_ji, so
+/// the matrix takes about half the memory of `solve_pcg_with_options`
+/// input. Jacobi, IC(0) and ICT are set up from the stored triangle; other
+/// preconditioners are set up on the expanded matrix. Other settings come
+/// from `options` as in `solve_pcg_amg`.
+#[wasm_bindgen]
+pub fn solve_pcg_symmetric(
+    values: &[f64],
+    col_indices: &[u32],
+    row_ptr: &[u32],
+    b: &[f64],
+    x0: &[f64],
+    options: &SolverOptions,
+) -> SolveResult {
+    let a = SymmetricCsr::new(Csr::new(values, col_indices, row_ptr));
+    let (m, report) = timed(|| setup_symmetric(options, &a));
+    pcg_preconditioned(&a, m.as_ref(), b, x0, options).with_setup(report)
+}
+
+/// PCG on a matrix with single-precision values
+///
+/// Same as `solve_pcg_with_options` with the values passed as a
+/// `Float32Array`, half the memory of f64 values; products accumulate and
+/// vectors stay in f64, so the accuracy is limited by the rounding of A to
+/// about 1e-7 relative. Jacobi and IC(0) are set up in single precision;
+/// other preconditioners on an f64 copy of A. Other settings come from
+/// `options` as in `solve_pcg_amg`.
+#[wasm_bindgen]
+pub fn solve_pcg_f32(
+    values: &[f32],
+    col_indices: &[u32],
+    row_ptr: &[u32],
+    b: &[f64],
+    x0: &[f64],
+    options: &SolverOptions,
+) -> SolveResult {
+    let a = Csr::new(values, col_indices, row_ptr);
+    let (m, report) = timed(|| setup_single(options, &a));
+    pcg_preconditioned(&a, m.as_ref(), b, x0, options).with_setup(report)
+}
+
+/// Number of terms in the energy-norm error estimate (delay in iterations)
+const ENERGY_DELAY: usize = 4;
+
+pub(crate) fn pcg(a: &Csr, b: &[f64], x0: &[f64], tol: f64, max_iter: u32) -> SolveResult {
+    let options = SolverOptions {
+        tol,
+        max_iter,
+        ..SolverOptions::new()
+    };
+    pcg_with_options(a, b, x0, &options)
+}
+
+/// PCG honouring the stopping criterion, monitored residual norm and
+/// residual replacement settings of `options`; the result records the
+/// criterion when it triggered
+pub(crate) fn pcg_with_options(
+    a: &Csr,
+    b: &[f64],
+    x0: &[f64],
+    options: &SolverOptions,
+) -> SolveResult {
+    let (m, report) = timed(|| setup(options, a));
+    pcg_preconditioned(a, m.as_ref(), b, x0, options).with_setup(report)
+}
+
+/// `pcg_with_options` with an already set up preconditioner `m`; `a` may
+/// be any operator, assembled or not
+pub(crate) fn pcg_preconditioned(
+    a: &impl LinearOperator,
+    m: &dyn Preconditioner,
+    b: &[f64],
+    x0: &[f64],
+    options: &SolverOptions,
+) -> SolveResult {
+    let (tol, max_iter, criterion) = (options.tol, options.max_iter, options.criterion);
+    let interval = options.residual_replacement;
+    let n = b.len();
+
+    // Solution vector (start from initial guess)
+    let mut x: Vec<f64> = x0.to_vec();
+
+    // Work vectors
+    let mut r = vec![0.0; n]; // Residual
+    let mut z = vec![0.0; n]; // Preconditioned residual
+    let mut p = vec![0.0; n]; // Search direction
+    let mut ap = vec![0.0; n]; // A * p
+
+    // Compute initial residual: r = b - A*x
+    a.residual(b, &x, &mut r);
+
+    // z = M^{-1} * r
+    m.apply(&r, &mut z);
+
+    // rz = r^T * z
+    let mut rz = dot(&r, &z);
+
+    // Compute convergence threshold in the monitored norm
+    let preconditioned = options.residual_norm == ResidualNorm::Preconditioned;
+    let bnorm = if preconditioned {
+        let mut mb = vec![0.0; n];
+        m.apply(b, &mut mb);
+        dot(b, &mb).max(0.0).sqrt()
+    } else {
+        norm(b)
+    };
+    let mut rnorm = if preconditioned {
+        rz.max(0.0).sqrt()
+    } else {
+        norm(&r)
+    };
+    let threshold = criterion_threshold(criterion, bnorm, rnorm, tol);
+    if rnorm < threshold {
+        return SolveResult::new(x, 0, norm(&r)).with_criterion(criterion);
+    }
+    // A failed operator or preconditioner (NaN or infinite products) can
+    // only get worse
+    if !rnorm.is_finite() {
+        return SolveResult::new(x, 0, norm(&r));
+    }
+
+    // Last terms alpha_j * rz_j of ||x - x*||_A^2 = sum_j alpha_j * rz_j
+    let mut energy_terms = [0.0; ENERGY_DELAY];
+
+    // p = z
+    p.copy_from_slice(&z);
+
+    let mut converged = false;
+    let mut iter = 0u32;
+    for i in 0..max_iter {
+        iter = i + 1;
+
+        // ap = A * p
+        a.spmv(&p, &mut ap);
+
+        // alpha = rz / (p^T * A*p)
+        let pap = dot(&p, &ap);
+        if pap.abs() < 1e-30 || !pap.is_finite() {
+            // Matrix might be singular or near-singular, or A p failed
+            break;
+        }
+        let alpha = rz / pap;
+
+        // x = x + alpha * p
+        axpy(alpha, &p, &mut x);
+
+        // r = r - alpha * A*p, or the true residual b - A*x every `interval`
+        // iterations so that rounding errors in the recurrence cannot build up
+        let replaced = interval > 0 && iter.is_multiple_of(interval);
+        if replaced {
+            a.residual(b, &x, &mut r);
+        } else {
+            axpy(-alpha, &ap, &mut r);
+        }
+
+        // Check convergence; the preconditioned norm needs z = M^{-1} r,
+        // which the update of p reuses
+        let mut rz_new = None;
+        rnorm = monitored_norm(preconditioned, m, &r, &mut z, &mut rz_new);
+        if rnorm < threshold && interval > 0 && !replaced {
+            // Confirm with the true residual before stopping
+            a.residual(b, &x, &mut r);
+            rnorm = monitored_norm(preconditioned, m, &r, &mut z, &mut rz_new);
+        }
+        if rnorm < threshold {
+            converged = true;
+            break;
+        }
+        if !rnorm.is_finite() {
+            break;
+        }
+        if criterion == ConvergenceCriterion::EnergyNorm {
+            energy_terms[i as usize % ENERGY_DELAY] = alpha * rz;
+            // The sum bounds the error of the iterate ENERGY_DELAY steps back
+            // from below; ||x||_A^2 = x^T (b - r)
+            let error_sq: f64 = energy_terms.iter().sum();
+            let x_energy = dot(&x, b) - dot(&x, &r);
+            if i as usize + 1 >= ENERGY_DELAY && error_sq <= tol * tol * x_energy.abs() {
+                converged = true;
+                break;
+            }
+        }
+
+        // z = M^{-1} * r, rz_new = r^T * z
+        let rz_new = rz_new.unwrap_or_else(|| {
+            m.apply(&r, &mut z);
+            dot(&r, &z)
+        });
+
+        // beta = (r_new^T * z_new) / (r_old^T * z_old)
+        let beta = rz_new / rz;
+        rz = rz_new;
+
+        // p = z + beta * p
+        for j in 0..n {
+            p[j] = z[j] + beta * p[j];
+        }
+    }
+
+    // Always report the 2-norm of the residual
+    if preconditioned {
+        rnorm = norm(&r);
+    }
+    let result = SolveResult::new(x, iter, rnorm);
+    if converged {
+        result.with_criterion(criterion)
+    } else {
+        result
+    }
+}
+
+/// Norm of r watched for convergence. For the preconditioned norm this
+/// computes z = M^{-1} r and stores r^T z in `rz`.
+fn monitored_norm(
+    preconditioned: bool,
+    m: &dyn Preconditioner,
+    r: &[f64],
+    z: &mut [f64],
+    rz: &mut Option<f64>,
+) -> f64 {
+    if preconditioned {
+        m.apply(r, z);
+        let value = dot(r, z);
+        *rz = Some(value);
+        value.max(0.0).sqrt()
+    } else {
+        *rz = None;
+        norm(r)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kernels::SparseMatrix;
+    use crate::options::{CgVariant, PreconditionerKind};
+    use crate::test_util::{diffusion_2d, laplacian_1d};
+
+    #[test]
+    fn test_simple_2x2() {
+        // [4, 1; 1, 3] * x = [1; 2]
+        // Solution: x = [1/11, 7/11] ≈ [0.0909, 0.6364]
+        let values = vec![4.0, 1.0, 1.0, 3.0];
+        let col_indices = vec![0u32, 1, 0, 1];
+        let row_ptr = vec![0u32, 2, 4];
+        let b = vec![1.0, 2.0];
+        let x0 = vec![0.0, 0.0];
+
+        let result = solve_pcg(&values, &col_indices, &row_ptr, &b, &x0, 1e-10, 100);
+
+        assert!((result.solution[0] - 1.0 / 11.0).abs() < 1e-8);
+        assert!((result.solution[1] - 7.0 / 11.0).abs() < 1e-8);
+    }
+
+    #[test]
+    fn test_3x3_identity() {
+        // Identity matrix: I * x = b, solution is x = b
+        let values = vec![1.0, 1.0, 1.0];
+        let col_indices = vec![0u32, 1, 2];
+        let row_ptr = vec![0u32, 1, 2, 3];
+        let b = vec![1.0, 2.0, 3.0];
+        let x0 = vec![0.0, 0.0, 0.0];
+
+        let result = solve_pcg(&values, &col_indices, &row_ptr, &b, &x0, 1e-10, 100);
+
+        for (xi, bi) in result.solution.iter().zip(&b) {
+            assert!((xi - bi).abs() < 1e-10);
+        }
+    }
+
+    #[test]
+    fn test_3x3_symmetric() {
+        // [4, 1, 1; 1, 4, 1; 1, 1, 4] * x = [6; 6; 6]
+        // Solution: x = [1, 1, 1]
+        let values = vec![4.0, 1.0, 1.0, 1.0, 4.0, 1.0, 1.0, 1.0, 4.0];
+        let col_indices = vec![0u32, 1, 2, 0, 1, 2, 0, 1, 2];
+        let row_ptr = vec![0u32, 3, 6, 9];
+        let b = vec![6.0, 6.0, 6.0];
+        let x0 = vec![0.0, 0.0, 0.0];
+
+        let result = solve_pcg(&values, &col_indices, &row_ptr, &b, &x0, 1e-10, 100);
+
+        for i in 0..3 {
+            assert!((result.solution[i] - 1.0).abs() < 1e-8);
+        }
+    }
+
+    #[test]
+    fn test_options_select_variant() {
+        let values = vec![4.0, 1.0, 1.0, 1.0, 4.0, 1.0, 1.0, 1.0, 4.0];
+        let col_indices = vec![0u32, 1, 2, 0, 1, 2, 0, 1, 2];
+        let row_ptr = vec![0u32, 3, 6, 9];
+        let b = vec![6.0, 6.0, 6.0];
+        let x0 = vec![0.0, 0.0, 0.0];
+
+        let mut options = SolverOptions::new();
+        options.tol = 1e-12;
+        for variant in [CgVariant::Classic, CgVariant::Pipelined, CgVariant::SStep] {
+            options.cg_variant = variant;
+            let result = solve_pcg_with_options(&values, &col_indices, &row_ptr, &b, &x0, &options);
+            for xi in &result.solution {
+                assert!((xi - 1.0).abs() < 1e-10);
+            }
+        }
+    }
+
+    #[test]
+    fn test_convergence_criteria() {
+        let n = 100;
+        let (values, col_indices, row_ptr) = laplacian_1d(n);
+        let a = Csr::new(&values, &col_indices, &row_ptr);
+        let b: Vec<f64> = (0..n).map(|i| 1.0 + (i as f64 * 0.37).sin()).collect();
+        let x0 = vec![0.0; n];
+        let energy = |x: &[f64]| {
+            let mut ax = vec![0.0; n];
+            a.spmv(x, &mut ax);
+            dot(x, &ax).sqrt()
+        };
+        let exact = pcg(&a, &b, &x0, 1e-14, 1000);
+        let criterion_options = |tol, max_iter, criterion| SolverOptions {
+            tol,
+            max_iter,
+            criterion,
+            ..SolverOptions::new()
+        };
+
+        let abs = pcg_with_options(
+            &a,
+            &b,
+            &x0,
+            &criterion_options(1e-6, 1000, ConvergenceCriterion::Absolute),
+        );
+        assert!(abs.residual < 1e-6);
+        assert_eq!(abs.criterion, Some(ConvergenceCriterion::Absolute));
+
+        let x_start = vec![5.0; n];
+        let mut r0 = vec![0.0; n];
+        a.residual(&b, &x_start, &mut r0);
+        let rel = pcg_with_options(
+            &a,
+            &b,
+            &x_start,
+            &criterion_options(1e-3, 1000, ConvergenceCriterion::RelativeToInitial),
+        );
+        assert!(rel.residual < 1e-3 * norm(&r0));
+        assert_eq!(rel.criterion, Some(ConvergenceCriterion::RelativeToInitial));
+
+        let tol = 1e-4;
+        let en = pcg_with_options(
+            &a,
+            &b,
+            &x0,
+            &criterion_options(tol, 1000, ConvergenceCriterion::EnergyNorm),
+        );
+        let error: Vec<f64> = en
+            .solution
+            .iter()
+            .zip(&exact.solution)
+            .map(|(x, e)| x - e)
+            .collect();
+        assert_eq!(en.criterion, Some(ConvergenceCriterion::EnergyNorm));
+        assert!(energy(&error) < 10.0 * tol * energy(&exact.solution));
+        assert!(en.iterations < exact.iterations);
+
+        let short = pcg_with_options(
+            &a,
+            &b,
+            &x0,
+            &criterion_options(1e-6, 2, ConvergenceCriterion::Absolute),
+        );
+        assert_eq!(short.criterion, None);
+    }
+
+    #[test]
+    fn test_residual_replacement_reports_true_residual() {
+        // D K D with D spanning six orders of magnitude: the recursive
+        // residual drifts far below the attainable accuracy
+        let n = 400;
+        let (mut values, col_indices, row_ptr) = laplacian_1d(n);
+        let d: Vec<f64> = (0..n)
+            .map(|i| 10f64.powf(3.0 * (i as f64 * 0.05).sin()))
+            .collect();
+        for i in 0..n {
+            for j in row_ptr[i] as usize..row_ptr[i + 1] as usize {
+                values[j] *= d[i] * d[col_indices[j] as usize];
+            }
+        }
+        let a = Csr::new(&values, &col_indices, &row_ptr);
+        let x0 = vec![0.0; n];
+        let mut r = vec![0.0; n];
+
+        let mut options = SolverOptions::new();
+        options.tol = 1e-13;
+        options.max_iter = 2000;
+        let drifted = pcg_with_options(&a, &d, &x0, &options);
+        a.residual(&d, &drifted.solution, &mut r);
+        assert!(drifted.residual < 1e-2 * norm(&r));
+
+        options.residual_replacement = 10;
+        let replaced = pcg_with_options(&a, &d, &x0, &options);
+        a.residual(&d, &replaced.solution, &mut r);
+        assert!((replaced.residual - norm(&r)).abs() <= 1e-12 * norm(&r));
+        assert_eq!(replaced.criterion, None);
+    }
+
+    #[test]
+    fn test_preconditioned_residual_monitor() {
+        // Badly scaled rows make the two norms differ by orders of magnitude
+        let n = 60;
+        let (mut values, col_indices, row_ptr) = laplacian_1d(n);
+        let d: Vec<f64> = (0..n).map(|i| if i < n / 2 { 1.0 } else { 1e4 }).collect();
+        for i in 0..n {
+            for j in row_ptr[i] as usize..row_ptr[i + 1] as usize {
+                values[j] *= d[i].sqrt() * d[col_indices[j] as usize].sqrt();
+            }
+        }
+        let a = Csr::new(&values, &col_indices, &row_ptr);
+        let diag = a.diagonal();
+        let b: Vec<f64> = d.iter().map(|di| di.sqrt()).collect();
+        let m_norm = |v: &[f64]| (0..n).map(|i| v[i] * v[i] / diag[i]).sum::<f64>().sqrt();
+
+        let mut options = SolverOptions::new();
+        options.tol = 1e-8;
+        options.residual_norm = ResidualNorm::Preconditioned;
+        let result = pcg_with_options(&a, &b, &vec![0.0; n], &options);
+
+        let mut r = vec![0.0; n];
+        a.residual(&b, &result.solution, &mut r);
+        assert_eq!(result.criterion, Some(ConvergenceCriterion::RelativeToRhs));
+        assert!(m_norm(&r) < 1e-8 * m_norm(&b).max(1.0) * (1.0 + 1e-6));
+        // The reported residual stays the 2-norm
+        assert!((result.residual - norm(&r)).abs() <= 1e-12 * norm(&b));
+    }
+
+    #[test]
+    fn test_symmetric_half_storage() {
+        let n = 40;
+        let kappa: Vec<f64> = (0..n * n).map(|i| 1.0 + (i % 7) as f64).collect();
+        let (values, col_indices, row_ptr) = diffusion_2d(n, n, &kappa);
+        let full = Csr::new(&values, &col_indices, &row_ptr);
+        let mut entries = Vec::new();
+        for i in 0..n * n {
+            for q in row_ptr[i] as usize..row_ptr[i + 1] as usize {
+                if col_indices[q] as usize >= i {
+                    entries.push((i, col_indices[q], values[q]));
+                }
+            }
+        }
+        let upper = SparseMatrix::from_triplets(n * n, n * n, entries);
+        let upper = upper.csr();
+        let half = SymmetricCsr::new(upper);
+        assert_eq!(2 * upper.values.len(), values.len() + n * n);
+
+        let x: Vec<f64> = (0..n * n).map(|i| (i as f64 * 0.37).sin()).collect();
+        let (mut y_full, mut y_half) = (vec![0.0; n * n], vec![0.0; n * n]);
+        full.spmv(&x, &mut y_full);
+        half.spmv(&x, &mut y_half);
+        assert!(y_full
+            .iter()
+            .zip(&y_half)
+            .all(|(a, b)| (a - b).abs() < 1e-12));
+        assert_eq!(half.full().values, values);
+
+        let b = vec![1.0; n * n];
+        let x0 = vec![0.0; n * n];
+        for preconditioner in [
+            PreconditionerKind::Jacobi,
+            PreconditionerKind::Ic0,
+            PreconditionerKind::Ict,
+            PreconditionerKind::Amg,
+        ] {
+            let options = SolverOptions {
+                tol: 1e-10,
+                max_iter: 1000,
+                preconditioner,
+                ..SolverOptions::new()
+            };
+            let reference = pcg_with_options(&full, &b, &x0, &options);
+            let result = solve_pcg_symmetric(
+                upper.values,
+                upper.col_indices,
+                upper.row_ptr,
+                &b,
+                &x0,
+                &options,
+            );
+            assert!(result.criterion.is_some());
+            assert!(result.iterations.abs_diff(reference.iterations) <= 1);
+        }
+    }
+
+    #[test]
+    fn test_single_precision_values() {
+        let n = 30;
+        let kappa: Vec<f64> = (0..n * n).map(|i| 1.0 + (i % 5) as f64).collect();
+        let (values, col_indices, row_ptr) = diffusion_2d(n, n, &kappa);
+        let single: Vec<f32> = values.iter().map(|&v| v as f32).collect();
+        let a = Csr::new(&values, &col_indices, &row_ptr);
+        let b: Vec<f64> = (0..n * n).map(|i| (i as f64 * 0.1).cos()).collect();
+        let x0 = vec![0.0; n * n];
+
+        for preconditioner in [PreconditionerKind::Jacobi, PreconditionerKind::Ic0] {
+            let options = SolverOptions {
+                tol: 1e-6,
+                max_iter: 1000,
+                preconditioner,
+                ..SolverOptions::new()
+            };
+            let reference = pcg_with_options(&a, &b, &x0, &options);
+            let result = solve_pcg_f32(&single, &col_indices, &row_ptr, &b, &x0, &options);
+            assert!(result.criterion.is_some());
+            assert!(result.iterations.abs_diff(reference.iterations) <= 2);
+            let diff: Vec<f64> = result
+                .solution
+                .iter()
+                .zip(&reference.solution)
+                .map(|(x, y)| x - y)
+                .collect();
+            assert!(norm(&diff) < 1e-5 * norm(&reference.solution));
+            if preconditioner == PreconditionerKind::Ic0 {
+                // Factor values take 4 bytes instead of 8
+                let (single, double) = (result.setup.unwrap(), reference.setup.unwrap());
+                assert_eq!(single.nnz(), double.nnz());
+                assert_eq!(double.bytes() - single.bytes(), 4 * double.nnz());
+            }
+        }
+    }
+}
@@ -0,0 +1,468 @@
+use wasm_bindgen::prelude::*;
+
+use crate::dense::{cholesky_factor, lower_solve, lower_transpose_solve, symmetric_eigen};
+use crate::kernels::{apply_jacobi, axpy, dot, norm, threshold, Csr};
+use crate::precond::MemoryReport;
+use crate::SolveResult;
+
+/// GCRO-DR solver that recycles a Krylov subspace between solves
+///
+/// Meant for sequences of slowly changing systems such as the stiffness
+/// matrices of a SIMP loop. At the end of each restart cycle the solver keeps
+/// the `recycle_dim` directions in which the operator is weakest and reuses
+/// them in the following cycles and in the following calls to `solve`.
+/// The new matrix is applied to the stored directions when `solve` starts,
+/// so they remain valid even though the matrix has changed.
+///
+/// Uses right Jacobi preconditioning. The directions are picked from the
+/// smallest singular values of the preconditioned operator on the search
+/// space, which gives a symmetric eigenproblem and targets the same modes as
+/// harmonic Ritz vectors.
+#[wasm_bindgen]
+pub struct RecyclingSolver {
+    restart: usize,
+    recycle_dim: usize,
+    /// Recycled directions U, in the right-preconditioned variable
+    u: Vec<Vec<f64>>,
+}
+
+#[wasm_bindgen]
+impl RecyclingSolver {
+    /// Create a solver with cycle length `restart` that keeps `recycle_dim`
+    /// vectors (clamped to `restart - 1`)
+    #[wasm_bindgen(constructor)]
+    pub fn new(restart: u32, recycle_dim: u32) -> RecyclingSolver {
+        let restart = restart.max(2) as usize;
+        RecyclingSolver {
+            restart,
+            recycle_dim: (recycle_dim as usize).min(restart - 1),
+            u: Vec::new(),
+        }
+    }
+
+    /// Solve A x = b, reusing and then updating the recycled subspace
+    ///
+    /// Takes the same arguments as `solve_pcg`. `iterations` counts Arnoldi
+    /// steps; the `recycled` matrix-vector products made at the start are
+    /// not included.
+    #[allow(clippy::too_many_arguments)]
+    pub fn solve(
+        &mut self,
+        values: &[f64],
+        col_indices: &[u32],
+        row_ptr: &[u32],
+        b: &[f64],
+        x0: &[f64],
+        tol: f64,
+        max_iter: u32,
+    ) -> SolveResult {
+        let a = Csr::new(values, col_indices, row_ptr);
+        self.solve_csr(&a, b, x0, tol, max_iter)
+    }
+
+    /// Drop the recycled subspace (e.g. after a change of mesh)
+    pub fn reset(&mut self) {
+        self.u.clear();
+    }
+
+    /// Number of vectors currently held in the recycled subspace
+    #[wasm_bindgen(getter)]
+    pub fn recycled(&self) -> u32 {
+        self.u.len() as u32
+    }
+
+    /// Memory of the recycled subspace kept between solves, as work; the
+    /// cycle's own vectors are allocated per solve
+    pub fn memory_report(&self) -> MemoryReport {
+        let kept = self
+            .u
+            .iter()
+            .map(|u| std::mem::size_of_val(u.as_slice()))
+            .sum();
+        MemoryReport::default().with_work(kept)
+    }
+}
+
+impl RecyclingSolver {
+    pub(crate) fn solve_csr(
+        &mut self,
+        a: &Csr,
+        b: &[f64],
+        x0: &[f64],
+        tol: f64,
+        max_iter: u32,
+    ) -> SolveResult {
+        let n = b.len();
+        let mut x: Vec<f64> = x0.to_vec();
+        let diag = a.diagonal();
+        let threshold = threshold(b, tol);
+
+        let mut z = vec![0.0; n]; // M^{-1} * v
+        let mut w = vec![0.0; n]; // A * M^{-1} * v
+
+        // C = A M^{-1} U for the current matrix, with C orthonormal
+        let mut u = std::mem::take(&mut self.u);
+        if u.iter().any(|ui| ui.len() != n) {
+            u.clear();
+        }
+        let pairs: Vec<(Vec<f64>, Vec<f64>)> = u
+            .into_iter()
+            .map(|ui| {
+                apply_jacobi(&diag, &ui, &mut z);
+                let mut ci = vec![0.0; n];
+                a.spmv(&z, &mut ci);
+                (ui, ci)
+            })
+            .collect();
+        let (mut u, mut c) = orthonormalize_images(pairs);
+
+        let mut r = vec![0.0; n];
+        a.residual(b, &x, &mut r);
+        let mut rnorm = norm(&r);
+        let mut iter = 0u32;
+
+        let m = self.restart.min(n.max(1));
+        loop {
+            // Minimize over the recycled space: x = x + M^{-1} U C^T r, r = r - C C^T r
+            if !c.is_empty() {
+                w.iter_mut().for_each(|wi| *wi = 0.0);
+                for (ui, ci) in u.iter().zip(&c) {
+                    let coef = dot(ci, &r);
+                    axpy(coef, ui, &mut w);
+                    axpy(-coef, ci, &mut r);
+                }
+                apply_jacobi(&diag, &w, &mut z);
+                axpy(1.0, &z, &mut x);
+                rnorm = norm(&r);
+            }
+            if rnorm < threshold || iter >= max_iter {
+                break;
+            }
+
+            let k = c.len();
+            let mk = m.saturating_sub(k).max(1);
+
+            // Arnoldi on (I - C C^T) A M^{-1}: basis V, Hessenberg H and the
+            // coefficients B = C^T A M^{-1} V, all column-major
+            let mut basis: Vec<Vec<f64>> = vec![vec![0.0; n]; mk + 1];
+            let mut h = vec![0.0; (mk + 1) * mk];
+            let mut bmat = vec![0.0; k * mk];
+            // Givens-rotated copy of H and right-hand side
+            let mut hr = vec![0.0; (mk + 1) * mk];
+            let mut cs = vec![0.0; mk];
+            let mut sn = vec![0.0; mk];
+            let mut g = vec![0.0; mk + 1];
+
+            for (vi, ri) in basis[0].iter_mut().zip(&r) {
+                *vi = ri / rnorm;
+            }
+            g[0] = rnorm;
+
+            let mut steps = 0;
+            let mut breakdown = false;
+            while steps < mk && iter < max_iter {
+                iter += 1;
+                let j = steps;
+
+                apply_jacobi(&diag, &basis[j], &mut z);
+                a.spmv(&z, &mut w);
+                for (i, ci) in c.iter().enumerate() {
+                    let bij = dot(&w, ci);
+                    bmat[j * k + i] = bij;
+                    axpy(-bij, ci, &mut w);
+                }
+                for i in 0..=j {
+                    let hij = dot(&w, &basis[i]);
+                    h[j * (mk + 1) + i] = hij;
+                    axpy(-hij, &basis[i], &mut w);
+                }
+                let wnorm = norm(&w);
+                h[j * (mk + 1) + j + 1] = wnorm;
+                if wnorm >= 1e-30 {
+                    for (vi, wi) in basis[j + 1].iter_mut().zip(&w) {
+                        *vi = wi / wnorm;
+                    }
+                }
+
+                // Apply previous rotations, then eliminate H[j+1, j]
+                hr[j * (mk + 1)..(j + 1) * (mk + 1)]
+                    .copy_from_slice(&h[j * (mk + 1)..(j + 1) * (mk + 1)]);
+                for i in 0..j {
+                    let hi = hr[j * (mk + 1) + i];
+                    let hi1 = hr[j * (mk + 1) + i + 1];
+                    hr[j * (mk + 1) + i] = cs[i] * hi + sn[i] * hi1;
+                    hr[j * (mk + 1) + i + 1] = -sn[i] * hi + cs[i] * hi1;
+                }
+                let hjj = hr[j * (mk + 1) + j];
+                let denom = hjj.hypot(wnorm);
+                if denom < 1e-300 {
+                    (cs[j], sn[j]) = (1.0, 0.0);
+                } else {
+                    (cs[j], sn[j]) = (hjj / denom, wnorm / denom);
+                }
+                hr[j * (mk + 1) + j] = cs[j] * hjj + sn[j] * wnorm;
+                hr[j * (mk + 1) + j + 1] = 0.0;
+                g[j + 1] = -sn[j] * g[j];
+                g[j] *= cs[j];
+
+                steps += 1;
+                if g[steps].abs() < threshold {
+                    break;
+                }
+                if wnorm < 1e-30 {
+                    breakdown = true;
+                    break;
+                }
+            }
+
+            // Least-squares solution y of the cycle; the C component of the
+            // residual is cancelled exactly by -B y
+            let mut y = vec![0.0; steps];
+            for i in (0..steps).rev() {
+                let mut sum = g[i];
+                for j in (i + 1)..steps {
+                    sum -= hr[j * (mk + 1) + i] * y[j];
+                }
+                let hii = hr[i * (mk + 1) + i];
+                y[i] = if hii.abs() > 1e-300 { sum / hii } else { 0.0 };
+            }
+
+            // x = x + M^{-1} (V y - U B y)
+            w.iter_mut().for_each(|wi| *wi = 0.0);
+            for (j, yj) in y.iter().enumerate() {
+                axpy(*yj, &basis[j], &mut w);
+            }
+            for (i, ui) in u.iter().enumerate() {
+                let by: f64 = (0..steps).map(|j| bmat[j * k + i] * y[j]).sum();
+                axpy(-by, ui, &mut w);
+            }
+            apply_jacobi(&diag, &w, &mut z);
+            axpy(1.0, &z, &mut x);
+
+            a.residual(b, &x, &mut r);
+            rnorm = norm(&r);
+
+            if self.recycle_dim > 0 && steps > 0 {
+                let cycle = Cycle {
+                    basis: &basis,
+                    h: &h,
+                    bmat: &bmat,
+                    ld: mk + 1,
+                    steps,
+                };
+                if let Some(pairs) = select_recycled(&u, &c, &cycle, self.recycle_dim) {
+                    (u, c) = orthonormalize_images(pairs);
+                }
+            }
+
+            if breakdown && rnorm >= threshold {
+                break;
+            }
+        }
+
+        self.u = u;
+        SolveResult::new(x, iter, rnorm)
+    }
+}
+
+/// Arnoldi data of one cycle (column-major, leading dimension `ld`)
+struct Cycle<'a> {
+    basis: &'a [Vec<f64>],
+    h: &'a [f64],
+    bmat: &'a [f64],
+    ld: usize,
+    steps: usize,
+}
+
+/// Pick the new recycled directions from W = [U, V] as pairs (u, A M^{-1} u)
+///
+/// With A M^{-1} W = [C, V] G and G = [I B; 0 H], the directions minimizing
+/// ||A M^{-1} W z|| / ||W z|| solve G^T G z = s^2 W^T W z.
+fn select_recycled(
+    u: &[Vec<f64>],
+    c: &[Vec<f64>],
+    cycle: &Cycle,
+    recycle_dim: usize,
+) -> Option<Vec<(Vec<f64>, Vec<f64>)>> {
+    let k = u.len();
+    let steps = cycle.steps;
+    let p = k + steps;
+    let rows = p + 1;
+    let n = cycle.basis[0].len();
+
+    // G, (p + 1) x p row-major
+    let mut gmat = vec![0.0; rows * p];
+    for i in 0..k {
+        gmat[i * p + i] = 1.0;
+        for j in 0..steps {
+            gmat[i * p + k + j] = cycle.bmat[j * k + i];
+        }
+    }
+    for i in 0..=steps {
+        for j in 0..steps {
+            gmat[(k + i) * p + k + j] = cycle.h[j * cycle.ld + i];
+        }
+    }
+
+    // Normal matrix G^T G and Gram matrix W^T W
+    let mut normal = vec![0.0; p * p];
+    for i in 0..p {
+        for j in 0..p {
+            normal[i * p + j] = (0..rows).map(|l| gmat[l * p + i] * gmat[l * p + j]).sum();
+        }
+    }
+    let mut gram = vec![0.0; p * p];
+    for i in 0..p {
+        gram[i * p + i] = 1.0;
+    }
+    for i in 0..k {
+        for j in 0..k {
+            gram[i * p + j] = dot(&u[i], &u[j]);
+        }
+        for j in 0..steps {
+            let uv = dot(&u[i], &cycle.basis[j]);
+            gram[i * p + k + j] = uv;
+            gram[(k + j) * p + i] = uv;
+        }
+    }
+
+    // Reduce to a standard problem with W^T W = L L^T: S = L^{-1} G^T G L^{-T}
+    let mut l = gram;
+    if !cholesky_factor(&mut l, p) {
+        return None;
+    }
+    let mut half = vec![0.0; p * p]; // L^{-1} G^T G, row-major
+    let mut col = vec![0.0; p];
+    for j in 0..p {
+        for i in 0..p {
+            col[i] = normal[i * p + j];
+        }
+        lower_solve(&l, p, &mut col);
+        for i in 0..p {
+            half[i * p + j] = col[i];
+        }
+    }
+    let mut s = vec![0.0; p * p];
+    for j in 0..p {
+        col.copy_from_slice(&half[j * p..(j + 1) * p]);
+        lower_solve(&l, p, &mut col);
+        for i in 0..p {
+            s[i * p + j] = col[i];
+        }
+    }
+    // Symmetrize away rounding before the eigensolve
+    for i in 0..p {
+        for j in (i + 1)..p {
+            let avg = 0.5 * (s[i * p + j] + s[j * p + i]);
+            s[i * p + j] = avg;
+            s[j * p + i] = avg;
+        }
+    }
+    let (_, vectors) = symmetric_eigen(&s, p);
+
+    let mut pairs = Vec::with_capacity(recycle_dim.min(p));
+    for e in 0..recycle_dim.min(p) {
+        // z = L^{-T} y for the e-th smallest eigenvalue
+        let mut zc: Vec<f64> = (0..p).map(|i| vectors[i * p + e]).collect();
+        lower_transpose_solve(&l, p, &mut zc);
+
+        let mut ue = vec![0.0; n];
+        for (i, ui) in u.iter().enumerate() {
+            axpy(zc[i], ui, &mut ue);
+        }
+        for j in 0..steps {
+            axpy(zc[k + j], &cycle.basis[j], &mut ue);
+        }
+
+        // A M^{-1} W z = [C, V] (G z)
+        let mut ce = vec![0.0; n];
+        for (i, basis_vec) in c.iter().chain(&cycle.basis[..=steps]).enumerate() {
+            let gz: f64 = (0..p).map(|j| gmat[i * p + j] * zc[j]).sum();
+            axpy(gz, basis_vec, &mut ce);
+        }
+        pairs.push((ue, ce));
+    }
+    Some(pairs)
+}
+
+/// Orthonormalize the images c_i by modified Gram-Schmidt, applying the same
+/// transformation to the u_i so that A M^{-1} u_i = c_i keeps holding.
+/// Numerically dependent pairs are dropped.
+fn orthonormalize_images(pairs: Vec<(Vec<f64>, Vec<f64>)>) -> (Vec<Vec<f64>>, Vec<Vec<f64>>) {
+    let mut u: Vec<Vec<f64>> = Vec::with_capacity(pairs.len());
+    let mut c: Vec<Vec<f64>> = Vec::with_capacity(pairs.len());
+    for (mut ui, mut ci) in pairs {
+        let original = norm(&ci);
+        for (uj, cj) in u.iter().zip(&c) {
+            let coef = dot(&ci, cj);
+            axpy(-coef, cj, &mut ci);
+            axpy(-coef, uj, &mut ui);
+        }
+        let cnorm = norm(&ci);
+        if cnorm > 1e-10 * original && cnorm > 0.0 {
+            ci.iter_mut().for_each(|v| *v /= cnorm);
+            ui.iter_mut().for_each(|v| *v /= cnorm);
+            u.push(ui);
+            c.push(ci);
+        }
+    }
+    (u, c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::krylov::gmres;
+    use crate::test_util::{convection_diffusion_1d, laplacian_1d};
+
+    #[test]
+    fn test_recycling_cuts_iterations() {
+        let n = 100;
+        let (values, col_indices, row_ptr) = laplacian_1d(n);
+        let b: Vec<f64> = (0..n).map(|i| 1.0 + (i as f64 * 0.3).sin()).collect();
+        let x0 = vec![0.0; n];
+        let mut solver = RecyclingSolver::new(30, 10);
+
+        // Slightly different matrix each step, as in a SIMP loop
+        let mut counts = Vec::new();
+        for step in 0..4 {
+            let scaled: Vec<f64> = values
+                .iter()
+                .map(|v| v * (1.0 + 0.02 * step as f64))
+                .collect();
+            let a = Csr::new(&scaled, &col_indices, &row_ptr);
+            let result = solver.solve_csr(&a, &b, &x0, 1e-8, 5000);
+
+            let mut r = vec![0.0; n];
+            a.residual(&b, &result.solution, &mut r);
+            assert!(norm(&r) < 1e-8 * norm(&b));
+            assert!((result.residual - norm(&r)).abs() < 1e-10);
+            counts.push(result.iterations);
+        }
+        assert_eq!(solver.recycled(), 10);
+
+        let a = Csr::new(&values, &col_indices, &row_ptr);
+        let plain = gmres(&a, &b, &x0, 1e-8, 5000, 30);
+        assert!(counts[0] < plain.iterations);
+        assert!(2 * counts[3] < counts[0]);
+    }
+
+    #[test]
+    fn test_nonsymmetric_and_reset() {
+        let n = 80;
+        let (values, col_indices, row_ptr) = convection_diffusion_1d(n, 0.7);
+        let a = Csr::new(&values, &col_indices, &row_ptr);
+        let b: Vec<f64> = (0..n).map(|i| (i as f64 * 0.1).sin()).collect();
+        let x0 = vec![0.0; n];
+        let mut solver = RecyclingSolver::new(8, 3);
+
+        let result = solver.solve_csr(&a, &b, &x0, 1e-10, 1000);
+        let mut r = vec![0.0; n];
+        a.residual(&b, &result.solution, &mut r);
+        assert!(norm(&r) < 1e-8);
+        assert_eq!(solver.recycled(), 3);
+
+        solver.reset();
+        assert_eq!(solver.recycled(), 0);
+    }
+}
@@ -0,0 +1,144 @@
+use wasm_bindgen::prelude::*;
+
+use super::pcg_preconditioned;
+use crate::kernels::{axpy, criterion_threshold, norm, Csr};
+use crate::options::{ConvergenceCriterion, SolverOptions};
+use crate::precond::{setup_single, timed};
+use crate::SolveResult;
+
+/// Relative residual reduction asked of each single-precision inner solve;
+/// f32 values cannot resolve much more
+const INNER_TOL: f64 = 1e-5;
+/// Outer refinement steps before giving up
+const MAX_REFINEMENTS: u32 = 20;
+
+/// PCG in single precision wrapped in f64 iterative refinement
+///
+/// A copy of A with f32 values and its preconditioner (set up as in
+/// `solve_pcg_f32`) do the inner PCG solves of A d = r; the residual
+/// r = b - A x is recomputed with the f64 values after each of them and
+/// the correction added to x in f64. Each step gains about five digits,
+/// so a few steps reach `options.tol` at close to f32 bandwidth cost per
+/// iteration. `iterations` counts the inner iterations; `EnergyNorm`
+/// stops on the relative residual instead.
+#[wasm_bindgen]
+pub fn solve_pcg_refined(
+    values: &[f64],
+    col_indices: &[u32],
+    row_ptr: &[u32],
+    b: &[f64],
+    x0: &[f64],
+    options: &SolverOptions,
+) -> SolveResult {
+    let a = Csr::new(values, col_indices, row_ptr);
+    let single: Vec<f32> = values.iter().map(|&v| v as f32).collect();
+    let a_single = Csr::new(&single, col_indices, row_ptr);
+    let (m, report) = timed(|| setup_single(options, &a_single));
+
+    let criterion = match options.criterion {
+        ConvergenceCriterion::EnergyNorm => ConvergenceCriterion::RelativeToRhs,
+        other => other,
+    };
+    let inner = SolverOptions {
+        tol: INNER_TOL,
+        criterion: ConvergenceCriterion::RelativeToInitial,
+        ..*options
+    };
+    let mut x = x0.to_vec();
+    let mut r = vec![0.0; b.len()];
+    a.residual(b, &x, &mut r);
+    let mut rnorm = norm(&r);
+    let threshold = criterion_threshold(criterion, norm(b), rnorm, options.tol);
+    let zero = vec![0.0; b.len()];
+    let mut iterations = 0;
+    for _ in 0..MAX_REFINEMENTS {
+        if rnorm <= threshold || iterations >= options.max_iter {
+            break;
+        }
+        let correction = pcg_preconditioned(
+            &a_single,
+            m.as_ref(),
+            &r,
+            &zero,
+            &SolverOptions {
+                max_iter: options.max_iter - iterations,
+                ..inner
+            },
+        );
+        iterations += correction.iterations;
+        axpy(1.0, &correction.solution, &mut x);
+        a.residual(b, &x, &mut r);
+        let previous = rnorm;
+        rnorm = norm(&r);
+        if rnorm.is_nan() || rnorm >= previous {
+            // No progress: A is too ill-conditioned for f32
+            break;
+        }
+    }
+
+    let result = SolveResult::new(x, iterations, rnorm).with_setup(report);
+    if rnorm <= threshold {
+        result.with_criterion(criterion)
+    } else {
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::krylov::solve_pcg_f32;
+    use crate::options::PreconditionerKind;
+    use crate::test_util::diffusion_2d;
+
+    #[test]
+    fn test_reaches_double_precision() {
+        let n = 32;
+        let kappa: Vec<f64> = (0..n * n).map(|i| 1.0 + 0.37 * (i % 11) as f64).collect();
+        let (values, col_indices, row_ptr) = diffusion_2d(n, n, &kappa);
+        let a = Csr::new(&values, &col_indices, &row_ptr);
+        let b: Vec<f64> = (0..n * n).map(|i| (i as f64 * 0.1).cos()).collect();
+        let x0 = vec![0.0; n * n];
+        let options = SolverOptions {
+            tol: 1e-12,
+            max_iter: 2000,
+            preconditioner: PreconditionerKind::Ic0,
+            ..SolverOptions::new()
+        };
+        let true_residual = |x: &[f64]| {
+            let mut r = vec![0.0; n * n];
+            a.residual(&b, x, &mut r);
+            norm(&r) / norm(&b)
+        };
+
+        // Single precision alone solves the rounded system
+        let single: Vec<f32> = values.iter().map(|&v| v as f32).collect();
+        let plain = solve_pcg_f32(&single, &col_indices, &row_ptr, &b, &x0, &options);
+        assert!(true_residual(&plain.solution) > 1e-10);
+
+        let refined = solve_pcg_refined(&values, &col_indices, &row_ptr, &b, &x0, &options);
+        assert!(refined.criterion.is_some());
+        assert!(true_residual(&refined.solution) < 1e-12);
+        assert!((refined.residual / norm(&b) - true_residual(&refined.solution)).abs() < 1e-14);
+    }
+
+    #[test]
+    fn test_iteration_limit() {
+        let (values, col_indices, row_ptr) = diffusion_2d(20, 20, &[1.0; 400]);
+        let options = SolverOptions {
+            tol: 1e-12,
+            max_iter: 5,
+            ..SolverOptions::new()
+        };
+        let result = solve_pcg_refined(
+            &values,
+            &col_indices,
+            &row_ptr,
+            &[1.0; 400],
+            &[0.0; 400],
+            &options,
+        );
+        assert!(result.criterion.is_none());
+        assert_eq!(result.iterations, 5);
+    }
+}
@@ -0,0 +1,183 @@
+use wasm_bindgen::prelude::*;
+
+use crate::kernels::{axpy, dot, norm, threshold, Csr};
+use crate::SolveResult;
+
+/// SYMMLQ solver for symmetric systems that may be indefinite or singular
+///
+/// Takes the same arguments as `solve_pcg`. For a consistent singular
+/// system started from `x0 = 0` the iterates stay in the range of A, so
+/// the result is the minimum-norm solution (e.g. a floating substructure
+/// with its rigid body motion removed). The solver is unpreconditioned on
+/// purpose: a diagonal preconditioner would minimize a weighted norm instead.
+#[wasm_bindgen]
+pub fn solve_symmlq(
+    values: &[f64],
+    col_indices: &[u32],
+    row_ptr: &[u32],
+    b: &[f64],
+    x0: &[f64],
+    tol: f64,
+    max_iter: u32,
+) -> SolveResult {
+    let a = Csr::new(values, col_indices, row_ptr);
+    symmlq(&a, b, x0, tol, max_iter)
+}
+
+pub(crate) fn symmlq(a: &Csr, b: &[f64], x0: &[f64], tol: f64, max_iter: u32) -> SolveResult {
+    let n = b.len();
+    let threshold = threshold(b, tol);
+
+    // SYMMLQ point x^L = x0 + sum zeta_i w_i over finished directions
+    let mut x: Vec<f64> = x0.to_vec();
+    let mut r = vec![0.0; n];
+    a.residual(b, &x, &mut r);
+
+    let beta1 = norm(&r);
+    if beta1 < threshold {
+        return SolveResult::new(x, 0, beta1);
+    }
+
+    // Lanczos vectors
+    let mut v: Vec<f64> = r.iter().map(|ri| ri / beta1).collect();
+    let mut v_prev = vec![0.0; n];
+    let mut u = vec![0.0; n];
+    let mut beta = beta1;
+
+    // LQ factorization of the Lanczos tridiagonal by Givens rotations;
+    // row k of L holds (eps_k, delta_k, gamma_k)
+    let (mut c_prev, mut s_prev) = (-1.0f64, 0.0f64);
+    let mut dbar = 0.0;
+    let mut eps = 0.0;
+    let (mut zeta_2, mut zeta_1) = (0.0, 0.0);
+    let mut wbar = v.clone();
+    let mut w = vec![0.0; n];
+
+    // CG point x^C = x^L + zeta_bar * wbar, when T_k is nonsingular
+    let mut cg_point: Option<(f64, f64)> = None;
+
+    let mut iter = 0u32;
+    for k in 0..max_iter {
+        iter = k + 1;
+
+        // u = A v - beta v_prev - alpha v
+        a.spmv(&v, &mut u);
+        if k > 0 {
+            axpy(-beta, &v_prev, &mut u);
+        }
+        let alpha = dot(&v, &u);
+        axpy(-alpha, &v, &mut u);
+        let beta_next = norm(&u);
+
+        // Apply the previous rotation to the new column of T
+        let delta = c_prev * dbar + s_prev * alpha;
+        let gbar = s_prev * dbar - c_prev * alpha;
+        let eps_next = s_prev * beta_next;
+        let dbar_next = -c_prev * beta_next;
+
+        // Forward substitution in L z = beta1 e1
+        let rhs = if k == 0 { beta1 } else { 0.0 } - eps * zeta_2 - delta * zeta_1;
+
+        // CG residual is beta_{k+1} times the v_k component of x^C
+        cg_point = None;
+        if gbar.abs() > 1e-300 {
+            let zeta_bar = rhs / gbar;
+            let rc = (beta_next * (s_prev * zeta_1 - c_prev * zeta_bar)).abs();
+            cg_point = Some((zeta_bar, rc));
+            if rc < threshold {
+                break;
+            }
+        }
+        if beta_next < 1e-14 * beta1 {
+            // Invariant subspace: nothing left to add
+            break;
+        }
+
+        // Rotation eliminating beta_{k+1} from row k
+        let gamma = gbar.hypot(beta_next);
+        let (c, s) = (gbar / gamma, beta_next / gamma);
+        let zeta = rhs / gamma;
+
+        // v_next = u / beta_{k+1}; w_k = c wbar + s v_next; wbar = s wbar - c v_next
+        for j in 0..n {
+            let v_next = u[j] / beta_next;
+            w[j] = c * wbar[j] + s * v_next;
+            wbar[j] = s * wbar[j] - c * v_next;
+            x[j] += zeta * w[j];
+            v_prev[j] = v[j];
+            v[j] = v_next;
+        }
+
+        zeta_2 = zeta_1;
+        zeta_1 = zeta;
+        eps = eps_next;
+        dbar = dbar_next;
+        c_prev = c;
+        s_prev = s;
+        beta = beta_next;
+    }
+
+    // Report whichever of the SYMMLQ and CG points has the smaller residual
+    a.residual(b, &x, &mut r);
+    let mut rnorm = norm(&r);
+    if let Some((zeta_bar, rc)) = cg_point {
+        if rc < rnorm {
+            axpy(zeta_bar, &wbar, &mut x);
+            a.residual(b, &x, &mut r);
+            rnorm = norm(&r);
+        }
+    }
+
+    SolveResult::new(x, iter, rnorm)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::dense_to_csr;
+
+    #[test]
+    fn test_indefinite_system() {
+        let dense = vec![2.0, 1.0, 0.0, 1.0, -1.0, 3.0, 0.0, 3.0, 1.0];
+        let (values, col_indices, row_ptr) = dense_to_csr(&dense, 3);
+        let x_true = [1.0, 2.0, -1.0];
+        let b: Vec<f64> = (0..3)
+            .map(|i| (0..3).map(|j| dense[i * 3 + j] * x_true[j]).sum())
+            .collect();
+
+        let result = solve_symmlq(&values, &col_indices, &row_ptr, &b, &[0.0; 3], 1e-12, 50);
+
+        for (xi, ti) in result.solution.iter().zip(&x_true) {
+            assert!((xi - ti).abs() < 1e-8);
+        }
+    }
+
+    #[test]
+    fn test_singular_consistent_minimum_norm() {
+        // Free-free spring chain: kernel is the constant vector
+        let n = 15;
+        let mut dense = vec![0.0; n * n];
+        for e in 0..n - 1 {
+            dense[e * n + e] += 1.0;
+            dense[(e + 1) * n + e + 1] += 1.0;
+            dense[e * n + e + 1] -= 1.0;
+            dense[(e + 1) * n + e] -= 1.0;
+        }
+        let (values, col_indices, row_ptr) = dense_to_csr(&dense, n);
+        let a = Csr::new(&values, &col_indices, &row_ptr);
+
+        // Self-equilibrated load, so the system is consistent
+        let mut b = vec![0.0; n];
+        b[0] = -1.0;
+        b[n - 1] = 1.0;
+
+        let result = symmlq(&a, &b, &vec![0.0; n], 1e-12, 100);
+
+        let mut r = vec![0.0; n];
+        a.residual(&b, &result.solution, &mut r);
+        assert!(norm(&r) < 1e-9);
+        // Minimum-norm solution has no rigid body component
+        let mean: f64 = result.solution.iter().sum::<f64>() / n as f64;
+        assert!(mean.abs() < 1e-9);
+    }
+}
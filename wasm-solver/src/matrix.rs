@@ -0,0 +1,1178 @@
+//! Matrices kept in wasm memory between solves
+
+use wasm_bindgen::prelude::*;
+
+use crate::analysis::{estimate_condition, ConditionEstimate};
+use crate::bsr::Bsr;
+use crate::kernels::{axpy, Csr, LinearOperator, SparseMatrix, SymmetricCsr};
+use crate::krylov::{
+    auto, fallback, pcg_preconditioned, run_preconditioned, run_solver, DEFAULT_CHAIN,
+};
+use crate::options::{PreconditionerKind, SolverKind, SolverOptions};
+use crate::precond::{
+    setup, setup_symmetric, timed, BlockJacobi, MemoryReport, Preconditioner, PreconditionerHandle,
+};
+use crate::SolveResult;
+
+/// Largest number of rows `CsrMatrix::to_dense` exports (32 MB of f64)
+const MAX_DENSE_EXPORT: usize = 2048;
+
+/// Entries of a matrix as coordinate triplets, from `CsrMatrix::to_triplets`
+#[wasm_bindgen]
+pub struct Triplets {
+    rows: Vec<u32>,
+    cols: Vec<u32>,
+    values: Vec<f64>,
+}
+
+#[wasm_bindgen]
+impl Triplets {
+    #[wasm_bindgen(getter)]
+    pub fn rows(&self) -> Vec<u32> {
+        self.rows.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn cols(&self) -> Vec<u32> {
+        self.cols.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn values(&self) -> Vec<f64> {
+        self.values.clone()
+    }
+}
+
+/// CSR matrix owned by the wasm module
+///
+/// The slice-taking entry points copy values, column indices and row
+/// pointers across the JS/wasm boundary on every call. A `CsrMatrix` is
+/// copied in once; its methods run the same solvers on it, and in a SIMP
+/// loop `update_values` replaces only the values.
+#[wasm_bindgen]
+pub struct CsrMatrix {
+    matrix: SparseMatrix,
+}
+
+#[wasm_bindgen]
+impl CsrMatrix {
+    /// Copy a square CSR matrix (both triangles of symmetric matrices)
+    #[wasm_bindgen(constructor)]
+    pub fn new(values: &[f64], col_indices: &[u32], row_ptr: &[u32]) -> CsrMatrix {
+        CsrMatrix {
+            matrix: SparseMatrix::from_csr(&Csr::new(values, col_indices, row_ptr)),
+        }
+    }
+
+    /// Replace the values, keeping the sparsity pattern. Returns `false`,
+    /// changing nothing, if `values` does not have one entry per stored
+    /// entry.
+    pub fn update_values(&mut self, values: &[f64]) -> bool {
+        if values.len() != self.matrix.values.len() {
+            return false;
+        }
+        self.matrix.values.copy_from_slice(values);
+        true
+    }
+
+    /// Sort every row by column and sum duplicate entries, dropping
+    /// explicit zeros too with `drop_zeros`, as assembly code in
+    /// JavaScript may leave them. Returns the number of entries removed;
+    /// scatter maps made before no longer apply.
+    pub fn canonicalize(&mut self, drop_zeros: bool) -> usize {
+        let before = self.nnz();
+        self.matrix = SparseMatrix::canonical(&self.csr(), drop_zeros);
+        before - self.nnz()
+    }
+
+    /// A = alpha A
+    pub fn scale(&mut self, alpha: f64) {
+        self.matrix.values.iter_mut().for_each(|v| *v *= alpha);
+    }
+
+    /// A = A + alpha X, e.g. K = K_base + sum_i alpha_i K_i one material
+    /// at a time. When X has the same sparsity pattern (`same_pattern`)
+    /// only the values are updated, in place; otherwise the pattern grows
+    /// to the union of both. Returns `false`, changing nothing, if the
+    /// sizes differ.
+    pub fn axpy(&mut self, alpha: f64, x: &CsrMatrix) -> bool {
+        if x.size() != self.size() {
+            return false;
+        }
+        if self.same_pattern(x) {
+            axpy(alpha, &x.matrix.values, &mut self.matrix.values);
+        } else {
+            let mut scaled = SparseMatrix::from_csr(&x.csr());
+            scaled.values.iter_mut().for_each(|v| *v *= alpha);
+            self.matrix = SparseMatrix::add(&self.matrix, &scaled);
+        }
+        true
+    }
+
+    /// Whether `other` stores exactly the same positions in the same order
+    pub fn same_pattern(&self, other: &CsrMatrix) -> bool {
+        self.matrix.row_ptr == other.matrix.row_ptr
+            && self.matrix.col_indices == other.matrix.col_indices
+    }
+
+    /// Positions in this matrix of the entries of every element matrix,
+    /// for `assemble` and `assemble_scaled`
+    ///
+    /// `element_dofs` lists the global DOFs of each element one element
+    /// after another, `dofs_per_element` each (8 for Q4 elasticity). Returns
+    /// `undefined` if a DOF is out of range or a pair of DOFs sharing an
+    /// element has no stored entry.
+    pub fn scatter_map(
+        &self,
+        element_dofs: &[u32],
+        dofs_per_element: usize,
+    ) -> Option<ElementScatter> {
+        let a = self.csr();
+        if dofs_per_element == 0 || !element_dofs.len().is_multiple_of(dofs_per_element) {
+            return None;
+        }
+        let mut positions = Vec::with_capacity(element_dofs.len() * dofs_per_element);
+        for dofs in element_dofs.chunks_exact(dofs_per_element) {
+            if dofs.iter().any(|&i| i as usize >= a.n()) {
+                return None;
+            }
+            for &i in dofs.iter() {
+                let row = a.row_ptr[i as usize]..a.row_ptr[i as usize + 1];
+                for &j in dofs.iter() {
+                    let q = row.clone().find(|&q| a.col_indices[q as usize] == j)?;
+                    positions.push(q);
+                }
+            }
+        }
+        Some(ElementScatter {
+            nnz: self.nnz(),
+            dofs_per_element,
+            positions,
+        })
+    }
+
+    /// Replace the values by the sum of the element matrices
+    /// `contributions` (row-major, one after another, in the order of the
+    /// map's elements); entries no element touches become 0. Returns
+    /// `false`, changing nothing, if `map` was not made for this pattern
+    /// or the length does not match.
+    pub fn assemble(&mut self, map: &ElementScatter, contributions: &[f64]) -> bool {
+        if map.nnz != self.nnz() || contributions.len() != map.positions.len() {
+            return false;
+        }
+        self.matrix.values.iter_mut().for_each(|v| *v = 0.0);
+        for (&q, &v) in map.positions.iter().zip(contributions) {
+            self.matrix.values[q as usize] += v;
+        }
+        true
+    }
+
+    /// `assemble` with element e contributing `scales[e]` times the
+    /// reference matrix `ke`, e.g. the SIMP stiffness
+    /// sum_e (E_min + rho_e^p (E_0 - E_min)) K_e
+    pub fn assemble_scaled(&mut self, map: &ElementScatter, ke: &[f64], scales: &[f64]) -> bool {
+        let block = map.dofs_per_element * map.dofs_per_element;
+        if map.nnz != self.nnz() || ke.len() != block || scales.len() * block != map.positions.len()
+        {
+            return false;
+        }
+        self.matrix.values.iter_mut().for_each(|v| *v = 0.0);
+        for (element, &s) in map.positions.chunks_exact(block).zip(scales) {
+            for (&q, &k) in element.iter().zip(ke) {
+                self.matrix.values[q as usize] += s * k;
+            }
+        }
+        true
+    }
+
+    /// Number of rows
+    #[wasm_bindgen(getter)]
+    pub fn size(&self) -> usize {
+        self.matrix.row_ptr.len() - 1
+    }
+
+    /// Number of stored entries
+    #[wasm_bindgen(getter)]
+    pub fn nnz(&self) -> usize {
+        self.matrix.values.len()
+    }
+
+    /// Memory of the values and index arrays; work is the vectors of one
+    /// `solve_pcg` (the preconditioner set up per solve not included)
+    pub fn memory_report(&self) -> MemoryReport {
+        MemoryReport::matrix(&self.matrix).with_pcg_work(self.size())
+    }
+
+    /// Copy of the values, for the slice-taking entry points
+    #[wasm_bindgen(getter)]
+    pub fn values(&self) -> Vec<f64> {
+        self.matrix.values.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn col_indices(&self) -> Vec<u32> {
+        self.matrix.col_indices.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn row_ptr(&self) -> Vec<u32> {
+        self.matrix.row_ptr.clone()
+    }
+
+    /// The stored entries as (row, column, value) triplets in storage
+    /// order, e.g. for `scipy.sparse.coo_matrix`
+    pub fn to_triplets(&self) -> Triplets {
+        let a = self.csr();
+        Triplets {
+            rows: (0..a.n())
+                .flat_map(|i| (a.row_ptr[i]..a.row_ptr[i + 1]).map(move |_| i as u32))
+                .collect(),
+            cols: a.col_indices.to_vec(),
+            values: a.values.to_vec(),
+        }
+    }
+
+    /// Row-major dense copy, duplicates summed; `undefined` above
+    /// `MAX_DENSE_EXPORT` rows
+    pub fn to_dense(&self) -> Option<Vec<f64>> {
+        let a = self.csr();
+        let n = a.n();
+        if n > MAX_DENSE_EXPORT {
+            return None;
+        }
+        let mut dense = vec![0.0; n * n];
+        for i in 0..n {
+            for q in a.row_ptr[i] as usize..a.row_ptr[i + 1] as usize {
+                dense[i * n + a.col_indices[q] as usize] += a.values[q];
+            }
+        }
+        Some(dense)
+    }
+
+    /// y = A x
+    pub fn spmv(&self, x: &[f64]) -> Vec<f64> {
+        let mut y = vec![0.0; self.size()];
+        self.csr().spmv(x, &mut y);
+        y
+    }
+
+    /// y = A^T x, without forming the transpose
+    pub fn spmv_transpose(&self, x: &[f64]) -> Vec<f64> {
+        let mut y = vec![0.0; self.size()];
+        self.csr().spmv_transpose(x, &mut y);
+        y
+    }
+
+    /// The same matrix in CSC storage
+    pub fn to_csc(&self) -> CscMatrix {
+        CscMatrix {
+            transpose: self.matrix.transpose(),
+        }
+    }
+
+    /// The upper triangle of this (symmetric) matrix in half storage; the
+    /// lower triangle is dropped unread
+    pub fn to_symmetric(&self) -> SymmetricMatrix {
+        let a = self.csr();
+        let mut entries = Vec::with_capacity((a.values.len() + a.n()) / 2);
+        for i in 0..a.n() {
+            for q in a.row_ptr[i] as usize..a.row_ptr[i + 1] as usize {
+                if a.col_indices[q] as usize >= i {
+                    entries.push((i, a.col_indices[q], a.values[q]));
+                }
+            }
+        }
+        SymmetricMatrix {
+            upper: SparseMatrix::from_triplets(a.n(), a.n(), entries),
+        }
+    }
+
+    /// The same matrix in BSR storage with `block_size` x `block_size`
+    /// blocks, e.g. 2 or 3 for nodal elasticity DOFs; `undefined` if the
+    /// size is not a multiple of `block_size`
+    pub fn to_bsr(&self, block_size: usize) -> Option<BsrMatrix> {
+        Bsr::from_csr(&self.csr(), block_size).map(|bsr| BsrMatrix { bsr })
+    }
+
+    /// `solve_pcg_with_options` on this matrix
+    pub fn solve_pcg(&self, b: &[f64], x0: &[f64], options: &SolverOptions) -> SolveResult {
+        run_solver(SolverKind::Pcg, &self.csr(), b, x0, options)
+    }
+
+    /// `solve_with_fallback` on this matrix
+    pub fn solve_with_fallback(
+        &self,
+        b: &[f64],
+        x0: &[f64],
+        chain: &[u32],
+        options: &SolverOptions,
+    ) -> SolveResult {
+        let chain: Vec<SolverKind> = if chain.is_empty() {
+            DEFAULT_CHAIN.to_vec()
+        } else {
+            chain
+                .iter()
+                .filter_map(|&c| SolverKind::from_code(c))
+                .collect()
+        };
+        fallback(&self.csr(), b, x0, &chain, options)
+    }
+
+    /// `solve_auto` on this matrix
+    pub fn solve_auto(&self, b: &[f64], x0: &[f64], options: &SolverOptions) -> SolveResult {
+        auto(&self.csr(), b, x0, options)
+    }
+
+    /// `solve_with_preconditioner` on this matrix
+    pub fn solve_with_preconditioner(
+        &self,
+        b: &[f64],
+        x0: &[f64],
+        kind: SolverKind,
+        preconditioner: &PreconditionerHandle,
+        options: &SolverOptions,
+    ) -> SolveResult {
+        if preconditioner.size() != self.size() {
+            return SolveResult::new(x0.to_vec(), 0, f64::INFINITY);
+        }
+        let m = preconditioner.preconditioner();
+        run_preconditioned(kind, &self.csr(), m, b, x0, options)
+    }
+
+    /// `estimate_condition` on this matrix
+    pub fn estimate_condition(
+        &self,
+        steps: u32,
+        preconditioned: bool,
+        options: &SolverOptions,
+    ) -> ConditionEstimate {
+        let a = self.csr();
+        estimate_condition(
+            a.values,
+            a.col_indices,
+            a.row_ptr,
+            steps,
+            preconditioned,
+            options,
+        )
+    }
+
+    /// Set up `options.preconditioner` for the current values
+    pub fn preconditioner(&self, options: &SolverOptions) -> PreconditionerHandle {
+        let a = self.csr();
+        PreconditionerHandle::new(a.values, a.col_indices, a.row_ptr, options)
+    }
+}
+
+impl CsrMatrix {
+    pub(crate) fn from_matrix(matrix: SparseMatrix) -> Self {
+        CsrMatrix { matrix }
+    }
+
+    pub(crate) fn csr(&self) -> Csr<'_> {
+        self.matrix.csr()
+    }
+}
+
+/// Where each entry of each element matrix lands in a `CsrMatrix`, made by
+/// `CsrMatrix::scatter_map`
+///
+/// Computed once per mesh; every design iteration then pushes only values
+/// and the index arrays of the matrix are never rebuilt.
+#[wasm_bindgen]
+pub struct ElementScatter {
+    /// Stored entries of the matrix the map was made for
+    nnz: usize,
+    dofs_per_element: usize,
+    /// Entry q of the values for local entry (l, m) of element e, at
+    /// (e * dofs_per_element + l) * dofs_per_element + m
+    positions: Vec<u32>,
+}
+
+#[wasm_bindgen]
+impl ElementScatter {
+    /// Number of elements
+    #[wasm_bindgen(getter)]
+    pub fn elements(&self) -> usize {
+        self.positions.len() / (self.dofs_per_element * self.dofs_per_element)
+    }
+}
+
+/// CSC (Compressed Sparse Column) matrix owned by the wasm module
+///
+/// Column j holds its row indices `row_indices[col_ptr[j]..col_ptr[j + 1]]`
+/// and the matching values; these are exactly the CSR arrays of A^T, which
+/// is how it is stored. Column-oriented codes can pass their arrays as
+/// they are, and A^T x (adjoint solves, normal equations) runs at the
+/// speed of a CSR product.
+#[wasm_bindgen]
+pub struct CscMatrix {
+    transpose: SparseMatrix,
+}
+
+#[wasm_bindgen]
+impl CscMatrix {
+    /// Copy a square CSC matrix
+    #[wasm_bindgen(constructor)]
+    pub fn new(values: &[f64], row_indices: &[u32], col_ptr: &[u32]) -> CscMatrix {
+        CscMatrix {
+            transpose: SparseMatrix::from_csr(&Csr::new(values, row_indices, col_ptr)),
+        }
+    }
+
+    /// Number of columns
+    #[wasm_bindgen(getter)]
+    pub fn size(&self) -> usize {
+        self.transpose.row_ptr.len() - 1
+    }
+
+    /// Number of stored entries
+    #[wasm_bindgen(getter)]
+    pub fn nnz(&self) -> usize {
+        self.transpose.values.len()
+    }
+
+    /// Memory of the values and index arrays
+    pub fn memory_report(&self) -> MemoryReport {
+        MemoryReport::matrix(&self.transpose)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn values(&self) -> Vec<f64> {
+        self.transpose.values.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn row_indices(&self) -> Vec<u32> {
+        self.transpose.col_indices.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn col_ptr(&self) -> Vec<u32> {
+        self.transpose.row_ptr.clone()
+    }
+
+    /// y = A x
+    pub fn spmv(&self, x: &[f64]) -> Vec<f64> {
+        let mut y = vec![0.0; self.size()];
+        self.transpose.csr().spmv_transpose(x, &mut y);
+        y
+    }
+
+    /// y = A^T x
+    pub fn spmv_transpose(&self, x: &[f64]) -> Vec<f64> {
+        let mut y = vec![0.0; self.size()];
+        self.transpose.csr().spmv(x, &mut y);
+        y
+    }
+
+    /// The same matrix in CSR storage, rows sorted by column, which the
+    /// solvers take
+    pub fn to_csr(&self) -> CsrMatrix {
+        CsrMatrix {
+            matrix: self.transpose.transpose(),
+        }
+    }
+}
+
+/// Symmetric matrix owned by the wasm module as its upper triangle
+///
+/// About half the memory of a `CsrMatrix`; `spmv` applies each stored
+/// off-diagonal entry to both of its positions in one pass, reading the
+/// matrix once. Solves work as in `solve_pcg_symmetric`.
+#[wasm_bindgen]
+pub struct SymmetricMatrix {
+    upper: SparseMatrix,
+}
+
+#[wasm_bindgen]
+impl SymmetricMatrix {
+    /// Copy the upper triangle (columns >= row, diagonal included) given
+    /// in CSR
+    #[wasm_bindgen(constructor)]
+    pub fn new(values: &[f64], col_indices: &[u32], row_ptr: &[u32]) -> SymmetricMatrix {
+        SymmetricMatrix {
+            upper: SparseMatrix::from_csr(&Csr::new(values, col_indices, row_ptr)),
+        }
+    }
+
+    /// Replace the values of the stored triangle, keeping its pattern.
+    /// Returns `false`, changing nothing, on a length mismatch.
+    pub fn update_values(&mut self, values: &[f64]) -> bool {
+        if values.len() != self.upper.values.len() {
+            return false;
+        }
+        self.upper.values.copy_from_slice(values);
+        true
+    }
+
+    /// Number of rows
+    #[wasm_bindgen(getter)]
+    pub fn size(&self) -> usize {
+        self.upper.row_ptr.len() - 1
+    }
+
+    /// Number of stored entries (upper triangle)
+    #[wasm_bindgen(getter)]
+    pub fn nnz(&self) -> usize {
+        self.upper.values.len()
+    }
+
+    /// Memory of the upper triangle; work is the vectors of one
+    /// `solve_pcg`
+    pub fn memory_report(&self) -> MemoryReport {
+        MemoryReport::matrix(&self.upper).with_pcg_work(self.size())
+    }
+
+    /// y = A x
+    pub fn spmv(&self, x: &[f64]) -> Vec<f64> {
+        let mut y = vec![0.0; self.size()];
+        self.symmetric().spmv(x, &mut y);
+        y
+    }
+
+    /// `solve_pcg_symmetric` on this matrix
+    pub fn solve_pcg(&self, b: &[f64], x0: &[f64], options: &SolverOptions) -> SolveResult {
+        let a = self.symmetric();
+        let (m, report) = timed(|| setup_symmetric(options, &a));
+        pcg_preconditioned(&a, m.as_ref(), b, x0, options).with_setup(report)
+    }
+
+    /// Both triangles in CSR storage
+    pub fn to_csr(&self) -> CsrMatrix {
+        CsrMatrix {
+            matrix: self.symmetric().full(),
+        }
+    }
+}
+
+impl SymmetricMatrix {
+    fn symmetric(&self) -> SymmetricCsr<'_> {
+        SymmetricCsr::new(self.upper.csr())
+    }
+}
+
+/// BSR (block CSR) matrix owned by the wasm module, made by
+/// `CsrMatrix::to_bsr`
+///
+/// One column index per dense block instead of per entry; `spmv` and the
+/// solves run the block product. Jacobi and block-Jacobi preconditioning
+/// invert the diagonal blocks of the storage (whatever
+/// `SolverOptions::block_size` says); other preconditioners are set up on
+/// the matrix expanded back to CSR.
+#[wasm_bindgen]
+pub struct BsrMatrix {
+    bsr: Bsr,
+}
+
+#[wasm_bindgen]
+impl BsrMatrix {
+    /// Number of rows
+    #[wasm_bindgen(getter)]
+    pub fn size(&self) -> usize {
+        self.bsr.n()
+    }
+
+    /// Rows and columns per block
+    #[wasm_bindgen(getter)]
+    pub fn block_size(&self) -> usize {
+        self.bsr.block_size
+    }
+
+    /// Memory of the blocks and block index arrays; work is the vectors
+    /// of one `solve_pcg`
+    pub fn memory_report(&self) -> MemoryReport {
+        MemoryReport::default()
+            .with_values(std::mem::size_of_val(self.bsr.blocks.as_slice()))
+            .with_indices(
+                std::mem::size_of_val(self.bsr.block_ptr.as_slice())
+                    + std::mem::size_of_val(self.bsr.block_cols.as_slice()),
+            )
+            .with_pcg_work(self.size())
+    }
+
+    /// Number of stored blocks
+    #[wasm_bindgen(getter)]
+    pub fn num_blocks(&self) -> usize {
+        self.bsr.num_blocks()
+    }
+
+    /// y = A x
+    pub fn spmv(&self, x: &[f64]) -> Vec<f64> {
+        let mut y = vec![0.0; self.size()];
+        self.bsr.spmv(x, &mut y);
+        y
+    }
+
+    /// PCG with the tolerance, iteration limit, stopping criterion and
+    /// preconditioner of `options` (classic kernel only)
+    pub fn solve_pcg(&self, b: &[f64], x0: &[f64], options: &SolverOptions) -> SolveResult {
+        let (m, report) = timed(|| -> Box<dyn Preconditioner> {
+            match options.preconditioner {
+                PreconditionerKind::Jacobi | PreconditionerKind::BlockJacobi => {
+                    Box::new(BlockJacobi::from_bsr(&self.bsr))
+                }
+                _ => setup(options, &self.bsr.to_csr().csr()),
+            }
+        });
+        pcg_preconditioned(&self.bsr, m.as_ref(), b, x0, options).with_setup(report)
+    }
+
+    /// The same matrix in CSR storage, zeros inside blocks included
+    pub fn to_csr(&self) -> CsrMatrix {
+        CsrMatrix {
+            matrix: self.bsr.to_csr(),
+        }
+    }
+}
+
+/// Removal of fixed DOFs (homogeneous Dirichlet constraints) from a system
+///
+/// Built once from the mask of fixed DOFs, it extracts the matrix and
+/// right-hand side of the free DOFs, K_ff u_f = f_f, and scatters the
+/// solution back to full size with zeros at the fixed DOFs; each is a
+/// single pass over the arrays.
+#[wasm_bindgen]
+pub struct DofReduction {
+    n: usize,
+    /// Free DOFs in increasing order
+    free: Vec<usize>,
+}
+
+#[wasm_bindgen]
+impl DofReduction {
+    /// Reduction for `fixed.len()` DOFs, DOF i fixed when `fixed[i] != 0`
+    #[wasm_bindgen(constructor)]
+    pub fn new(fixed: &[u8]) -> DofReduction {
+        DofReduction {
+            n: fixed.len(),
+            free: (0..fixed.len()).filter(|&i| fixed[i] == 0).collect(),
+        }
+    }
+
+    /// Number of DOFs before reduction
+    #[wasm_bindgen(getter)]
+    pub fn size(&self) -> usize {
+        self.n
+    }
+
+    /// Number of free DOFs
+    #[wasm_bindgen(getter)]
+    pub fn free_size(&self) -> usize {
+        self.free.len()
+    }
+
+    /// Free DOFs in increasing order: DOF `free_dofs[k]` is unknown k of
+    /// the reduced system
+    #[wasm_bindgen(getter)]
+    pub fn free_dofs(&self) -> Vec<u32> {
+        self.free.iter().map(|&i| i as u32).collect()
+    }
+
+    /// K_ff from the CSR arrays of K; `undefined` if K does not have
+    /// `size` rows
+    pub fn reduce_matrix(
+        &self,
+        values: &[f64],
+        col_indices: &[u32],
+        row_ptr: &[u32],
+    ) -> Option<CsrMatrix> {
+        let a = Csr::new(values, col_indices, row_ptr);
+        (a.n() == self.n).then(|| CsrMatrix {
+            matrix: SparseMatrix::submatrix(&a, &self.free),
+        })
+    }
+
+    /// `reduce_matrix` of a matrix already in wasm memory
+    pub fn reduce(&self, matrix: &CsrMatrix) -> Option<CsrMatrix> {
+        let a = matrix.csr();
+        self.reduce_matrix(a.values, a.col_indices, a.row_ptr)
+    }
+
+    /// f_f; `undefined` if `b` does not have `size` entries
+    pub fn reduce_vector(&self, b: &[f64]) -> Option<Vec<f64>> {
+        (b.len() == self.n).then(|| self.free.iter().map(|&i| b[i]).collect())
+    }
+
+    /// Full-size vector with `x_free` at the free DOFs and zeros at the
+    /// fixed ones; `undefined` if `x_free` does not have `free_size`
+    /// entries
+    pub fn expand(&self, x_free: &[f64]) -> Option<Vec<f64>> {
+        if x_free.len() != self.free.len() {
+            return None;
+        }
+        let mut x = vec![0.0; self.n];
+        for (&i, &v) in self.free.iter().zip(x_free) {
+            x[i] = v;
+        }
+        Some(x)
+    }
+}
+
+/// Pending triplets beyond which `TripletBuilder` merges duplicates
+const COMPACT_THRESHOLD: usize = 1 << 16;
+
+/// Assembles a `CsrMatrix` from (row, column, value) triplets
+///
+/// Element loops can stream their entries in chunks of any size with
+/// `add`; entries at the same position are summed and each row ends up
+/// sorted by column. Duplicates are merged whenever the pending triplets
+/// double, so memory stays close to that of the assembled matrix even
+/// though every node of a mesh is shared by several elements.
+#[wasm_bindgen]
+pub struct TripletBuilder {
+    n: usize,
+    entries: Vec<(usize, u32, f64)>,
+    /// Leading entries of `entries` already sorted and merged
+    compacted: usize,
+}
+
+#[wasm_bindgen]
+impl TripletBuilder {
+    /// Builder for an n x n matrix
+    #[wasm_bindgen(constructor)]
+    pub fn new(n: usize) -> TripletBuilder {
+        TripletBuilder {
+            n,
+            entries: Vec::new(),
+            compacted: 0,
+        }
+    }
+
+    /// Append a chunk of triplets. Returns `false`, adding nothing, if the
+    /// three arrays differ in length or an index is out of range.
+    pub fn add(&mut self, rows: &[u32], cols: &[u32], values: &[f64]) -> bool {
+        let n = self.n as u32;
+        if rows.len() != cols.len()
+            || rows.len() != values.len()
+            || rows.iter().chain(cols).any(|&i| i >= n)
+        {
+            return false;
+        }
+        self.entries.extend(
+            rows.iter()
+                .zip(cols)
+                .zip(values)
+                .map(|((&i, &j), &v)| (i as usize, j, v)),
+        );
+        if self.entries.len() > COMPACT_THRESHOLD.max(2 * self.compacted) {
+            self.compact();
+        }
+        true
+    }
+
+    /// Triplets held, duplicates merged so far counted once
+    #[wasm_bindgen(getter)]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether no triplet has been added
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The assembled matrix; the builder keeps its triplets and can go on
+    /// accepting more
+    pub fn build(&mut self) -> CsrMatrix {
+        self.compact();
+        CsrMatrix {
+            matrix: SparseMatrix::from_triplets(self.n, self.n, self.entries.clone()),
+        }
+    }
+}
+
+impl TripletBuilder {
+    /// Sort the triplets and sum those at the same position
+    fn compact(&mut self) {
+        self.entries.sort_by_key(|&(i, j, _)| (i, j));
+        let mut merged: Vec<(usize, u32, f64)> = Vec::with_capacity(self.entries.len());
+        for &(i, j, v) in self.entries.iter() {
+            match merged.last_mut() {
+                Some(last) if (last.0, last.1) == (i, j) => last.2 += v,
+                _ => merged.push((i, j, v)),
+            }
+        }
+        self.compacted = merged.len();
+        self.entries = merged;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::ElementGrid;
+    use crate::krylov::solve_pcg_with_options;
+    use crate::options::PreconditionerKind;
+    use crate::test_util::{
+        assemble_grid, convection_diffusion_1d, coupled_2x2, dense_to_csr, diffusion_2d,
+        laplacian_1d, q4_stiffness,
+    };
+
+    #[test]
+    fn test_same_result_as_slices() {
+        let (values, col_indices, row_ptr) = diffusion_2d(16, 12, &[1.0; 192]);
+        let (b, x0) = (vec![1.0; 192], vec![0.0; 192]);
+        let options = SolverOptions::new();
+        let matrix = CsrMatrix::new(&values, &col_indices, &row_ptr);
+        assert_eq!((matrix.size(), matrix.nnz()), (192, values.len()));
+
+        let handle = matrix.solve_pcg(&b, &x0, &options);
+        let slices = solve_pcg_with_options(&values, &col_indices, &row_ptr, &b, &x0, &options);
+        assert_eq!(handle.solution, slices.solution);
+        assert_eq!(handle.iterations, slices.iterations);
+        let auto = matrix.solve_auto(&b, &x0, &options);
+        assert!(auto.criterion.is_some());
+    }
+
+    #[test]
+    fn test_update_values_in_a_loop() {
+        let (nx, ny) = (20, 20);
+        let (values, col_indices, row_ptr) = diffusion_2d(nx, ny, &vec![1.0; nx * ny]);
+        let mut matrix = CsrMatrix::new(&values, &col_indices, &row_ptr);
+        let (b, x0) = (vec![1.0; nx * ny], vec![0.0; nx * ny]);
+        let mut options = SolverOptions::new();
+        options.preconditioner = PreconditionerKind::Ic0;
+        let mut m = matrix.preconditioner(&options);
+
+        for step in 1..4 {
+            let kappa: Vec<f64> = (0..nx * ny)
+                .map(|c| 1.0 + 0.1 * step as f64 * (c % 5) as f64)
+                .collect();
+            let (values, _, _) = diffusion_2d(nx, ny, &kappa);
+            assert!(matrix.update_values(&values));
+            let result = matrix.solve_with_preconditioner(&b, &x0, SolverKind::Pcg, &m, &options);
+            assert!(result.criterion.is_some());
+            // The solution is that of the new values
+            let ax = matrix.spmv(&result.solution);
+            let r: f64 = ax.iter().zip(&b).map(|(y, bi)| (y - bi).powi(2)).sum();
+            assert!(r.sqrt() < 1e-6 * (nx as f64));
+        }
+        assert!(m.refresh_from(&matrix));
+        assert!(!matrix.update_values(&values[1..]));
+    }
+
+    #[test]
+    fn test_triplets_from_element_loop() {
+        // Q4 grid streamed one element row at a time: 64 triplets per
+        // element, enough to trigger merging on the way
+        let (nelx, nely) = (40, 30);
+        let ke = q4_stiffness(0.3);
+        let scales = vec![1.0; nelx * nely];
+        let grid = ElementGrid::new([nelx, nely, 0], 2, &ke, &scales, &[]);
+        let mut builder = TripletBuilder::new(grid.n());
+        let mut dofs = Vec::new();
+        for ey in 0..nely {
+            let (mut rows, mut cols, mut values) = (Vec::new(), Vec::new(), Vec::new());
+            for ex in 0..nelx {
+                grid.element_dofs(ex * nely + ey, &mut dofs);
+                for (l, &i) in dofs.iter().enumerate() {
+                    for (m, &j) in dofs.iter().enumerate() {
+                        rows.push(i as u32);
+                        cols.push(j as u32);
+                        values.push(ke[l * 8 + m]);
+                    }
+                }
+            }
+            assert!(builder.add(&rows, &cols, &values));
+        }
+        assert!(builder.len() < 64 * nelx * nely);
+
+        let matrix = builder.build();
+        let (values, col_indices, row_ptr) = assemble_grid(&grid);
+        assert_eq!(matrix.row_ptr(), row_ptr);
+        assert_eq!(matrix.col_indices(), col_indices);
+        for (u, v) in matrix.values().iter().zip(&values) {
+            assert!((u - v).abs() < 1e-14);
+        }
+    }
+
+    #[test]
+    fn test_rejects_bad_chunks() {
+        let mut builder = TripletBuilder::new(2);
+        assert!(builder.add(&[1, 0, 1], &[1, 0, 1], &[1.0, 2.0, 3.0]));
+        assert!(!builder.add(&[2], &[0], &[1.0]));
+        assert!(!builder.add(&[0, 1], &[0], &[1.0]));
+        let matrix = builder.build();
+        assert_eq!(matrix.values(), vec![2.0, 4.0]);
+        assert_eq!(matrix.row_ptr(), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_csc_round_trip() {
+        // Upwind convection: A != A^T
+        let (values, col_indices, row_ptr) = convection_diffusion_1d(30, 5.0);
+        let csr = CsrMatrix::new(&values, &col_indices, &row_ptr);
+        let csc = csr.to_csc();
+        assert_eq!((csc.size(), csc.nnz()), (30, values.len()));
+        assert_eq!(csc.col_ptr()[30] as usize, values.len());
+
+        let x: Vec<f64> = (0..30).map(|i| (i as f64 * 0.7).cos()).collect();
+        assert_eq!(csc.spmv(&x), csr.spmv(&x));
+        assert_eq!(csc.spmv_transpose(&x), csr.spmv_transpose(&x));
+        assert_ne!(csr.spmv(&x), csr.spmv_transpose(&x));
+
+        let back = csc.to_csr();
+        assert_eq!(back.values(), values);
+        assert_eq!(back.col_indices(), col_indices);
+        assert_eq!(back.row_ptr(), row_ptr);
+        // CSC arrays of A are the CSR arrays of A^T
+        let transpose = CscMatrix::new(&values, &col_indices, &row_ptr).to_csr();
+        assert_eq!(transpose.spmv(&x), csr.spmv_transpose(&x));
+    }
+
+    #[test]
+    fn test_bsr_handle() {
+        let (nx, ny) = (24, 24);
+        let (values, col_indices, row_ptr) =
+            coupled_2x2(&diffusion_2d(nx, ny, &vec![1.0; nx * ny]));
+        let csr = CsrMatrix::new(&values, &col_indices, &row_ptr);
+        assert!(csr.to_bsr(5).is_none());
+        let bsr = csr.to_bsr(2).unwrap();
+        assert_eq!((bsr.size(), bsr.block_size()), (csr.size(), 2));
+        assert_eq!(4 * bsr.num_blocks(), csr.nnz());
+
+        let n = csr.size();
+        let b: Vec<f64> = (0..n).map(|i| (i % 2) as f64).collect();
+        let x0 = vec![0.0; n];
+        let mut options = SolverOptions::new();
+        options.preconditioner = PreconditionerKind::BlockJacobi;
+        let reference = csr.solve_pcg(&b, &x0, &options);
+        let result = bsr.solve_pcg(&b, &x0, &options);
+        assert!(result.criterion.is_some());
+        assert!(result.iterations.abs_diff(reference.iterations) <= 1);
+
+        options.preconditioner = PreconditionerKind::Ic0;
+        assert!(bsr.solve_pcg(&b, &x0, &options).criterion.is_some());
+        assert_eq!(bsr.to_csr().values(), values);
+    }
+
+    #[test]
+    fn test_linear_combination() {
+        let (nx, ny) = (8, 6);
+        let n = nx * ny;
+        let kappa: Vec<f64> = (0..n).map(|c| 1.0 + (c % 3) as f64).collect();
+        let parts = [
+            diffusion_2d(nx, ny, &vec![1.0; n]),
+            diffusion_2d(nx, ny, &kappa),
+        ];
+        let [base, other] = parts.map(|(v, c, r)| CsrMatrix::new(&v, &c, &r));
+        let x: Vec<f64> = (0..n).map(|i| (i as f64).sin()).collect();
+        let expected: Vec<f64> = base
+            .spmv(&x)
+            .iter()
+            .zip(other.spmv(&x))
+            .map(|(u, v)| 2.0 * u - 0.5 * v)
+            .collect();
+        let close = |y: Vec<f64>| y.iter().zip(&expected).all(|(u, v)| (u - v).abs() < 1e-12);
+
+        // Same pattern: values only
+        let mut k = CsrMatrix::new(&base.values(), &base.col_indices(), &base.row_ptr());
+        assert!(k.same_pattern(&other));
+        k.scale(2.0);
+        assert!(k.axpy(-0.5, &other));
+        assert!(k.same_pattern(&base) && close(k.spmv(&x)));
+
+        // Different pattern: the union, here the diagonal of `base` plus the
+        // full pattern of `other`
+        let diag: Vec<f64> = base.csr().diagonal().iter().map(|d| 2.0 * d).collect();
+        let rows: Vec<u32> = (0..=n as u32).collect();
+        let mut k = CsrMatrix::new(&diag, &rows[..n], &rows);
+        assert!(!k.same_pattern(&other));
+        assert!(k.axpy(-0.5, &other));
+        assert!(k.same_pattern(&other));
+        let off_diagonal: Vec<f64> = base
+            .spmv(&x)
+            .iter()
+            .zip(&x)
+            .zip(base.csr().diagonal())
+            .map(|((y, xi), d)| 2.0 * (y - d * xi))
+            .collect();
+        let y: Vec<f64> = k
+            .spmv(&x)
+            .iter()
+            .zip(&off_diagonal)
+            .map(|(u, v)| u + v)
+            .collect();
+        assert!(close(y));
+        assert!(!k.axpy(1.0, &CsrMatrix::new(&[1.0], &[0], &[0, 1])));
+    }
+
+    #[test]
+    fn test_reduce_fixed_dofs() {
+        // Cantilever: left edge nodes of a Q4 grid clamped
+        let (nelx, nely) = (12, 6);
+        let ke = q4_stiffness(0.3);
+        let scales = vec![1.0; nelx * nely];
+        let fixed: Vec<u32> = (0..2 * (nely as u32 + 1)).collect();
+        let grid = ElementGrid::new([nelx, nely, 0], 2, &ke, &scales, &[]);
+        let constrained = ElementGrid::new([nelx, nely, 0], 2, &ke, &scales, &fixed);
+        let (values, col_indices, row_ptr) = assemble_grid(&grid);
+        let n = grid.n();
+        let mut mask = vec![0u8; n];
+        fixed.iter().for_each(|&i| mask[i as usize] = 1);
+        let reduction = DofReduction::new(&mask);
+        assert_eq!(
+            (reduction.size(), reduction.free_size()),
+            (n, n - fixed.len())
+        );
+
+        let k = reduction
+            .reduce_matrix(&values, &col_indices, &row_ptr)
+            .unwrap();
+        let mut f = vec![0.0; n];
+        f[n - 1] = -1.0;
+        let f_free = reduction.reduce_vector(&f).unwrap();
+        let options = SolverOptions::new();
+        let reduced = k.solve_pcg(&f_free, &vec![0.0; f_free.len()], &options);
+        let u = reduction.expand(&reduced.solution).unwrap();
+
+        // Same as the full system with identity rows at the fixed DOFs
+        let (values, col_indices, row_ptr) = assemble_grid(&constrained);
+        let full = CsrMatrix::new(&values, &col_indices, &row_ptr);
+        let reference = full.solve_pcg(&f, &vec![0.0; n], &options);
+        assert!(fixed.iter().all(|&i| u[i as usize] == 0.0));
+        let scale = u.iter().fold(0.0f64, |m, v| m.max(v.abs()));
+        assert!(u
+            .iter()
+            .zip(&reference.solution)
+            .all(|(a, b)| (a - b).abs() < 1e-6 * scale));
+
+        assert!(reduction.reduce_vector(&f[1..]).is_none());
+        assert!(reduction.expand(&f).is_none());
+        assert!(reduction.reduce(&k).is_none());
+    }
+
+    #[test]
+    fn test_symmetric_handle() {
+        let (nelx, nely) = (16, 10);
+        let ke = q4_stiffness(0.3);
+        let scales: Vec<f64> = (0..nelx * nely).map(|e| 0.1 + (e % 4) as f64).collect();
+        let fixed: Vec<u32> = (0..2 * (nely as u32 + 1)).collect();
+        let grid = ElementGrid::new([nelx, nely, 0], 2, &ke, &scales, &fixed);
+        let (values, col_indices, row_ptr) = assemble_grid(&grid);
+        let full = CsrMatrix::new(&values, &col_indices, &row_ptr);
+        let mut half = full.to_symmetric();
+        let n = full.size();
+        assert_eq!(2 * half.nnz(), full.nnz() + n);
+
+        let x: Vec<f64> = (0..n).map(|i| (i as f64 * 0.4).cos()).collect();
+        let (y_full, y_half) = (full.spmv(&x), half.spmv(&x));
+        assert!(y_full
+            .iter()
+            .zip(&y_half)
+            .all(|(u, v)| (u - v).abs() < 1e-12));
+        assert_eq!(half.to_csr().values(), values);
+
+        let mut options = SolverOptions::new();
+        options.preconditioner = PreconditionerKind::Ic0;
+        let (b, x0) = (vec![1.0; n], vec![0.0; n]);
+        let result = half.solve_pcg(&b, &x0, &options);
+        assert!(result.criterion.is_some());
+        assert!(
+            result
+                .iterations
+                .abs_diff(full.solve_pcg(&b, &x0, &options).iterations)
+                <= 1
+        );
+
+        let doubled: Vec<f64> = half.upper.values.iter().map(|v| 2.0 * v).collect();
+        assert!(half.update_values(&doubled));
+        let y: Vec<f64> = half.spmv(&x);
+        assert!(y
+            .iter()
+            .zip(&y_half)
+            .all(|(u, v)| (u - 2.0 * v).abs() < 1e-12));
+        assert!(!half.update_values(&doubled[1..]));
+    }
+
+    #[test]
+    fn test_scatter_element_values() {
+        let (nelx, nely) = (10, 8);
+        let ke = q4_stiffness(0.3);
+        let mut scales: Vec<f64> = (0..nelx * nely).map(|e| 0.5 + (e % 3) as f64).collect();
+        let grid = ElementGrid::new([nelx, nely, 0], 2, &ke, &scales, &[]);
+        let (values, col_indices, row_ptr) = assemble_grid(&grid);
+        let mut element_dofs = Vec::new();
+        let mut dofs = Vec::new();
+        for e in 0..nelx * nely {
+            grid.element_dofs(e, &mut dofs);
+            element_dofs.extend(dofs.iter().map(|&i| i as u32));
+        }
+
+        let mut matrix = CsrMatrix::new(&values, &col_indices, &row_ptr);
+        let map = matrix.scatter_map(&element_dofs, 8).unwrap();
+        assert_eq!(map.elements(), nelx * nely);
+        scales.iter_mut().for_each(|s| *s *= 2.0);
+        assert!(matrix.assemble_scaled(&map, &ke, &scales));
+        assert!(matrix
+            .values()
+            .iter()
+            .zip(&values)
+            .all(|(u, v)| (u - 2.0 * v).abs() < 1e-12));
+
+        let contributions: Vec<f64> = (0..nelx * nely).flat_map(|_| ke.iter().copied()).collect();
+        assert!(matrix.assemble(&map, &contributions));
+        let (unit, _, _) =
+            assemble_grid(&ElementGrid::new([nelx, nely, 0], 2, &ke, &[1.0; 80], &[]));
+        assert!(matrix
+            .values()
+            .iter()
+            .zip(&unit)
+            .all(|(u, v)| (u - v).abs() < 1e-12));
+        assert!(!matrix.assemble(&map, &contributions[1..]));
+        assert!(!matrix.assemble_scaled(&map, &ke, &scales[1..]));
+
+        // A pair of DOFs with no stored entry
+        assert!(matrix
+            .scatter_map(&[0, 2 * (nely as u32 + 1) * 3], 2)
+            .is_none());
+        assert!(matrix.scatter_map(&[0, u32::MAX], 2).is_none());
+    }
+
+    #[test]
+    fn test_export_round_trip() {
+        let dense = vec![
+            4.0, 0.0, 1.0, //
+            0.0, 3.0, 0.0, //
+            2.0, 0.0, 5.0,
+        ];
+        let (values, col_indices, row_ptr) = dense_to_csr(&dense, 3);
+        let matrix = CsrMatrix::new(&values, &col_indices, &row_ptr);
+        assert_eq!(matrix.to_dense().unwrap(), dense);
+
+        let triplets = matrix.to_triplets();
+        assert_eq!(triplets.rows(), vec![0, 0, 1, 2, 2]);
+        let mut builder = TripletBuilder::new(3);
+        assert!(builder.add(&triplets.rows(), &triplets.cols(), &triplets.values()));
+        assert_eq!(builder.build().to_dense(), Some(dense));
+
+        let n = MAX_DENSE_EXPORT + 1;
+        let (values, col_indices, row_ptr) = laplacian_1d(n);
+        assert!(CsrMatrix::new(&values, &col_indices, &row_ptr)
+            .to_dense()
+            .is_none());
+    }
+
+    #[test]
+    fn test_canonicalize() {
+        // Row 0: columns 2, 0, 2 (duplicate) and an explicit zero at 1;
+        // row 1: entries cancelling at column 0
+        let values = [1.0, 4.0, 2.0, 0.0, 3.0, 5.0, -3.0, 6.0];
+        let col_indices = [2, 0, 2, 1, 0, 1, 0, 2];
+        let row_ptr = [0, 4, 7, 8];
+        let mut matrix = CsrMatrix::new(&values, &col_indices, &row_ptr);
+        let mut kept = CsrMatrix::new(&values, &col_indices, &row_ptr);
+        let dense = matrix.to_dense().unwrap();
+
+        assert_eq!(kept.canonicalize(false), 2);
+        assert_eq!(kept.col_indices(), vec![0, 1, 2, 0, 1, 2]);
+        assert_eq!(kept.values(), vec![4.0, 0.0, 3.0, 0.0, 5.0, 6.0]);
+
+        assert_eq!(matrix.canonicalize(true), 4);
+        assert_eq!(matrix.col_indices(), vec![0, 2, 1, 2]);
+        assert_eq!(matrix.row_ptr(), vec![0, 2, 3, 4]);
+        assert_eq!(matrix.to_dense().unwrap(), dense);
+        assert_eq!(matrix.canonicalize(true), 0);
+    }
+}
@@ -0,0 +1,254 @@
+//! Solver configuration passed from JavaScript
+
+use wasm_bindgen::prelude::*;
+
+/// Which conjugate gradient kernel `solve_pcg_with_options` runs
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CgVariant {
+    /// Textbook PCG: two separate dot-product reductions per iteration
+    Classic = 0,
+    /// Ghysels-Vanroose pipelined PCG: one fused reduction per iteration,
+    /// overlapped with the SpMV and preconditioner application
+    Pipelined = 1,
+    /// s-step PCG: s SpMVs per outer step with fused block updates, see
+    /// `SolverOptions::s_step`
+    SStep = 2,
+}
+
+/// Stopping rule of the option-taking solvers
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConvergenceCriterion {
+    /// ||r|| < tol * max(||b||, 1), the rule of the plain entry points
+    RelativeToRhs = 0,
+    /// ||r|| < tol
+    Absolute = 1,
+    /// ||r|| < tol * ||r0||
+    RelativeToInitial = 2,
+    /// Estimated ||x - x*||_A < tol * ||x||_A (Hestenes-Stiefel estimate with
+    /// a delay of a few iterations). Only classic PCG supports it; the other
+    /// solvers use `RelativeToRhs` instead.
+    EnergyNorm = 3,
+}
+
+/// Residual norm that classic PCG tests for convergence
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResidualNorm {
+    /// ||r||_2
+    Unpreconditioned = 0,
+    /// ||r||_{M^{-1}} = sqrt(r^T z), already available from the CG
+    /// recurrence; relative criteria then use ||b||_{M^{-1}} as well
+    Preconditioned = 1,
+}
+
+/// Preconditioner used by PCG (every `CgVariant`), BiCGSTAB and GMRES
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PreconditionerKind {
+    /// Diagonal scaling
+    Jacobi = 0,
+    /// Zero fill-in incomplete Cholesky, for SPD matrices; falls back to
+    /// Jacobi if it breaks down even with a diagonal shift
+    Ic0 = 1,
+    /// Zero fill-in incomplete LU, for nonsymmetric matrices; falls back
+    /// to Jacobi if a pivot vanishes even with a diagonal shift
+    Ilu0 = 2,
+    /// Symmetric SOR with relaxation factor `SolverOptions::ssor_omega`;
+    /// setup only extracts the diagonal
+    Ssor = 3,
+    /// Exact inverses of the diagonal blocks of `SolverOptions::block_size`
+    /// consecutive rows (the DOFs of one node)
+    BlockJacobi = 4,
+    /// Smoothed-aggregation algebraic multigrid V-cycle, for SPD matrices;
+    /// aggregates nodes of `SolverOptions::block_size` DOFs. Pass rigid
+    /// body modes through `solve_pcg_amg` for elasticity.
+    Amg = 5,
+    /// Fixed-degree Jacobi-Chebyshev polynomial of degree
+    /// `SolverOptions::chebyshev_degree`, with spectral bounds estimated at
+    /// setup; SpMVs only, no triangular solves
+    Chebyshev = 6,
+    /// Sparse approximate inverse with the pattern of
+    /// A^`SolverOptions::spai_pattern`, applied as an SpMV; nonsymmetric, so
+    /// meant for BiCGSTAB and GMRES
+    Spai = 7,
+    /// Threshold incomplete Cholesky ICT(tau, p) with tau =
+    /// `SolverOptions::drop_tolerance` and p = `SolverOptions::max_fill`,
+    /// for SPD matrices where IC(0) is too weak; falls back to Jacobi like
+    /// `Ic0`
+    Ict = 8,
+    /// Threshold incomplete LU ILUT(tau, p) with the same two settings, for
+    /// nonsymmetric systems where ILU(0) is too weak; falls back to Jacobi
+    /// like `Ilu0`
+    Ilut = 9,
+    /// Overlapping additive Schwarz over `SolverOptions::subdomains`
+    /// subdomains grown by `SolverOptions::overlap` layers; subdomain
+    /// solves are exact up to a few hundred rows, ILU(0) beyond
+    Schwarz = 10,
+    /// `SolverOptions::smoother` with a coarse correction spanned by the
+    /// near-null modes of every Schwarz subdomain, solved directly; for
+    /// SPD matrices. Setup uses the constant vector of each DOF component;
+    /// pass rigid body modes through `solve_pcg_two_level` for elasticity.
+    TwoLevel = 11,
+    /// Truncated Neumann series in I - D^{-1} A of order
+    /// `SolverOptions::neumann_order` (damped if the series would diverge);
+    /// SpMVs only, between Jacobi and IC(0) in strength
+    Neumann = 12,
+}
+
+/// What the option-taking solvers do with rows whose diagonal entry is
+/// missing or tiny (|a_ii| <= 1e-30), usually a sign of an assembly bug
+/// such as a node no element touches
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DiagonalPolicy {
+    /// Solve anyway, with 1 in their place in the preconditioner diagonal
+    Substitute = 0,
+    /// Do not solve: return `x0` with an infinite residual and the rows in
+    /// `SolveResult::invalid_diagonal_rows`
+    Reject = 1,
+    /// Add `SolverOptions::diagonal_regularization` times the largest
+    /// |a_ii| to those diagonal entries, inserting missing ones, and solve
+    /// the regularized system; the rows are still reported
+    Regularize = 2,
+}
+
+/// Solver that can be chosen at run time
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SolverKind {
+    /// Jacobi-preconditioned CG (`solve_pcg`)
+    Pcg = 0,
+    /// MINRES (`solve_minres`)
+    Minres = 1,
+    /// Restarted GMRES (`solve_gmres`)
+    Gmres = 2,
+    /// BiCGSTAB (`solve_bicgstab`)
+    Bicgstab = 3,
+}
+
+impl SolverKind {
+    /// Map the numeric value used on the JavaScript side back to a kind
+    pub(crate) fn from_code(code: u32) -> Option<SolverKind> {
+        match code {
+            0 => Some(SolverKind::Pcg),
+            1 => Some(SolverKind::Minres),
+            2 => Some(SolverKind::Gmres),
+            3 => Some(SolverKind::Bicgstab),
+            _ => None,
+        }
+    }
+}
+
+/// Options shared by the option-taking solver entry points
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug)]
+pub struct SolverOptions {
+    /// Convergence tolerance
+    pub tol: f64,
+    /// Maximum number of iterations
+    pub max_iter: u32,
+    /// CG kernel to use
+    pub cg_variant: CgVariant,
+    /// Block size s of the s-step CG kernel
+    pub s_step: u32,
+    /// Krylov subspace size of GMRES before restarting
+    pub gmres_restart: u32,
+    /// Preconditioner
+    pub preconditioner: PreconditionerKind,
+    /// Relaxation factor in (0, 2) of the SSOR preconditioner
+    pub ssor_omega: f64,
+    /// Rows per block of the block-Jacobi preconditioner (DOFs per node)
+    pub block_size: u32,
+    /// Polynomial degree of the Chebyshev preconditioner
+    pub chebyshev_degree: u32,
+    /// Sparsity pattern of the SPAI preconditioner: that of A^k for k = 1, 2, ...
+    pub spai_pattern: u32,
+    /// Highest power k of the Neumann preconditioner (k SpMVs per apply)
+    pub neumann_order: u32,
+    /// Relative drop tolerance tau of threshold incomplete factorizations
+    pub drop_tolerance: f64,
+    /// Largest number of off-diagonal entries kept per row (column) of a
+    /// threshold incomplete factor
+    pub max_fill: u32,
+    /// Set up IC(0), ILU(0), ICT and ILUT on the reverse Cuthill–McKee
+    /// ordering of A, which makes them stronger on badly numbered meshes;
+    /// the solver still works in the original ordering
+    pub reorder: bool,
+    /// Number of subdomains of the Schwarz preconditioner
+    pub subdomains: u32,
+    /// Layers of neighbouring rows each Schwarz subdomain is grown by
+    pub overlap: u32,
+    /// One-level preconditioner the two-level method smooths with (Jacobi
+    /// or IC(0) are the usual choices); `TwoLevel` itself means Jacobi
+    pub smoother: PreconditionerKind,
+    /// Stopping rule
+    pub criterion: ConvergenceCriterion,
+    /// Residual norm the stopping rule is applied to
+    pub residual_norm: ResidualNorm,
+    /// Classic PCG replaces the recursively updated residual with the true
+    /// residual b - A x every this many iterations, and confirms convergence
+    /// with it; 0 disables replacement
+    pub residual_replacement: u32,
+    /// Solve D^{-1/2} A D^{-1/2} y = D^{-1/2} b with D = |diag(A)| and
+    /// return x = D^{-1/2} y, for systems whose rows are in mixed units.
+    /// The stopping rule applies to the scaled system; the reported
+    /// residual is that of the original one. Used by `solve_pcg_with_options`,
+    /// `solve_with_fallback` and `solve_auto`.
+    pub equilibrate: bool,
+    /// Handling of missing or tiny diagonal entries; used by the same
+    /// entry points as `equilibrate`
+    pub diagonal_policy: DiagonalPolicy,
+    /// Relative shift of `DiagonalPolicy::Regularize`
+    pub diagonal_regularization: f64,
+    /// Systems with fewer rows than this are solved by a dense
+    /// factorization instead of iterating (`solve_dense`); 0 disables.
+    /// Used by the same entry points as `equilibrate`.
+    pub dense_threshold: u32,
+    /// Run `validate_csr` and check the vector lengths before solving;
+    /// malformed input returns `x0` with an infinite residual and the
+    /// report in `SolveResult::validation`. Used by the same entry points
+    /// as `equilibrate`.
+    pub validate: bool,
+}
+
+#[wasm_bindgen]
+impl SolverOptions {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> SolverOptions {
+        SolverOptions {
+            tol: 1e-8,
+            max_iter: 10000,
+            cg_variant: CgVariant::Classic,
+            s_step: 4,
+            gmres_restart: 30,
+            preconditioner: PreconditionerKind::Jacobi,
+            ssor_omega: 1.0,
+            block_size: 2,
+            chebyshev_degree: 4,
+            spai_pattern: 1,
+            neumann_order: 2,
+            drop_tolerance: 1e-3,
+            max_fill: 10,
+            reorder: false,
+            subdomains: 4,
+            overlap: 1,
+            smoother: PreconditionerKind::Jacobi,
+            criterion: ConvergenceCriterion::RelativeToRhs,
+            residual_norm: ResidualNorm::Unpreconditioned,
+            residual_replacement: 0,
+            equilibrate: false,
+            diagonal_policy: DiagonalPolicy::Substitute,
+            diagonal_regularization: 1e-8,
+            dense_threshold: 0,
+            validate: false,
+        }
+    }
+}
+
+impl Default for SolverOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,339 @@
+use wasm_bindgen::prelude::*;
+
+use super::block_jacobi::uniform_ranges;
+use super::multigrid::{spectral_radius, Multigrid};
+use super::{Footprint, Jacobi, Preconditioner};
+use crate::kernels::{norm, Csr, SparseMatrix};
+
+/// Strength-of-connection threshold: nodes i and j are coupled when
+/// ||A_ij|| >= theta * sqrt(||A_ii|| ||A_jj||) (Vanek, Mandel & Brezina)
+const STRENGTH: f64 = 0.08;
+
+/// Rigid body modes of a 2D or 3D elastic body, the near-null space AMG
+/// needs for elasticity
+///
+/// `coords` holds the nodal coordinates interleaved (x0, y0, x1, y1, ...
+/// or x, y, z per node), in the same node order as the DOFs. Returns
+/// 3 (2D) or 6 (3D) vectors of length `coords.len()`, one after another:
+/// translations first, then rotations about the centroid.
+#[wasm_bindgen]
+pub fn rigid_body_modes(coords: &[f64], dim: u32) -> Vec<f64> {
+    let dim = dim.clamp(2, 3) as usize;
+    let n = coords.len();
+    let nodes = n / dim;
+    let mut center = vec![0.0; dim];
+    for node in coords.chunks_exact(dim) {
+        for (c, x) in center.iter_mut().zip(node) {
+            *c += x / nodes.max(1) as f64;
+        }
+    }
+
+    let count = if dim == 2 { 3 } else { 6 };
+    let mut modes = vec![0.0; count * n];
+    for i in 0..nodes {
+        let x: Vec<f64> = (0..dim).map(|d| coords[i * dim + d] - center[d]).collect();
+        for d in 0..dim {
+            modes[d * n + i * dim + d] = 1.0;
+        }
+        if dim == 2 {
+            modes[2 * n + i * 2] = -x[1];
+            modes[2 * n + i * 2 + 1] = x[0];
+        } else {
+            // Rotations about z, x and y
+            modes[3 * n + i * 3] = -x[1];
+            modes[3 * n + i * 3 + 1] = x[0];
+            modes[4 * n + i * 3 + 1] = -x[2];
+            modes[4 * n + i * 3 + 2] = x[1];
+            modes[5 * n + i * 3] = x[2];
+            modes[5 * n + i * 3 + 2] = -x[0];
+        }
+    }
+    modes
+}
+
+/// Smoothed-aggregation algebraic multigrid, applied as one V-cycle
+///
+/// Setup groups strongly connected nodes (blocks of `block_size` rows)
+/// into aggregates, restricts the near-null-space vectors to each
+/// aggregate and orthonormalizes them to form the tentative prolongator,
+/// and smooths it with one damped Jacobi step; see `Multigrid` for the
+/// cycle itself.
+pub(crate) struct Amg {
+    hierarchy: Multigrid,
+}
+
+impl Amg {
+    /// Set up the hierarchy for `a`
+    ///
+    /// `near_null_space` holds k vectors of length n one after another
+    /// (e.g. from `rigid_body_modes`). When empty, the `block_size`
+    /// constant vectors of each DOF component are used.
+    pub fn new(a: &Csr, near_null_space: &[f64], block_size: usize) -> Self {
+        let n = a.n();
+        let block_size = block_size.max(1);
+        let (mut null, k) = near_null_vectors(n, near_null_space, block_size);
+
+        let hierarchy = Multigrid::new(a, uniform_ranges(n, block_size), |fine, nodes| {
+            let aggregates = aggregate(fine, nodes);
+            let (tentative, coarse_nodes, coarse_null) = tentative(&aggregates, nodes, &null, k);
+            null = coarse_null;
+
+            // P = (I - omega D^{-1} A) P_tent
+            let diag = fine.diagonal();
+            let omega = 4.0 / (3.0 * spectral_radius(fine, &Jacobi::new(fine)));
+            let mut p = SparseMatrix::multiply(fine, &tentative);
+            for (i, di) in diag.iter().enumerate() {
+                for q in p.row_ptr[i] as usize..p.row_ptr[i + 1] as usize {
+                    p.values[q] *= -omega / di;
+                }
+            }
+            Some((SparseMatrix::add(&p, &tentative), coarse_nodes))
+        });
+        Amg { hierarchy }
+    }
+}
+
+impl Preconditioner for Amg {
+    /// z = M^{-1} r, one V-cycle
+    fn apply(&self, r: &[f64], z: &mut [f64]) {
+        self.hierarchy.apply(r, z);
+    }
+
+    fn footprint(&self) -> Footprint {
+        self.hierarchy.footprint()
+    }
+}
+
+/// The k near-null-space vectors of length n in `given`, one after another,
+/// or the `block_size` constant vectors of each DOF component if `given`
+/// is empty or not a multiple of n long
+pub(super) fn near_null_vectors(n: usize, given: &[f64], block_size: usize) -> (Vec<f64>, usize) {
+    if n > 0 && !given.is_empty() && given.len().is_multiple_of(n) {
+        (given.to_vec(), given.len() / n)
+    } else {
+        let mut null = vec![0.0; block_size * n];
+        for i in 0..n {
+            null[(i % block_size) * n + i] = 1.0;
+        }
+        (null, block_size)
+    }
+}
+
+/// Greedy aggregation of the strong-connection graph between nodes
+///
+/// `nodes[g]..nodes[g + 1]` are the rows of node g. Returns the aggregate
+/// of every node.
+fn aggregate(a: &Csr, nodes: &[usize]) -> Vec<usize> {
+    let count = nodes.len() - 1;
+    let mut node_of = vec![0usize; a.n()];
+    for g in 0..count {
+        node_of[nodes[g]..nodes[g + 1]].fill(g);
+    }
+
+    // Squared Frobenius norms of the node-to-node blocks
+    let mut block_norms: Vec<Vec<(usize, f64)>> = vec![Vec::new(); count];
+    let mut diag_norm = vec![0.0; count];
+    let mut acc = vec![0.0; count];
+    let mut touched = Vec::new();
+    for g in 0..count {
+        for i in nodes[g]..nodes[g + 1] {
+            for k in a.row_ptr[i] as usize..a.row_ptr[i + 1] as usize {
+                let h = node_of[a.col_indices[k] as usize];
+                if acc[h] == 0.0 {
+                    touched.push(h);
+                }
+                acc[h] += a.values[k] * a.values[k];
+            }
+        }
+        for &h in touched.iter() {
+            if h == g {
+                diag_norm[g] = acc[h];
+            } else {
+                block_norms[g].push((h, acc[h]));
+            }
+            acc[h] = 0.0;
+        }
+        touched.clear();
+    }
+    let strong: Vec<Vec<usize>> = (0..count)
+        .map(|g| {
+            block_norms[g]
+                .iter()
+                .filter(|&&(h, s)| s >= STRENGTH * STRENGTH * (diag_norm[g] * diag_norm[h]).sqrt())
+                .map(|&(h, _)| h)
+                .collect()
+        })
+        .collect();
+
+    const NONE: usize = usize::MAX;
+    let mut agg = vec![NONE; count];
+    let mut next = 0;
+
+    // 1: seed aggregates from nodes whose whole neighbourhood is free
+    for g in 0..count {
+        if agg[g] == NONE && strong[g].iter().all(|&h| agg[h] == NONE) {
+            agg[g] = next;
+            for &h in strong[g].iter() {
+                agg[h] = next;
+            }
+            next += 1;
+        }
+    }
+    // 2: attach leftover nodes to a neighbouring aggregate
+    let seeded = agg.clone();
+    for g in 0..count {
+        if agg[g] == NONE {
+            if let Some(&h) = strong[g].iter().find(|&&h| seeded[h] != NONE) {
+                agg[g] = seeded[h];
+            }
+        }
+    }
+    // 3: whatever remains forms aggregates with its free neighbours
+    for g in 0..count {
+        if agg[g] == NONE {
+            agg[g] = next;
+            for &h in strong[g].iter() {
+                if agg[h] == NONE {
+                    agg[h] = next;
+                }
+            }
+            next += 1;
+        }
+    }
+    agg
+}
+
+/// Tentative prolongator from the near-null space restricted to each
+/// aggregate and orthonormalized (modified Gram-Schmidt, dropping columns
+/// that are numerically dependent); the R factors give the coarse near-null
+/// space. Returns P_tent, the coarse node ranges and the coarse vectors.
+pub(super) fn tentative(
+    aggregates: &[usize],
+    nodes: &[usize],
+    null: &[f64],
+    k: usize,
+) -> (SparseMatrix, Vec<usize>, Vec<f64>) {
+    let n = *nodes.last().unwrap();
+    let count = aggregates.iter().map(|&g| g + 1).max().unwrap_or(0);
+    let mut rows_of: Vec<Vec<usize>> = vec![Vec::new(); count];
+    for (node, &g) in aggregates.iter().enumerate() {
+        rows_of[g].extend(nodes[node]..nodes[node + 1]);
+    }
+
+    // Entries (row, coarse column, value) and R factors per aggregate
+    let mut entries: Vec<(usize, u32, f64)> = Vec::new();
+    let mut coarse_nodes = vec![0usize];
+    let mut r_factors: Vec<Vec<f64>> = Vec::with_capacity(count);
+    for rows in rows_of.iter() {
+        let m = rows.len();
+        let mut q: Vec<Vec<f64>> = Vec::new();
+        let mut r = Vec::new();
+        for c in 0..k {
+            let mut v: Vec<f64> = rows.iter().map(|&i| null[c * n + i]).collect();
+            let original = norm(&v);
+            let mut coeffs = vec![0.0; k];
+            for (j, qj) in q.iter().enumerate() {
+                let d: f64 = qj.iter().zip(&v).map(|(a, b)| a * b).sum();
+                v.iter_mut().zip(qj).for_each(|(vi, qi)| *vi -= d * qi);
+                coeffs[j] = d;
+            }
+            let vnorm = norm(&v);
+            if vnorm > 1e-10 * original && q.len() < m {
+                coeffs[q.len()] = vnorm;
+                v.iter_mut().for_each(|vi| *vi /= vnorm);
+                q.push(v);
+            }
+            r.push(coeffs);
+        }
+
+        let base = *coarse_nodes.last().unwrap();
+        for (j, qj) in q.iter().enumerate() {
+            for (&i, &v) in rows.iter().zip(qj) {
+                entries.push((i, (base + j) as u32, v));
+            }
+        }
+        // R is kept column by column: r[c][j] = coefficient of q_j in column c
+        let kept = q.len();
+        r_factors.push(
+            r.into_iter()
+                .flat_map(|col| col.into_iter().take(kept))
+                .collect(),
+        );
+        coarse_nodes.push(base + kept);
+    }
+
+    let nc = *coarse_nodes.last().unwrap();
+    let mut coarse_null = vec![0.0; k * nc];
+    for (g, rf) in r_factors.iter().enumerate() {
+        let kept = coarse_nodes[g + 1] - coarse_nodes[g];
+        for c in 0..k {
+            for j in 0..kept {
+                coarse_null[c * nc + coarse_nodes[g] + j] = rf[c * kept + j];
+            }
+        }
+    }
+
+    let p = SparseMatrix::from_triplets(n, nc, entries);
+    (p, coarse_nodes, coarse_null)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::krylov::{pcg_preconditioned, pcg_with_options, solve_pcg_amg};
+    use crate::options::SolverOptions;
+    use crate::test_util::{coupled_2x2, diffusion_2d};
+
+    #[test]
+    fn test_iterations_nearly_independent_of_size() {
+        let mut counts = Vec::new();
+        for size in [32, 64] {
+            let (values, col_indices, row_ptr) = diffusion_2d(size, size, &vec![1.0; size * size]);
+            let a = Csr::new(&values, &col_indices, &row_ptr);
+            let n = a.n();
+            let b = vec![1.0; n];
+            let x0 = vec![0.0; n];
+
+            let options = SolverOptions::new();
+            let amg = Amg::new(&a, &[], 1);
+            assert!(amg.hierarchy.depth() > 0);
+            let result = pcg_preconditioned(&a, &amg, &b, &x0, &options);
+            let jacobi = pcg_with_options(&a, &b, &x0, &options);
+            assert!(result.criterion.is_some());
+            assert!(4 * result.iterations < jacobi.iterations);
+            counts.push(result.iterations);
+        }
+        // Jacobi needs about twice as many iterations on the finer grid
+        assert!(counts[1] < counts[0] * 3 / 2);
+    }
+
+    #[test]
+    fn test_nodal_aggregation_on_coupled_system() {
+        let (nx, ny) = (24, 24);
+        let (values, col_indices, row_ptr) =
+            coupled_2x2(&diffusion_2d(nx, ny, &vec![1.0; nx * ny]));
+        let n = row_ptr.len() - 1;
+        let b: Vec<f64> = (0..n).map(|i| (i % 2) as f64).collect();
+        let x0 = vec![0.0; n];
+
+        let mut options = SolverOptions::new();
+        options.block_size = 2;
+        let amg = solve_pcg_amg(&values, &col_indices, &row_ptr, &b, &x0, &[], &options);
+        let a = Csr::new(&values, &col_indices, &row_ptr);
+        let jacobi = pcg_with_options(&a, &b, &x0, &options);
+        assert!(amg.criterion.is_some());
+        assert!(4 * amg.iterations < jacobi.iterations);
+    }
+
+    #[test]
+    fn test_rigid_body_modes_2d() {
+        let coords = [0.0, 0.0, 1.0, 0.0, 1.0, 1.0, 0.0, 1.0];
+        let modes = rigid_body_modes(&coords, 2);
+        assert_eq!(modes.len(), 3 * 8);
+        // Translations
+        assert_eq!(&modes[0..4], &[1.0, 0.0, 1.0, 0.0]);
+        assert_eq!(&modes[8..12], &[0.0, 1.0, 0.0, 1.0]);
+        // Rotation about the centroid (0.5, 0.5): u = (-y, x)
+        assert_eq!(&modes[16..20], &[0.5, -0.5, 0.5, 0.5]);
+    }
+}
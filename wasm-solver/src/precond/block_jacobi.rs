@@ -0,0 +1,185 @@
+use super::{Footprint, Preconditioner};
+use crate::bsr::Bsr;
+use crate::dense::{lu_factor, lu_solve};
+use crate::kernels::Csr;
+
+/// Block-Jacobi preconditioner over consecutive groups of `block_size` rows
+///
+/// With nodal DOF numbering (2 per node in 2D, 3 in 3D) the blocks are the
+/// node-to-node stiffness blocks, so the coupling between the displacement
+/// components of a node is inverted exactly. A trailing group shorter than
+/// `block_size` forms its own block. Singular blocks fall back to their
+/// scalar diagonal.
+pub(crate) struct BlockJacobi {
+    /// Rows `ranges[g]..ranges[g + 1]` form block g
+    ranges: Vec<usize>,
+    /// LU factors of the blocks, row-major, one after another
+    factors: Vec<f64>,
+    perms: Vec<usize>,
+}
+
+impl BlockJacobi {
+    pub fn new(a: &Csr, block_size: usize) -> Self {
+        Self::with_ranges(a, uniform_ranges(a.n(), block_size))
+    }
+
+    /// Blocks of a BSR matrix: its diagonal blocks, read as stored
+    pub fn from_bsr(a: &Bsr) -> Self {
+        let (n, bs) = (a.n(), a.block_size);
+        let mut factors = Vec::with_capacity(n * bs);
+        let mut perms = vec![0usize; n];
+        for (r, perm) in perms.chunks_exact_mut(bs).enumerate() {
+            let mut block = match a.diagonal_block(r) {
+                Some(block) => block.to_vec(),
+                None => vec![0.0; bs * bs],
+            };
+            if !lu_factor(&mut block, bs, perm) {
+                let diag: Vec<f64> = (0..bs).map(|i| block_diagonal(a, r, i)).collect();
+                block.iter_mut().for_each(|v| *v = 0.0);
+                for (i, p) in perm.iter_mut().enumerate() {
+                    block[i * bs + i] = diag[i];
+                    *p = i;
+                }
+            }
+            factors.extend_from_slice(&block);
+        }
+        BlockJacobi {
+            ranges: uniform_ranges(n, bs),
+            factors,
+            perms,
+        }
+    }
+
+    /// Blocks over arbitrary consecutive row ranges (e.g. AMG aggregates)
+    pub fn with_ranges(a: &Csr, ranges: Vec<usize>) -> Self {
+        let n = a.n();
+        let mut factors = Vec::new();
+        let mut perms = vec![0usize; n];
+        let diag = a.diagonal();
+
+        for range in ranges.windows(2) {
+            let (start, bs) = (range[0], range[1] - range[0]);
+            let mut block = vec![0.0; bs * bs];
+            for i in 0..bs {
+                let row = start + i;
+                for k in a.row_ptr[row] as usize..a.row_ptr[row + 1] as usize {
+                    let j = a.col_indices[k] as usize;
+                    if (start..start + bs).contains(&j) {
+                        block[i * bs + j - start] += a.values[k];
+                    }
+                }
+            }
+            let perm = &mut perms[start..start + bs];
+            if !lu_factor(&mut block, bs, perm) {
+                // Scalar diagonal as a 1 x 1 LU per row
+                block.iter_mut().for_each(|v| *v = 0.0);
+                for i in 0..bs {
+                    block[i * bs + i] = diag[start + i];
+                    perm[i] = i;
+                }
+            }
+            factors.extend_from_slice(&block);
+        }
+
+        BlockJacobi {
+            ranges,
+            factors,
+            perms,
+        }
+    }
+}
+
+impl Preconditioner for BlockJacobi {
+    /// z = M^{-1} r, one dense solve per block
+    fn apply(&self, r: &[f64], z: &mut [f64]) {
+        let mut offset = 0;
+        for range in self.ranges.windows(2) {
+            let (start, bs) = (range[0], range[1] - range[0]);
+            let zb = &mut z[start..start + bs];
+            zb.copy_from_slice(&r[start..start + bs]);
+            lu_solve(
+                &self.factors[offset..offset + bs * bs],
+                bs,
+                &self.perms[start..start + bs],
+                zb,
+            );
+            offset += bs * bs;
+        }
+    }
+
+    fn footprint(&self) -> Footprint {
+        Footprint::values(&self.factors)
+            + Footprint::indices(&self.perms)
+            + Footprint::indices(&self.ranges)
+    }
+}
+
+/// Entry (i, i) of the diagonal block of block row r, 1 if not stored
+/// or tiny, as in `Csr::diagonal`
+fn block_diagonal(a: &Bsr, r: usize, i: usize) -> f64 {
+    let bs = a.block_size;
+    match a.diagonal_block(r).map(|block| block[i * bs + i]) {
+        Some(d) if d.abs() > 1e-30 => d,
+        _ => 1.0,
+    }
+}
+
+/// Row ranges of consecutive groups of `block_size` rows; a shorter
+/// trailing group forms its own range
+pub(super) fn uniform_ranges(n: usize, block_size: usize) -> Vec<usize> {
+    (0..n)
+        .step_by(block_size.max(1))
+        .chain(std::iter::once(n))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kernels::norm;
+    use crate::krylov::pcg_with_options;
+    use crate::options::{PreconditionerKind, SolverOptions};
+    use crate::test_util::{coupled_2x2, dense_to_csr, diffusion_2d};
+
+    #[test]
+    fn test_exact_on_block_diagonal() {
+        // Two 2 x 2 blocks and a trailing 1 x 1 block
+        let dense = vec![
+            0.0, 2.0, 0.0, 0.0, 0.0, //
+            1.0, 3.0, 0.0, 0.0, 0.0, //
+            0.0, 0.0, 4.0, 1.0, 0.0, //
+            0.0, 0.0, 1.0, 5.0, 0.0, //
+            0.0, 0.0, 0.0, 0.0, 2.0,
+        ];
+        let (values, col_indices, row_ptr) = dense_to_csr(&dense, 5);
+        let a = Csr::new(&values, &col_indices, &row_ptr);
+        let m = BlockJacobi::new(&a, 2);
+
+        let x_true = [1.0, -2.0, 3.0, 0.5, -1.0];
+        let mut b = vec![0.0; 5];
+        a.spmv(&x_true, &mut b);
+        let mut x = vec![0.0; 5];
+        m.apply(&b, &mut x);
+        let err: Vec<f64> = x.iter().zip(&x_true).map(|(a, b)| a - b).collect();
+        assert!(norm(&err) < 1e-12);
+    }
+
+    #[test]
+    fn test_fewer_pcg_iterations_on_coupled_system() {
+        let (nx, ny) = (16, 16);
+        let (values, col_indices, row_ptr) =
+            coupled_2x2(&diffusion_2d(nx, ny, &vec![1.0; nx * ny]));
+        let a = Csr::new(&values, &col_indices, &row_ptr);
+        let n = a.n();
+        let b: Vec<f64> = (0..n).map(|i| (i % 2) as f64).collect();
+        let x0 = vec![0.0; n];
+
+        let mut options = SolverOptions::new();
+        let jacobi = pcg_with_options(&a, &b, &x0, &options);
+        options.preconditioner = PreconditionerKind::BlockJacobi;
+        options.block_size = 2;
+        let block = pcg_with_options(&a, &b, &x0, &options);
+        assert!(block.criterion.is_some());
+        assert!(2 * block.iterations < jacobi.iterations);
+    }
+}
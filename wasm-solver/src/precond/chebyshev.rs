@@ -0,0 +1,132 @@
+use super::{Footprint, Preconditioner};
+use crate::kernels::{apply_jacobi, axpy, Csr, SparseMatrix};
+use crate::krylov::spectral_bounds;
+
+/// Power iterations spent estimating the spectrum of D^{-1} A
+const BOUND_ITERATIONS: u32 = 30;
+/// Safety factor on the estimated lambda_max: power iteration approaches
+/// it from below and the polynomial must not be applied past it
+const LMAX_MARGIN: f64 = 1.1;
+
+/// Chebyshev polynomial preconditioner z = p(D^{-1} A) D^{-1} r
+///
+/// Applies `degree` steps of Jacobi-preconditioned Chebyshev iteration to
+/// A z = r from z = 0, which costs `degree - 1` SpMVs and no inner
+/// products or triangular solves. The residual polynomial lies in (0, 1)
+/// on (0, lambda_max], so M is SPD whenever A is and PCG can use it.
+/// The spectral bounds are estimated with `spectral_bounds` at setup.
+pub(crate) struct ChebyshevPoly {
+    a: SparseMatrix,
+    diag: Vec<f64>,
+    degree: u32,
+    lambda_min: f64,
+    lambda_max: f64,
+}
+
+impl ChebyshevPoly {
+    pub fn new(a: &Csr, degree: u32) -> Self {
+        // Targeting the whole spectrum would flatten the polynomial; the
+        // interval [lambda_max / degree^2, lambda_max] balances the damping
+        // of large and small eigenvalues
+        let degree = degree.max(1);
+        let (lmin, lmax) = spectral_bounds(a, BOUND_ITERATIONS);
+        let lambda_max = if lmax > 0.0 && lmax.is_finite() {
+            LMAX_MARGIN * lmax
+        } else {
+            1.0
+        };
+        ChebyshevPoly {
+            a: SparseMatrix::from_csr(a),
+            diag: a.diagonal(),
+            degree,
+            lambda_min: lmin.clamp(
+                lambda_max / (degree * degree).max(2) as f64,
+                0.9 * lambda_max,
+            ),
+            lambda_max,
+        }
+    }
+}
+
+impl Preconditioner for ChebyshevPoly {
+    /// z = M^{-1} r
+    fn apply(&self, r: &[f64], z: &mut [f64]) {
+        let n = r.len();
+        let a = self.a.csr();
+        let theta = 0.5 * (self.lambda_max + self.lambda_min);
+        let delta = 0.5 * (self.lambda_max - self.lambda_min);
+        let sigma = theta / delta;
+        let mut rho = 1.0 / sigma;
+
+        let mut res = r.to_vec(); // Residual of A z = r
+        let mut d = vec![0.0; n]; // Update
+        let mut t = vec![0.0; n]; // A * d, then M^{-1} res
+        apply_jacobi(&self.diag, r, &mut d);
+        d.iter_mut().for_each(|di| *di /= theta);
+        z.iter_mut().for_each(|zi| *zi = 0.0);
+
+        for step in 1..=self.degree {
+            axpy(1.0, &d, z);
+            if step == self.degree {
+                break;
+            }
+            a.spmv(&d, &mut t);
+            axpy(-1.0, &t, &mut res);
+
+            let rho_new = 1.0 / (2.0 * sigma - rho);
+            apply_jacobi(&self.diag, &res, &mut t);
+            let (c1, c2) = (rho_new * rho, 2.0 * rho_new / delta);
+            for (di, ti) in d.iter_mut().zip(&t) {
+                *di = c1 * *di + c2 * ti;
+            }
+            rho = rho_new;
+        }
+    }
+
+    fn footprint(&self) -> Footprint {
+        Footprint::matrix(&self.a) + Footprint::values(&self.diag)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kernels::dot;
+    use crate::krylov::pcg_with_options;
+    use crate::options::{PreconditionerKind, SolverOptions};
+    use crate::test_util::diffusion_2d;
+
+    #[test]
+    fn test_operator_is_symmetric_positive() {
+        let (nx, ny) = (7, 6);
+        let kappa: Vec<f64> = (0..nx * ny).map(|c| 1.0 + (c % 4) as f64).collect();
+        let (values, col_indices, row_ptr) = diffusion_2d(nx, ny, &kappa);
+        let a = Csr::new(&values, &col_indices, &row_ptr);
+        let m = ChebyshevPoly::new(&a, 5);
+
+        let u: Vec<f64> = (0..nx * ny).map(|i| (i as f64 * 0.9).sin()).collect();
+        let v: Vec<f64> = (0..nx * ny).map(|i| (i as f64 * 0.4).cos()).collect();
+        let (mut mu, mut mv) = (vec![0.0; nx * ny], vec![0.0; nx * ny]);
+        m.apply(&u, &mut mu);
+        m.apply(&v, &mut mv);
+        assert!((dot(&v, &mu) - dot(&u, &mv)).abs() < 1e-12 * dot(&u, &mu).abs());
+        assert!(dot(&u, &mu) > 0.0 && dot(&v, &mv) > 0.0);
+    }
+
+    #[test]
+    fn test_fewer_pcg_iterations_than_jacobi() {
+        let (nx, ny) = (32, 32);
+        let (values, col_indices, row_ptr) = diffusion_2d(nx, ny, &vec![1.0; nx * ny]);
+        let a = Csr::new(&values, &col_indices, &row_ptr);
+        let b = vec![1.0; nx * ny];
+        let x0 = vec![0.0; nx * ny];
+
+        let mut options = SolverOptions::new();
+        let jacobi = pcg_with_options(&a, &b, &x0, &options);
+        options.preconditioner = PreconditionerKind::Chebyshev;
+        options.chebyshev_degree = 4;
+        let poly = pcg_with_options(&a, &b, &x0, &options);
+        assert!(poly.criterion.is_some());
+        assert!(3 * poly.iterations < jacobi.iterations);
+    }
+}
@@ -0,0 +1,172 @@
+use super::block_jacobi::uniform_ranges;
+use super::multigrid::Multigrid;
+use super::{Footprint, Preconditioner};
+use crate::grid::{node_count, node_index};
+use crate::kernels::{Csr, SparseMatrix};
+
+/// Geometric multigrid on a structured nelx x nely (x nelz) element grid
+///
+/// Unknowns are the nodal DOFs of the grid, numbered as in the FEM code:
+/// node (x, y, z) is z (nelx + 1)(nely + 1) + x (nely + 1) + y, with
+/// `dofs_per_node` consecutive DOFs per node; 2D grids have nelz = 0. Each
+/// level halves every direction with an even element count (directions
+/// that cannot be halved are kept, i.e. semi-coarsening) and interpolates
+/// bi/trilinearly; the coarse operators are Galerkin products, so
+/// Dirichlet rows and density contrast carry over without re-discretizing.
+pub(crate) struct Gmg {
+    hierarchy: Multigrid,
+}
+
+impl Gmg {
+    pub fn new(a: &Csr, elements: [usize; 3], dofs_per_node: usize) -> Self {
+        let dofs = dofs_per_node.max(1);
+        let mut grid = elements;
+        let matches = node_count(grid) * dofs == a.n();
+        let hierarchy = Multigrid::new(a, uniform_ranges(a.n(), dofs), |_, _| {
+            if !matches {
+                return None;
+            }
+            let (p, coarse) = interpolation(grid, dofs)?;
+            grid = coarse;
+            let nodes = uniform_ranges(p.ncols, dofs);
+            Some((p, nodes))
+        });
+        Gmg { hierarchy }
+    }
+}
+
+impl Preconditioner for Gmg {
+    /// z = M^{-1} r, one V-cycle
+    fn apply(&self, r: &[f64], z: &mut [f64]) {
+        self.hierarchy.apply(r, z);
+    }
+
+    fn footprint(&self) -> Footprint {
+        self.hierarchy.footprint()
+    }
+}
+
+/// Prolongator from the next coarser grid and that grid's element counts,
+/// or `None` when no direction can be halved
+fn interpolation(elements: [usize; 3], dofs: usize) -> Option<(SparseMatrix, [usize; 3])> {
+    let halved = elements.map(|e| e >= 2 && e.is_multiple_of(2));
+    if !halved.iter().any(|&h| h) {
+        return None;
+    }
+    let coarse: [usize; 3] = std::array::from_fn(|d| {
+        if halved[d] {
+            elements[d] / 2
+        } else {
+            elements[d]
+        }
+    });
+
+    // 1D weights: (coarse index, weight) pairs for each fine index
+    let weights = |d: usize, i: usize| -> Vec<(usize, f64)> {
+        if !halved[d] {
+            vec![(i, 1.0)]
+        } else if i.is_multiple_of(2) {
+            vec![(i / 2, 1.0)]
+        } else {
+            vec![(i / 2, 0.5), (i / 2 + 1, 0.5)]
+        }
+    };
+
+    let mut entries = Vec::new();
+    for z in 0..=elements[2] {
+        for x in 0..=elements[0] {
+            for y in 0..=elements[1] {
+                let fine = node_index(elements, x, y, z);
+                for &(cz, wz) in weights(2, z).iter() {
+                    for &(cx, wx) in weights(0, x).iter() {
+                        for &(cy, wy) in weights(1, y).iter() {
+                            let c = node_index(coarse, cx, cy, cz);
+                            for d in 0..dofs {
+                                entries.push((
+                                    fine * dofs + d,
+                                    (c * dofs + d) as u32,
+                                    wx * wy * wz,
+                                ));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+    let n = node_count(elements) * dofs;
+    let nc = node_count(coarse) * dofs;
+    Some((SparseMatrix::from_triplets(n, nc, entries), coarse))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::krylov::{pcg_preconditioned, pcg_with_options};
+    use crate::options::SolverOptions;
+    use crate::test_util::{coupled_2x2, diffusion_2d};
+
+    #[test]
+    fn test_interpolation_is_bilinear() {
+        // f = 1 + x + 2 y on the coarse nodes interpolates exactly
+        let elements = [4, 6, 0];
+        let (p, coarse) = interpolation(elements, 1).unwrap();
+        assert_eq!(coarse, [2, 3, 0]);
+        let f = |x: f64, y: f64| 1.0 + x + 2.0 * y;
+
+        let mut fc = vec![0.0; node_count(coarse)];
+        for x in 0..=coarse[0] {
+            for y in 0..=coarse[1] {
+                fc[node_index(coarse, x, y, 0)] = f(2.0 * x as f64, 2.0 * y as f64);
+            }
+        }
+        let mut ff = vec![0.0; node_count(elements)];
+        p.csr().spmv(&fc, &mut ff);
+        for x in 0..=elements[0] {
+            for y in 0..=elements[1] {
+                let expected = f(x as f64, y as f64);
+                assert!((ff[node_index(elements, x, y, 0)] - expected).abs() < 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn test_iterations_nearly_independent_of_size() {
+        let mut counts = Vec::new();
+        for nel in [32, 64] {
+            let nodes = nel + 1;
+            let (values, col_indices, row_ptr) =
+                diffusion_2d(nodes, nodes, &vec![1.0; nodes * nodes]);
+            let a = Csr::new(&values, &col_indices, &row_ptr);
+            let b = vec![1.0; a.n()];
+            let x0 = vec![0.0; a.n()];
+
+            let options = SolverOptions::new();
+            let gmg = Gmg::new(&a, [nel, nel, 0], 1);
+            let result = pcg_preconditioned(&a, &gmg, &b, &x0, &options);
+            let jacobi = pcg_with_options(&a, &b, &x0, &options);
+            assert!(result.criterion.is_some());
+            assert!(4 * result.iterations < jacobi.iterations);
+            counts.push(result.iterations);
+        }
+        assert!(counts[1] < counts[0] * 3 / 2);
+    }
+
+    #[test]
+    fn test_vector_problem() {
+        let nel = 24;
+        let nodes = nel + 1;
+        let (values, col_indices, row_ptr) =
+            coupled_2x2(&diffusion_2d(nodes, nodes, &vec![1.0; nodes * nodes]));
+        let a = Csr::new(&values, &col_indices, &row_ptr);
+        let b: Vec<f64> = (0..a.n()).map(|i| (i % 2) as f64).collect();
+        let x0 = vec![0.0; a.n()];
+
+        let options = SolverOptions::new();
+        let gmg = Gmg::new(&a, [nel, nel, 0], 2);
+        let result = pcg_preconditioned(&a, &gmg, &b, &x0, &options);
+        let jacobi = pcg_with_options(&a, &b, &x0, &options);
+        assert!(result.criterion.is_some());
+        assert!(4 * result.iterations < jacobi.iterations);
+    }
+}
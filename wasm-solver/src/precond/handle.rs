@@ -0,0 +1,159 @@
+use wasm_bindgen::prelude::*;
+
+use super::{setup, timed, MemoryReport, Preconditioner, SetupReport};
+use crate::kernels::{Csr, SparseMatrix};
+use crate::matrix::CsrMatrix;
+use crate::options::{PreconditionerKind, SolverOptions};
+
+/// Preconditioner set up once and reused across solves
+///
+/// Pass it to `solve_with_preconditioner` instead of letting every solve
+/// set up `options.preconditioner` again. In a SIMP loop the stiffness
+/// changes a little from one design iteration to the next, so an older
+/// setup usually remains a good preconditioner; call `refresh` with the
+/// new values when the iteration counts start to grow.
+#[wasm_bindgen(js_name = Preconditioner)]
+pub struct PreconditionerHandle {
+    matrix: SparseMatrix,
+    options: SolverOptions,
+    inner: Box<dyn Preconditioner>,
+    report: SetupReport,
+}
+
+#[wasm_bindgen(js_class = Preconditioner)]
+impl PreconditionerHandle {
+    /// Set up `options.preconditioner` for the CSR matrix; the sparsity
+    /// pattern is kept for `refresh`
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        values: &[f64],
+        col_indices: &[u32],
+        row_ptr: &[u32],
+        options: &SolverOptions,
+    ) -> PreconditionerHandle {
+        let a = Csr::new(values, col_indices, row_ptr);
+        let (inner, report) = timed(|| setup(options, &a));
+        PreconditionerHandle {
+            matrix: SparseMatrix::from_csr(&a),
+            options: *options,
+            inner,
+            report,
+        }
+    }
+
+    /// Set up again for new matrix values on the same sparsity pattern.
+    /// Returns `false`, keeping the old setup, if `values` does not match
+    /// the pattern's length.
+    pub fn refresh(&mut self, values: &[f64]) -> bool {
+        if values.len() != self.matrix.values.len() {
+            return false;
+        }
+        self.matrix.values.copy_from_slice(values);
+        (self.inner, self.report) = timed(|| setup(&self.options, &self.matrix.csr()));
+        true
+    }
+
+    /// `refresh` with the current values of `matrix`, without copying them
+    /// through JavaScript
+    pub fn refresh_from(&mut self, matrix: &CsrMatrix) -> bool {
+        self.refresh(matrix.csr().values)
+    }
+
+    /// Requested kind; an incomplete factorization that broke down runs
+    /// as Jacobi
+    #[wasm_bindgen(getter)]
+    pub fn kind(&self) -> PreconditionerKind {
+        self.options.preconditioner
+    }
+
+    /// Cost of the latest setup (construction or `refresh`)
+    #[wasm_bindgen(getter)]
+    pub fn setup(&self) -> SetupReport {
+        self.report
+    }
+
+    /// Number of rows of the matrix it was set up for
+    #[wasm_bindgen(getter)]
+    pub fn size(&self) -> usize {
+        self.matrix.row_ptr.len() - 1
+    }
+
+    /// Memory of the matrix kept for `refresh` and of the preconditioner
+    pub fn memory_report(&self) -> MemoryReport {
+        MemoryReport::matrix(&self.matrix).with_factors(self.inner.footprint().bytes)
+    }
+}
+
+impl PreconditionerHandle {
+    pub(crate) fn preconditioner(&self) -> &dyn Preconditioner {
+        self.inner.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::krylov::solve_with_preconditioner;
+    use crate::options::SolverKind;
+    use crate::test_util::diffusion_2d;
+
+    #[test]
+    fn test_stale_setup_still_converges() {
+        let (nx, ny) = (20, 20);
+        let kappa: Vec<f64> = (0..nx * ny).map(|c| 1.0 + (c % 7) as f64).collect();
+        let (values, col_indices, row_ptr) = diffusion_2d(nx, ny, &kappa);
+        let b = vec![1.0; nx * ny];
+        let x0 = vec![0.0; nx * ny];
+        let mut options = SolverOptions::new();
+        options.preconditioner = PreconditionerKind::Ic0;
+        let m = PreconditionerHandle::new(&values, &col_indices, &row_ptr, &options);
+        assert_eq!(m.size(), nx * ny);
+
+        // Next design iteration: stiffness changed by up to 20 %
+        let kappa: Vec<f64> = kappa
+            .iter()
+            .enumerate()
+            .map(|(c, k)| k * (1.0 + 0.2 * (c as f64 * 0.3).sin()))
+            .collect();
+        let (values, _, _) = diffusion_2d(nx, ny, &kappa);
+        let solve = |m: &PreconditionerHandle| {
+            let result = solve_with_preconditioner(
+                &values,
+                &col_indices,
+                &row_ptr,
+                &b,
+                &x0,
+                SolverKind::Pcg,
+                m,
+                &options,
+            );
+            assert!(result.criterion.is_some());
+            result.iterations
+        };
+        let stale = solve(&m);
+        let fresh = solve(&PreconditionerHandle::new(
+            &values,
+            &col_indices,
+            &row_ptr,
+            &options,
+        ));
+        assert!(stale <= fresh + fresh / 2);
+    }
+
+    #[test]
+    fn test_refresh_checks_pattern() {
+        let (values, col_indices, row_ptr) = diffusion_2d(6, 6, &[1.0; 36]);
+        let mut m =
+            PreconditionerHandle::new(&values, &col_indices, &row_ptr, &SolverOptions::new());
+        assert!(!m.refresh(&values[1..]));
+
+        let doubled: Vec<f64> = values.iter().map(|v| 2.0 * v).collect();
+        assert!(m.refresh(&doubled));
+        let mut z = vec![0.0; 36];
+        m.preconditioner().apply(&[1.0; 36], &mut z);
+        let a = Csr::new(&values, &col_indices, &row_ptr);
+        for (zi, d) in z.iter().zip(a.diagonal()) {
+            assert!((zi - 0.5 / d).abs() < 1e-14);
+        }
+    }
+}
@@ -0,0 +1,195 @@
+use super::{Footprint, Preconditioner, SHIFTS};
+use crate::kernels::{Csr, Scalar};
+
+/// Zero fill-in incomplete Cholesky factor A ~ L L^T
+///
+/// L keeps the sparsity pattern of the lower triangle of A. Matrices that
+/// are SPD but not M-matrices (e.g. elasticity) can still produce
+/// non-positive pivots; the factorization is then retried on
+/// A + alpha * diag(A) with increasing alpha (Manteuffel shift). The factor
+/// is computed in f64 and can be stored as f32 (`convert`).
+pub(crate) struct Ic0<T: Scalar = f64> {
+    /// Rows of L: sorted columns with the diagonal stored last
+    row_ptr: Vec<usize>,
+    col_indices: Vec<usize>,
+    values: Vec<T>,
+}
+
+impl Ic0 {
+    /// Factorize `a`, reading only its lower triangle. Returns `None` if
+    /// every shift breaks down.
+    pub fn new<S: Scalar>(a: &Csr<S>) -> Option<Self> {
+        let (mut factor, lower) = Self::pattern(a);
+        SHIFTS
+            .iter()
+            .any(|&shift| factor.factorize(&lower, shift))
+            .then_some(factor)
+    }
+
+    /// Lower triangle pattern of `a` with summed duplicates and an explicit
+    /// diagonal, plus the matching values of A
+    fn pattern<S: Scalar>(a: &Csr<S>) -> (Self, Vec<f64>) {
+        let n = a.n();
+        let mut row_ptr = vec![0usize; n + 1];
+        let mut col_indices = Vec::new();
+        let mut lower = Vec::new();
+        let mut entries: Vec<(usize, f64)> = Vec::new();
+        for i in 0..n {
+            entries.clear();
+            entries.push((i, 0.0));
+            for k in a.row_ptr[i] as usize..a.row_ptr[i + 1] as usize {
+                let j = a.col_indices[k] as usize;
+                if j <= i {
+                    entries.push((j, a.values[k].to_f64()));
+                }
+            }
+            entries.sort_by_key(|&(j, _)| j);
+            for &(j, v) in entries.iter() {
+                if col_indices.len() > row_ptr[i] && col_indices.last() == Some(&j) {
+                    *lower.last_mut().unwrap() += v;
+                } else {
+                    col_indices.push(j);
+                    lower.push(v);
+                }
+            }
+            row_ptr[i + 1] = col_indices.len();
+        }
+
+        let values = vec![0.0; lower.len()];
+        let factor = Ic0 {
+            row_ptr,
+            col_indices,
+            values,
+        };
+        (factor, lower)
+    }
+
+    /// Row-by-row IC(0) of the lower triangle `lower` with the diagonal
+    /// scaled by 1 + `shift`. Returns `false` on a non-positive pivot.
+    fn factorize(&mut self, lower: &[f64], shift: f64) -> bool {
+        let n = self.row_ptr.len() - 1;
+        self.values.copy_from_slice(lower);
+        for i in 0..n {
+            let start = self.row_ptr[i];
+            let diag_pos = self.row_ptr[i + 1] - 1;
+            let a_ii = lower[diag_pos] * (1.0 + shift);
+
+            for p in start..diag_pos {
+                let k = self.col_indices[p];
+                // L_ik = (A_ik - sum_j L_ij L_kj) / L_kk over shared columns j < k
+                let (mut q, k_diag) = (self.row_ptr[k], self.row_ptr[k + 1] - 1);
+                let mut sum = 0.0;
+                for t in start..p {
+                    let j = self.col_indices[t];
+                    while q < k_diag && self.col_indices[q] < j {
+                        q += 1;
+                    }
+                    if q < k_diag && self.col_indices[q] == j {
+                        sum += self.values[t] * self.values[q];
+                    }
+                }
+                self.values[p] = (self.values[p] - sum) / self.values[k_diag];
+            }
+
+            let off: f64 = self.values[start..diag_pos].iter().map(|v| v * v).sum();
+            let pivot = a_ii - off;
+            if pivot.is_nan() || pivot <= f64::EPSILON * a_ii.abs() {
+                return false;
+            }
+            self.values[diag_pos] = pivot.sqrt();
+        }
+        true
+    }
+
+    /// The factor with its values stored as `T`
+    pub fn convert<T: Scalar>(self) -> Ic0<T> {
+        Ic0 {
+            row_ptr: self.row_ptr,
+            col_indices: self.col_indices,
+            values: self.values.into_iter().map(T::from_f64).collect(),
+        }
+    }
+}
+
+impl<T: Scalar> Ic0<T> {
+    /// z = (L L^T)^{-1} r by forward and backward substitution
+    pub fn solve(&self, r: &[f64], z: &mut [f64]) {
+        let n = self.row_ptr.len() - 1;
+        // L y = r
+        for i in 0..n {
+            let diag_pos = self.row_ptr[i + 1] - 1;
+            let mut s = r[i];
+            for p in self.row_ptr[i]..diag_pos {
+                s -= self.values[p].to_f64() * z[self.col_indices[p]];
+            }
+            z[i] = s / self.values[diag_pos].to_f64();
+        }
+        // L^T z = y, column-oriented over the rows of L
+        for i in (0..n).rev() {
+            let diag_pos = self.row_ptr[i + 1] - 1;
+            z[i] /= self.values[diag_pos].to_f64();
+            let zi = z[i];
+            for p in self.row_ptr[i]..diag_pos {
+                z[self.col_indices[p]] -= self.values[p].to_f64() * zi;
+            }
+        }
+    }
+}
+
+impl<T: Scalar> Preconditioner for Ic0<T> {
+    fn apply(&self, r: &[f64], z: &mut [f64]) {
+        self.solve(r, z);
+    }
+
+    fn footprint(&self) -> Footprint {
+        Footprint::values(&self.values)
+            + Footprint::indices(&self.col_indices)
+            + Footprint::indices(&self.row_ptr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kernels::norm;
+    use crate::test_util::{dense_to_csr, laplacian_1d};
+
+    #[test]
+    fn test_exact_on_tridiagonal() {
+        // No fill-in for a tridiagonal matrix, so IC(0) is the exact Cholesky
+        let n = 12;
+        let (values, col_indices, row_ptr) = laplacian_1d(n);
+        let a = Csr::new(&values, &col_indices, &row_ptr);
+        let factor = Ic0::new(&a).unwrap();
+
+        let x_true: Vec<f64> = (0..n).map(|i| (i as f64).sin()).collect();
+        let mut b = vec![0.0; n];
+        a.spmv(&x_true, &mut b);
+        let mut x = vec![0.0; n];
+        factor.solve(&b, &mut x);
+
+        let err: Vec<f64> = x.iter().zip(&x_true).map(|(a, b)| a - b).collect();
+        assert!(norm(&err) < 1e-12);
+    }
+
+    #[test]
+    fn test_shift_rescues_breakdown() {
+        // SPD, but dropping the fill-in makes an unshifted pivot non-positive
+        let dense = vec![
+            3.0, -2.0, 0.0, 2.0, //
+            -2.0, 3.0, -2.0, 0.0, //
+            0.0, -2.0, 3.0, -2.0, //
+            2.0, 0.0, -2.0, 3.0,
+        ];
+        let (values, col_indices, row_ptr) = dense_to_csr(&dense, 4);
+        let a = Csr::new(&values, &col_indices, &row_ptr);
+
+        let (mut unshifted, lower) = Ic0::pattern(&a);
+        assert!(!unshifted.factorize(&lower, 0.0));
+
+        let factor = Ic0::new(&a).unwrap();
+        let mut z = vec![0.0; 4];
+        factor.solve(&[1.0, 1.0, 1.0, 1.0], &mut z);
+        assert!(z.iter().all(|v| v.is_finite()));
+    }
+}
@@ -0,0 +1,210 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use super::{Footprint, Preconditioner, SHIFTS};
+use crate::kernels::Csr;
+
+/// Threshold incomplete LU factor A ~ L U with dual dropping (ILUT)
+///
+/// Saad's row-wise IKJ elimination: row i is loaded into a dense work row
+/// and eliminated with the finished rows of U in increasing column order.
+/// Multipliers and fill smaller than `drop_tolerance` times the 2-norm of
+/// row i of A are dropped, then the `max_fill` largest entries of the L
+/// part and of the U part are kept (the diagonal always is). Stored like
+/// `Ilu0`, so the solves are the same; vanishing pivots are handled with
+/// diagonal shifts as there.
+pub(crate) struct Ilut {
+    /// Rows of L \ U with sorted columns
+    row_ptr: Vec<usize>,
+    col_indices: Vec<usize>,
+    values: Vec<f64>,
+    /// Position of the diagonal within each row
+    diag_pos: Vec<usize>,
+}
+
+impl Ilut {
+    /// Factorize `a`. Returns `None` if every shift breaks down.
+    pub fn new(a: &Csr, drop_tolerance: f64, max_fill: usize) -> Option<Self> {
+        SHIFTS
+            .iter()
+            .find_map(|&shift| Self::factorize(a, drop_tolerance, max_fill, shift))
+    }
+
+    fn factorize(a: &Csr, drop_tolerance: f64, max_fill: usize, shift: f64) -> Option<Self> {
+        let n = a.n();
+        let mut row_ptr = vec![0usize; n + 1];
+        let mut col_indices: Vec<usize> = Vec::new();
+        let mut values: Vec<f64> = Vec::new();
+        let mut diag_pos = vec![0usize; n];
+
+        // Dense work row, the columns it holds and the lower ones still to
+        // eliminate, smallest first
+        let mut w = vec![0.0; n];
+        let mut present = vec![false; n];
+        let mut cols: Vec<usize> = Vec::new();
+        let mut pending: BinaryHeap<Reverse<usize>> = BinaryHeap::new();
+
+        for i in 0..n {
+            cols.clear();
+            let mut row_norm = 0.0;
+            present[i] = true;
+            cols.push(i);
+            for q in a.row_ptr[i] as usize..a.row_ptr[i + 1] as usize {
+                let j = a.col_indices[q] as usize;
+                if !present[j] {
+                    present[j] = true;
+                    cols.push(j);
+                    if j < i {
+                        pending.push(Reverse(j));
+                    }
+                }
+                w[j] += a.values[q];
+                row_norm += a.values[q] * a.values[q];
+            }
+            let a_ii = w[i] * (1.0 + shift);
+            w[i] = a_ii;
+            let threshold = drop_tolerance * row_norm.sqrt();
+
+            // w -= (w_k / u_kk) u_k for the lower columns k in order
+            while let Some(Reverse(k)) = pending.pop() {
+                let multiplier = w[k] / values[diag_pos[k]];
+                if multiplier.abs() <= threshold {
+                    w[k] = 0.0;
+                    continue;
+                }
+                w[k] = multiplier;
+                for q in diag_pos[k] + 1..row_ptr[k + 1] {
+                    let j = col_indices[q];
+                    if !present[j] {
+                        present[j] = true;
+                        cols.push(j);
+                        if j < i {
+                            pending.push(Reverse(j));
+                        }
+                    }
+                    w[j] -= multiplier * values[q];
+                }
+            }
+
+            let pivot = w[i];
+            if !pivot.is_finite() || pivot.abs() <= f64::EPSILON * a_ii.abs() {
+                return None;
+            }
+
+            // Drop small entries, keep the `max_fill` largest of each part
+            let select = |part: &mut Vec<(usize, f64)>| {
+                if part.len() > max_fill {
+                    part.sort_by(|x, y| y.1.abs().total_cmp(&x.1.abs()));
+                    part.truncate(max_fill);
+                }
+                part.sort_by_key(|&(j, _)| j);
+            };
+            let (mut lower, mut upper): (Vec<_>, Vec<_>) = cols
+                .iter()
+                .filter(|&&j| j != i && w[j] != 0.0 && (j < i || w[j].abs() > threshold))
+                .map(|&j| (j, w[j]))
+                .partition(|&(j, _)| j < i);
+            select(&mut lower);
+            select(&mut upper);
+            for &j in cols.iter() {
+                w[j] = 0.0;
+                present[j] = false;
+            }
+
+            for &(j, v) in lower.iter() {
+                col_indices.push(j);
+                values.push(v);
+            }
+            diag_pos[i] = col_indices.len();
+            col_indices.push(i);
+            values.push(pivot);
+            for &(j, v) in upper.iter() {
+                col_indices.push(j);
+                values.push(v);
+            }
+            row_ptr[i + 1] = col_indices.len();
+        }
+
+        Some(Ilut {
+            row_ptr,
+            col_indices,
+            values,
+            diag_pos,
+        })
+    }
+}
+
+impl Preconditioner for Ilut {
+    /// z = (L U)^{-1} r by forward and backward substitution
+    fn apply(&self, r: &[f64], z: &mut [f64]) {
+        let n = self.row_ptr.len() - 1;
+        // L y = r, L unit lower
+        for i in 0..n {
+            let mut s = r[i];
+            for p in self.row_ptr[i]..self.diag_pos[i] {
+                s -= self.values[p] * z[self.col_indices[p]];
+            }
+            z[i] = s;
+        }
+        // U z = y
+        for i in (0..n).rev() {
+            let d = self.diag_pos[i];
+            let mut s = z[i];
+            for p in d + 1..self.row_ptr[i + 1] {
+                s -= self.values[p] * z[self.col_indices[p]];
+            }
+            z[i] = s / self.values[d];
+        }
+    }
+
+    fn footprint(&self) -> Footprint {
+        Footprint::values(&self.values)
+            + Footprint::indices(&self.col_indices)
+            + Footprint::indices(&self.row_ptr)
+            + Footprint::indices(&self.diag_pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kernels::norm;
+    use crate::krylov::run_solver;
+    use crate::options::{PreconditionerKind, SolverKind, SolverOptions};
+    use crate::test_util::convection_diffusion_2d;
+
+    #[test]
+    fn test_no_dropping_is_exact_lu() {
+        let (values, col_indices, row_ptr) = convection_diffusion_2d(7, 6, 1.5);
+        let a = Csr::new(&values, &col_indices, &row_ptr);
+        let n = a.n();
+        let factor = Ilut::new(&a, 0.0, n).unwrap();
+
+        let x_true: Vec<f64> = (0..n).map(|i| (i as f64 * 0.4).cos()).collect();
+        let mut b = vec![0.0; n];
+        a.spmv(&x_true, &mut b);
+        let mut x = vec![0.0; n];
+        factor.apply(&b, &mut x);
+        let err: Vec<f64> = x.iter().zip(&x_true).map(|(a, b)| a - b).collect();
+        assert!(norm(&err) < 1e-10 * norm(&x_true));
+    }
+
+    #[test]
+    fn test_fewer_gmres_iterations_than_ilu0() {
+        let (nx, ny) = (30, 30);
+        let (values, col_indices, row_ptr) = convection_diffusion_2d(nx, ny, 2.0);
+        let a = Csr::new(&values, &col_indices, &row_ptr);
+        let b: Vec<f64> = (0..nx * ny).map(|i| (i as f64 * 0.1).sin()).collect();
+        let x0 = vec![0.0; nx * ny];
+
+        let mut options = SolverOptions::new();
+        options.preconditioner = PreconditionerKind::Ilu0;
+        let ilu0 = run_solver(SolverKind::Gmres, &a, &b, &x0, &options);
+        options.preconditioner = PreconditionerKind::Ilut;
+        options.drop_tolerance = 1e-4;
+        options.max_fill = 15;
+        let ilut = run_solver(SolverKind::Gmres, &a, &b, &x0, &options);
+        assert!(ilut.criterion.is_some());
+        assert!(2 * ilut.iterations < ilu0.iterations);
+    }
+}
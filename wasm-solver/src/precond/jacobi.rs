@@ -0,0 +1,29 @@
+use super::{Footprint, Preconditioner};
+use crate::kernels::{apply_jacobi, Csr, Scalar};
+
+/// Diagonal scaling M = diag(A)
+pub(crate) struct Jacobi {
+    diag: Vec<f64>,
+}
+
+impl Jacobi {
+    pub fn new<T: Scalar>(a: &Csr<T>) -> Self {
+        Self::from_diagonal(a.diagonal())
+    }
+
+    /// Jacobi for an operator whose diagonal is known without a stored
+    /// matrix (e.g. `ElementGrid::diagonal`)
+    pub fn from_diagonal(diag: Vec<f64>) -> Self {
+        Jacobi { diag }
+    }
+}
+
+impl Preconditioner for Jacobi {
+    fn apply(&self, r: &[f64], z: &mut [f64]) {
+        apply_jacobi(&self.diag, r, z);
+    }
+
+    fn footprint(&self) -> Footprint {
+        Footprint::values(&self.diag)
+    }
+}
@@ -0,0 +1,270 @@
+//! Preconditioners M ~ A, applied as z = M^{-1} r
+//!
+//! Every preconditioner implements `Preconditioner`; its constructor does
+//! the setup. PCG (all kernels), BiCGSTAB and GMRES get theirs from
+//! `setup` with `SolverOptions::preconditioner`, so any kind combines with
+//! any of them. The other solvers use the Jacobi diagonal unless
+//! documented otherwise. `PreconditionerHandle` keeps a setup alive
+//! across solves; `SetupReport` gives the time and memory a setup took.
+
+mod amg;
+mod block_jacobi;
+mod chebyshev;
+mod ebe;
+mod gmg;
+mod handle;
+mod ic0;
+mod ict;
+mod ilu0;
+mod ilut;
+mod jacobi;
+mod multigrid;
+mod neumann;
+mod reordered;
+mod report;
+mod schwarz;
+mod spai;
+mod ssor;
+mod two_level;
+
+pub use amg::rigid_body_modes;
+pub(crate) use amg::Amg;
+pub(crate) use block_jacobi::BlockJacobi;
+pub(crate) use chebyshev::ChebyshevPoly;
+pub(crate) use ebe::Ebe;
+pub(crate) use gmg::Gmg;
+pub use handle::PreconditionerHandle;
+pub(crate) use ic0::Ic0;
+pub(crate) use ict::Ict;
+pub(crate) use ilu0::Ilu0;
+pub(crate) use ilut::Ilut;
+pub(crate) use jacobi::Jacobi;
+pub(crate) use neumann::Neumann;
+pub(crate) use reordered::Reordered;
+pub(crate) use report::{timed, Footprint};
+pub use report::{MemoryReport, SetupReport};
+pub(crate) use schwarz::Schwarz;
+pub(crate) use spai::Spai;
+pub(crate) use ssor::Ssor;
+pub(crate) use two_level::TwoLevel;
+
+use crate::kernels::{Csr, Scalar, SymmetricCsr};
+use crate::options::{PreconditionerKind, SolverOptions};
+
+/// Relative diagonal shifts tried when an incomplete factorization breaks down
+const SHIFTS: [f64; 5] = [0.0, 1e-3, 1e-2, 1e-1, 1.0];
+
+/// A preconditioner set up for one matrix
+pub(crate) trait Preconditioner {
+    /// z = M^{-1} r
+    fn apply(&self, r: &[f64], z: &mut [f64]);
+
+    /// Storage held after setup
+    fn footprint(&self) -> Footprint;
+}
+
+impl<P: Preconditioner + ?Sized> Preconditioner for Box<P> {
+    fn apply(&self, r: &[f64], z: &mut [f64]) {
+        (**self).apply(r, z)
+    }
+
+    fn footprint(&self) -> Footprint {
+        (**self).footprint()
+    }
+}
+
+/// Set up `options.preconditioner` for `a`. Factorizations that break
+/// down even after diagonal shifting fall back to Jacobi; with
+/// `options.reorder` they are set up on the RCM ordering.
+pub(crate) fn setup(options: &SolverOptions, a: &Csr) -> Box<dyn Preconditioner> {
+    let factorization = matches!(
+        options.preconditioner,
+        PreconditionerKind::Ic0
+            | PreconditionerKind::Ilu0
+            | PreconditionerKind::Ict
+            | PreconditionerKind::Ilut
+    );
+    if options.reorder && factorization {
+        let inner = SolverOptions {
+            reorder: false,
+            ..*options
+        };
+        return Box::new(Reordered::new(a, |permuted| setup(&inner, permuted)));
+    }
+    match options.preconditioner {
+        PreconditionerKind::Jacobi => Box::new(Jacobi::new(a)),
+        PreconditionerKind::Ic0 => match Ic0::new(a) {
+            Some(factor) => Box::new(factor),
+            None => Box::new(Jacobi::new(a)),
+        },
+        PreconditionerKind::Ilu0 => match Ilu0::new(a) {
+            Some(factor) => Box::new(factor),
+            None => Box::new(Jacobi::new(a)),
+        },
+        PreconditionerKind::Ssor => Box::new(Ssor::new(a, options.ssor_omega)),
+        PreconditionerKind::BlockJacobi => {
+            Box::new(BlockJacobi::new(a, options.block_size as usize))
+        }
+        PreconditionerKind::Amg => Box::new(Amg::new(a, &[], options.block_size as usize)),
+        PreconditionerKind::Chebyshev => Box::new(ChebyshevPoly::new(a, options.chebyshev_degree)),
+        PreconditionerKind::Spai => Box::new(Spai::new(a, options.spai_pattern)),
+        PreconditionerKind::Ict => {
+            match Ict::new(a, options.drop_tolerance, options.max_fill as usize) {
+                Some(factor) => Box::new(factor),
+                None => Box::new(Jacobi::new(a)),
+            }
+        }
+        PreconditionerKind::Ilut => {
+            match Ilut::new(a, options.drop_tolerance, options.max_fill as usize) {
+                Some(factor) => Box::new(factor),
+                None => Box::new(Jacobi::new(a)),
+            }
+        }
+        PreconditionerKind::Schwarz => Box::new(Schwarz::new(
+            a,
+            options.subdomains as usize,
+            options.overlap as usize,
+        )),
+        PreconditionerKind::TwoLevel => Box::new(TwoLevel::new(
+            a,
+            &[],
+            options.block_size as usize,
+            options.subdomains as usize,
+            smoother(options, a),
+        )),
+        PreconditionerKind::Neumann => Box::new(Neumann::new(a, options.neumann_order)),
+    }
+}
+
+/// `setup` for a matrix in symmetric half storage
+///
+/// Jacobi, IC(0) and ICT are set up from the stored triangle (they read
+/// one triangle only); the other kinds need full rows and are set up on
+/// the expanded matrix, which those keeping a copy of A then hold in full.
+pub(crate) fn setup_symmetric(
+    options: &SolverOptions,
+    a: &SymmetricCsr,
+) -> Box<dyn Preconditioner> {
+    match options.preconditioner {
+        PreconditionerKind::Jacobi => Box::new(Jacobi::new(&a.upper)),
+        PreconditionerKind::Ic0 if !options.reorder => match Ic0::new(&a.lower().csr()) {
+            Some(factor) => Box::new(factor),
+            None => Box::new(Jacobi::new(&a.upper)),
+        },
+        PreconditionerKind::Ict if !options.reorder => {
+            match Ict::new(&a.upper, options.drop_tolerance, options.max_fill as usize) {
+                Some(factor) => Box::new(factor),
+                None => Box::new(Jacobi::new(&a.upper)),
+            }
+        }
+        _ => setup(options, &a.full().csr()),
+    }
+}
+
+/// `setup` for a matrix with f32 values
+///
+/// Jacobi and IC(0) are set up from the f32 values and IC(0) stores its
+/// factor as f32; the other kinds are set up on an f64 copy of A, which
+/// those keeping a copy then hold in double precision.
+pub(crate) fn setup_single(options: &SolverOptions, a: &Csr<f32>) -> Box<dyn Preconditioner> {
+    match options.preconditioner {
+        PreconditionerKind::Jacobi => Box::new(Jacobi::new(a)),
+        PreconditionerKind::Ic0 if !options.reorder => match Ic0::new(a) {
+            Some(factor) => Box::new(factor.convert::<f32>()),
+            None => Box::new(Jacobi::new(a)),
+        },
+        _ => {
+            let values: Vec<f64> = a.values.iter().map(|&v| v.to_f64()).collect();
+            setup(options, &Csr::new(&values, a.col_indices, a.row_ptr))
+        }
+    }
+}
+
+/// One-level smoother of a two-level preconditioner
+pub(crate) fn smoother(options: &SolverOptions, a: &Csr) -> Box<dyn Preconditioner> {
+    match options.smoother {
+        PreconditionerKind::TwoLevel => Box::new(Jacobi::new(a)),
+        kind => setup(
+            &SolverOptions {
+                preconditioner: kind,
+                ..*options
+            },
+            a,
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::krylov::{pcg_with_options, run_solver};
+    use crate::options::{CgVariant, SolverKind, SolverOptions};
+    use crate::test_util::{convection_diffusion_1d, diffusion_2d};
+
+    #[test]
+    fn test_ic0_beats_jacobi_on_high_contrast() {
+        // Solid/void checkerboard of 4 x 4 blocks with contrast 1e-6
+        let (nx, ny) = (24, 24);
+        let kappa: Vec<f64> = (0..nx * ny)
+            .map(|c| {
+                if ((c % nx) / 4 + (c / nx) / 4) % 2 == 0 {
+                    1.0
+                } else {
+                    1e-6
+                }
+            })
+            .collect();
+        let (values, col_indices, row_ptr) = diffusion_2d(nx, ny, &kappa);
+        let a = Csr::new(&values, &col_indices, &row_ptr);
+        let b = vec![1e-3; nx * ny];
+        let x0 = vec![0.0; nx * ny];
+
+        let mut options = SolverOptions::new();
+        let jacobi = pcg_with_options(&a, &b, &x0, &options);
+        options.preconditioner = PreconditionerKind::Ic0;
+        let ic0 = pcg_with_options(&a, &b, &x0, &options);
+        assert!(ic0.criterion.is_some() && jacobi.criterion.is_some());
+        assert!(3 * ic0.iterations < 2 * jacobi.iterations);
+    }
+
+    #[test]
+    fn test_ilu0_for_nonsymmetric_solvers() {
+        // ILU(0) is the exact LU here, so both solvers converge at once
+        let n = 400;
+        let (values, col_indices, row_ptr) = convection_diffusion_1d(n, 0.8);
+        let a = Csr::new(&values, &col_indices, &row_ptr);
+        let b: Vec<f64> = (0..n).map(|i| (i as f64 * 0.03).sin()).collect();
+        let x0 = vec![0.0; n];
+
+        let mut options = SolverOptions::new();
+        for kind in [SolverKind::Bicgstab, SolverKind::Gmres] {
+            options.preconditioner = PreconditionerKind::Jacobi;
+            let jacobi = run_solver(kind, &a, &b, &x0, &options);
+            options.preconditioner = PreconditionerKind::Ilu0;
+            let ilu0 = run_solver(kind, &a, &b, &x0, &options);
+            assert!(ilu0.criterion.is_some());
+            assert!(ilu0.iterations <= 2 && ilu0.iterations < jacobi.iterations);
+        }
+    }
+
+    #[test]
+    fn test_kind_applies_to_every_cg_kernel() {
+        let (nx, ny) = (24, 24);
+        let kappa: Vec<f64> = (0..nx * ny).map(|c| 1.0 + 99.0 * (c % 3) as f64).collect();
+        let (values, col_indices, row_ptr) = diffusion_2d(nx, ny, &kappa);
+        let a = Csr::new(&values, &col_indices, &row_ptr);
+        let b = vec![1.0; nx * ny];
+        let x0 = vec![0.0; nx * ny];
+
+        let mut options = SolverOptions::new();
+        for variant in [CgVariant::Classic, CgVariant::Pipelined, CgVariant::SStep] {
+            options.cg_variant = variant;
+            options.preconditioner = PreconditionerKind::Jacobi;
+            let jacobi = run_solver(SolverKind::Pcg, &a, &b, &x0, &options);
+            options.preconditioner = PreconditionerKind::Ic0;
+            let ic0 = run_solver(SolverKind::Pcg, &a, &b, &x0, &options);
+            assert!(ic0.criterion.is_some());
+            assert!(ic0.iterations < jacobi.iterations);
+        }
+    }
+}
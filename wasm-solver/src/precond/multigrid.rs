@@ -0,0 +1,202 @@
+use super::{BlockJacobi, Footprint, Preconditioner};
+use crate::dense::DenseFactor;
+use crate::kernels::{axpy, norm, Csr, SparseMatrix};
+
+/// Coarsening stops once a level has at most this many unknowns
+const COARSE_SIZE: usize = 200;
+/// Largest coarsest level solved by a dense factorization; bigger ones are smoothed
+const MAX_DIRECT: usize = 2000;
+const MAX_LEVELS: usize = 12;
+/// Power iterations used to estimate the spectral radius of M^{-1} A
+const POWER_STEPS: usize = 20;
+
+/// One level of the hierarchy: its operator, the nodal block-Jacobi
+/// smoother with its damping and the prolongator from the next coarser level
+struct Level {
+    a: SparseMatrix,
+    smoother: BlockJacobi,
+    omega: f64,
+    p: SparseMatrix,
+}
+
+enum CoarseSolve {
+    /// Dense factor of the coarsest operator
+    Direct(DenseFactor),
+    /// Too large for a dense factor (coarsening stalled): smooth instead
+    Smooth,
+}
+
+/// Galerkin multigrid hierarchy applied as one V-cycle
+///
+/// The prolongators come from the caller (aggregation for AMG,
+/// interpolation for geometric multigrid); coarse operators are always
+/// P^T A P. Every level takes one damped block-Jacobi pre- and
+/// post-smoothing step over its nodes (scalar Jacobi cannot damp the
+/// oscillating modes of strongly coupled DOFs), so the cycle is symmetric
+/// and can precondition CG.
+pub(crate) struct Multigrid {
+    levels: Vec<Level>,
+    coarse: SparseMatrix,
+    coarse_smoother: BlockJacobi,
+    coarse_omega: f64,
+    coarse_solve: CoarseSolve,
+}
+
+impl Multigrid {
+    /// Build the hierarchy for `a`, whose rows are grouped into `nodes`
+    /// (`nodes[g]..nodes[g + 1]` are the rows of node g)
+    ///
+    /// `coarsen` receives each level's operator and nodes and returns the
+    /// prolongator from the next coarser level with that level's nodes, or
+    /// `None` to make the current level the coarsest.
+    pub fn new(
+        a: &Csr,
+        nodes: Vec<usize>,
+        mut coarsen: impl FnMut(&Csr, &[usize]) -> Option<(SparseMatrix, Vec<usize>)>,
+    ) -> Self {
+        let mut levels = Vec::new();
+        let mut current = SparseMatrix::from_csr(a);
+        let mut nodes = nodes;
+        while current.row_ptr.len() - 1 > COARSE_SIZE && levels.len() + 1 < MAX_LEVELS {
+            let fine = current.csr();
+            let Some((p, coarse_nodes)) = coarsen(&fine, &nodes) else {
+                break;
+            };
+            if p.ncols >= fine.n() {
+                break;
+            }
+            let smoother = BlockJacobi::with_ranges(&fine, nodes);
+            let omega = 4.0 / (3.0 * spectral_radius(&fine, &smoother));
+
+            let ap = SparseMatrix::multiply(&fine, &p);
+            let coarse = SparseMatrix::multiply(&p.transpose().csr(), &ap);
+            levels.push(Level {
+                a: current,
+                smoother,
+                omega,
+                p,
+            });
+            current = coarse;
+            nodes = coarse_nodes;
+        }
+
+        let coarse_csr = current.csr();
+        let coarse_smoother = BlockJacobi::with_ranges(&coarse_csr, nodes);
+        let coarse_omega = 4.0 / (3.0 * spectral_radius(&coarse_csr, &coarse_smoother));
+        let nc = coarse_csr.n();
+        let coarse_solve = if nc <= MAX_DIRECT {
+            DenseFactor::from_csr(&coarse_csr).map_or(CoarseSolve::Smooth, CoarseSolve::Direct)
+        } else {
+            CoarseSolve::Smooth
+        };
+
+        Multigrid {
+            levels,
+            coarse: current,
+            coarse_smoother,
+            coarse_omega,
+            coarse_solve,
+        }
+    }
+
+    /// Number of levels above the coarse one, 0 if `a` was not coarsened
+    #[cfg(test)]
+    pub fn depth(&self) -> usize {
+        self.levels.len()
+    }
+
+    /// z = M^{-1} r, one V-cycle
+    pub fn apply(&self, r: &[f64], z: &mut [f64]) {
+        self.cycle(0, r, z);
+    }
+
+    fn cycle(&self, depth: usize, r: &[f64], z: &mut [f64]) {
+        let Some(level) = self.levels.get(depth) else {
+            match &self.coarse_solve {
+                CoarseSolve::Direct(factor) => {
+                    z.copy_from_slice(r);
+                    factor.solve(z);
+                }
+                CoarseSolve::Smooth => {
+                    z.iter_mut().for_each(|v| *v = 0.0);
+                    let a = self.coarse.csr();
+                    for _ in 0..10 {
+                        smooth(&a, &self.coarse_smoother, self.coarse_omega, r, z);
+                    }
+                }
+            }
+            return;
+        };
+
+        let a = level.a.csr();
+        let n = r.len();
+        z.iter_mut().for_each(|v| *v = 0.0);
+        smooth(&a, &level.smoother, level.omega, r, z);
+
+        // Coarse-grid correction z += P A_c^{-1} P^T (r - A z)
+        let mut res = vec![0.0; n];
+        a.residual(r, z, &mut res);
+        let nc = level.p.ncols;
+        let mut rc = vec![0.0; nc];
+        level.p.csr().spmv_transpose(&res, &mut rc);
+        let mut zc = vec![0.0; nc];
+        self.cycle(depth + 1, &rc, &mut zc);
+        level.p.csr().spmv(&zc, &mut res);
+        for (zi, ci) in z.iter_mut().zip(&res) {
+            *zi += ci;
+        }
+
+        smooth(&a, &level.smoother, level.omega, r, z);
+    }
+
+    /// Storage of all levels, the coarse factor included
+    pub fn footprint(&self) -> Footprint {
+        let coarse = match &self.coarse_solve {
+            CoarseSolve::Direct(factor) => factor.footprint(),
+            CoarseSolve::Smooth => Footprint::default(),
+        };
+        self.levels
+            .iter()
+            .map(|level| {
+                Footprint::matrix(&level.a)
+                    + level.smoother.footprint()
+                    + Footprint::matrix(&level.p)
+            })
+            .sum::<Footprint>()
+            + Footprint::matrix(&self.coarse)
+            + self.coarse_smoother.footprint()
+            + coarse
+    }
+}
+
+/// z = z + omega B^{-1} (r - A z) with the block diagonal B of A
+fn smooth(a: &Csr, smoother: &BlockJacobi, omega: f64, r: &[f64], z: &mut [f64]) {
+    let n = r.len();
+    let (mut res, mut dz) = (vec![0.0; n], vec![0.0; n]);
+    a.residual(r, z, &mut res);
+    smoother.apply(&res, &mut dz);
+    axpy(omega, &dz, z);
+}
+
+/// Power-iteration estimate of the spectral radius of M^{-1} A
+pub(super) fn spectral_radius(a: &Csr, m: &dyn Preconditioner) -> f64 {
+    let n = a.n();
+    let mut x: Vec<f64> = (0..n).map(|i| 1.0 + ((i * 7919) % 13) as f64).collect();
+    let (mut y, mut t) = (vec![0.0; n], vec![0.0; n]);
+    let mut rho = 1.0;
+    for _ in 0..POWER_STEPS {
+        let xnorm = norm(&x);
+        if xnorm == 0.0 || !xnorm.is_finite() {
+            break;
+        }
+        a.spmv(&x, &mut t);
+        m.apply(&t, &mut y);
+        rho = norm(&y) / xnorm;
+        std::mem::swap(&mut x, &mut y);
+    }
+    if rho > 0.0 && rho.is_finite() {
+        rho
+    } else {
+        1.0
+    }
+}
@@ -0,0 +1,280 @@
+use std::ops::Add;
+
+use wasm_bindgen::prelude::*;
+
+use super::Preconditioner;
+use crate::kernels::SparseMatrix;
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_namespace = performance, js_name = now)]
+    fn performance_now() -> f64;
+}
+
+/// Wall-clock milliseconds from an arbitrary origin: `performance.now()`
+/// in the browser, the system clock natively
+fn now_ms() -> f64 {
+    #[cfg(target_arch = "wasm32")]
+    {
+        performance_now()
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        use std::time::{SystemTime, UNIX_EPOCH};
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0.0, |d| d.as_secs_f64() * 1e3)
+    }
+}
+
+/// Storage held by a preconditioner after setup
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Footprint {
+    /// Stored matrix and vector entries (factor nonzeros, diagonals,
+    /// copies of A)
+    pub nnz: usize,
+    /// Bytes of all arrays, index arrays included
+    pub bytes: usize,
+}
+
+impl Footprint {
+    /// Entries and bytes of a vector of values
+    pub fn values<T>(v: &[T]) -> Self {
+        Footprint {
+            nnz: v.len(),
+            bytes: std::mem::size_of_val(v),
+        }
+    }
+
+    /// Bytes of an index or bookkeeping array, which adds no entries
+    pub fn indices<T>(v: &[T]) -> Self {
+        Footprint {
+            nnz: 0,
+            bytes: std::mem::size_of_val(v),
+        }
+    }
+
+    pub fn matrix(m: &SparseMatrix) -> Self {
+        Self::values(&m.values) + Self::indices(&m.col_indices) + Self::indices(&m.row_ptr)
+    }
+}
+
+impl Add for Footprint {
+    type Output = Footprint;
+
+    fn add(self, other: Footprint) -> Footprint {
+        Footprint {
+            nnz: self.nnz + other.nnz,
+            bytes: self.bytes + other.bytes,
+        }
+    }
+}
+
+impl std::iter::Sum for Footprint {
+    fn sum<I: Iterator<Item = Footprint>>(iter: I) -> Footprint {
+        iter.fold(Footprint::default(), Add::add)
+    }
+}
+
+/// Cost of a preconditioner setup, for comparing kinds on a real browser
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug)]
+pub struct SetupReport {
+    time_ms: f64,
+    footprint: Footprint,
+}
+
+#[wasm_bindgen]
+impl SetupReport {
+    /// Wall time of the setup in milliseconds
+    #[wasm_bindgen(getter)]
+    pub fn time_ms(&self) -> f64 {
+        self.time_ms
+    }
+
+    /// Stored entries: factor nonzeros for incomplete factorizations,
+    /// all levels for multigrid, the copy of A for SpMV-based kinds
+    #[wasm_bindgen(getter)]
+    pub fn nnz(&self) -> usize {
+        self.footprint.nnz
+    }
+
+    /// Estimated memory in bytes, index arrays included
+    #[wasm_bindgen(getter)]
+    pub fn bytes(&self) -> usize {
+        self.footprint.bytes
+    }
+}
+
+/// Vectors of length n allocated by one PCG solve: x, r, z, p and A p
+const PCG_WORK_VECTORS: usize = 5;
+
+/// Memory held by a matrix, preconditioner or solver handle, in bytes
+///
+/// Lets the page see what a model costs before the browser tab runs out
+/// of memory. Work vectors are those a call allocates for its duration
+/// (the Krylov vectors of a solve) plus any kept between calls (recycled
+/// directions); a preconditioner's own apply scratch is not counted.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MemoryReport {
+    values: usize,
+    indices: usize,
+    factors: usize,
+    work: usize,
+}
+
+impl MemoryReport {
+    /// Values and index arrays of a stored matrix
+    pub(crate) fn matrix(m: &SparseMatrix) -> Self {
+        MemoryReport {
+            values: std::mem::size_of_val(m.values.as_slice()),
+            indices: std::mem::size_of_val(m.col_indices.as_slice())
+                + std::mem::size_of_val(m.row_ptr.as_slice()),
+            ..Self::default()
+        }
+    }
+
+    pub(crate) fn with_values(mut self, bytes: usize) -> Self {
+        self.values += bytes;
+        self
+    }
+
+    pub(crate) fn with_indices(mut self, bytes: usize) -> Self {
+        self.indices += bytes;
+        self
+    }
+
+    pub(crate) fn with_factors(mut self, bytes: usize) -> Self {
+        self.factors += bytes;
+        self
+    }
+
+    pub(crate) fn with_work(mut self, bytes: usize) -> Self {
+        self.work += bytes;
+        self
+    }
+
+    /// Add the vectors of a PCG solve of size n
+    pub(crate) fn with_pcg_work(self, n: usize) -> Self {
+        self.with_work(PCG_WORK_VECTORS * n * std::mem::size_of::<f64>())
+    }
+}
+
+#[wasm_bindgen]
+impl MemoryReport {
+    /// Matrix entries
+    #[wasm_bindgen(getter)]
+    pub fn values(&self) -> usize {
+        self.values
+    }
+
+    /// Column indices, row (or block, column) pointers and kept patterns
+    #[wasm_bindgen(getter)]
+    pub fn indices(&self) -> usize {
+        self.indices
+    }
+
+    /// Preconditioner storage or factors, their index arrays included
+    #[wasm_bindgen(getter)]
+    pub fn factors(&self) -> usize {
+        self.factors
+    }
+
+    /// Work vectors of a solve and vectors kept between solves
+    #[wasm_bindgen(getter)]
+    pub fn work(&self) -> usize {
+        self.work
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn total(&self) -> usize {
+        self.values + self.indices + self.factors + self.work
+    }
+}
+
+/// Run a preconditioner setup and report its cost
+pub(crate) fn timed<P: Preconditioner>(build: impl FnOnce() -> P) -> (P, SetupReport) {
+    let start = now_ms();
+    let m = build();
+    let report = SetupReport {
+        time_ms: (now_ms() - start).max(0.0),
+        footprint: m.footprint(),
+    };
+    (m, report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::direct::factorize;
+    use crate::kernels::Csr;
+    use crate::krylov::pcg_with_options;
+    use crate::matrix::CsrMatrix;
+    use crate::options::{PreconditionerKind, SolverOptions};
+    use crate::precond::{Ic0, Ict, Jacobi, PreconditionerHandle};
+    use crate::test_util::diffusion_2d;
+
+    #[test]
+    fn test_footprint_counts_factor_entries() {
+        let (nx, ny) = (12, 10);
+        let (values, col_indices, row_ptr) = diffusion_2d(nx, ny, &vec![1.0; nx * ny]);
+        let a = Csr::new(&values, &col_indices, &row_ptr);
+        let n = a.n();
+
+        let (jacobi, report) = timed(|| Jacobi::new(&a));
+        assert_eq!(jacobi.footprint(), Footprint::values(&a.diagonal()));
+        assert_eq!((report.nnz(), report.bytes()), (n, 8 * n));
+        assert!(report.time_ms() >= 0.0);
+
+        // IC(0) keeps the lower triangle of A
+        let lower = (values.len() + n) / 2;
+        assert_eq!(Ic0::new(&a).unwrap().footprint().nnz, lower);
+        assert!(Ict::new(&a, 1e-4, 8).unwrap().footprint().nnz > lower);
+    }
+
+    #[test]
+    fn test_reported_by_result_and_handle() {
+        let (values, col_indices, row_ptr) = diffusion_2d(16, 16, &[1.0; 256]);
+        let a = Csr::new(&values, &col_indices, &row_ptr);
+        let mut options = SolverOptions::new();
+        options.preconditioner = PreconditionerKind::Amg;
+        options.block_size = 1;
+
+        let result = pcg_with_options(&a, &[1.0; 256], &[0.0; 256], &options);
+        let solve_report = result.setup().unwrap();
+        let handle = PreconditionerHandle::new(&values, &col_indices, &row_ptr, &options);
+        assert_eq!(handle.setup().nnz(), solve_report.nnz());
+        // The hierarchy holds at least a copy of A
+        assert!(solve_report.nnz() > values.len());
+        assert!(solve_report.bytes() > 12 * values.len());
+    }
+
+    #[test]
+    fn test_memory_report_of_handles() {
+        let (nx, ny) = (10, 10);
+        let (values, col_indices, row_ptr) = diffusion_2d(nx, ny, &vec![1.0; nx * ny]);
+        let (n, nnz) = (nx * ny, values.len());
+        let csr = CsrMatrix::new(&values, &col_indices, &row_ptr);
+        let report = csr.memory_report();
+        assert_eq!(report.values(), 8 * nnz);
+        assert_eq!(report.indices(), 4 * (nnz + n + 1));
+        assert_eq!((report.factors(), report.work()), (0, 40 * n));
+        assert_eq!(report.total(), 8 * nnz + 4 * (nnz + n + 1) + 40 * n);
+        // Half storage keeps about half the entries
+        assert!(csr.to_symmetric().memory_report().values() < report.values() / 2 + 8 * n);
+
+        let mut options = SolverOptions::new();
+        options.preconditioner = PreconditionerKind::Ic0;
+        let handle = PreconditionerHandle::new(&values, &col_indices, &row_ptr, &options);
+        let m = handle.memory_report();
+        assert_eq!(m.values(), report.values());
+        assert_eq!(m.factors(), handle.setup().bytes());
+
+        let factor = factorize(&values, &col_indices, &row_ptr).unwrap();
+        let f = factor.memory_report();
+        assert!(f.factors() >= 8 * factor.nnz());
+        assert_eq!(f.work(), 8 * n);
+    }
+}
@@ -0,0 +1,148 @@
+use super::{Footprint, Ilu0, Jacobi, Preconditioner};
+use crate::dense::DenseFactor;
+use crate::graph::{expand, partition};
+use crate::kernels::{Csr, SparseMatrix};
+
+/// Largest subdomain solved by a dense factorization; bigger ones use
+/// ILU(0)
+const MAX_DIRECT: usize = 400;
+
+enum LocalSolve {
+    /// Dense factor of the subdomain matrix
+    Direct(DenseFactor),
+    /// ILU(0) of the subdomain matrix, or its diagonal if that breaks down
+    Incomplete(Box<dyn Preconditioner>),
+}
+
+struct Subdomain {
+    /// Rows of A in the subdomain, overlap included, sorted
+    rows: Vec<usize>,
+    solve: LocalSolve,
+}
+
+/// Overlapping additive Schwarz preconditioner
+///
+/// The matrix graph is split into `subdomains` slabs of nearly equal size
+/// (`graph::partition`), each grown by `overlap` rings of neighbours.
+/// M^{-1} = sum_i R_i^T A_i^{-1} R_i with A_i = R_i A R_i^T the subdomain
+/// matrix, so M stays symmetric for symmetric A and the preconditioner
+/// works with PCG. The subdomain solves are independent of each other,
+/// only their sum couples them, so builds with threads can run them in
+/// parallel. One level only: the iteration count grows with the number of
+/// subdomains.
+pub(crate) struct Schwarz {
+    subdomains: Vec<Subdomain>,
+}
+
+impl Schwarz {
+    pub fn new(a: &Csr, subdomains: usize, overlap: usize) -> Self {
+        let subdomains = partition(a, subdomains)
+            .iter()
+            .map(|core| {
+                let rows = expand(a, core, overlap);
+                let local = SparseMatrix::submatrix(a, &rows);
+                Subdomain {
+                    solve: Self::factorize(&local.csr()),
+                    rows,
+                }
+            })
+            .collect();
+        Schwarz { subdomains }
+    }
+
+    fn factorize(local: &Csr) -> LocalSolve {
+        if local.n() <= MAX_DIRECT {
+            if let Some(factor) = DenseFactor::from_csr(local) {
+                return LocalSolve::Direct(factor);
+            }
+        }
+        LocalSolve::Incomplete(match Ilu0::new(local) {
+            Some(factor) => Box::new(factor),
+            None => Box::new(Jacobi::new(local)),
+        })
+    }
+}
+
+impl Preconditioner for Schwarz {
+    /// z = sum_i R_i^T A_i^{-1} R_i r
+    fn apply(&self, r: &[f64], z: &mut [f64]) {
+        z.iter_mut().for_each(|v| *v = 0.0);
+        let mut local_r = Vec::new();
+        let mut local_z = Vec::new();
+        for sub in self.subdomains.iter() {
+            local_r.clear();
+            local_r.extend(sub.rows.iter().map(|&i| r[i]));
+            match &sub.solve {
+                LocalSolve::Direct(factor) => {
+                    factor.solve(&mut local_r);
+                    for (&i, v) in sub.rows.iter().zip(&local_r) {
+                        z[i] += v;
+                    }
+                }
+                LocalSolve::Incomplete(m) => {
+                    local_z.resize(sub.rows.len(), 0.0);
+                    m.apply(&local_r, &mut local_z);
+                    for (&i, v) in sub.rows.iter().zip(&local_z) {
+                        z[i] += v;
+                    }
+                }
+            }
+        }
+    }
+
+    fn footprint(&self) -> Footprint {
+        self.subdomains
+            .iter()
+            .map(|sub| {
+                Footprint::indices(&sub.rows)
+                    + match &sub.solve {
+                        LocalSolve::Direct(factor) => factor.footprint(),
+                        LocalSolve::Incomplete(m) => m.footprint(),
+                    }
+            })
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::krylov::pcg_with_options;
+    use crate::options::{PreconditionerKind, SolverOptions};
+    use crate::test_util::diffusion_2d;
+
+    #[test]
+    fn test_single_subdomain_is_exact() {
+        let (values, col_indices, row_ptr) = diffusion_2d(10, 8, &[1.0; 80]);
+        let a = Csr::new(&values, &col_indices, &row_ptr);
+        let b: Vec<f64> = (0..80).map(|i| (i as f64 * 0.3).sin()).collect();
+        let mut options = SolverOptions::new();
+        options.preconditioner = PreconditionerKind::Schwarz;
+        options.subdomains = 1;
+        let result = pcg_with_options(&a, &b, &[0.0; 80], &options);
+        assert!(result.criterion.is_some());
+        assert!(result.iterations <= 1);
+    }
+
+    #[test]
+    fn test_overlap_lowers_iterations() {
+        let (nx, ny) = (40, 40);
+        let kappa: Vec<f64> = (0..nx * ny).map(|c| 1.0 + (c % 3) as f64).collect();
+        let (values, col_indices, row_ptr) = diffusion_2d(nx, ny, &kappa);
+        let a = Csr::new(&values, &col_indices, &row_ptr);
+        let b = vec![1.0; nx * ny];
+        let x0 = vec![0.0; nx * ny];
+
+        let mut options = SolverOptions::new();
+        let jacobi = pcg_with_options(&a, &b, &x0, &options);
+        options.preconditioner = PreconditionerKind::Schwarz;
+        options.subdomains = 8;
+        options.overlap = 0;
+        let block = pcg_with_options(&a, &b, &x0, &options);
+        options.overlap = 2;
+        let overlapping = pcg_with_options(&a, &b, &x0, &options);
+        assert!(overlapping.criterion.is_some() && block.criterion.is_some());
+        assert!(block.iterations < jacobi.iterations);
+        assert!(2 * overlapping.iterations < block.iterations);
+    }
+}
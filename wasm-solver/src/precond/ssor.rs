@@ -0,0 +1,87 @@
+use super::{Footprint, Preconditioner};
+use crate::kernels::{Csr, SparseMatrix};
+use crate::stationary::{sor_sweep, SweepDirection};
+
+/// SSOR preconditioner
+///
+/// M = (D + omega L) D^{-1} (D + omega U) / (omega (2 - omega)), whose
+/// inverse is exactly one symmetric SOR sweep on A z = r started from
+/// z = 0. M is SPD whenever A is and 0 < omega < 2, so it can be used
+/// inside CG. Setup only extracts the diagonal; the sweeps read a copy
+/// of A, so the preconditioner can outlive the arrays it was built from.
+pub(crate) struct Ssor {
+    a: SparseMatrix,
+    diag: Vec<f64>,
+    omega: f64,
+}
+
+impl Ssor {
+    pub fn new(a: &Csr, omega: f64) -> Self {
+        Ssor {
+            a: SparseMatrix::from_csr(a),
+            diag: a.diagonal(),
+            omega,
+        }
+    }
+}
+
+impl Preconditioner for Ssor {
+    /// z = M^{-1} r
+    fn apply(&self, r: &[f64], z: &mut [f64]) {
+        z.iter_mut().for_each(|v| *v = 0.0);
+        sor_sweep(
+            &self.a.csr(),
+            &self.diag,
+            r,
+            z,
+            self.omega,
+            SweepDirection::Symmetric,
+        );
+    }
+
+    fn footprint(&self) -> Footprint {
+        Footprint::matrix(&self.a) + Footprint::values(&self.diag)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kernels::dot;
+    use crate::krylov::pcg_with_options;
+    use crate::options::{PreconditionerKind, SolverOptions};
+    use crate::test_util::diffusion_2d;
+
+    #[test]
+    fn test_operator_is_symmetric() {
+        let (nx, ny) = (6, 5);
+        let kappa: Vec<f64> = (0..nx * ny).map(|c| 1.0 + (c % 3) as f64).collect();
+        let (values, col_indices, row_ptr) = diffusion_2d(nx, ny, &kappa);
+        let a = Csr::new(&values, &col_indices, &row_ptr);
+        let ssor = Ssor::new(&a, 1.3);
+
+        let u: Vec<f64> = (0..nx * ny).map(|i| (i as f64 * 0.7).sin()).collect();
+        let v: Vec<f64> = (0..nx * ny).map(|i| (i as f64 * 0.3).cos()).collect();
+        let (mut mu, mut mv) = (vec![0.0; nx * ny], vec![0.0; nx * ny]);
+        ssor.apply(&u, &mut mu);
+        ssor.apply(&v, &mut mv);
+        assert!((dot(&v, &mu) - dot(&u, &mv)).abs() < 1e-12 * dot(&u, &mu).abs());
+    }
+
+    #[test]
+    fn test_fewer_pcg_iterations_than_jacobi() {
+        let (nx, ny) = (32, 32);
+        let (values, col_indices, row_ptr) = diffusion_2d(nx, ny, &vec![1.0; nx * ny]);
+        let a = Csr::new(&values, &col_indices, &row_ptr);
+        let b = vec![1.0; nx * ny];
+        let x0 = vec![0.0; nx * ny];
+
+        let mut options = SolverOptions::new();
+        let jacobi = pcg_with_options(&a, &b, &x0, &options);
+        options.preconditioner = PreconditionerKind::Ssor;
+        options.ssor_omega = 1.5;
+        let ssor = pcg_with_options(&a, &b, &x0, &options);
+        assert!(ssor.criterion.is_some());
+        assert!(2 * ssor.iterations <= jacobi.iterations);
+    }
+}
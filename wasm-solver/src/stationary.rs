@@ -0,0 +1,368 @@
+//! Stationary iterations, usable as solvers or as smoothers, and simple
+//! reference iterations (steepest descent, damped Richardson)
+
+use wasm_bindgen::prelude::*;
+
+use crate::kernels::{apply_jacobi, axpy, dot, norm, threshold, Csr};
+use crate::SolveResult;
+
+/// Order in which Gauss-Seidel/SOR visits the rows
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SweepDirection {
+    /// Rows 0, 1, ..., n-1
+    Forward = 0,
+    /// Rows n-1, ..., 0
+    Backward = 1,
+    /// A forward sweep followed by a backward sweep (SSOR); keeps the
+    /// smoother symmetric for use inside CG
+    Symmetric = 2,
+}
+
+/// Gauss-Seidel (`omega = 1`) or SOR solver
+///
+/// # Arguments
+/// * `values`, `col_indices`, `row_ptr`, `b`, `x0` - As in `solve_pcg`
+/// * `omega` - Relaxation factor in (0, 2)
+/// * `direction` - Sweep order
+/// * `tol` - Convergence tolerance, checked after every sweep
+/// * `max_iter` - Maximum number of sweeps (a symmetric sweep counts once)
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn solve_sor(
+    values: &[f64],
+    col_indices: &[u32],
+    row_ptr: &[u32],
+    b: &[f64],
+    x0: &[f64],
+    omega: f64,
+    direction: SweepDirection,
+    tol: f64,
+    max_iter: u32,
+) -> SolveResult {
+    let a = Csr::new(values, col_indices, row_ptr);
+    sor(&a, b, x0, omega, direction, tol, max_iter)
+}
+
+/// Apply a fixed number of SOR sweeps to `x` in place (smoother use)
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn sor_smooth(
+    values: &[f64],
+    col_indices: &[u32],
+    row_ptr: &[u32],
+    b: &[f64],
+    x: &mut [f64],
+    omega: f64,
+    direction: SweepDirection,
+    sweeps: u32,
+) {
+    let a = Csr::new(values, col_indices, row_ptr);
+    let diag = a.diagonal();
+    for _ in 0..sweeps {
+        sor_sweep(&a, &diag, b, x, omega, direction);
+    }
+}
+
+pub(crate) fn sor(
+    a: &Csr,
+    b: &[f64],
+    x0: &[f64],
+    omega: f64,
+    direction: SweepDirection,
+    tol: f64,
+    max_iter: u32,
+) -> SolveResult {
+    let n = b.len();
+    let mut x: Vec<f64> = x0.to_vec();
+    let diag = a.diagonal();
+    let threshold = threshold(b, tol);
+
+    let mut r = vec![0.0; n];
+    a.residual(b, &x, &mut r);
+    let mut rnorm = norm(&r);
+
+    let mut iter = 0u32;
+    while rnorm >= threshold && iter < max_iter {
+        iter += 1;
+        sor_sweep(a, &diag, b, &mut x, omega, direction);
+        a.residual(b, &x, &mut r);
+        rnorm = norm(&r);
+        if !rnorm.is_finite() {
+            break;
+        }
+    }
+
+    SolveResult::new(x, iter, rnorm)
+}
+
+/// Jacobi-preconditioned steepest descent
+///
+/// Takes the same arguments as `solve_pcg`. Each step does an exact line
+/// search along z = M^{-1} r; convergence is slow but the energy norm of the
+/// error decreases monotonically, which makes it a simple reference.
+#[wasm_bindgen]
+pub fn solve_steepest_descent(
+    values: &[f64],
+    col_indices: &[u32],
+    row_ptr: &[u32],
+    b: &[f64],
+    x0: &[f64],
+    tol: f64,
+    max_iter: u32,
+) -> SolveResult {
+    let a = Csr::new(values, col_indices, row_ptr);
+    steepest_descent(&a, b, x0, tol, max_iter)
+}
+
+/// Damped Richardson iteration x = x + omega * M^{-1} (b - A x)
+///
+/// # Arguments
+/// * `values`, `col_indices`, `row_ptr`, `b`, `x0` - As in `solve_pcg`
+/// * `omega` - Damping factor; converges for 0 < omega < 2 / lambda_max(M^{-1} A)
+/// * `tol` - Convergence tolerance
+/// * `max_iter` - Maximum number of iterations
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn solve_richardson(
+    values: &[f64],
+    col_indices: &[u32],
+    row_ptr: &[u32],
+    b: &[f64],
+    x0: &[f64],
+    omega: f64,
+    tol: f64,
+    max_iter: u32,
+) -> SolveResult {
+    let a = Csr::new(values, col_indices, row_ptr);
+    richardson(&a, b, x0, omega, tol, max_iter)
+}
+
+pub(crate) fn steepest_descent(
+    a: &Csr,
+    b: &[f64],
+    x0: &[f64],
+    tol: f64,
+    max_iter: u32,
+) -> SolveResult {
+    let n = b.len();
+    let mut x: Vec<f64> = x0.to_vec();
+    let diag = a.diagonal();
+    let threshold = threshold(b, tol);
+
+    let mut r = vec![0.0; n]; // Residual
+    let mut z = vec![0.0; n]; // Preconditioned residual
+    let mut az = vec![0.0; n]; // A * z
+
+    a.residual(b, &x, &mut r);
+    let mut rnorm = norm(&r);
+
+    let mut iter = 0u32;
+    while rnorm >= threshold && iter < max_iter {
+        iter += 1;
+
+        // alpha = (r^T z) / (z^T A z)
+        apply_jacobi(&diag, &r, &mut z);
+        a.spmv(&z, &mut az);
+        let zaz = dot(&z, &az);
+        if zaz.abs() < 1e-30 {
+            break;
+        }
+        let alpha = dot(&r, &z) / zaz;
+
+        // x = x + alpha * z, r = r - alpha * A*z
+        axpy(alpha, &z, &mut x);
+        axpy(-alpha, &az, &mut r);
+        rnorm = norm(&r);
+    }
+
+    SolveResult::new(x, iter, rnorm)
+}
+
+pub(crate) fn richardson(
+    a: &Csr,
+    b: &[f64],
+    x0: &[f64],
+    omega: f64,
+    tol: f64,
+    max_iter: u32,
+) -> SolveResult {
+    let n = b.len();
+    let mut x: Vec<f64> = x0.to_vec();
+    let diag = a.diagonal();
+    let threshold = threshold(b, tol);
+
+    let mut r = vec![0.0; n]; // Residual
+    let mut z = vec![0.0; n]; // Preconditioned residual
+
+    a.residual(b, &x, &mut r);
+    let mut rnorm = norm(&r);
+
+    let mut iter = 0u32;
+    while rnorm >= threshold && iter < max_iter {
+        iter += 1;
+
+        // x = x + omega * M^{-1} r
+        apply_jacobi(&diag, &r, &mut z);
+        axpy(omega, &z, &mut x);
+        a.residual(b, &x, &mut r);
+        rnorm = norm(&r);
+        if !rnorm.is_finite() {
+            break;
+        }
+    }
+
+    SolveResult::new(x, iter, rnorm)
+}
+
+/// One SOR sweep over the rows in the given direction
+pub(crate) fn sor_sweep(
+    a: &Csr,
+    diag: &[f64],
+    b: &[f64],
+    x: &mut [f64],
+    omega: f64,
+    direction: SweepDirection,
+) {
+    let n = a.n();
+    match direction {
+        SweepDirection::Forward => (0..n).for_each(|i| relax_row(a, diag, b, x, omega, i)),
+        SweepDirection::Backward => (0..n)
+            .rev()
+            .for_each(|i| relax_row(a, diag, b, x, omega, i)),
+        SweepDirection::Symmetric => {
+            (0..n).for_each(|i| relax_row(a, diag, b, x, omega, i));
+            (0..n)
+                .rev()
+                .for_each(|i| relax_row(a, diag, b, x, omega, i));
+        }
+    }
+}
+
+/// x_i = x_i + omega * (b_i - A_i x) / a_ii, using the latest values of x
+#[inline]
+fn relax_row(a: &Csr, diag: &[f64], b: &[f64], x: &mut [f64], omega: f64, i: usize) {
+    let mut sum = b[i];
+    for j in a.row_ptr[i] as usize..a.row_ptr[i + 1] as usize {
+        sum -= a.values[j] * x[a.col_indices[j] as usize];
+    }
+    x[i] += omega * sum / diag[i];
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::laplacian_1d;
+
+    #[test]
+    fn test_gauss_seidel_converges() {
+        // Diagonally dominant, so every sweep order converges
+        let values = vec![4.0, 1.0, 1.0, 1.0, 4.0, 1.0, 1.0, 1.0, 4.0];
+        let col_indices = vec![0u32, 1, 2, 0, 1, 2, 0, 1, 2];
+        let row_ptr = vec![0u32, 3, 6, 9];
+        let b = vec![6.0, 6.0, 6.0];
+
+        for direction in [
+            SweepDirection::Forward,
+            SweepDirection::Backward,
+            SweepDirection::Symmetric,
+        ] {
+            let result = solve_sor(
+                &values,
+                &col_indices,
+                &row_ptr,
+                &b,
+                &[0.0; 3],
+                1.0,
+                direction,
+                1e-12,
+                200,
+            );
+            for xi in &result.solution {
+                assert!((xi - 1.0).abs() < 1e-10);
+            }
+        }
+    }
+
+    #[test]
+    fn test_over_relaxation_beats_gauss_seidel() {
+        let n = 30;
+        let (values, col_indices, row_ptr) = laplacian_1d(n);
+        let a = Csr::new(&values, &col_indices, &row_ptr);
+        let b = vec![1.0; n];
+        let x0 = vec![0.0; n];
+
+        let gs = sor(&a, &b, &x0, 1.0, SweepDirection::Forward, 1e-8, 10000);
+        let sor_opt = sor(&a, &b, &x0, 1.8, SweepDirection::Forward, 1e-8, 10000);
+
+        assert!(sor_opt.residual < 1e-8 * n as f64);
+        assert!(sor_opt.iterations < gs.iterations);
+    }
+
+    #[test]
+    fn test_smoother_damps_oscillatory_error() {
+        let n = 32;
+        let (values, col_indices, row_ptr) = laplacian_1d(n);
+        let b = vec![0.0; n];
+        // Highest-frequency error mode
+        let mut x: Vec<f64> = (0..n)
+            .map(|i| if i % 2 == 0 { 1.0 } else { -1.0 })
+            .collect();
+        let before = norm(&x);
+
+        sor_smooth(
+            &values,
+            &col_indices,
+            &row_ptr,
+            &b,
+            &mut x,
+            1.0,
+            SweepDirection::Symmetric,
+            2,
+        );
+
+        assert!(norm(&x) < 0.1 * before);
+    }
+
+    #[test]
+    fn test_steepest_descent_energy_decreases() {
+        let n = 20;
+        let (values, col_indices, row_ptr) = laplacian_1d(n);
+        let a = Csr::new(&values, &col_indices, &row_ptr);
+        let b: Vec<f64> = (0..n).map(|i| 1.0 + (i % 3) as f64).collect();
+        let x0 = vec![0.0; n];
+        // Energy functional 1/2 x^T A x - b^T x, minimal at the solution
+        let energy = |x: &[f64]| {
+            let mut ax = vec![0.0; n];
+            a.spmv(x, &mut ax);
+            0.5 * dot(x, &ax) - dot(b.as_slice(), x)
+        };
+
+        let mut previous = energy(&x0);
+        for k in 1..30 {
+            let result = steepest_descent(&a, &b, &x0, 0.0, k);
+            let current = energy(&result.solution);
+            assert!(current <= previous + 1e-12);
+            previous = current;
+        }
+
+        let result = steepest_descent(&a, &b, &x0, 1e-8, 100000);
+        assert!(result.residual < 1e-8 * norm(&b));
+    }
+
+    #[test]
+    fn test_richardson_damping() {
+        // M^{-1} A of the Laplacian has eigenvalues in (0, 2)
+        let n = 10;
+        let (values, col_indices, row_ptr) = laplacian_1d(n);
+        let a = Csr::new(&values, &col_indices, &row_ptr);
+        let b = vec![1.0; n];
+        let x0 = vec![0.0; n];
+
+        let damped = richardson(&a, &b, &x0, 0.9, 1e-8, 10000);
+        assert!(damped.residual < 1e-8 * norm(&b));
+
+        let unstable = richardson(&a, &b, &x0, 1.5, 1e-8, 200);
+        assert!(unstable.residual > norm(&b));
+    }
+}
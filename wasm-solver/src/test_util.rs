@@ -0,0 +1,223 @@
+//! Matrix generators shared by the unit tests
+
+use crate::grid::{node_count, node_index, ElementGrid};
+use crate::kernels::SparseMatrix;
+
+/// CSR arrays (values, col_indices, row_ptr)
+pub type CsrParts = (Vec<f64>, Vec<u32>, Vec<u32>);
+
+/// Build CSR arrays from a row-major dense matrix, skipping zeros
+pub fn dense_to_csr(dense: &[f64], n: usize) -> CsrParts {
+    let mut values = Vec::new();
+    let mut col_indices = Vec::new();
+    let mut row_ptr = vec![0u32];
+    for i in 0..n {
+        for j in 0..n {
+            let v = dense[i * n + j];
+            if v != 0.0 {
+                values.push(v);
+                col_indices.push(j as u32);
+            }
+        }
+        row_ptr.push(values.len() as u32);
+    }
+    (values, col_indices, row_ptr)
+}
+
+/// 1D Laplacian tridiag(-1, 2, -1): SPD, condition number grows as n^2
+pub fn laplacian_1d(n: usize) -> CsrParts {
+    tridiagonal(n, -1.0, 2.0, -1.0)
+}
+
+/// 1D convection-diffusion-reaction operator: nonsymmetric for `peclet` != 0,
+/// the reaction term keeps it diagonally dominant
+pub fn convection_diffusion_1d(n: usize, peclet: f64) -> CsrParts {
+    tridiagonal(n, -1.0 - peclet, 3.0 + peclet, -1.0 + peclet)
+}
+
+/// Five-point diffusion operator on an nx x ny grid of cells with
+/// per-cell coefficients `kappa` (harmonic averages on the faces) and
+/// Dirichlet boundaries: SPD, high contrast when `kappa` varies a lot
+pub fn diffusion_2d(nx: usize, ny: usize, kappa: &[f64]) -> CsrParts {
+    let face = |a: f64, b: f64| 2.0 * a * b / (a + b);
+    let mut values = Vec::with_capacity(5 * nx * ny);
+    let mut col_indices = Vec::with_capacity(5 * nx * ny);
+    let mut row_ptr = vec![0u32];
+    for j in 0..ny {
+        for i in 0..nx {
+            let c = j * nx + i;
+            let k = kappa[c];
+            let mut diag = 0.0;
+            let mut row: Vec<(usize, f64)> = Vec::with_capacity(5);
+            for (di, dj) in [(0i64, -1i64), (-1, 0), (1, 0), (0, 1)] {
+                let (ni, nj) = (i as i64 + di, j as i64 + dj);
+                if ni < 0 || nj < 0 || ni >= nx as i64 || nj >= ny as i64 {
+                    diag += 2.0 * k;
+                    continue;
+                }
+                let nb = nj as usize * nx + ni as usize;
+                let w = face(k, kappa[nb]);
+                diag += w;
+                row.push((nb, -w));
+            }
+            row.push((c, diag));
+            row.sort_by_key(|&(col, _)| col);
+            for (col, v) in row {
+                values.push(v);
+                col_indices.push(col as u32);
+            }
+            row_ptr.push(values.len() as u32);
+        }
+    }
+    (values, col_indices, row_ptr)
+}
+
+/// Strongly coupled two-component system K (x) C with a nearly singular
+/// 2 x 2 coupling C, DOFs numbered node by node as in 2D elasticity
+pub fn coupled_2x2(k: &CsrParts) -> CsrParts {
+    let coupling = [2.0, 1.9, 1.9, 2.0];
+    let (values, col_indices, row_ptr) = k;
+    let n = row_ptr.len() - 1;
+    let mut out = (Vec::new(), Vec::new(), vec![0u32]);
+    for i in 0..n {
+        for c in 0..2 {
+            for p in row_ptr[i] as usize..row_ptr[i + 1] as usize {
+                for d in 0..2 {
+                    out.0.push(values[p] * coupling[c * 2 + d]);
+                    out.1.push(col_indices[p] * 2 + d as u32);
+                }
+            }
+            out.2.push(out.0.len() as u32);
+        }
+    }
+    out
+}
+
+/// Five-point convection-diffusion operator on an nx x ny grid (x
+/// fastest) with central differences for a flow in +x and +y: not
+/// diagonally dominant once `peclet` > 1
+pub fn convection_diffusion_2d(nx: usize, ny: usize, peclet: f64) -> CsrParts {
+    let mut values = Vec::with_capacity(5 * nx * ny);
+    let mut col_indices = Vec::with_capacity(5 * nx * ny);
+    let mut row_ptr = vec![0u32];
+    for y in 0..ny {
+        for x in 0..nx {
+            let mut push = |c: usize, v: f64| {
+                values.push(v);
+                col_indices.push(c as u32);
+            };
+            let c = y * nx + x;
+            if y > 0 {
+                push(c - nx, -1.0 - peclet);
+            }
+            if x > 0 {
+                push(c - 1, -1.0 - peclet);
+            }
+            push(c, 4.0);
+            if x + 1 < nx {
+                push(c + 1, -1.0 + peclet);
+            }
+            if y + 1 < ny {
+                push(c + nx, -1.0 + peclet);
+            }
+            row_ptr.push(values.len() as u32);
+        }
+    }
+    (values, col_indices, row_ptr)
+}
+
+/// Plane-stress Q4 element stiffness of a unit square with E = 1 (the
+/// `top88` formula), row-major 8 x 8
+pub fn q4_stiffness(nu: f64) -> Vec<f64> {
+    let k = [
+        0.5 - nu / 6.0,
+        0.125 + nu / 8.0,
+        -0.25 - nu / 12.0,
+        -0.125 + 3.0 * nu / 8.0,
+        -0.25 + nu / 12.0,
+        -0.125 - nu / 8.0,
+        nu / 6.0,
+        0.125 - 3.0 * nu / 8.0,
+    ];
+    let pattern: [[usize; 8]; 8] = [
+        [0, 1, 2, 3, 4, 5, 6, 7],
+        [1, 0, 7, 6, 5, 4, 3, 2],
+        [2, 7, 0, 5, 6, 3, 4, 1],
+        [3, 6, 5, 0, 7, 2, 1, 4],
+        [4, 5, 6, 7, 0, 1, 2, 3],
+        [5, 4, 3, 2, 1, 0, 7, 6],
+        [6, 3, 4, 1, 2, 7, 0, 5],
+        [7, 2, 1, 4, 3, 6, 5, 0],
+    ];
+    let scale = 1.0 / (1.0 - nu * nu);
+    pattern.iter().flatten().map(|&p| scale * k[p]).collect()
+}
+
+/// Assembled CSR of a matrix-free grid operator, with unit rows and
+/// columns at its fixed DOFs
+pub fn assemble_grid(grid: &ElementGrid) -> CsrParts {
+    let size = grid.local_size();
+    let mut entries = Vec::new();
+    let mut dofs = Vec::new();
+    for e in 0..grid.element_count() {
+        grid.element_dofs(e, &mut dofs);
+        for (l, &i) in dofs.iter().enumerate() {
+            for (m, &j) in dofs.iter().enumerate() {
+                if !grid.is_fixed(i) && !grid.is_fixed(j) {
+                    entries.push((i, j as u32, grid.scale(e) * grid.ke()[l * size + m]));
+                }
+            }
+        }
+    }
+    entries.extend(
+        (0..grid.n())
+            .filter(|&i| grid.is_fixed(i))
+            .map(|i| (i, i as u32, 1.0)),
+    );
+    let k = SparseMatrix::from_triplets(grid.n(), grid.n(), entries);
+    (k.values, k.col_indices, k.row_ptr)
+}
+
+fn tridiagonal(n: usize, lower: f64, diag: f64, upper: f64) -> CsrParts {
+    let mut values = Vec::with_capacity(3 * n);
+    let mut col_indices = Vec::with_capacity(3 * n);
+    let mut row_ptr = vec![0u32];
+    for i in 0..n {
+        if i > 0 {
+            values.push(lower);
+            col_indices.push((i - 1) as u32);
+        }
+        values.push(diag);
+        col_indices.push(i as u32);
+        if i + 1 < n {
+            values.push(upper);
+            col_indices.push((i + 1) as u32);
+        }
+        row_ptr.push(values.len() as u32);
+    }
+    (values, col_indices, row_ptr)
+}
+
+/// DOFs `components` (0 for x, 1 for y) of every node on the left edge
+/// x = 0 of a 2D grid of `elements`
+pub fn left_edge_dofs(elements: [usize; 3], components: &[u32]) -> Vec<u32> {
+    (0..=elements[1])
+        .flat_map(|y| {
+            let n = 2 * node_index(elements, 0, y, 0) as u32;
+            components.iter().map(move |c| n + c)
+        })
+        .collect()
+}
+
+/// Supports of a cantilever clamped on the left edge of a 2D grid
+pub fn clamped_left_edge(elements: [usize; 3]) -> Vec<u32> {
+    left_edge_dofs(elements, &[0, 1])
+}
+
+/// Force vector of a 2D grid with a unit load down at the node at height
+/// `y` of the right edge
+pub fn tip_load(elements: [usize; 3], y: usize) -> Vec<f64> {
+    let mut f = vec![0.0; 2 * node_count(elements)];
+    f[2 * node_index(elements, elements[0], y, 0) + 1] = -1.0;
+    f
+}
@@ -0,0 +1,158 @@
+use crate::fem::{assemble_elements, element_energies, h8_element_stiffness, q4_element_stiffness};
+use crate::grid::{node_count, ElementGrid};
+use crate::kernels::SparseMatrix;
+use crate::krylov::run_solver;
+use crate::options::{SolverKind, SolverOptions};
+
+/// Linear elastic analysis of a structured grid of unit Q4 (`nelz` = 0)
+/// or H8 elements, shared by the optimizers
+///
+/// Holds the loads, supports, grounded springs and the displacements of
+/// the latest solve, which warm-start the next PCG solve as the design
+/// changes.
+pub(crate) struct GridAnalysis {
+    pub elements: [usize; 3],
+    pub dims: usize,
+    ke: Vec<f64>,
+    forces: Vec<f64>,
+    fixed: Vec<u32>,
+    /// Grounded springs (DOF, stiffness), added to the diagonal of K
+    springs: Vec<(u32, f64)>,
+    pub solver: SolverOptions,
+    pub u: Vec<f64>,
+    pub solver_iterations: u32,
+}
+
+impl GridAnalysis {
+    /// Unloaded, unsupported grid of Poisson's ratio `nu`; `None` if the
+    /// grid is empty or `nu` is not admissible
+    pub fn new(elements: [usize; 3], nu: f64) -> Option<Self> {
+        let [nelx, nely, nelz] = elements;
+        if nelx == 0 || nely == 0 {
+            return None;
+        }
+        let (dims, ke) = if nelz > 0 {
+            (3, h8_element_stiffness(1.0, nu, 1.0, 1.0, 1.0)?)
+        } else {
+            (2, q4_element_stiffness(1.0, nu, 1.0, 1.0, 1.0)?)
+        };
+        let n = node_count(elements) * dims;
+        Some(GridAnalysis {
+            elements,
+            dims,
+            ke,
+            forces: vec![0.0; n],
+            fixed: Vec::new(),
+            springs: Vec::new(),
+            solver: SolverOptions::new(),
+            u: vec![0.0; n],
+            solver_iterations: 0,
+        })
+    }
+
+    pub fn element_count(&self) -> usize {
+        let [nelx, nely, nelz] = self.elements;
+        nelx * nely * nelz.max(1)
+    }
+
+    /// Set the nodal force vector; `false`, changing nothing, unless it
+    /// has one entry per DOF
+    pub fn set_forces(&mut self, forces: &[f64]) -> bool {
+        if forces.len() != self.u.len() {
+            return false;
+        }
+        self.forces.copy_from_slice(forces);
+        true
+    }
+
+    /// Set the supported DOFs; `false`, changing nothing, if one is out
+    /// of range
+    pub fn set_fixed_dofs(&mut self, fixed_dofs: &[u32]) -> bool {
+        if fixed_dofs.iter().any(|&i| i as usize >= self.u.len()) {
+            return false;
+        }
+        self.fixed = fixed_dofs.to_vec();
+        true
+    }
+
+    /// Set grounded springs of stiffness `stiffness` at the DOFs `dofs`,
+    /// such as the input and output ports of a compliant mechanism;
+    /// `false`, changing nothing, unless the lengths match, every DOF is
+    /// in range and every stiffness >= 0
+    pub fn set_springs(&mut self, dofs: &[u32], stiffness: &[f64]) -> bool {
+        if dofs.len() != stiffness.len()
+            || dofs.iter().any(|&i| i as usize >= self.u.len())
+            || !stiffness.iter().all(|&k| k >= 0.0)
+        {
+            return false;
+        }
+        self.springs = dofs
+            .iter()
+            .copied()
+            .zip(stiffness.iter().copied())
+            .collect();
+        true
+    }
+
+    /// Elements of modulus `moduli` with the supports of the analysis
+    pub fn grid<'a>(&'a self, moduli: &'a [f64]) -> ElementGrid<'a> {
+        ElementGrid::new(self.elements, self.dims, &self.ke, moduli, &self.fixed)
+    }
+
+    /// K for the element moduli `moduli` with the springs, the supports
+    /// applied
+    pub fn stiffness(&self, moduli: &[f64]) -> SparseMatrix {
+        let grid = self.grid(moduli);
+        let mut k = assemble_elements(&grid);
+        for &(i, stiffness) in &self.springs {
+            let i = i as usize;
+            if !grid.is_fixed(i) {
+                let start = k.row_ptr[i] as usize;
+                let cols = &k.col_indices[start..k.row_ptr[i + 1] as usize];
+                k.values[start + cols.binary_search(&(i as u32)).unwrap()] += stiffness;
+            }
+        }
+        k
+    }
+
+    /// Solution of K x = `load` by PCG from `x0`, the load zeroed at the
+    /// supports, with the number of iterations; adjoint problems of the
+    /// self-adjoint K use the same solve
+    pub fn solve_load(&self, k: &SparseMatrix, load: &[f64], x0: &[f64]) -> (Vec<f64>, u32) {
+        let mut b = load.to_vec();
+        for &i in &self.fixed {
+            b[i as usize] = 0.0;
+        }
+        let result = run_solver(SolverKind::Pcg, &k.csr(), &b, x0, &self.solver);
+        (result.solution, result.iterations)
+    }
+
+    /// Solve K u = f for the element moduli `moduli`; returns u_e^T K_e
+    /// u_e per element for unit modulus and the compliance f^T u
+    pub fn solve(&mut self, moduli: &[f64]) -> (Vec<f64>, f64) {
+        let k = self.stiffness(moduli);
+        (self.u, self.solver_iterations) = self.solve_load(&k, &self.forces, &self.u);
+        let energies = self.energies(moduli, &self.u);
+        let compliance = self.forces.iter().zip(&self.u).map(|(f, u)| f * u).sum();
+        (energies, compliance)
+    }
+
+    /// u_e^T K_e u_e per element of the displacements `u`, for unit
+    /// modulus
+    pub fn energies(&self, moduli: &[f64], u: &[f64]) -> Vec<f64> {
+        element_energies(&self.grid(moduli), u)
+    }
+
+    /// a_e^T K_e b_e per element for unit modulus, by polarization of the
+    /// element energies, for adjoint sensitivities lambda^T (dK / drho) u
+    pub fn mixed_energies(&self, moduli: &[f64], a: &[f64], b: &[f64]) -> Vec<f64> {
+        let sum: Vec<f64> = a.iter().zip(b).map(|(x, y)| x + y).collect();
+        let difference: Vec<f64> = a.iter().zip(b).map(|(x, y)| x - y).collect();
+        let plus = self.energies(moduli, &sum);
+        let minus = self.energies(moduli, &difference);
+        plus.iter()
+            .zip(&minus)
+            .map(|(p, m)| 0.25 * (p - m))
+            .collect()
+    }
+}
@@ -0,0 +1,346 @@
+use wasm_bindgen::prelude::*;
+
+use super::analysis::GridAnalysis;
+use super::filter::FilterWeights;
+use super::BesoOptions;
+use crate::fem::simp_moduli;
+use crate::options::SolverOptions;
+
+/// Iterations whose mean compliance the convergence check compares with
+/// that of the iterations before them
+const CONVERGENCE_WINDOW: usize = 5;
+
+/// Huang and Xie's convergence criterion on the compliance `history`:
+/// the sum over the last N iterations differs relatively by at most `tol`
+/// from that over the N before
+pub(super) fn compliance_settled(history: &[f64], tol: f64) -> bool {
+    let n = history.len();
+    if n < 2 * CONVERGENCE_WINDOW {
+        return false;
+    }
+    let recent: f64 = history[n - CONVERGENCE_WINDOW..].iter().sum();
+    let before: f64 = history[n - 2 * CONVERGENCE_WINDOW..n - CONVERGENCE_WINDOW]
+        .iter()
+        .sum();
+    (recent - before).abs() <= tol * recent
+}
+
+/// Bi-directional evolutionary structural optimization, the soft-kill
+/// BESO of Huang and Xie (2010), as an alternative to `TopOpt`
+///
+/// Elements are either solid (x = 1) or removed (x = `xmin`), with E =
+/// x^p E0. Every `step` solves the same grid analysis as `TopOpt`, ranks
+/// the elements by their sensitivity numbers alpha_e = x_e^(p-1) u_e^T K_e
+/// u_e, filtered over `rmin` and averaged with those of the previous
+/// iteration, and keeps the highest ranked ones solid. The volume shrinks
+/// by the evolution rate `er` per iteration until it reaches `volfrac`;
+/// removed elements whose sensitivity rises again are added back, at most
+/// `ar_max` of all elements per iteration. The design starts fully solid.
+#[wasm_bindgen]
+pub struct Beso {
+    analysis: GridAnalysis,
+    options: BesoOptions,
+    weights: FilterWeights,
+    densities: Vec<f64>,
+    /// Sensitivity numbers of the latest update, filtered and averaged
+    alpha: Vec<f64>,
+    /// Volume fraction of the current design
+    target: f64,
+    energies: Vec<f64>,
+    compliance: Vec<f64>,
+    iteration: u32,
+    added: u32,
+    removed: u32,
+    converged: bool,
+}
+
+#[wasm_bindgen]
+impl Beso {
+    /// Solid design on a `nelx` x `nely` (x `nelz` if > 0) grid, unloaded
+    /// and unsupported. Returns `undefined` if the grid is empty,
+    /// `volfrac` is not in (0, 1], `er` or `ar_max` not in (0, 1], `xmin`
+    /// not in (0, 1), rmin <= 0 or `nu` is not admissible.
+    pub fn new(nelx: usize, nely: usize, nelz: usize, options: &BesoOptions) -> Option<Beso> {
+        let unit = |v: f64| v > 0.0 && v <= 1.0;
+        let fractions = [options.volfrac, options.er, options.ar_max];
+        let (xmin, rmin) = (options.xmin, options.rmin);
+        if !(fractions.into_iter().all(unit) && xmin > 0.0 && xmin < 1.0 && rmin > 0.0) {
+            return None;
+        }
+        let elements = [nelx, nely, nelz];
+        let analysis = GridAnalysis::new(elements, options.nu)?;
+        let count = analysis.element_count();
+        Some(Beso {
+            analysis,
+            options: *options,
+            weights: FilterWeights::new(elements, options.rmin),
+            densities: vec![1.0; count],
+            alpha: Vec::new(),
+            target: 1.0,
+            energies: vec![0.0; count],
+            compliance: Vec::new(),
+            iteration: 0,
+            added: 0,
+            removed: 0,
+            converged: false,
+        })
+    }
+
+    /// Set the nodal force vector; returns `false`, changing nothing,
+    /// unless it has one entry per DOF
+    pub fn set_forces(&mut self, forces: &[f64]) -> bool {
+        self.analysis.set_forces(forces)
+    }
+
+    /// Set the supported DOFs; returns `false`, changing nothing, if one
+    /// is out of range
+    pub fn set_fixed_dofs(&mut self, fixed_dofs: &[u32]) -> bool {
+        self.analysis.set_fixed_dofs(fixed_dofs)
+    }
+
+    /// Options of the state solves (PCG), by default those of `solve_pcg`
+    pub fn set_solver_options(&mut self, options: &SolverOptions) {
+        self.analysis.solver = *options;
+    }
+
+    /// Back to the solid initial design, keeping loads and supports
+    pub fn reset(&mut self) {
+        self.densities.fill(1.0);
+        self.alpha.clear();
+        self.target = 1.0;
+        self.analysis.u.fill(0.0);
+        self.energies.fill(0.0);
+        self.compliance.clear();
+        self.iteration = 0;
+        self.added = 0;
+        self.removed = 0;
+        self.converged = false;
+    }
+
+    /// One BESO iteration; returns whether the run has converged (or
+    /// reached `max_iter`), after which `step` does nothing
+    pub fn step(&mut self) -> bool {
+        if self.converged {
+            return true;
+        }
+        let BesoOptions {
+            volfrac,
+            er,
+            penal,
+            e0,
+            ..
+        } = self.options;
+        let moduli = simp_moduli(&self.densities, penal, e0, 0.0);
+        let (energies, compliance) = self.analysis.solve(&moduli);
+        self.energies = energies;
+        self.compliance.push(compliance);
+
+        let raw: Vec<f64> = self
+            .energies
+            .iter()
+            .zip(&self.densities)
+            .map(|(ce, x)| x.powf(penal - 1.0) * ce)
+            .collect();
+        let mut alpha = self.weights.densities(&raw);
+        // History averaging damps the oscillation of the discrete updates
+        if !self.alpha.is_empty() {
+            for (a, old) in alpha.iter_mut().zip(&self.alpha) {
+                *a = 0.5 * (*a + old);
+            }
+        }
+        self.alpha = alpha;
+        let reached = self.target <= volfrac;
+        self.target = (self.target * (1.0 - er)).max(volfrac);
+
+        let xnew = self.select();
+        self.added = 0;
+        self.removed = 0;
+        for (new, old) in xnew.iter().zip(&self.densities) {
+            if new > old {
+                self.added += 1;
+            } else if new < old {
+                self.removed += 1;
+            }
+        }
+        self.densities = xnew;
+        self.iteration += 1;
+
+        // Only once the volume has been reached
+        let settled = reached && compliance_settled(&self.compliance, self.options.tol);
+        self.converged = settled || self.iteration >= self.options.max_iter;
+        self.converged
+    }
+
+    /// Up to `steps` iterations, fewer if the run converges; returns the
+    /// number done
+    pub fn run(&mut self, steps: u32) -> u32 {
+        let mut done = 0;
+        while done < steps && !self.converged {
+            self.step();
+            done += 1;
+        }
+        done
+    }
+
+    /// Element densities, 1 or `xmin`
+    #[wasm_bindgen(getter)]
+    pub fn densities(&self) -> Vec<f64> {
+        self.densities.clone()
+    }
+
+    /// Filtered and history-averaged sensitivity numbers of the latest
+    /// update, empty before the first step
+    #[wasm_bindgen(getter)]
+    pub fn sensitivities(&self) -> Vec<f64> {
+        self.alpha.clone()
+    }
+
+    /// Displacements of the latest solve
+    #[wasm_bindgen(getter)]
+    pub fn displacements(&self) -> Vec<f64> {
+        self.analysis.u.clone()
+    }
+
+    /// u_e^T K_e u_e per element of the latest solve, for unit modulus
+    #[wasm_bindgen(getter)]
+    pub fn strain_energy(&self) -> Vec<f64> {
+        self.energies.clone()
+    }
+
+    /// Compliance f^T u of the design before the latest update; infinite
+    /// before the first step
+    #[wasm_bindgen(getter)]
+    pub fn compliance(&self) -> f64 {
+        self.compliance.last().copied().unwrap_or(f64::INFINITY)
+    }
+
+    /// Compliance of every iteration so far
+    #[wasm_bindgen(getter)]
+    pub fn compliance_history(&self) -> Vec<f64> {
+        self.compliance.clone()
+    }
+
+    /// Fraction of solid elements of the current design
+    #[wasm_bindgen(getter)]
+    pub fn volume(&self) -> f64 {
+        let solid = self.densities.iter().filter(|&&x| x == 1.0).count();
+        solid as f64 / self.densities.len() as f64
+    }
+
+    /// Elements added back in the latest update
+    #[wasm_bindgen(getter)]
+    pub fn added(&self) -> u32 {
+        self.added
+    }
+
+    /// Elements removed in the latest update
+    #[wasm_bindgen(getter)]
+    pub fn removed(&self) -> u32 {
+        self.removed
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn iteration(&self) -> u32 {
+        self.iteration
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn converged(&self) -> bool {
+        self.converged
+    }
+
+    /// PCG iterations of the latest solve
+    #[wasm_bindgen(getter)]
+    pub fn solver_iterations(&self) -> u32 {
+        self.analysis.solver_iterations
+    }
+}
+
+impl Beso {
+    /// Design of volume fraction `target` keeping the highest-ranked
+    /// elements solid. When that would add back more than `ar_max` of
+    /// all elements, only the best ranked removed elements up to that
+    /// limit are added and the rest of the volume is taken from the best
+    /// ranked solid ones.
+    fn select(&self) -> Vec<f64> {
+        let n = self.densities.len();
+        let solid_count = (self.target * n as f64).round() as usize;
+        let mut order: Vec<usize> = (0..n).collect();
+        order.sort_by(|&a, &b| self.alpha[b].total_cmp(&self.alpha[a]));
+        let solid = |e: usize| self.densities[e] == 1.0;
+
+        let max_added = (self.options.ar_max * n as f64).floor() as usize;
+        let added = order[..solid_count].iter().filter(|&&e| !solid(e)).count();
+        let mut xnew = vec![self.options.xmin; n];
+        if added <= max_added {
+            for &e in &order[..solid_count] {
+                xnew[e] = 1.0;
+            }
+        } else {
+            let add = order.iter().filter(|&&e| !solid(e)).take(max_added);
+            let keep = order
+                .iter()
+                .filter(|&&e| solid(e))
+                .take(solid_count - max_added);
+            for &e in add.chain(keep) {
+                xnew[e] = 1.0;
+            }
+        }
+        xnew
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{clamped_left_edge, tip_load};
+
+    /// Cantilever clamped on the left edge, loaded down at the middle of
+    /// the right edge
+    fn cantilever(nelx: usize, nely: usize, options: &BesoOptions) -> Beso {
+        let mut beso = Beso::new(nelx, nely, 0, options).unwrap();
+        let elements = [nelx, nely, 0];
+        assert!(beso.set_fixed_dofs(&clamped_left_edge(elements)));
+        assert!(beso.set_forces(&tip_load(elements, nely / 2)));
+        beso
+    }
+
+    #[test]
+    fn test_cantilever_evolves_to_volume() {
+        let options = BesoOptions {
+            rmin: 2.0,
+            ..BesoOptions::new()
+        };
+        let mut beso = cantilever(32, 16, &options);
+        beso.run(150);
+        assert!(beso.converged() && beso.iteration() < 150);
+        assert!((beso.volume() - 0.5).abs() < 1e-3);
+        assert!(beso.densities().iter().all(|&x| x == 1.0 || x == 1e-3));
+        // Half the material, far stiffer than a uniform grey design would
+        // be, and settled over the final iterations
+        let history = beso.compliance_history();
+        let (solid, last) = (history[0], beso.compliance());
+        assert!(last > solid && last < 2.0 * solid);
+        assert!(beso.removed() + beso.added() < 20);
+        assert!(Beso::new(0, 16, 0, &options).is_none());
+    }
+
+    #[test]
+    fn test_addition_ratio_limits_additions() {
+        let options = BesoOptions {
+            rmin: 2.0,
+            ar_max: 0.01,
+            max_iter: 60,
+            ..BesoOptions::new()
+        };
+        let mut beso = cantilever(24, 12, &options);
+        let mut volumes = Vec::new();
+        while !beso.step() {
+            assert!(beso.added() as f64 <= 0.01 * 288.0);
+            volumes.push(beso.volume());
+        }
+        // The volume still follows the evolution rate
+        assert!(volumes.windows(2).all(|w| w[1] <= w[0] + 1e-12));
+        assert!((beso.volume() - 0.5).abs() < 1e-2);
+    }
+}
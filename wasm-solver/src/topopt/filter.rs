@@ -0,0 +1,275 @@
+use wasm_bindgen::prelude::*;
+
+use super::helmholtz::HelmholtzFilter;
+use super::FilterKind;
+use crate::kernels::SparseMatrix;
+use crate::options::SolverOptions;
+
+/// Neighbour weights H_ei = max(0, rmin - |c_e - c_i|) between the element
+/// centres of a structured grid, the `prepareFilter` weights of
+/// `filter.ts`, as the rows of a sparse matrix
+pub(crate) struct FilterWeights {
+    h: SparseMatrix,
+    /// Row sums of H
+    hs: Vec<f64>,
+}
+
+impl FilterWeights {
+    pub fn new(elements: [usize; 3], rmin: f64) -> Self {
+        let [nelx, nely, nelz] = elements;
+        let layers = nelz.max(1);
+        let reach = rmin.ceil().max(0.0) as usize;
+        let near = |c: usize, len: usize| c.saturating_sub(reach)..(c + reach + 1).min(len);
+        let mut entries = Vec::new();
+        for z in 0..layers {
+            for x in 0..nelx {
+                for y in 0..nely {
+                    let e = z * nelx * nely + x * nely + y;
+                    for zz in near(z, layers) {
+                        for xx in near(x, nelx) {
+                            for yy in near(y, nely) {
+                                let d = [x.abs_diff(xx), y.abs_diff(yy), z.abs_diff(zz)];
+                                let dist = d.iter().map(|&c| (c * c) as f64).sum::<f64>().sqrt();
+                                let w = rmin - dist;
+                                if w > 0.0 {
+                                    let i = zz * nelx * nely + xx * nely + yy;
+                                    entries.push((e, i as u32, w));
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        let count = nelx * nely * layers;
+        let h = SparseMatrix::from_triplets(count, count, entries);
+        let hs = h
+            .row_ptr
+            .windows(2)
+            .map(|r| h.values[r[0] as usize..r[1] as usize].iter().sum())
+            .collect();
+        FilterWeights { h, hs }
+    }
+
+    /// Weighted neighbours (i, H_ei) of element e
+    fn row(&self, e: usize) -> impl Iterator<Item = (usize, f64)> + '_ {
+        let range = self.h.row_ptr[e] as usize..self.h.row_ptr[e + 1] as usize;
+        self.h.col_indices[range.clone()]
+            .iter()
+            .zip(&self.h.values[range])
+            .map(|(&i, &w)| (i as usize, w))
+    }
+
+    /// Sigmund's sensitivity filter: sum_i H_ei x_i dc_i / (x_e sum_i H_ei),
+    /// x_e kept above 1e-9 as in `applySensitivityFilter`
+    pub fn sensitivities(&self, x: &[f64], dc: &[f64]) -> Vec<f64> {
+        (0..self.hs.len())
+            .map(|e| {
+                let sum: f64 = self.row(e).map(|(i, w)| w * x[i] * dc[i]).sum();
+                sum / (x[e].max(1e-9) * self.hs[e])
+            })
+            .collect()
+    }
+
+    /// Density filter x~_e = sum_i H_ei x_i / sum_i H_ei
+    pub fn densities(&self, x: &[f64]) -> Vec<f64> {
+        (0..self.hs.len())
+            .map(|e| self.row(e).map(|(i, w)| w * x[i]).sum::<f64>() / self.hs[e])
+            .collect()
+    }
+
+    /// Chain rule through `densities`, df/dx_i = sum_e H_ei df/dx~_e /
+    /// sum_j H_ej, summed over the row of i as H is symmetric
+    pub fn backproject(&self, df: &[f64]) -> Vec<f64> {
+        (0..self.hs.len())
+            .map(|i| self.row(i).map(|(e, w)| w * df[e] / self.hs[e]).sum())
+            .collect()
+    }
+
+    fn count(&self) -> usize {
+        self.hs.len()
+    }
+}
+
+/// Linear density filter of radius `rmin` on a structured grid
+///
+/// The weights max(0, rmin - |c_e - c_i|) between element centres are
+/// computed once; each iteration then filters the design variables into
+/// physical densities with `apply` and maps the sensitivities of any
+/// response by the physical densities back to the design variables with
+/// `backproject`. Elements use the FEM numbering of `TopOpt`.
+#[wasm_bindgen]
+pub struct DensityFilter {
+    weights: FilterWeights,
+}
+
+#[wasm_bindgen]
+impl DensityFilter {
+    /// Filter of a `nelx` x `nely` (x `nelz` if > 0) grid; `undefined`
+    /// if the grid is empty or rmin <= 0
+    pub fn new(nelx: usize, nely: usize, nelz: usize, rmin: f64) -> Option<DensityFilter> {
+        (nelx > 0 && nely > 0 && rmin > 0.0).then(|| DensityFilter {
+            weights: FilterWeights::new([nelx, nely, nelz], rmin),
+        })
+    }
+
+    /// Number of elements
+    #[wasm_bindgen(getter)]
+    pub fn element_count(&self) -> usize {
+        self.weights.count()
+    }
+
+    /// Physical densities of the design variables `x`; `undefined` unless
+    /// `x` holds one value per element
+    pub fn apply(&self, x: &[f64]) -> Option<Vec<f64>> {
+        (x.len() == self.weights.count()).then(|| self.weights.densities(x))
+    }
+
+    /// Sensitivities by the design variables from sensitivities `df` by
+    /// the physical densities; `undefined` as `apply`
+    pub fn backproject(&self, df: &[f64]) -> Option<Vec<f64>> {
+        (df.len() == self.weights.count()).then(|| self.weights.backproject(df))
+    }
+}
+
+/// Sigmund's (1997) sensitivity filter of radius `rmin` on a structured
+/// grid, the filter of `simp.ts` and of the 99-line code
+///
+/// dc~_e = sum_i H_ei x_i dc_i / (x_e sum_i H_ei) smooths the
+/// sensitivities of the undiluted design directly; it is heuristic (dc~
+/// is not the gradient of any function) but reproduces the classic
+/// benchmark results. Weights and numbering as in `DensityFilter`.
+#[wasm_bindgen]
+pub struct SensitivityFilter {
+    weights: FilterWeights,
+}
+
+#[wasm_bindgen]
+impl SensitivityFilter {
+    /// Filter of a `nelx` x `nely` (x `nelz` if > 0) grid; `undefined`
+    /// if the grid is empty or rmin <= 0
+    pub fn new(nelx: usize, nely: usize, nelz: usize, rmin: f64) -> Option<SensitivityFilter> {
+        (nelx > 0 && nely > 0 && rmin > 0.0).then(|| SensitivityFilter {
+            weights: FilterWeights::new([nelx, nely, nelz], rmin),
+        })
+    }
+
+    /// Number of elements
+    #[wasm_bindgen(getter)]
+    pub fn element_count(&self) -> usize {
+        self.weights.count()
+    }
+
+    /// Filtered sensitivities `dc` of the densities `x`; `undefined`
+    /// unless both hold one value per element
+    pub fn apply(&self, x: &[f64], dc: &[f64]) -> Option<Vec<f64>> {
+        let count = self.weights.count();
+        (x.len() == count && dc.len() == count).then(|| self.weights.sensitivities(x, dc))
+    }
+}
+
+/// Filter of a `TopOpt` run, by `OptimizerOptions::filter`
+pub(crate) enum DesignFilter {
+    Sensitivity(FilterWeights),
+    Density(FilterWeights),
+    Helmholtz(HelmholtzFilter),
+}
+
+impl DesignFilter {
+    pub fn new(kind: FilterKind, elements: [usize; 3], rmin: f64) -> Option<Self> {
+        Some(match kind {
+            FilterKind::Sensitivity => {
+                DesignFilter::Sensitivity(FilterWeights::new(elements, rmin))
+            }
+            FilterKind::Density => DesignFilter::Density(FilterWeights::new(elements, rmin)),
+            FilterKind::Helmholtz => {
+                let [nelx, nely, nelz] = elements;
+                let options = SolverOptions::new();
+                DesignFilter::Helmholtz(HelmholtzFilter::new(nelx, nely, nelz, rmin, &options)?)
+            }
+        })
+    }
+
+    /// Physical densities of the design variables `x`
+    pub fn physical(&self, x: &[f64]) -> Vec<f64> {
+        match self {
+            DesignFilter::Sensitivity(_) => x.to_vec(),
+            DesignFilter::Density(weights) => weights.densities(x),
+            DesignFilter::Helmholtz(helmholtz) => helmholtz.filter(x),
+        }
+    }
+
+    /// Objective and volume sensitivities by the design variables `x`
+    /// from those by the physical densities
+    pub fn sensitivities(&self, x: &[f64], dc: &[f64], dv: &[f64]) -> (Vec<f64>, Vec<f64>) {
+        match self {
+            DesignFilter::Sensitivity(weights) => (weights.sensitivities(x, dc), dv.to_vec()),
+            DesignFilter::Density(weights) => (weights.backproject(dc), weights.backproject(dv)),
+            DesignFilter::Helmholtz(helmholtz) => {
+                (helmholtz.transpose(dc), helmholtz.transpose(dv))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_weights_of_prepare_filter() {
+        // rmin 1.5 on a 3 x 3 grid: the centre element sees all 8
+        // neighbours, the edge ones at distance 1 with weight 0.5
+        let filter = FilterWeights::new([3, 3, 0], 1.5);
+        let centre: Vec<(usize, f64)> = filter.row(4).collect();
+        assert_eq!(centre.len(), 9);
+        assert!((filter.hs[4] - (1.5 + 4.0 * 0.5 + 4.0 * (1.5 - 2f64.sqrt()))).abs() < 1e-12);
+
+        // Uniform fields pass unchanged
+        let x = vec![0.4; 9];
+        let dc = vec![-2.0; 9];
+        assert!(filter
+            .sensitivities(&x, &dc)
+            .iter()
+            .all(|v| (v + 2.0).abs() < 1e-12));
+        assert_eq!(FilterWeights::new([2, 2, 2], 1.0).hs, vec![1.0; 8]);
+    }
+
+    #[test]
+    fn test_backproject_is_transpose_of_apply() {
+        let filter = DensityFilter::new(5, 4, 2, 2.0).unwrap();
+        let x: Vec<f64> = (0..40).map(|e| 0.1 + (e % 7) as f64 / 8.0).collect();
+        let df: Vec<f64> = (0..40).map(|e| ((e * 3) % 5) as f64 - 2.0).collect();
+        let xf = filter.apply(&x).unwrap();
+        let back = filter.backproject(&df).unwrap();
+        let lhs: f64 = df.iter().zip(&xf).map(|(a, b)| a * b).sum();
+        let rhs: f64 = back.iter().zip(&x).map(|(a, b)| a * b).sum();
+        assert!((lhs - rhs).abs() < 1e-12);
+        // Smoothing keeps the density range and a uniform design
+        let (lo, hi) = (0.1, 0.1 + 6.0 / 8.0);
+        assert!(xf.iter().all(|&v| v >= lo - 1e-12 && v <= hi + 1e-12));
+        assert!(filter
+            .apply(&[0.3; 40])
+            .unwrap()
+            .iter()
+            .all(|v| (v - 0.3).abs() < 1e-12));
+        assert!(filter.apply(&[0.3; 39]).is_none());
+        assert!(DensityFilter::new(5, 4, 0, 0.0).is_none());
+    }
+
+    #[test]
+    fn test_sensitivity_filter_weights_by_density() {
+        // A lone solid element in void: its neighbours' filtered
+        // sensitivities come mostly from it
+        let filter = SensitivityFilter::new(3, 3, 0, 1.5).unwrap();
+        let mut x = vec![1e-3; 9];
+        x[4] = 1.0;
+        let dc = vec![-1.0; 9];
+        let filtered = filter.apply(&x, &dc).unwrap();
+        let hs = filter.weights.hs[4];
+        assert!((filtered[4] + (1.5 + 1e-3 * (hs - 1.5)) / hs).abs() < 1e-12);
+        assert!(filtered[1] < -50.0);
+        assert!(filter.apply(&x, &dc[1..]).is_none());
+        assert_eq!(filter.element_count(), 9);
+    }
+}
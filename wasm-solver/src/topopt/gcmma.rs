@@ -0,0 +1,231 @@
+use wasm_bindgen::prelude::*;
+
+use super::mma::{valid_evaluation, Asymptotes, Subproblem};
+
+/// Smallest curvature of the GCMMA approximations
+const RAA_MIN: f64 = 1e-6;
+/// Tolerance of the conservativeness check, half the subproblem barrier
+/// tolerance as in Svanberg's `concheck`
+const CONSERVATIVE_TOL: f64 = 0.5e-7;
+/// Inner iterations after which the candidate is accepted regardless
+const MAX_INNER: u32 = 20;
+
+/// Initial curvature rho_i = 0.1 / n sum_j |df_i/dx_j| (x_max - x_min)_j
+/// of an outer iteration
+fn initial_curvature(asymptotes: &Asymptotes, df: &[f64]) -> f64 {
+    let s: f64 = df
+        .iter()
+        .enumerate()
+        .map(|(j, d)| d.abs() * asymptotes.width(j))
+        .sum();
+    (0.1 * s / df.len() as f64).max(RAA_MIN)
+}
+
+/// Globally convergent MMA (Svanberg 2002, 2007) for the problem of `Mma`
+///
+/// An outer iteration builds the MMA approximations around x_k and
+/// proposes their minimizer. The caller evaluates the objective and
+/// constraints at the candidate and hands them to `inner`: if an
+/// approximation falls below its function there, its curvature rho_i is
+/// raised and a new, more cautious candidate proposed, until every
+/// approximation is conservative. The accepted candidate is the next
+/// outer iterate. This costs extra function evaluations (no gradients)
+/// per outer iteration but stops the oscillation plain MMA can show on
+/// strongly non-convex responses such as stress constraints and compliant
+/// mechanisms.
+#[wasm_bindgen]
+pub struct Gcmma {
+    asymptotes: Asymptotes,
+    x: Vec<f64>,
+    f0val: f64,
+    df0dx: Vec<f64>,
+    fval: Vec<f64>,
+    dfdx: Vec<f64>,
+    rho0: f64,
+    rho: Vec<f64>,
+    subproblem: Option<Subproblem>,
+    candidate: Vec<f64>,
+    inner: u32,
+}
+
+#[wasm_bindgen]
+impl Gcmma {
+    /// Optimizer for the design box [xmin, xmax]; `undefined` as
+    /// `Mma::new`
+    pub fn new(xmin: &[f64], xmax: &[f64]) -> Option<Gcmma> {
+        Some(Gcmma {
+            asymptotes: Asymptotes::new(xmin, xmax)?,
+            x: Vec::new(),
+            f0val: 0.0,
+            df0dx: Vec::new(),
+            fval: Vec::new(),
+            dfdx: Vec::new(),
+            rho0: 0.0,
+            rho: Vec::new(),
+            subproblem: None,
+            candidate: Vec::new(),
+            inner: 0,
+        })
+    }
+
+    /// Start an outer iteration at x from the values and gradients of
+    /// the objective and constraints there, arguments as `Mma::update`;
+    /// returns the first candidate, or `undefined` on a size mismatch
+    pub fn outer(
+        &mut self,
+        x: &[f64],
+        f0val: f64,
+        df0dx: &[f64],
+        fval: &[f64],
+        dfdx: &[f64],
+    ) -> Option<Vec<f64>> {
+        let n = self.asymptotes.n();
+        if !valid_evaluation(n, x, df0dx, fval, dfdx) {
+            return None;
+        }
+        self.asymptotes.update(x);
+        let asymptotes = &self.asymptotes;
+        self.rho0 = initial_curvature(asymptotes, df0dx);
+        self.rho = dfdx
+            .chunks_exact(n.max(1))
+            .map(|df| initial_curvature(asymptotes, df))
+            .collect();
+        self.x = x.to_vec();
+        self.f0val = f0val;
+        self.df0dx = df0dx.to_vec();
+        self.fval = fval.to_vec();
+        self.dfdx = dfdx.to_vec();
+        self.inner = 0;
+        self.propose();
+        Some(self.candidate.clone())
+    }
+
+    /// Check the candidate against the objective `f0new` and constraints
+    /// `fnew` evaluated there. Returns `true` when the candidate is
+    /// accepted as the next outer iterate, because every approximation is
+    /// conservative or after MAX_INNER inner iterations, and `false` after
+    /// proposing a new `candidate`; `undefined` before the first `outer`
+    /// or if `fnew` has the wrong length.
+    pub fn inner(&mut self, f0new: f64, fnew: &[f64]) -> Option<bool> {
+        let sub = self.subproblem.as_ref()?;
+        if fnew.len() != self.fval.len() {
+            return None;
+        }
+        let (f0app, fapp) = sub.values(&self.candidate);
+        let conservative = f0app + CONSERVATIVE_TOL >= f0new
+            && fapp
+                .iter()
+                .zip(fnew)
+                .all(|(a, f)| a + CONSERVATIVE_TOL >= *f);
+        if conservative || self.inner >= MAX_INNER {
+            return Some(true);
+        }
+
+        // d(x) = sum_j (U_j - L_j) (x_j - x_k,j)^2 /
+        // ((U_j - x_j) (x_j - L_j) (x_max - x_min)_j), the growth of
+        // f~_i per unit rho_i at the candidate
+        let (low, upp) = (&self.asymptotes.low, &self.asymptotes.upp);
+        let d = (0..self.x.len())
+            .map(|j| {
+                let (xc, xk) = (self.candidate[j], self.x[j]);
+                let step = (xc - xk) * (xc - xk) / ((upp[j] - xc) * (xc - low[j]));
+                step * (upp[j] - low[j]) / self.asymptotes.width(j)
+            })
+            .sum::<f64>()
+            .max(1e-12);
+        let raise = |rho: f64, app: f64, f: f64| {
+            if app + CONSERVATIVE_TOL < f {
+                (1.1 * (rho + (f - app) / d)).min(10.0 * rho)
+            } else {
+                rho
+            }
+        };
+        self.rho0 = raise(self.rho0, f0app, f0new);
+        for ((rho, &app), &f) in self.rho.iter_mut().zip(&fapp).zip(fnew) {
+            *rho = raise(*rho, app, f);
+        }
+        self.inner += 1;
+        self.propose();
+        Some(false)
+    }
+
+    /// Current candidate of the outer iteration
+    #[wasm_bindgen(getter)]
+    pub fn candidate(&self) -> Vec<f64> {
+        self.candidate.clone()
+    }
+
+    /// Inner iterations of the current outer iteration
+    #[wasm_bindgen(getter)]
+    pub fn inner_iterations(&self) -> u32 {
+        self.inner
+    }
+
+    /// Number of outer iterations so far
+    #[wasm_bindgen(getter)]
+    pub fn iteration(&self) -> u32 {
+        self.asymptotes.iteration
+    }
+}
+
+impl Gcmma {
+    /// Solve the approximations of the current curvatures for a new
+    /// candidate; GCMMA needs no move limit beyond the asymptotes
+    fn propose(&mut self) {
+        let sub = Subproblem::new(
+            &self.asymptotes,
+            &self.x,
+            self.f0val,
+            &self.df0dx,
+            &self.fval,
+            &self.dfdx,
+            self.rho0,
+            &self.rho,
+            f64::INFINITY,
+        );
+        self.candidate = sub.solve();
+        self.subproblem = Some(sub);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Svanberg's cantilever beam of five hollow square segments: weight
+    /// 0.0624 sum x_j subject to the tip deflection 61 / x_1^3 + 37 / x_2^3
+    /// + 19 / x_3^3 + 7 / x_4^3 + 1 / x_5^3 <= 1
+    fn beam(x: &[f64]) -> (f64, Vec<f64>, Vec<f64>, Vec<f64>) {
+        let c = [61.0, 37.0, 19.0, 7.0, 1.0];
+        let f0 = 0.0624 * x.iter().sum::<f64>();
+        let f = c.iter().zip(x).map(|(c, v)| c / v.powi(3)).sum::<f64>() - 1.0;
+        let df = c.iter().zip(x).map(|(c, v)| -3.0 * c / v.powi(4)).collect();
+        (f0, vec![0.0624; 5], vec![f], df)
+    }
+
+    #[test]
+    fn test_gcmma_solves_beam() {
+        let mut gcmma = Gcmma::new(&[1.0; 5], &[10.0; 5]).unwrap();
+        assert!(gcmma.inner(0.0, &[0.0]).is_none());
+        let mut x = vec![5.0; 5];
+        let mut evaluations = 0;
+        for _ in 0..30 {
+            let (f0, df0, f, df) = beam(&x);
+            gcmma.outer(&x, f0, &df0, &f, &df).unwrap();
+            loop {
+                let (f0, _, f, _) = beam(&gcmma.candidate());
+                evaluations += 1;
+                if gcmma.inner(f0, &f).unwrap() {
+                    break;
+                }
+            }
+            x = gcmma.candidate();
+        }
+        let expected = [6.016, 5.309, 4.494, 3.502, 2.153];
+        assert!(x.iter().zip(expected).all(|(a, b)| (a - b).abs() < 1e-2));
+        let (f0, _, f, _) = beam(&x);
+        assert!((f0 - 1.340).abs() < 1e-3 && f[0] < 1e-6);
+        // Some first candidates were not conservative
+        assert!(evaluations > 30);
+    }
+}
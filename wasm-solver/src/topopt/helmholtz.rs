@@ -0,0 +1,323 @@
+use wasm_bindgen::prelude::*;
+
+use crate::fem::{assemble_elements, h8_conduction_stiffness, q4_conduction_stiffness};
+use crate::grid::ElementGrid;
+use crate::kernels::SparseMatrix;
+use crate::krylov::run_solver;
+use crate::matrix::CsrMatrix;
+use crate::options::{SolverKind, SolverOptions};
+
+/// Local node offsets (dx, dy) of the element nodes within a layer, the
+/// order of `ElementGrid::element_dofs`
+const CORNERS: [(usize, usize); 4] = [(0, 0), (1, 0), (1, 1), (0, 1)];
+
+/// Consistent mass matrix of the unit Q4 (`nelz` = 0) or H8 element,
+/// the tensor product of the 1D mass [1/3 1/6; 1/6 1/3]
+fn unit_mass(nelz: usize) -> Vec<f64> {
+    let layers = if nelz > 0 { 2 } else { 1 };
+    let nodes = 4 * layers;
+    let offset = |l: usize| {
+        let (dx, dy) = CORNERS[l % 4];
+        [dx, dy, l / 4]
+    };
+    let m1 = |a: usize, b: usize| if a == b { 1.0 / 3.0 } else { 1.0 / 6.0 };
+    let dims = if nelz > 0 { 3 } else { 2 };
+    let mut m = vec![0.0; nodes * nodes];
+    for i in 0..nodes {
+        for j in 0..nodes {
+            let (oi, oj) = (offset(i), offset(j));
+            m[i * nodes + j] = (0..dims).map(|d| m1(oi[d], oj[d])).product();
+        }
+    }
+    m
+}
+
+/// Volume and shape function gradients of a linear triangle (`dims` = 2)
+/// or tetrahedron from its corner coordinates, `None` if it is flat
+fn simplex_gradients(points: &[[f64; 3]], dims: usize) -> Option<(f64, Vec<[f64; 3]>)> {
+    let edge = |k: usize| [0, 1, 2].map(|c| points[k][c] - points[0][c]);
+    let sub = |a: [f64; 3], b: [f64; 3]| [a[0] - b[0], a[1] - b[1], a[2] - b[2]];
+    let cross = |a: [f64; 3], b: [f64; 3]| {
+        [
+            a[1] * b[2] - a[2] * b[1],
+            a[2] * b[0] - a[0] * b[2],
+            a[0] * b[1] - a[1] * b[0],
+        ]
+    };
+    // Rows of J^-1 for J = [p_1 - p_0, ..., p_d - p_0] are the gradients
+    // of the barycentric coordinates L_1..L_d
+    let (det, mut rows) = if dims == 3 {
+        let (a, b, c) = (edge(1), edge(2), edge(3));
+        let det: f64 = (0..3).map(|k| a[k] * cross(b, c)[k]).sum();
+        (det, vec![cross(b, c), cross(c, a), cross(a, b)])
+    } else {
+        let (a, b) = (edge(1), edge(2));
+        let det = a[0] * b[1] - a[1] * b[0];
+        (det, vec![[b[1], -b[0], 0.0], [-a[1], a[0], 0.0]])
+    };
+    if det.abs() <= 1e-14 || !det.is_finite() {
+        return None;
+    }
+    rows.iter_mut()
+        .for_each(|r| r.iter_mut().for_each(|v| *v /= det));
+    let first = rows.iter().fold([0.0; 3], |acc, r| sub(acc, *r));
+    rows.insert(0, first);
+    let volume = det.abs() / if dims == 3 { 6.0 } else { 2.0 };
+    Some((volume, rows))
+}
+
+/// Helmholtz PDE filter of Lazarov and Sigmund (2011)
+///
+/// The filtered density field solves -r^2 laplace(x~) + x~ = x with zero
+/// flux on the boundary, r = rmin / (2 sqrt(3)) matching the convolution
+/// filter of radius rmin. K_F = r^2 K + M over the nodes of the mesh is
+/// assembled once; `apply` and `backproject` then cost one PCG solve each,
+/// independent of the radius, and the same code serves structured grids
+/// and unstructured triangle and tetrahedron meshes. The nodal solution
+/// is averaged over each element, and the filter preserves the volume
+/// sum_e V_e x~_e = sum_e V_e x_e up to the solver tolerance.
+#[wasm_bindgen]
+pub struct HelmholtzFilter {
+    matrix: CsrMatrix,
+    /// Nodes of every element, `nodes_per_element` each
+    connectivity: Vec<usize>,
+    nodes_per_element: usize,
+    volumes: Vec<f64>,
+    options: SolverOptions,
+}
+
+#[wasm_bindgen]
+impl HelmholtzFilter {
+    /// Filter of a `nelx` x `nely` (x `nelz` if > 0) grid of unit Q4 or
+    /// H8 elements in the FEM numbering; `undefined` if the grid is empty
+    /// or rmin <= 0
+    pub fn new(
+        nelx: usize,
+        nely: usize,
+        nelz: usize,
+        rmin: f64,
+        options: &SolverOptions,
+    ) -> Option<HelmholtzFilter> {
+        if !(nelx > 0 && nely > 0 && rmin > 0.0) {
+            return None;
+        }
+        let r = rmin / (2.0 * 3f64.sqrt());
+        let laplace = if nelz > 0 {
+            h8_conduction_stiffness(r * r, 1.0, 1.0, 1.0)?
+        } else {
+            q4_conduction_stiffness(r * r, 1.0, 1.0, 1.0)?
+        };
+        let ke: Vec<f64> = laplace
+            .iter()
+            .zip(unit_mass(nelz))
+            .map(|(k, m)| k + m)
+            .collect();
+        let count = nelx * nely * nelz.max(1);
+        let scales = vec![1.0; count];
+        let grid = ElementGrid::new([nelx, nely, nelz], 1, &ke, &scales, &[]);
+        let mut connectivity = Vec::with_capacity(count * grid.local_size());
+        let mut nodes = Vec::with_capacity(grid.local_size());
+        for e in 0..count {
+            grid.element_dofs(e, &mut nodes);
+            connectivity.extend_from_slice(&nodes);
+        }
+        Some(HelmholtzFilter {
+            matrix: CsrMatrix::from_matrix(assemble_elements(&grid)),
+            connectivity,
+            nodes_per_element: grid.local_size(),
+            volumes: vec![1.0; count],
+            options: *options,
+        })
+    }
+
+    /// Filter of an unstructured mesh of linear triangles (`dims` = 2,
+    /// x, y per node in `coords`) or tetrahedra (`dims` = 3, x, y, z),
+    /// `connectivity` holding 3 or 4 nodes per element; `undefined` if the
+    /// arrays do not match, a node is out of range, an element is flat or
+    /// rmin <= 0
+    pub fn from_mesh(
+        coords: &[f64],
+        connectivity: &[u32],
+        dims: usize,
+        rmin: f64,
+        options: &SolverOptions,
+    ) -> Option<HelmholtzFilter> {
+        let k = dims + 1;
+        let nodes = coords.len() / dims.max(1);
+        if !((dims == 2 || dims == 3) && rmin > 0.0)
+            || !coords.len().is_multiple_of(dims)
+            || connectivity.is_empty()
+            || !connectivity.len().is_multiple_of(k)
+            || connectivity.iter().any(|&i| i as usize >= nodes)
+        {
+            return None;
+        }
+        let r2 = (rmin / (2.0 * 3f64.sqrt())).powi(2);
+        let mut entries = Vec::with_capacity(connectivity.len() * k);
+        let mut volumes = Vec::with_capacity(connectivity.len() / k);
+        let mut points = vec![[0.0; 3]; k];
+        for element in connectivity.chunks_exact(k) {
+            for (p, &i) in points.iter_mut().zip(element) {
+                let i = i as usize;
+                p[..dims].copy_from_slice(&coords[dims * i..dims * (i + 1)]);
+            }
+            let (volume, grads) = simplex_gradients(&points, dims)?;
+            // r^2 V g_a . g_b + V (1 + delta_ab) / ((d + 1) (d + 2))
+            let mass = volume / (k * (k + 1)) as f64;
+            for (a, &i) in element.iter().enumerate() {
+                for (b, &j) in element.iter().enumerate() {
+                    let dot: f64 = (0..3).map(|c| grads[a][c] * grads[b][c]).sum();
+                    let m = if a == b { 2.0 * mass } else { mass };
+                    entries.push((i as usize, j, r2 * volume * dot + m));
+                }
+            }
+            volumes.push(volume);
+        }
+        Some(HelmholtzFilter {
+            matrix: CsrMatrix::from_matrix(SparseMatrix::from_triplets(nodes, nodes, entries)),
+            connectivity: connectivity.iter().map(|&i| i as usize).collect(),
+            nodes_per_element: k,
+            volumes,
+            options: *options,
+        })
+    }
+
+    /// Number of elements
+    #[wasm_bindgen(getter)]
+    pub fn element_count(&self) -> usize {
+        self.volumes.len()
+    }
+
+    /// Filtered element densities of the element densities `x`;
+    /// `undefined` unless `x` holds one value per element
+    pub fn apply(&self, x: &[f64]) -> Option<Vec<f64>> {
+        (x.len() == self.volumes.len()).then(|| self.filter(x))
+    }
+
+    /// Sensitivities by the unfiltered densities from sensitivities `df`
+    /// by the filtered ones; `undefined` as `apply`
+    pub fn backproject(&self, df: &[f64]) -> Option<Vec<f64>> {
+        (df.len() == self.volumes.len()).then(|| self.transpose(df))
+    }
+}
+
+impl HelmholtzFilter {
+    /// Nodal solution of K_F y = b, b gathering `weights[e] x_e /
+    /// nodes_per_element` from every element
+    fn solve(&self, x: &[f64], weights: &[f64]) -> Vec<f64> {
+        let k = self.nodes_per_element;
+        let mut b = vec![0.0; self.matrix.size()];
+        for ((element, &xe), &w) in self.connectivity.chunks_exact(k).zip(x).zip(weights) {
+            element.iter().for_each(|&i| b[i] += w * xe / k as f64);
+        }
+        let x0 = vec![0.0; b.len()];
+        run_solver(SolverKind::Pcg, &self.matrix.csr(), &b, &x0, &self.options).solution
+    }
+
+    /// Element averages of a nodal field, scaled by `weights`
+    fn average(&self, y: &[f64], weights: &[f64]) -> Vec<f64> {
+        let k = self.nodes_per_element;
+        self.connectivity
+            .chunks_exact(k)
+            .zip(weights)
+            .map(|(element, w)| w * element.iter().map(|&i| y[i]).sum::<f64>() / k as f64)
+            .collect()
+    }
+
+    /// x~ = T^T K_F^-1 T_V x, T_V integrating the element densities
+    /// against the shape functions and T^T averaging the nodal field
+    pub(crate) fn filter(&self, x: &[f64]) -> Vec<f64> {
+        let ones = vec![1.0; x.len()];
+        self.average(&self.solve(x, &self.volumes), &ones)
+    }
+
+    /// Transpose of `filter`, T_V^T K_F^-1 T df
+    pub(crate) fn transpose(&self, df: &[f64]) -> Vec<f64> {
+        let ones = vec![1.0; df.len()];
+        self.average(&self.solve(df, &ones), &self.volumes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options() -> SolverOptions {
+        let mut options = SolverOptions::new();
+        options.tol = 1e-12;
+        options
+    }
+
+    #[test]
+    fn test_grid_filter_smooths_and_keeps_volume() {
+        for nelz in [0, 3] {
+            let filter = HelmholtzFilter::new(8, 6, nelz, 3.0, &options()).unwrap();
+            let count = filter.element_count();
+            assert!(filter
+                .apply(&vec![0.4; count])
+                .unwrap()
+                .iter()
+                .all(|v| (v - 0.4).abs() < 1e-9));
+            // A single solid element spreads over its neighbours
+            let mut x = vec![0.0; count];
+            x[count / 2] = 1.0;
+            let xf = filter.apply(&x).unwrap();
+            assert!((xf.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+            assert!(xf[count / 2] < 0.5 && xf[count / 2 + 1] > 0.0);
+            assert!(xf.iter().all(|&v| v > -1e-12));
+
+            let df: Vec<f64> = (0..count).map(|e| (e % 5) as f64 - 2.0).collect();
+            let lhs: f64 = df.iter().zip(&xf).map(|(a, b)| a * b).sum();
+            let back = filter.backproject(&df).unwrap();
+            let rhs: f64 = back.iter().zip(&x).map(|(a, b)| a * b).sum();
+            assert!((lhs - rhs).abs() < 1e-9);
+        }
+        assert!(HelmholtzFilter::new(8, 6, 0, 0.0, &options()).is_none());
+    }
+
+    #[test]
+    fn test_unstructured_mesh_matches_grid() {
+        // The 6 x 4 grid split into right triangles: the same volume and
+        // close to the Q4 filter
+        let (nelx, nely) = (6, 4);
+        let node = |x: usize, y: usize| (x * (nely + 1) + y) as u32;
+        let mut coords = Vec::new();
+        for x in 0..=nelx {
+            for y in 0..=nely {
+                coords.extend([x as f64, y as f64]);
+            }
+        }
+        let mut connectivity = Vec::new();
+        for x in 0..nelx {
+            for y in 0..nely {
+                let (a, b, c, d) = (
+                    node(x, y),
+                    node(x + 1, y),
+                    node(x + 1, y + 1),
+                    node(x, y + 1),
+                );
+                connectivity.extend([a, b, c, a, c, d]);
+            }
+        }
+        let mesh = HelmholtzFilter::from_mesh(&coords, &connectivity, 2, 2.5, &options()).unwrap();
+        let grid = HelmholtzFilter::new(nelx, nely, 0, 2.5, &options()).unwrap();
+        assert_eq!(mesh.element_count(), 2 * nelx * nely);
+
+        let x: Vec<f64> = (0..nelx * nely)
+            .map(|e| if e % 7 == 0 { 1.0 } else { 0.1 })
+            .collect();
+        let xt: Vec<f64> = x.iter().flat_map(|&v| [v, v]).collect();
+        let (xf, xtf) = (grid.apply(&x).unwrap(), mesh.apply(&xt).unwrap());
+        let volume: f64 = xtf.iter().sum::<f64>() / 2.0;
+        assert!((volume - x.iter().sum::<f64>()).abs() < 1e-9);
+        for (e, v) in xf.iter().enumerate() {
+            let mean = (xtf[2 * e] + xtf[2 * e + 1]) / 2.0;
+            assert!((mean - v).abs() < 0.05);
+        }
+
+        // A flat triangle and a dangling node index are rejected
+        let flat = [0.0, 0.0, 1.0, 0.0, 2.0, 0.0];
+        assert!(HelmholtzFilter::from_mesh(&flat, &[0, 1, 2], 2, 1.0, &options()).is_none());
+        assert!(HelmholtzFilter::from_mesh(&flat, &[0, 1, 3], 2, 1.0, &options()).is_none());
+    }
+}
@@ -0,0 +1,423 @@
+use std::f64::consts::PI;
+
+use wasm_bindgen::prelude::*;
+
+use super::analysis::GridAnalysis;
+use super::beso::compliance_settled;
+use super::filter::FilterWeights;
+use super::volume::find_multiplier;
+use super::LevelSetOptions;
+use crate::fem::simp_moduli;
+use crate::options::SolverOptions;
+
+/// Pseudo-time steps and step size of one reinitialization, enough to
+/// restore the distance to 1% over about 4 elements on either side of
+/// the boundary
+const REINIT_STEPS: usize = 30;
+const REINIT_DT: f64 = 0.3;
+
+/// One-sided differences (phi_e - phi_prev, phi_next - phi_e) of the
+/// element values `phi` along x, y and z at element e, zero across the
+/// edge of the grid
+fn differences(phi: &[f64], elements: [usize; 3], e: usize) -> [(f64, f64); 3] {
+    let [nelx, nely, nelz] = elements;
+    let coords = [(e / nely) % nelx, e % nely, e / (nelx * nely)];
+    let lens = [nelx, nely, nelz.max(1)];
+    let strides = [nely, 1, nelx * nely];
+    std::array::from_fn(|a| {
+        let minus = if coords[a] > 0 {
+            phi[e] - phi[e - strides[a]]
+        } else {
+            0.0
+        };
+        let plus = if coords[a] + 1 < lens[a] {
+            phi[e + strides[a]] - phi[e]
+        } else {
+            0.0
+        };
+        (minus, plus)
+    })
+}
+
+/// Godunov upwind |grad phi| for phi_t + F |grad phi| = 0 with speed F
+fn upwind_norm(d: &[(f64, f64); 3], speed: f64) -> f64 {
+    d.iter()
+        .map(|&(m, p)| {
+            if speed > 0.0 {
+                m.max(0.0).powi(2) + p.min(0.0).powi(2)
+            } else {
+                m.min(0.0).powi(2) + p.max(0.0).powi(2)
+            }
+        })
+        .sum::<f64>()
+        .sqrt()
+}
+
+/// One explicit upwind step of phi_t = V |grad phi|: the boundary moves
+/// outward of the material (phi > 0) with normal velocity V
+fn advect(phi: &[f64], elements: [usize; 3], velocity: &[f64], dt: f64) -> Vec<f64> {
+    (0..phi.len())
+        .map(|e| {
+            let d = differences(phi, elements, e);
+            phi[e] + dt * velocity[e] * upwind_norm(&d, -velocity[e])
+        })
+        .collect()
+}
+
+/// Signed distance with the zero level of `phi`, by Sussman's PDE
+/// phi_t + S(phi_0) (|grad phi| - 1) = 0 with the smoothed sign S(phi_0)
+/// = phi_0 / sqrt(phi_0^2 + |grad phi_0|^2) of Peng et al., which also
+/// moves flat regions of phi
+fn reinitialize(phi: &[f64], elements: [usize; 3]) -> Vec<f64> {
+    let sign: Vec<f64> = (0..phi.len())
+        .map(|e| {
+            let d = differences(phi, elements, e);
+            let grad: f64 = d.iter().map(|(m, p)| 0.25 * (m + p) * (m + p)).sum();
+            phi[e] / (phi[e] * phi[e] + grad.max(1e-12)).sqrt()
+        })
+        .collect();
+    let mut phi = phi.to_vec();
+    for _ in 0..REINIT_STEPS {
+        phi = (0..phi.len())
+            .map(|e| {
+                let d = differences(&phi, elements, e);
+                phi[e] - REINIT_DT * sign[e] * (upwind_norm(&d, sign[e]) - 1.0)
+            })
+            .collect();
+    }
+    phi
+}
+
+/// Smoothed Heaviside of half-width one element, the material fraction
+/// of an element whose centre is at signed distance phi of the boundary
+fn heaviside(phi: f64) -> f64 {
+    if phi <= -1.0 {
+        0.0
+    } else if phi >= 1.0 {
+        1.0
+    } else {
+        0.5 * (1.0 + phi + (PI * phi).sin() / PI)
+    }
+}
+
+fn volume(phi: &[f64]) -> f64 {
+    phi.iter().map(|&p| heaviside(p)).sum::<f64>() / phi.len() as f64
+}
+
+/// Level-set topology optimization of minimum compliance on a structured
+/// grid, an alternative to `TopOpt` with crisp boundaries by construction
+///
+/// The design is the zero contour of a level set function phi, positive
+/// in the material, stored at the element centres and kept near a signed
+/// distance by reinitializing every `reinit_interval` iterations. Every
+/// `step` solves the grid analysis of `TopOpt` on the ersatz material
+/// densities xmin + (1 - xmin) H(phi), takes the shape sensitivity of the
+/// compliance, the strain energy density u_e^T K_e u_e smoothed over
+/// `rmin`, as the velocity V = g / max g - lambda (clamped to [-1, 1]) and
+/// advances phi_t = V |grad phi| by one upwind Hamilton-Jacobi step. The
+/// multiplier lambda is searched with that of the volume constraint so
+/// that the volume follows the schedule of `er` down to `volfrac`. Holes
+/// are not nucleated, so the initial design is perforated by `holes`.
+#[wasm_bindgen]
+pub struct LevelSet {
+    analysis: GridAnalysis,
+    options: LevelSetOptions,
+    weights: FilterWeights,
+    phi: Vec<f64>,
+    densities: Vec<f64>,
+    velocity: Vec<f64>,
+    /// Volume fraction of the current design
+    target: f64,
+    multiplier: f64,
+    energies: Vec<f64>,
+    compliance: Vec<f64>,
+    iteration: u32,
+    converged: bool,
+}
+
+#[wasm_bindgen]
+impl LevelSet {
+    /// Perforated initial design on a `nelx` x `nely` (x `nelz` if > 0)
+    /// grid, unloaded and unsupported. Returns `undefined` if the grid is
+    /// empty, `volfrac` or `er` is not in (0, 1], `step` not in (0,
+    /// 1/sqrt(d)] (the CFL bound of the upwind scheme for |V| <= 1),
+    /// `xmin` not in (0, 1), rmin <= 0, `reinit_interval` is 0 or `nu` is
+    /// not admissible.
+    pub fn new(
+        nelx: usize,
+        nely: usize,
+        nelz: usize,
+        options: &LevelSetOptions,
+    ) -> Option<LevelSet> {
+        let unit = |v: f64| v > 0.0 && v <= 1.0;
+        let fractions = [options.volfrac, options.er];
+        let (xmin, rmin, step) = (options.xmin, options.rmin, options.step);
+        let dims: f64 = if nelz > 0 { 3.0 } else { 2.0 };
+        let stable = step > 0.0 && step <= 1.0 / dims.sqrt();
+        if !(fractions.into_iter().all(unit) && xmin > 0.0 && xmin < 1.0 && rmin > 0.0 && stable)
+            || options.reinit_interval == 0
+        {
+            return None;
+        }
+        let elements = [nelx, nely, nelz];
+        let analysis = GridAnalysis::new(elements, options.nu)?;
+        let count = analysis.element_count();
+        let mut levelset = LevelSet {
+            analysis,
+            options: *options,
+            weights: FilterWeights::new(elements, options.rmin),
+            phi: Vec::new(),
+            densities: Vec::new(),
+            velocity: vec![0.0; count],
+            target: 1.0,
+            multiplier: 0.0,
+            energies: vec![0.0; count],
+            compliance: Vec::new(),
+            iteration: 0,
+            converged: false,
+        };
+        levelset.reset();
+        Some(levelset)
+    }
+
+    /// Set the nodal force vector; returns `false`, changing nothing,
+    /// unless it has one entry per DOF
+    pub fn set_forces(&mut self, forces: &[f64]) -> bool {
+        self.analysis.set_forces(forces)
+    }
+
+    /// Set the supported DOFs; returns `false`, changing nothing, if one
+    /// is out of range
+    pub fn set_fixed_dofs(&mut self, fixed_dofs: &[u32]) -> bool {
+        self.analysis.set_fixed_dofs(fixed_dofs)
+    }
+
+    /// Options of the state solves (PCG), by default those of `solve_pcg`
+    pub fn set_solver_options(&mut self, options: &SolverOptions) {
+        self.analysis.solver = *options;
+    }
+
+    /// Back to the perforated initial design, keeping loads and supports.
+    /// phi starts as cos(2 pi m x / nelx) cos(2 pi m y / nely) (cos(2 pi m
+    /// z / nelz)) + 0.4 with m = `holes`, before reinitialization.
+    pub fn reset(&mut self) {
+        let [nelx, nely, nelz] = self.analysis.elements;
+        let m = self.options.holes as f64;
+        let wave = |c: usize, len: usize| (2.0 * PI * m * (c as f64 + 0.5) / len as f64).cos();
+        let spacing = nelx.min(nely) as f64 / (2.0 * PI * m.max(1.0));
+        let phi: Vec<f64> = (0..self.analysis.element_count())
+            .map(|e| {
+                let (x, y, z) = ((e / nely) % nelx, e % nely, e / (nelx * nely));
+                let layers = if nelz > 0 { wave(z, nelz) } else { 1.0 };
+                spacing * (wave(x, nelx) * wave(y, nely) * layers + 0.4)
+            })
+            .collect();
+        self.phi = reinitialize(&phi, self.analysis.elements);
+        self.update_densities();
+        self.target = self.volume();
+        self.velocity.fill(0.0);
+        self.multiplier = 0.0;
+        self.analysis.u.fill(0.0);
+        self.energies.fill(0.0);
+        self.compliance.clear();
+        self.iteration = 0;
+        self.converged = false;
+    }
+
+    /// One level-set iteration; returns whether the run has converged (or
+    /// reached `max_iter`), after which `step` does nothing
+    pub fn step(&mut self) -> bool {
+        if self.converged {
+            return true;
+        }
+        let LevelSetOptions {
+            volfrac,
+            er,
+            step,
+            e0,
+            ..
+        } = self.options;
+        let moduli = simp_moduli(&self.densities, 1.0, e0, 0.0);
+        let (energies, compliance) = self.analysis.solve(&moduli);
+        self.energies = energies;
+        self.compliance.push(compliance);
+
+        // Normalized by the mean over the material so that lambda ~ 1
+        let mut g = self.weights.densities(&self.energies);
+        let (sum, solid) = g
+            .iter()
+            .zip(&self.phi)
+            .filter(|(_, &p)| p > 0.0)
+            .fold((0.0, 0.0), |(s, n), (v, _)| (s + v, n + 1.0));
+        if sum > 0.0 {
+            g.iter_mut().for_each(|v| *v *= solid / sum);
+        }
+        let reached = self.target <= volfrac;
+        self.target = if reached {
+            volfrac
+        } else {
+            (self.target * (1.0 - er)).max(volfrac)
+        };
+
+        let elements = self.analysis.elements;
+        let speed =
+            |lambda: f64| -> Vec<f64> { g.iter().map(|v| (v - lambda).clamp(-1.0, 1.0)).collect() };
+        let (lambda, _, _) = find_multiplier(self.target, |lambda| {
+            volume(&advect(&self.phi, elements, &speed(lambda), step))
+        });
+        self.multiplier = lambda;
+        self.velocity = speed(lambda);
+        self.phi = advect(&self.phi, elements, &self.velocity, step);
+        self.iteration += 1;
+        if self.iteration.is_multiple_of(self.options.reinit_interval) {
+            self.phi = reinitialize(&self.phi, elements);
+        }
+        self.update_densities();
+
+        let settled = reached && compliance_settled(&self.compliance, self.options.tol);
+        self.converged = settled || self.iteration >= self.options.max_iter;
+        self.converged
+    }
+
+    /// Up to `steps` iterations, fewer if the run converges; returns the
+    /// number done
+    pub fn run(&mut self, steps: u32) -> u32 {
+        let mut done = 0;
+        while done < steps && !self.converged {
+            self.step();
+            done += 1;
+        }
+        done
+    }
+
+    /// Level set function at the element centres, positive in the
+    /// material; its zero contour is the boundary
+    #[wasm_bindgen(getter)]
+    pub fn level_set(&self) -> Vec<f64> {
+        self.phi.clone()
+    }
+
+    /// Ersatz material densities of the analysis, one per element; only
+    /// the elements cut by the boundary are grey
+    #[wasm_bindgen(getter)]
+    pub fn densities(&self) -> Vec<f64> {
+        self.densities.clone()
+    }
+
+    /// Normal velocity of the latest update, positive where the material
+    /// grows
+    #[wasm_bindgen(getter)]
+    pub fn velocity(&self) -> Vec<f64> {
+        self.velocity.clone()
+    }
+
+    /// Volume multiplier lambda of the latest update
+    #[wasm_bindgen(getter)]
+    pub fn multiplier(&self) -> f64 {
+        self.multiplier
+    }
+
+    /// Displacements of the latest solve
+    #[wasm_bindgen(getter)]
+    pub fn displacements(&self) -> Vec<f64> {
+        self.analysis.u.clone()
+    }
+
+    /// Compliance f^T u of the design before the latest update; infinite
+    /// before the first step
+    #[wasm_bindgen(getter)]
+    pub fn compliance(&self) -> f64 {
+        self.compliance.last().copied().unwrap_or(f64::INFINITY)
+    }
+
+    /// Material volume fraction mean H(phi) of the current design
+    #[wasm_bindgen(getter)]
+    pub fn volume(&self) -> f64 {
+        volume(&self.phi)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn iteration(&self) -> u32 {
+        self.iteration
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn converged(&self) -> bool {
+        self.converged
+    }
+
+    /// PCG iterations of the latest solve
+    #[wasm_bindgen(getter)]
+    pub fn solver_iterations(&self) -> u32 {
+        self.analysis.solver_iterations
+    }
+}
+
+impl LevelSet {
+    fn update_densities(&mut self) {
+        let xmin = self.options.xmin;
+        self.densities = self
+            .phi
+            .iter()
+            .map(|&p| xmin + (1.0 - xmin) * heaviside(p))
+            .collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{clamped_left_edge, tip_load};
+
+    #[test]
+    fn test_reinitialize_restores_distance() {
+        // Steep level set of the plane x = 6 on a 12 x 4 grid
+        let elements = [12, 4, 0];
+        let phi: Vec<f64> = (0..48)
+            .map(|e| 3.0 * ((e / 4) as f64 + 0.5 - 6.0))
+            .collect();
+        let distance = reinitialize(&phi, elements);
+        for (e, d) in distance.iter().enumerate() {
+            let x = (e / 4) as f64 + 0.5;
+            if (x - 6.0).abs() < 3.0 {
+                assert!((d - (x - 6.0)).abs() < 0.02, "{e}: {d}");
+            }
+        }
+        // Growing everywhere moves the boundary by dt
+        let moved = advect(&distance, elements, &[1.0; 48], 0.5);
+        assert!((volume(&moved) - volume(&distance) - 0.5 / 12.0).abs() < 1e-2);
+    }
+
+    #[test]
+    fn test_cantilever_reaches_volume_with_crisp_boundary() {
+        let (nelx, nely) = (32, 16);
+        let options = LevelSetOptions {
+            max_iter: 120,
+            ..LevelSetOptions::new()
+        };
+        let mut levelset = LevelSet::new(nelx, nely, 0, &options).unwrap();
+        let elements = [nelx, nely, 0];
+        assert!(levelset.set_fixed_dofs(&clamped_left_edge(elements)));
+        assert!(levelset.set_forces(&tip_load(elements, nely / 2)));
+        let initial = levelset.volume();
+        assert!(initial > 0.6 && initial < 1.0);
+
+        levelset.run(120);
+        assert!(levelset.converged() && levelset.iteration() < 120);
+        assert!((levelset.volume() - 0.5).abs() < 1e-2);
+        // Grey only along the boundary
+        let grey = levelset
+            .densities()
+            .iter()
+            .filter(|&&x| x > 0.01 && x < 0.99)
+            .count();
+        assert!(grey < nelx * nely / 3);
+        let history = &levelset.compliance;
+        assert!(levelset.compliance() < 2.5 * history[0]);
+        // Beyond the CFL bound of 1/sqrt(2) in 2D and 1/sqrt(3) in 3D
+        let with_step = |step| LevelSetOptions { step, ..options };
+        assert!(LevelSet::new(32, 16, 0, &with_step(0.8)).is_none());
+        assert!(LevelSet::new(8, 4, 4, &with_step(0.65)).is_none());
+        assert!(LevelSet::new(8, 4, 4, &with_step(0.55)).is_some());
+    }
+}
@@ -0,0 +1,578 @@
+use wasm_bindgen::prelude::*;
+
+use crate::dense::{cholesky_factor, cholesky_solve};
+
+/// Initial distance of the asymptotes from x, per unit of the box width
+const ASYMPTOTE_INIT: f64 = 0.5;
+/// Widening and narrowing factors of the asymptotes when the design
+/// keeps moving the same way or oscillates
+const ASYMPTOTE_INCREASE: f64 = 1.2;
+const ASYMPTOTE_DECREASE: f64 = 0.7;
+/// Fraction of the distance to an asymptote the subproblem may move
+const ALBEFA: f64 = 0.1;
+/// Largest move of the MMA subproblem, per unit of the box width
+const MOVE_LIMIT: f64 = 0.5;
+/// Curvature of the MMA approximations that keeps them strictly convex
+const RAA0: f64 = 1e-5;
+/// Smallest box width used to scale the approximations
+const XMAMI_MIN: f64 = 1e-5;
+/// Cost c_i y_i + y_i^2 / 2 of the elastic variable of every constraint;
+/// large enough that y = 0 whenever the subproblem is feasible
+const ELASTIC_COST: f64 = 1000.0;
+/// Barrier parameter at which the subproblem solve stops
+const EPSIMIN: f64 = 1e-7;
+
+/// Moving asymptotes of the design variables, bounded by the design box
+pub(super) struct Asymptotes {
+    xmin: Vec<f64>,
+    xmax: Vec<f64>,
+    pub low: Vec<f64>,
+    pub upp: Vec<f64>,
+    xold1: Vec<f64>,
+    xold2: Vec<f64>,
+    pub iteration: u32,
+}
+
+impl Asymptotes {
+    /// Asymptotes of the box [xmin, xmax]; `None` unless the bounds have
+    /// the same length and xmin < xmax
+    pub fn new(xmin: &[f64], xmax: &[f64]) -> Option<Self> {
+        if xmin.len() != xmax.len() || !xmin.iter().zip(xmax).all(|(a, b)| a < b) {
+            return None;
+        }
+        Some(Asymptotes {
+            xmin: xmin.to_vec(),
+            xmax: xmax.to_vec(),
+            low: Vec::new(),
+            upp: Vec::new(),
+            xold1: Vec::new(),
+            xold2: Vec::new(),
+            iteration: 0,
+        })
+    }
+
+    pub fn n(&self) -> usize {
+        self.xmin.len()
+    }
+
+    /// Width x_max - x_min of the box of variable j, at least XMAMI_MIN
+    pub fn width(&self, j: usize) -> f64 {
+        (self.xmax[j] - self.xmin[j]).max(XMAMI_MIN)
+    }
+
+    /// Place the asymptotes around the iterate x_k: at a fixed distance in
+    /// the first two iterations, then widened where x moved the same way
+    /// in the last two iterations and narrowed where it oscillated
+    pub fn update(&mut self, x: &[f64]) {
+        self.iteration += 1;
+        if self.iteration <= 2 {
+            self.low = (0..x.len())
+                .map(|j| x[j] - ASYMPTOTE_INIT * (self.xmax[j] - self.xmin[j]))
+                .collect();
+            self.upp = (0..x.len())
+                .map(|j| x[j] + ASYMPTOTE_INIT * (self.xmax[j] - self.xmin[j]))
+                .collect();
+        } else {
+            for (j, &xj) in x.iter().enumerate() {
+                let trend = (xj - self.xold1[j]) * (self.xold1[j] - self.xold2[j]);
+                let factor = if trend > 0.0 {
+                    ASYMPTOTE_INCREASE
+                } else if trend < 0.0 {
+                    ASYMPTOTE_DECREASE
+                } else {
+                    1.0
+                };
+                let width = self.xmax[j] - self.xmin[j];
+                self.low[j] = (xj - factor * (self.xold1[j] - self.low[j]))
+                    .clamp(xj - 10.0 * width, xj - 0.01 * width);
+                self.upp[j] = (xj + factor * (self.upp[j] - self.xold1[j]))
+                    .clamp(xj + 0.01 * width, xj + 10.0 * width);
+            }
+        }
+        self.xold2 = std::mem::replace(&mut self.xold1, x.to_vec());
+    }
+}
+
+/// Convex separable approximation of the objective and the m constraints
+/// in the current asymptotes
+///
+/// f~_i(x) = r_i + sum_j p_ij / (U_j - x_j) + q_ij / (x_j - L_j), with the
+/// curvature terms rho_i / (x_max - x_min) of Svanberg (2007): rho_i is a
+/// small constant in MMA and is raised by the GCMMA inner iterations
+/// until the approximations are conservative.
+pub(super) struct Subproblem {
+    low: Vec<f64>,
+    upp: Vec<f64>,
+    alpha: Vec<f64>,
+    beta: Vec<f64>,
+    p0: Vec<f64>,
+    q0: Vec<f64>,
+    r0: f64,
+    /// Row-major m x n
+    p: Vec<f64>,
+    q: Vec<f64>,
+    r: Vec<f64>,
+}
+
+/// Primal-dual point of the subproblem: design x, elastic variables y,
+/// constraint multipliers lam with slacks s, and the multipliers xsi, eta,
+/// mu of the bounds on x and y
+#[derive(Clone)]
+struct Point {
+    x: Vec<f64>,
+    y: Vec<f64>,
+    lam: Vec<f64>,
+    xsi: Vec<f64>,
+    eta: Vec<f64>,
+    mu: Vec<f64>,
+    s: Vec<f64>,
+}
+
+impl Point {
+    fn add_scaled(&self, d: &Point, t: f64) -> Point {
+        let axpy = |a: &[f64], b: &[f64]| a.iter().zip(b).map(|(a, b)| a + t * b).collect();
+        Point {
+            x: axpy(&self.x, &d.x),
+            y: axpy(&self.y, &d.y),
+            lam: axpy(&self.lam, &d.lam),
+            xsi: axpy(&self.xsi, &d.xsi),
+            eta: axpy(&self.eta, &d.eta),
+            mu: axpy(&self.mu, &d.mu),
+            s: axpy(&self.s, &d.s),
+        }
+    }
+}
+
+impl Subproblem {
+    /// Approximations around x_k with f0val, df0dx, fval and dfdx (m x n
+    /// row-major) evaluated there, curvature `rho0` of the objective and
+    /// `rho` of the constraints, and x moving at most `move_limit` box
+    /// widths
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        asymptotes: &Asymptotes,
+        x: &[f64],
+        f0val: f64,
+        df0dx: &[f64],
+        fval: &[f64],
+        dfdx: &[f64],
+        rho0: f64,
+        rho: &[f64],
+        move_limit: f64,
+    ) -> Self {
+        let (low, upp) = (asymptotes.low.clone(), asymptotes.upp.clone());
+        let n = x.len();
+        let mut alpha = vec![0.0; n];
+        let mut beta = vec![0.0; n];
+        for j in 0..n {
+            let width = asymptotes.xmax[j] - asymptotes.xmin[j];
+            alpha[j] = (low[j] + ALBEFA * (x[j] - low[j]))
+                .max(x[j] - move_limit * width)
+                .max(asymptotes.xmin[j]);
+            beta[j] = (upp[j] - ALBEFA * (upp[j] - x[j]))
+                .min(x[j] + move_limit * width)
+                .min(asymptotes.xmax[j]);
+        }
+        // p_j = (U_j - x_j)^2 (1.001 max(df_j, 0) + 0.001 max(-df_j, 0)
+        // + rho / xmami_j) and q_j alike with (x_j - L_j)^2
+        let approximate = |df: &[f64], rho: f64, p: &mut Vec<f64>, q: &mut Vec<f64>| {
+            let mut r = 0.0;
+            for j in 0..n {
+                let xmami = asymptotes.width(j);
+                let (ux, xl) = (upp[j] - x[j], x[j] - low[j]);
+                let pq = 0.001 * df[j].abs() + rho / xmami;
+                let (pj, qj) = (
+                    (df[j].max(0.0) + pq) * ux * ux,
+                    ((-df[j]).max(0.0) + pq) * xl * xl,
+                );
+                r += pj / ux + qj / xl;
+                p.push(pj);
+                q.push(qj);
+            }
+            r
+        };
+        let (mut p0, mut q0) = (Vec::with_capacity(n), Vec::with_capacity(n));
+        let r0 = f0val - approximate(df0dx, rho0, &mut p0, &mut q0);
+        let (mut p, mut q) = (
+            Vec::with_capacity(dfdx.len()),
+            Vec::with_capacity(dfdx.len()),
+        );
+        let r = fval
+            .iter()
+            .zip(dfdx.chunks_exact(n.max(1)))
+            .zip(rho)
+            .map(|((f, df), &rho)| f - approximate(df, rho, &mut p, &mut q))
+            .collect();
+        Subproblem {
+            low,
+            upp,
+            alpha,
+            beta,
+            p0,
+            q0,
+            r0,
+            p,
+            q,
+            r,
+        }
+    }
+
+    fn n(&self) -> usize {
+        self.p0.len()
+    }
+
+    fn m(&self) -> usize {
+        self.r.len()
+    }
+
+    /// Approximate objective and constraints at x
+    pub fn values(&self, x: &[f64]) -> (f64, Vec<f64>) {
+        let eval = |p: &[f64], q: &[f64]| -> f64 {
+            (0..x.len())
+                .map(|j| p[j] / (self.upp[j] - x[j]) + q[j] / (x[j] - self.low[j]))
+                .sum()
+        };
+        let n = self.n();
+        let f0 = self.r0 + eval(&self.p0, &self.q0);
+        let f = (0..self.m())
+            .map(|i| self.r[i] + eval(&self.p[i * n..(i + 1) * n], &self.q[i * n..(i + 1) * n]))
+            .collect();
+        (f0, f)
+    }
+
+    /// Gradient of the Lagrangian in x and the approximate constraints g
+    /// (without r) at the point
+    fn lagrangian(&self, pt: &Point) -> (Vec<f64>, Vec<f64>) {
+        let (n, m) = (self.n(), self.m());
+        let mut dpsi = vec![0.0; n];
+        let mut g = vec![0.0; m];
+        for (j, dj) in dpsi.iter_mut().enumerate() {
+            let (ux, xl) = (self.upp[j] - pt.x[j], pt.x[j] - self.low[j]);
+            let (mut plam, mut qlam) = (self.p0[j], self.q0[j]);
+            for (i, gi) in g.iter_mut().enumerate() {
+                let (pij, qij) = (self.p[i * n + j], self.q[i * n + j]);
+                plam += pt.lam[i] * pij;
+                qlam += pt.lam[i] * qij;
+                *gi += pij / ux + qij / xl;
+            }
+            *dj = plam / (ux * ux) - qlam / (xl * xl);
+        }
+        (dpsi, g)
+    }
+
+    /// 2-norm and max-norm of the perturbed KKT residual at barrier `epsi`
+    fn residual(&self, pt: &Point, epsi: f64) -> (f64, f64) {
+        let (dpsi, g) = self.lagrangian(pt);
+        let mut res = Vec::with_capacity(3 * dpsi.len() + 4 * g.len());
+        for (j, dj) in dpsi.iter().enumerate() {
+            res.push(dj - pt.xsi[j] + pt.eta[j]);
+            res.push(pt.xsi[j] * (pt.x[j] - self.alpha[j]) - epsi);
+            res.push(pt.eta[j] * (self.beta[j] - pt.x[j]) - epsi);
+        }
+        for (i, gi) in g.iter().enumerate() {
+            res.push(ELASTIC_COST + pt.y[i] - pt.mu[i] - pt.lam[i]);
+            res.push(gi + self.r[i] - pt.y[i] + pt.s[i]);
+            res.push(pt.mu[i] * pt.y[i] - epsi);
+            res.push(pt.lam[i] * pt.s[i] - epsi);
+        }
+        let norm = res.iter().map(|v| v * v).sum::<f64>().sqrt();
+        (norm, res.iter().fold(0.0, |a: f64, v| a.max(v.abs())))
+    }
+
+    /// Newton direction of the perturbed KKT conditions, reduced to the
+    /// m x m system of the multipliers (`subsolv` of Svanberg)
+    fn direction(&self, pt: &Point, epsi: f64) -> Option<Point> {
+        let (n, m) = (self.n(), self.m());
+        let (dpsi, g) = self.lagrangian(pt);
+        let mut delx = vec![0.0; n];
+        let mut diagx = vec![0.0; n];
+        // GG_ij = d g_i / d x_j
+        let mut gg = vec![0.0; m * n];
+        for j in 0..n {
+            let (ux, xl) = (self.upp[j] - pt.x[j], pt.x[j] - self.low[j]);
+            let (xa, bx) = (pt.x[j] - self.alpha[j], self.beta[j] - pt.x[j]);
+            let (mut plam, mut qlam) = (self.p0[j], self.q0[j]);
+            for i in 0..m {
+                let (pij, qij) = (self.p[i * n + j], self.q[i * n + j]);
+                plam += pt.lam[i] * pij;
+                qlam += pt.lam[i] * qij;
+                gg[i * n + j] = pij / (ux * ux) - qij / (xl * xl);
+            }
+            delx[j] = dpsi[j] - epsi / xa + epsi / bx;
+            diagx[j] = 2.0 * (plam / (ux * ux * ux) + qlam / (xl * xl * xl))
+                + pt.xsi[j] / xa
+                + pt.eta[j] / bx;
+        }
+        let dely: Vec<f64> = (0..m)
+            .map(|i| ELASTIC_COST + pt.y[i] - pt.lam[i] - epsi / pt.y[i])
+            .collect();
+        let diagy: Vec<f64> = (0..m).map(|i| 1.0 + pt.mu[i] / pt.y[i]).collect();
+
+        // (diag(s / lam + 1 / diagy) + GG diagx^-1 GG^T) dlam = blam
+        let mut a = vec![0.0; m * m];
+        let mut dlam = vec![0.0; m];
+        for i in 0..m {
+            let dellam = g[i] + self.r[i] - pt.y[i] + epsi / pt.lam[i];
+            let gx: f64 = (0..n).map(|j| gg[i * n + j] * delx[j] / diagx[j]).sum();
+            dlam[i] = dellam + dely[i] / diagy[i] - gx;
+            a[i * m + i] = pt.s[i] / pt.lam[i] + 1.0 / diagy[i];
+            for k in 0..=i {
+                let v: f64 = (0..n)
+                    .map(|j| gg[i * n + j] * gg[k * n + j] / diagx[j])
+                    .sum();
+                a[i * m + k] += v;
+                if k != i {
+                    a[k * m + i] += v;
+                }
+            }
+        }
+        if !cholesky_factor(&mut a, m) {
+            return None;
+        }
+        cholesky_solve(&a, m, &mut dlam);
+
+        let dx: Vec<f64> = (0..n)
+            .map(|j| {
+                let gl: f64 = (0..m).map(|i| gg[i * n + j] * dlam[i]).sum();
+                -(delx[j] + gl) / diagx[j]
+            })
+            .collect();
+        let dy: Vec<f64> = (0..m).map(|i| (dlam[i] - dely[i]) / diagy[i]).collect();
+        let dxsi = (0..n)
+            .map(|j| {
+                let xa = pt.x[j] - self.alpha[j];
+                -pt.xsi[j] + (epsi - pt.xsi[j] * dx[j]) / xa
+            })
+            .collect();
+        let deta = (0..n)
+            .map(|j| {
+                let bx = self.beta[j] - pt.x[j];
+                -pt.eta[j] + (epsi + pt.eta[j] * dx[j]) / bx
+            })
+            .collect();
+        let dmu = (0..m)
+            .map(|i| -pt.mu[i] + (epsi - pt.mu[i] * dy[i]) / pt.y[i])
+            .collect();
+        let ds = (0..m)
+            .map(|i| -pt.s[i] + (epsi - pt.s[i] * dlam[i]) / pt.lam[i])
+            .collect();
+        Some(Point {
+            x: dx,
+            y: dy,
+            lam: dlam,
+            xsi: dxsi,
+            eta: deta,
+            mu: dmu,
+            s: ds,
+        })
+    }
+
+    /// Longest step, up to 1, that keeps the point strictly inside its
+    /// bounds with a 1% margin
+    fn max_step(&self, pt: &Point, d: &Point) -> f64 {
+        let mut most: f64 = 1.0;
+        let positive = [
+            (&pt.y, &d.y),
+            (&pt.lam, &d.lam),
+            (&pt.xsi, &d.xsi),
+            (&pt.eta, &d.eta),
+            (&pt.mu, &d.mu),
+            (&pt.s, &d.s),
+        ];
+        for (v, dv) in positive {
+            for (v, dv) in v.iter().zip(dv.iter()) {
+                most = most.max(-1.01 * dv / v);
+            }
+        }
+        for j in 0..self.n() {
+            most = most
+                .max(-1.01 * d.x[j] / (pt.x[j] - self.alpha[j]))
+                .max(1.01 * d.x[j] / (self.beta[j] - pt.x[j]));
+        }
+        1.0 / most
+    }
+
+    /// Minimizer of the subproblem by the primal-dual interior point
+    /// method of Svanberg's `subsolv`, following the barrier parameter
+    /// down from 1 to EPSIMIN
+    pub fn solve(&self) -> Vec<f64> {
+        let (n, m) = (self.n(), self.m());
+        let x: Vec<f64> = (0..n)
+            .map(|j| 0.5 * (self.alpha[j] + self.beta[j]))
+            .collect();
+        let mut pt = Point {
+            xsi: (0..n)
+                .map(|j| (1.0 / (x[j] - self.alpha[j])).max(1.0))
+                .collect(),
+            eta: (0..n)
+                .map(|j| (1.0 / (self.beta[j] - x[j])).max(1.0))
+                .collect(),
+            x,
+            y: vec![1.0; m],
+            lam: vec![1.0; m],
+            mu: vec![(0.5 * ELASTIC_COST).max(1.0); m],
+            s: vec![1.0; m],
+        };
+        let mut epsi = 1.0;
+        while epsi > EPSIMIN {
+            let (mut norm, mut max) = self.residual(&pt, epsi);
+            let mut newton = 0;
+            while max > 0.9 * epsi && newton < 200 {
+                newton += 1;
+                let Some(d) = self.direction(&pt, epsi) else {
+                    return pt.x;
+                };
+                // Backtrack until the residual decreases
+                let mut step = self.max_step(&pt, &d);
+                let mut next = pt.add_scaled(&d, step);
+                let (mut new_norm, mut new_max) = self.residual(&next, epsi);
+                let mut halvings = 0;
+                while new_norm > norm && halvings < 50 {
+                    halvings += 1;
+                    step /= 2.0;
+                    next = pt.add_scaled(&d, step);
+                    (new_norm, new_max) = self.residual(&next, epsi);
+                }
+                pt = next;
+                (norm, max) = (new_norm, new_max);
+            }
+            epsi *= 0.1;
+        }
+        pt.x
+    }
+}
+
+/// Whether the sizes of one evaluation of the objective and constraints
+/// match n variables and m constraints
+pub(super) fn valid_evaluation(
+    n: usize,
+    x: &[f64],
+    df0dx: &[f64],
+    fval: &[f64],
+    dfdx: &[f64],
+) -> bool {
+    x.len() == n && df0dx.len() == n && dfdx.len() == fval.len() * n
+}
+
+/// Method of Moving Asymptotes (Svanberg 1987, 2007) for
+/// min f_0(x) subject to f_i(x) <= 0, i = 1..m, and x_min <= x <= x_max
+///
+/// Each `update` replaces the problem by a convex separable approximation
+/// around the current design, from the values and gradients of the
+/// objective and constraints there, and returns its minimizer, the next
+/// design. Infeasible subproblems are relaxed by elastic variables of
+/// cost 1000 y_i + y_i^2 / 2, so the usual scaling of the constraints to
+/// about unit size matters. The problem functions are evaluated by the
+/// caller, in JavaScript or in another optimizer of this crate.
+#[wasm_bindgen]
+pub struct Mma {
+    asymptotes: Asymptotes,
+    move_limit: f64,
+}
+
+#[wasm_bindgen]
+impl Mma {
+    /// Optimizer for the design box [xmin, xmax]; returns `undefined`
+    /// unless the bounds have the same length and xmin < xmax throughout
+    pub fn new(xmin: &[f64], xmax: &[f64]) -> Option<Mma> {
+        Some(Mma {
+            asymptotes: Asymptotes::new(xmin, xmax)?,
+            move_limit: MOVE_LIMIT,
+        })
+    }
+
+    /// Largest move per update as a fraction of the box width, 0.5 by
+    /// default; smaller limits tame strongly non-linear responses such as
+    /// stresses. Returns `false`, changing nothing, unless in (0, 1].
+    pub fn set_move_limit(&mut self, move_limit: f64) -> bool {
+        if !(move_limit > 0.0 && move_limit <= 1.0) {
+            return false;
+        }
+        self.move_limit = move_limit;
+        true
+    }
+
+    /// Next design from the design x, objective value `f0val` and
+    /// gradient `df0dx`, constraint values `fval` (m, scaled so f_i <= 0)
+    /// and gradients `dfdx` (m x n row-major); `undefined` on a size
+    /// mismatch
+    pub fn update(
+        &mut self,
+        x: &[f64],
+        f0val: f64,
+        df0dx: &[f64],
+        fval: &[f64],
+        dfdx: &[f64],
+    ) -> Option<Vec<f64>> {
+        if !valid_evaluation(self.asymptotes.n(), x, df0dx, fval, dfdx) {
+            return None;
+        }
+        self.asymptotes.update(x);
+        let rho = vec![RAA0; fval.len()];
+        let sub = Subproblem::new(
+            &self.asymptotes,
+            x,
+            f0val,
+            df0dx,
+            fval,
+            dfdx,
+            RAA0,
+            &rho,
+            self.move_limit,
+        );
+        Some(sub.solve())
+    }
+
+    /// Number of updates so far
+    #[wasm_bindgen(getter)]
+    pub fn iteration(&self) -> u32 {
+        self.asymptotes.iteration
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn lower_asymptotes(&self) -> Vec<f64> {
+        self.asymptotes.low.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn upper_asymptotes(&self) -> Vec<f64> {
+        self.asymptotes.upp.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Svanberg's toy problem: min x^T x subject to two spheres of radius
+    /// 3, from x = (4, 3, 2) in [0, 5]^3
+    fn toy(x: &[f64]) -> (f64, Vec<f64>, Vec<f64>, Vec<f64>) {
+        let centres = [[5.0, 2.0, 1.0], [3.0, 4.0, 3.0]];
+        let f0 = x.iter().map(|v| v * v).sum();
+        let df0 = x.iter().map(|v| 2.0 * v).collect();
+        let mut f = Vec::new();
+        let mut df = Vec::new();
+        for c in centres {
+            f.push(x.iter().zip(c).map(|(v, c)| (v - c) * (v - c)).sum::<f64>() - 9.0);
+            df.extend(x.iter().zip(c).map(|(v, c)| 2.0 * (v - c)));
+        }
+        (f0, df0, f, df)
+    }
+
+    #[test]
+    fn test_mma_solves_toy_problem() {
+        let mut mma = Mma::new(&[0.0; 3], &[5.0; 3]).unwrap();
+        let mut x = vec![4.0, 3.0, 2.0];
+        for _ in 0..50 {
+            let (f0, df0, f, df) = toy(&x);
+            x = mma.update(&x, f0, &df0, &f, &df).unwrap();
+        }
+        let expected = [2.017526, 1.780027, 1.237467];
+        assert!(x.iter().zip(expected).all(|(a, b)| (a - b).abs() < 1e-4));
+        let (f0, _, f, _) = toy(&x);
+        assert!((f0 - 8.7702).abs() < 1e-3 && f.iter().all(|&v| v < 1e-6));
+        assert_eq!(mma.iteration(), 50);
+        assert!(mma.update(&x, 0.0, &[0.0; 2], &[], &[]).is_none());
+        assert!(Mma::new(&[0.0, 1.0], &[1.0, 1.0]).is_none());
+    }
+}
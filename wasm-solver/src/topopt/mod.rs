@@ -0,0 +1,36 @@
+//! Topology optimization loops run entirely inside wasm
+//!
+//! `TopOpt` runs the SIMP loop of `simp.ts` (assemble, solve, element
+//! sensitivities, filter, optimality criteria update, convergence check)
+//! so JavaScript only defines the problem and reads density snapshots
+//! between batches of iterations. `Beso` evolves discrete solid/void
+//! designs and `LevelSet` the boundary of a level set function on the
+//! same grid analysis, and `StressTopOpt` minimizes the volume under an
+//! aggregated stress limit with `Mma`. Designs use the FEM numbering:
+//! element e = z nelx nely + x nely + y, nodal DOFs as in `assemble_simp`.
+
+mod analysis;
+mod beso;
+mod filter;
+mod gcmma;
+mod helmholtz;
+mod levelset;
+mod mma;
+mod oc;
+mod options;
+mod projection;
+mod simp;
+mod stress;
+mod volume;
+
+pub use beso::Beso;
+pub use filter::{DensityFilter, SensitivityFilter};
+pub use gcmma::*;
+pub use helmholtz::HelmholtzFilter;
+pub use levelset::LevelSet;
+pub use mma::*;
+pub use options::*;
+pub use projection::HeavisideProjection;
+pub use simp::*;
+pub use stress::StressTopOpt;
+pub use volume::VolumeConstraint;
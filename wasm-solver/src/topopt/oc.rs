@@ -0,0 +1,71 @@
+use super::volume::VolumeConstraint;
+
+/// Lower density bound, keeping void elements in the model
+pub(crate) const DENSITY_MIN: f64 = 1e-3;
+
+/// Optimality criteria design for the multiplier lambda: x_e B_e^eta
+/// with B_e = -dc_e / (lambda dv_e) and the damping eta, 1/2 for
+/// compliance, limited to `move_limit` around x_e and to [DENSITY_MIN, 1]
+pub(crate) fn oc_step(
+    x: &[f64],
+    dc: &[f64],
+    dv: &[f64],
+    lambda: f64,
+    move_limit: f64,
+    damping: f64,
+) -> Vec<f64> {
+    x.iter()
+        .zip(dc)
+        .zip(dv)
+        .map(|((&xe, &dce), &dve)| {
+            let be = (-dce / (lambda * dve)).max(0.0);
+            (xe * be.powf(damping))
+                .clamp(xe - move_limit, xe + move_limit)
+                .clamp(DENSITY_MIN, 1.0)
+        })
+        .collect()
+}
+
+/// Optimality criteria update of the design `x` for the objective
+/// sensitivities `dc` and volume sensitivities `dv` (`updateDensities` of
+/// `simp.ts`)
+///
+/// The multiplier of `oc_step` with the damping `damping` is searched by
+/// `constraint` until the `physical` densities of the new design meet its
+/// volume fraction. `physical` maps design variables to physical
+/// densities, the identity unless they are filtered or projected.
+pub(crate) fn oc_update(
+    constraint: &mut VolumeConstraint,
+    x: &[f64],
+    dc: &[f64],
+    dv: &[f64],
+    move_limit: f64,
+    damping: f64,
+    physical: impl Fn(&[f64]) -> Vec<f64>,
+) -> Vec<f64> {
+    let step = |lambda| oc_step(x, dc, dv, lambda, move_limit, damping);
+    let lambda = constraint.search(|lambda| physical(&step(lambda)));
+    step(lambda)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_meets_volume_within_move_limit() {
+        let x = vec![0.5; 10];
+        let dc: Vec<f64> = (0..10).map(|e| -1.0 - e as f64).collect();
+        let mut constraint = VolumeConstraint::uniform(0.5, 10).unwrap();
+        let xnew = oc_update(&mut constraint, &x, &dc, &[1.0; 10], 0.2, 0.5, |x| {
+            x.to_vec()
+        });
+        let volume = xnew.iter().sum::<f64>() / 10.0;
+        assert!((volume - 0.5).abs() < 1e-3);
+        assert!(xnew
+            .iter()
+            .all(|&v| (0.3 - 1e-12..=0.7 + 1e-12).contains(&v)));
+        // More sensitive elements gain material
+        assert!(xnew.windows(2).all(|w| w[0] <= w[1]));
+    }
+}
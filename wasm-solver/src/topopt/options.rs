@@ -0,0 +1,303 @@
+use wasm_bindgen::prelude::*;
+
+/// Regularization of the design by the filter of radius
+/// `OptimizerOptions::rmin`
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FilterKind {
+    /// Sigmund's sensitivity filter of `simp.ts` and the 99-line code, the
+    /// default for reproducing the classic benchmarks: the design
+    /// variables are the physical densities and only dc is smoothed
+    Sensitivity = 0,
+    /// Linear density filter (Bruns and Tortorelli, Bourdin): the
+    /// physical densities are the filtered design variables and the
+    /// sensitivities are mapped back by the chain rule
+    Density = 1,
+    /// Helmholtz PDE filter (Lazarov and Sigmund), used as the density
+    /// filter; one PCG solve per application, for large radii
+    Helmholtz = 2,
+}
+
+/// Objective of a `TopOpt` run over several load cases
+/// (`TopOpt::set_load_cases`), from the compliances c_i of the cases and
+/// their weights w_i
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MultiLoad {
+    /// sum_i w_i c_i, updated by optimality criteria like a single load
+    WeightedSum = 0,
+    /// max_i w_i c_i, by the bound formulation min beta subject to w_i
+    /// c_i <= beta, solved by `Mma`
+    WorstCase = 1,
+}
+
+/// Settings of a `TopOpt` run; the defaults are those of the JavaScript
+/// optimizer (`SIMP_DEFAULTS` and `OC_PARAMS`)
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug)]
+pub struct OptimizerOptions {
+    /// Target volume fraction in (0, 1)
+    pub volfrac: f64,
+    /// SIMP penalization power p
+    pub penal: f64,
+    /// Filter radius in element lengths, > 0
+    pub rmin: f64,
+    /// Filter applied with radius `rmin`
+    pub filter: FilterKind,
+    /// Iterations after which the run stops unconverged
+    pub max_iter: u32,
+    /// The run has converged once no density changes by more than this
+    /// in an iteration
+    pub tolx: f64,
+    /// Young's modulus of the solid
+    pub e0: f64,
+    /// Young's modulus of the void, keeping K non-singular
+    pub emin: f64,
+    /// Poisson's ratio
+    pub nu: f64,
+    /// Largest density change per iteration, in (0, 1], of the OC update
+    /// and of the `Mma` update of `MultiLoad::WorstCase`
+    pub move_limit: f64,
+    /// Exponent eta of the OC update x B^eta, 1/2 for compliance; about
+    /// 0.3 with a move limit of 0.1 for compliant mechanisms, whose
+    /// sensitivities change sign
+    pub damping: f64,
+    /// Objective over the load cases of `TopOpt::set_load_cases`
+    pub multi_load: MultiLoad,
+    /// Heaviside projection of the filtered densities, with the density
+    /// or Helmholtz filter only
+    pub projection: bool,
+    /// Projection threshold in [0, 1]
+    pub eta: f64,
+    /// Initial projection sharpness, doubled every `beta_interval`
+    /// iterations or when the design stops changing, up to `beta_max`
+    pub beta: f64,
+    /// Cap of the beta continuation; the run converges only once beta
+    /// has reached it
+    pub beta_max: f64,
+    /// Iterations between raises of beta, unless the design settles first
+    pub beta_interval: u32,
+}
+
+#[wasm_bindgen]
+impl OptimizerOptions {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> OptimizerOptions {
+        OptimizerOptions {
+            volfrac: 0.5,
+            penal: 3.0,
+            rmin: 1.5,
+            filter: FilterKind::Sensitivity,
+            max_iter: 200,
+            tolx: 0.01,
+            e0: 1.0,
+            emin: 1e-9,
+            nu: 0.3,
+            move_limit: 0.2,
+            damping: 0.5,
+            multi_load: MultiLoad::WeightedSum,
+            projection: false,
+            eta: 0.5,
+            beta: 1.0,
+            beta_max: 64.0,
+            beta_interval: 50,
+        }
+    }
+}
+
+impl Default for OptimizerOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Settings of a `Beso` run; the defaults are those of the soft-kill
+/// BESO code of Huang and Xie (2010)
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug)]
+pub struct BesoOptions {
+    /// Target volume fraction in (0, 1]
+    pub volfrac: f64,
+    /// Evolution rate: the volume shrinks by this fraction per iteration
+    /// until it reaches `volfrac`
+    pub er: f64,
+    /// Largest fraction of all elements added back in one iteration; 1
+    /// lifts the limit
+    pub ar_max: f64,
+    /// Sensitivity filter radius in element lengths, > 0
+    pub rmin: f64,
+    /// Penalization power p of the soft-kill interpolation E = x^p E0
+    pub penal: f64,
+    /// Density of removed elements, in (0, 1)
+    pub xmin: f64,
+    /// Iterations after which the run stops unconverged
+    pub max_iter: u32,
+    /// The run has converged once the volume is reached and the mean
+    /// compliance of the last 5 iterations differs relatively by at most
+    /// this from that of the 5 before
+    pub tol: f64,
+    /// Young's modulus of the solid
+    pub e0: f64,
+    /// Poisson's ratio
+    pub nu: f64,
+}
+
+#[wasm_bindgen]
+impl BesoOptions {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> BesoOptions {
+        BesoOptions {
+            volfrac: 0.5,
+            er: 0.02,
+            ar_max: 1.0,
+            rmin: 3.0,
+            penal: 3.0,
+            xmin: 1e-3,
+            max_iter: 200,
+            tol: 1e-3,
+            e0: 1.0,
+            nu: 0.3,
+        }
+    }
+}
+
+impl Default for BesoOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Settings of a `LevelSet` run
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug)]
+pub struct LevelSetOptions {
+    /// Target volume fraction in (0, 1]
+    pub volfrac: f64,
+    /// The volume shrinks by this fraction per iteration until it reaches
+    /// `volfrac`, as the evolution rate of BESO
+    pub er: f64,
+    /// Radius in element lengths, > 0, of the smoothing of the velocity
+    pub rmin: f64,
+    /// Largest boundary motion per iteration in element lengths, in
+    /// (0, 1/sqrt(d)] on a d-dimensional grid (0.71 in 2D, 0.58 in 3D)
+    /// for the upwind scheme to stay stable
+    pub step: f64,
+    /// Iterations between reinitializations of the level set to a signed
+    /// distance, at least 1; longer intervals let phi flatten and the
+    /// boundary blur
+    pub reinit_interval: u32,
+    /// Holes per grid side in the initial design, which the level set
+    /// cannot nucleate by itself
+    pub holes: u32,
+    /// Ersatz density of the void
+    pub xmin: f64,
+    /// Iterations after which the run stops unconverged
+    pub max_iter: u32,
+    /// Convergence tolerance on the compliance, as `BesoOptions::tol`
+    pub tol: f64,
+    /// Young's modulus of the solid
+    pub e0: f64,
+    /// Poisson's ratio
+    pub nu: f64,
+}
+
+#[wasm_bindgen]
+impl LevelSetOptions {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> LevelSetOptions {
+        LevelSetOptions {
+            volfrac: 0.5,
+            er: 0.02,
+            rmin: 1.5,
+            step: 0.5,
+            reinit_interval: 1,
+            holes: 3,
+            xmin: 1e-3,
+            max_iter: 200,
+            tol: 1e-3,
+            e0: 1.0,
+            nu: 0.3,
+        }
+    }
+}
+
+impl Default for LevelSetOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Smooth approximation of the largest element stress of a
+/// `StressTopOpt` run
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StressAggregation {
+    /// p-norm (sum_e s_e^P)^(1/P), an upper bound of the maximum that
+    /// approaches it from above as P grows
+    PNorm = 0,
+    /// Kreisselmeier-Steinhauser s_max + ln(sum_e exp(P (s_e - s_max))) /
+    /// P, an upper bound of the maximum
+    Ks = 1,
+}
+
+/// Settings of a `StressTopOpt` run, volume minimization subject to an
+/// aggregated stress limit
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug)]
+pub struct StressOptions {
+    /// Allowed von Mises stress of the solid, > 0
+    pub limit: f64,
+    /// Exponent q of the relaxed stress rho^q s_vm, below `penal` so that
+    /// void elements carry no stress (qp-relaxation)
+    pub q: f64,
+    /// SIMP penalization power p
+    pub penal: f64,
+    pub aggregation: StressAggregation,
+    /// Exponent P of the p-norm or KS aggregation, > 0
+    pub aggregation_parameter: f64,
+    /// Density filter radius in element lengths, > 0
+    pub rmin: f64,
+    /// Uniform initial density, in (0, 1]
+    pub initial: f64,
+    /// Largest change of a design variable per MMA update, in (0, 1]
+    pub move_limit: f64,
+    /// Iterations after which the run stops unconverged
+    pub max_iter: u32,
+    /// The run has converged once no design variable changes by more
+    /// than this in an iteration
+    pub tolx: f64,
+    /// Young's modulus of the solid
+    pub e0: f64,
+    /// Young's modulus of the void, keeping K non-singular
+    pub emin: f64,
+    /// Poisson's ratio
+    pub nu: f64,
+}
+
+#[wasm_bindgen]
+impl StressOptions {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> StressOptions {
+        StressOptions {
+            limit: 1.0,
+            q: 0.5,
+            penal: 3.0,
+            aggregation: StressAggregation::PNorm,
+            aggregation_parameter: 8.0,
+            rmin: 1.5,
+            initial: 1.0,
+            move_limit: 0.1,
+            max_iter: 200,
+            tolx: 0.01,
+            e0: 1.0,
+            emin: 1e-9,
+            nu: 0.3,
+        }
+    }
+}
+
+impl Default for StressOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
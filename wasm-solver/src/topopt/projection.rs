@@ -0,0 +1,130 @@
+use wasm_bindgen::prelude::*;
+
+/// Smoothed Heaviside projection of Wang, Lazarov and Sigmund (2011)
+///
+/// H(x) = (tanh(beta eta) + tanh(beta (x - eta))) / (tanh(beta eta) +
+/// tanh(beta (1 - eta))) maps 0 to 0 and 1 to 1 and pushes densities
+/// below the threshold eta towards 0 and above it towards 1, the sharper
+/// the larger beta.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Heaviside {
+    pub beta: f64,
+    pub eta: f64,
+}
+
+impl Heaviside {
+    fn denominator(&self) -> f64 {
+        (self.beta * self.eta).tanh() + (self.beta * (1.0 - self.eta)).tanh()
+    }
+
+    pub fn project(&self, x: &[f64]) -> Vec<f64> {
+        let (t, d) = ((self.beta * self.eta).tanh(), self.denominator());
+        x.iter()
+            .map(|&v| (t + (self.beta * (v - self.eta)).tanh()) / d)
+            .collect()
+    }
+
+    /// Chain rule through `project` at `x`: df/dx = df/dH beta (1 -
+    /// tanh^2(beta (x - eta))) / denominator
+    pub fn backproject(&self, x: &[f64], df: &[f64]) -> Vec<f64> {
+        let d = self.denominator();
+        x.iter()
+            .zip(df)
+            .map(|(&v, g)| {
+                let t = (self.beta * (v - self.eta)).tanh();
+                g * self.beta * (1.0 - t * t) / d
+            })
+            .collect()
+    }
+}
+
+/// Smoothed Heaviside projection of filtered densities with a
+/// beta-continuation schedule
+///
+/// Projecting the output of a density or Helmholtz filter gives
+/// black-and-white designs with a minimum length scale. Starting with a
+/// small beta and raising it in steps (`continue_beta`) keeps the early
+/// iterations smooth; `TopOpt` runs the same schedule under
+/// `OptimizerOptions::projection`.
+#[wasm_bindgen]
+pub struct HeavisideProjection {
+    heaviside: Heaviside,
+}
+
+#[wasm_bindgen]
+impl HeavisideProjection {
+    /// Projection of threshold `eta` in [0, 1] and sharpness `beta` > 0;
+    /// `undefined` otherwise
+    pub fn new(eta: f64, beta: f64) -> Option<HeavisideProjection> {
+        ((0.0..=1.0).contains(&eta) && beta > 0.0).then_some(HeavisideProjection {
+            heaviside: Heaviside { beta, eta },
+        })
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn beta(&self) -> f64 {
+        self.heaviside.beta
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn eta(&self) -> f64 {
+        self.heaviside.eta
+    }
+
+    /// Projected densities of the filtered densities `x`
+    pub fn apply(&self, x: &[f64]) -> Vec<f64> {
+        self.heaviside.project(x)
+    }
+
+    /// Sensitivities by the filtered densities `x` from sensitivities
+    /// `df` by the projected ones; `undefined` unless the lengths match
+    pub fn backproject(&self, x: &[f64], df: &[f64]) -> Option<Vec<f64>> {
+        (x.len() == df.len()).then(|| self.heaviside.backproject(x, df))
+    }
+
+    /// One continuation step, beta multiplied by `factor` up to
+    /// `beta_max`; returns whether beta changed
+    pub fn continue_beta(&mut self, factor: f64, beta_max: f64) -> bool {
+        let beta = (self.heaviside.beta * factor).min(beta_max);
+        let raised = beta > self.heaviside.beta;
+        if raised {
+            self.heaviside.beta = beta;
+        }
+        raised
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_projection_and_chain_rule() {
+        let mut projection = HeavisideProjection::new(0.5, 1.0).unwrap();
+        let x = [0.0, 0.2, 0.5, 0.8, 1.0];
+        let h = projection.apply(&x);
+        assert!(h[0].abs() < 1e-15 && (h[4] - 1.0).abs() < 1e-15 && (h[2] - 0.5).abs() < 1e-15);
+
+        // Finite differences of sum_e w_e H(x_e)
+        let w = [1.0, -2.0, 0.5, 3.0, 1.5];
+        let grad = projection.backproject(&x, &w).unwrap();
+        for (e, g) in grad.iter().enumerate() {
+            let (mut xp, mut xm) = (x, x);
+            xp[e] += 1e-6;
+            xm[e] -= 1e-6;
+            let f =
+                |x: &[f64]| -> f64 { projection.apply(x).iter().zip(&w).map(|(a, b)| a * b).sum() };
+            assert!((g - (f(&xp) - f(&xm)) / 2e-6).abs() < 1e-6);
+        }
+
+        // Continuation sharpens the projection up to beta_max
+        assert!(projection.continue_beta(2.0, 6.0) && projection.continue_beta(2.0, 6.0));
+        assert_eq!(projection.beta(), 4.0);
+        assert!(projection.continue_beta(2.0, 6.0));
+        assert!(!projection.continue_beta(2.0, 6.0));
+        let sharp = projection.apply(&x);
+        assert!(sharp[1] < h[1] && sharp[3] > h[3]);
+        assert!(projection.backproject(&x, &w[1..]).is_none());
+        assert!(HeavisideProjection::new(1.5, 1.0).is_none());
+    }
+}
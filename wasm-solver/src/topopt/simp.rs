@@ -0,0 +1,725 @@
+use wasm_bindgen::prelude::*;
+
+use super::analysis::GridAnalysis;
+use super::filter::DesignFilter;
+use super::mma::Mma;
+use super::oc::{oc_update, DENSITY_MIN};
+use super::projection::Heaviside;
+use super::volume::VolumeConstraint;
+use super::{FilterKind, MultiLoad, OptimizerOptions};
+use crate::fem::simp_moduli;
+use crate::options::SolverOptions;
+
+/// Upper bound of the bound variable of the worst-case formulation, the
+/// worst compliance relative to that of the first iteration
+const BOUND_MAX: f64 = 10.0;
+
+/// Minimum-compliance SIMP optimization of a structured grid, top88 style
+///
+/// Every `step` assembles K(rho) from unit Q4 (`nelz` = 0) or H8
+/// elements, solves K u = f by PCG warm-started from the previous
+/// displacements, computes the compliance sensitivities, applies the
+/// filter of `OptimizerOptions::filter` and takes an optimality criteria
+/// step. JavaScript sets the loads and supports once and reads
+/// `densities` after a batch of `run` iterations.
+///
+/// With the density and Helmholtz filters the design variables and the
+/// physical densities of the analysis differ: `design` holds the former and
+/// `densities` the latter. `OptimizerOptions::projection` adds a Heaviside
+/// projection of the filtered densities with beta-continuation; the run
+/// then converges only once beta has reached `beta_max`.
+///
+/// `set_output` turns the run into compliant mechanism design: the
+/// objective becomes the output displacement l^T u (minimized, so a
+/// negative l^T u is achieved motion against l), whose sensitivities take
+/// one adjoint solve K lambda = l per iteration. Grounded springs at the
+/// input and output ports (`set_springs`) model the workpiece and the
+/// actuator.
+///
+/// `set_load_cases` optimizes the compliance over several load cases,
+/// by their weighted sum or their maximum (`OptimizerOptions::multi_load`).
+/// The worst case is not differentiable where two cases tie, so it is
+/// recast as min beta subject to w_i c_i / c_ref <= beta and the volume
+/// constraint, with c_ref the worst compliance of the first iteration,
+/// and updated by `Mma` in place of the optimality criteria. Every case
+/// costs one solve per iteration on the same stiffness.
+#[wasm_bindgen]
+pub struct TopOpt {
+    analysis: GridAnalysis,
+    options: OptimizerOptions,
+    filter: DesignFilter,
+    volume: VolumeConstraint,
+    /// Design variables
+    densities: Vec<f64>,
+    /// Filtered design variables, the design variables themselves under
+    /// `FilterKind::Sensitivity`
+    filtered: Vec<f64>,
+    /// Physical densities, the projected filtered design variables under
+    /// `OptimizerOptions::projection`
+    physical: Vec<f64>,
+    projection: Option<Heaviside>,
+    /// Iterations since beta was last raised
+    beta_iterations: u32,
+    energies: Vec<f64>,
+    /// Output vector l of the mechanism objective, empty for compliance
+    output: Vec<f64>,
+    /// Adjoint of the latest iteration, warm-starting the next
+    adjoint: Vec<f64>,
+    /// Forces of the load cases, one vector per case, and their weights;
+    /// empty for the single load of `set_forces`
+    cases: Vec<f64>,
+    weights: Vec<f64>,
+    /// Displacements of every load case and their compliances c_i
+    case_u: Vec<Vec<f64>>,
+    case_compliance: Vec<f64>,
+    /// Optimizer of the worst-case formulation and its compliance scale
+    /// c_ref, from the first iteration
+    mma: Option<Mma>,
+    reference: f64,
+    iteration: u32,
+    compliance: f64,
+    objective: f64,
+    change: f64,
+    converged: bool,
+}
+
+#[wasm_bindgen]
+impl TopOpt {
+    /// Uniform design at `options.volfrac` on a `nelx` x `nely` (x `nelz`
+    /// if > 0) grid, unloaded and unsupported. Returns `undefined` if the
+    /// grid is empty, `volfrac` or `move_limit` is not in (0, 1], rmin <= 0
+    /// or `nu` is not admissible, or if the projection is asked for with
+    /// the sensitivity filter, eta outside [0, 1] or not 0 < beta <=
+    /// beta_max.
+    pub fn new(
+        nelx: usize,
+        nely: usize,
+        nelz: usize,
+        options: &OptimizerOptions,
+    ) -> Option<TopOpt> {
+        let unit = |v: f64| v > 0.0 && v <= 1.0;
+        if !(unit(options.volfrac) && unit(options.move_limit) && options.rmin > 0.0) {
+            return None;
+        }
+        let projection = options.projection.then_some(Heaviside {
+            beta: options.beta,
+            eta: options.eta,
+        });
+        if options.projection
+            && (options.filter == FilterKind::Sensitivity
+                || !(0.0..=1.0).contains(&options.eta)
+                || !(options.beta > 0.0 && options.beta <= options.beta_max))
+        {
+            return None;
+        }
+        let elements = [nelx, nely, nelz];
+        let analysis = GridAnalysis::new(elements, options.nu)?;
+        let count = analysis.element_count();
+        Some(TopOpt {
+            analysis,
+            options: *options,
+            filter: DesignFilter::new(options.filter, elements, options.rmin)?,
+            volume: VolumeConstraint::uniform(options.volfrac, count)?,
+            densities: vec![options.volfrac; count],
+            filtered: vec![options.volfrac; count],
+            physical: vec![options.volfrac; count],
+            projection,
+            beta_iterations: 0,
+            energies: vec![0.0; count],
+            output: Vec::new(),
+            adjoint: Vec::new(),
+            cases: Vec::new(),
+            weights: Vec::new(),
+            case_u: Vec::new(),
+            case_compliance: Vec::new(),
+            mma: None,
+            reference: 1.0,
+            iteration: 0,
+            compliance: f64::INFINITY,
+            objective: f64::INFINITY,
+            change: 1.0,
+            converged: false,
+        })
+    }
+
+    /// Set the nodal force vector (e.g. `LoadVector::forces`), replacing
+    /// any load cases; returns `false`, changing nothing, unless it has
+    /// one entry per DOF
+    pub fn set_forces(&mut self, forces: &[f64]) -> bool {
+        if !self.analysis.set_forces(forces) {
+            return false;
+        }
+        self.set_load_cases(&[], &[])
+    }
+
+    /// Set several load cases, `forces` holding one force vector per case
+    /// after another and `weights` the weight w_i >= 0 of every case;
+    /// empty vectors return to the single load of `set_forces`. Load cases
+    /// apply to the compliance objective, not to `set_output`. Returns
+    /// `false`, changing nothing, unless there are as many force vectors
+    /// as weights.
+    pub fn set_load_cases(&mut self, forces: &[f64], weights: &[f64]) -> bool {
+        let n = self.analysis.u.len();
+        if forces.len() != n * weights.len() || !weights.iter().all(|&w| w >= 0.0) {
+            return false;
+        }
+        self.cases = forces.to_vec();
+        self.weights = weights.to_vec();
+        self.case_u = vec![vec![0.0; n]; weights.len()];
+        self.case_compliance = vec![0.0; weights.len()];
+        self.mma = None;
+        true
+    }
+
+    /// Set the supported DOFs; returns `false`, changing nothing, if one
+    /// is out of range
+    pub fn set_fixed_dofs(&mut self, fixed_dofs: &[u32]) -> bool {
+        self.analysis.set_fixed_dofs(fixed_dofs)
+    }
+
+    /// Grounded springs of stiffness `stiffness` at the DOFs `dofs`;
+    /// returns `false`, changing nothing, unless the lengths match, every
+    /// DOF is in range and no stiffness is negative
+    pub fn set_springs(&mut self, dofs: &[u32], stiffness: &[f64]) -> bool {
+        self.analysis.set_springs(dofs, stiffness)
+    }
+
+    /// Minimize the output displacement l^T u instead of the compliance,
+    /// `output` holding l with one entry per DOF (1 at the output DOF for
+    /// a single port); an empty vector restores compliance. Returns
+    /// `false`, changing nothing, for any other length.
+    pub fn set_output(&mut self, output: &[f64]) -> bool {
+        if !output.is_empty() && output.len() != self.analysis.u.len() {
+            return false;
+        }
+        self.output = output.to_vec();
+        self.adjoint = vec![0.0; output.len()];
+        true
+    }
+
+    /// Options of the state solves (PCG), by default those of `solve_pcg`
+    pub fn set_solver_options(&mut self, options: &SolverOptions) {
+        self.analysis.solver = *options;
+    }
+
+    /// Back to the uniform initial design, keeping loads and supports
+    pub fn reset(&mut self) {
+        self.densities.fill(self.options.volfrac);
+        if let Some(h) = &mut self.projection {
+            h.beta = self.options.beta;
+        }
+        self.beta_iterations = 0;
+        self.update_physical();
+        self.analysis.u.fill(0.0);
+        self.energies.fill(0.0);
+        self.adjoint.fill(0.0);
+        self.case_u.iter_mut().for_each(|u| u.fill(0.0));
+        self.case_compliance.fill(0.0);
+        self.mma = None;
+        self.iteration = 0;
+        self.compliance = f64::INFINITY;
+        self.objective = f64::INFINITY;
+        self.change = 1.0;
+        self.converged = false;
+    }
+
+    /// One optimization iteration; returns whether the run has converged
+    /// (or reached `max_iter`), after which `step` does nothing
+    pub fn step(&mut self) -> bool {
+        if self.converged {
+            return true;
+        }
+        let OptimizerOptions {
+            penal, e0, emin, ..
+        } = self.options;
+        let moduli = simp_moduli(&self.physical, penal, e0, emin);
+        let sensitivity = |products: &[f64], physical: &[f64]| -> Vec<f64> {
+            products
+                .iter()
+                .zip(physical)
+                .map(|(ce, &rho)| -penal * rho.powf(penal - 1.0) * (e0 - emin) * ce)
+                .collect()
+        };
+        let xnew = if self.weights.is_empty() || !self.output.is_empty() {
+            (self.energies, self.compliance) = self.analysis.solve(&moduli);
+            // d/drho_e of l^T u is -lambda_e^T (dK_e / drho_e) u_e with K
+            // lambda = l; for the compliance l = f and lambda = u
+            let products = if self.output.is_empty() {
+                self.objective = self.compliance;
+                self.energies.clone()
+            } else {
+                let u = &self.analysis.u;
+                self.objective = self.output.iter().zip(u).map(|(l, v)| l * v).sum();
+                let k = self.analysis.stiffness(&moduli);
+                (self.adjoint, _) = self.analysis.solve_load(&k, &self.output, &self.adjoint);
+                self.analysis.mixed_energies(&moduli, &self.adjoint, u)
+            };
+            let (dc, dv) = self.chain(sensitivity(&products, &self.physical));
+            self.oc(&dc, &dv)
+        } else {
+            let energies = self.solve_cases(&moduli);
+            let weighted: Vec<f64> = (0..self.physical.len())
+                .map(|e| {
+                    energies
+                        .iter()
+                        .zip(&self.weights)
+                        .map(|(ce, w)| w * ce[e])
+                        .sum()
+                })
+                .collect();
+            self.energies = weighted;
+            if self.options.multi_load == MultiLoad::WeightedSum {
+                self.compliance = self
+                    .case_compliance
+                    .iter()
+                    .zip(&self.weights)
+                    .map(|(c, w)| w * c)
+                    .sum();
+                self.objective = self.compliance;
+                let (dc, dv) = self.chain(sensitivity(&self.energies, &self.physical));
+                self.oc(&dc, &dv)
+            } else {
+                let dcs: Vec<Vec<f64>> = energies
+                    .iter()
+                    .map(|ce| sensitivity(ce, &self.physical))
+                    .collect();
+                self.bound_update(&dcs)
+            }
+        };
+        self.change = xnew
+            .iter()
+            .zip(&self.densities)
+            .map(|(a, b)| (a - b).abs())
+            .fold(0.0, f64::max);
+        self.densities = xnew;
+        self.iteration += 1;
+
+        // Continuation: double beta every beta_interval iterations or once
+        // the design settles, and converge only at beta_max
+        let (mut raised, mut sharp) = (false, true);
+        if let Some(h) = &mut self.projection {
+            let OptimizerOptions {
+                beta_max,
+                beta_interval,
+                tolx,
+                ..
+            } = self.options;
+            self.beta_iterations += 1;
+            if h.beta < beta_max && (self.beta_iterations >= beta_interval || self.change < tolx) {
+                h.beta = (2.0 * h.beta).min(beta_max);
+                self.beta_iterations = 0;
+                raised = true;
+            }
+            sharp = h.beta >= beta_max;
+        }
+        self.update_physical();
+        self.converged = (sharp && !raised && self.change < self.options.tolx)
+            || self.iteration >= self.options.max_iter;
+        self.converged
+    }
+
+    /// Up to `steps` iterations, fewer if the run converges; returns the
+    /// number done
+    pub fn run(&mut self, steps: u32) -> u32 {
+        let mut done = 0;
+        while done < steps && !self.converged {
+            self.step();
+            done += 1;
+        }
+        done
+    }
+
+    /// Physical element densities, one per element
+    #[wasm_bindgen(getter)]
+    pub fn densities(&self) -> Vec<f64> {
+        self.physical.clone()
+    }
+
+    /// Design variables, one per element; the physical densities unless
+    /// they are density filtered
+    #[wasm_bindgen(getter)]
+    pub fn design(&self) -> Vec<f64> {
+        self.densities.clone()
+    }
+
+    /// Displacements of the latest solve
+    #[wasm_bindgen(getter)]
+    pub fn displacements(&self) -> Vec<f64> {
+        self.analysis.u.clone()
+    }
+
+    /// u_e^T K_e u_e per element of the latest solve, for unit modulus
+    /// (the `strainEnergy` of `simp.ts`)
+    #[wasm_bindgen(getter)]
+    pub fn strain_energy(&self) -> Vec<f64> {
+        self.energies.clone()
+    }
+
+    /// Compliance f^T u of the design before the latest update; infinite
+    /// before the first step
+    #[wasm_bindgen(getter)]
+    pub fn compliance(&self) -> f64 {
+        self.compliance
+    }
+
+    /// Objective of the design before the latest update: the compliance,
+    /// or l^T u under `set_output`; infinite before the first step
+    #[wasm_bindgen(getter)]
+    pub fn objective(&self) -> f64 {
+        self.objective
+    }
+
+    /// Compliances c_i of the load cases before the latest update, empty
+    /// without `set_load_cases`
+    #[wasm_bindgen(getter)]
+    pub fn case_compliances(&self) -> Vec<f64> {
+        self.case_compliance.clone()
+    }
+
+    /// Mean physical density of the current design
+    #[wasm_bindgen(getter)]
+    pub fn volume(&self) -> f64 {
+        self.physical.iter().sum::<f64>() / self.physical.len() as f64
+    }
+
+    /// Largest density change of the latest update
+    #[wasm_bindgen(getter)]
+    pub fn change(&self) -> f64 {
+        self.change
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn iteration(&self) -> u32 {
+        self.iteration
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn converged(&self) -> bool {
+        self.converged
+    }
+
+    /// PCG iterations of the latest solve
+    #[wasm_bindgen(getter)]
+    pub fn solver_iterations(&self) -> u32 {
+        self.analysis.solver_iterations
+    }
+
+    /// Current projection sharpness, `undefined` without projection
+    #[wasm_bindgen(getter)]
+    pub fn beta(&self) -> Option<f64> {
+        self.projection.map(|h| h.beta)
+    }
+}
+
+/// Projected `filtered` densities, unchanged without projection
+fn project(projection: Option<Heaviside>, filtered: &[f64]) -> Vec<f64> {
+    match projection {
+        Some(h) => h.project(filtered),
+        None => filtered.to_vec(),
+    }
+}
+
+impl TopOpt {
+    /// Sensitivities by the design variables from sensitivities `dc` by
+    /// the physical densities, through the projection and the filter,
+    /// with those of the volume
+    fn chain(&self, mut dc: Vec<f64>) -> (Vec<f64>, Vec<f64>) {
+        let mut dv = vec![1.0; dc.len()];
+        if let Some(h) = &self.projection {
+            dc = h.backproject(&self.filtered, &dc);
+            dv = h.backproject(&self.filtered, &dv);
+        }
+        self.filter.sensitivities(&self.densities, &dc, &dv)
+    }
+
+    /// Optimality criteria update of the design variables
+    fn oc(&mut self, dc: &[f64], dv: &[f64]) -> Vec<f64> {
+        let (filter, projection) = (&self.filter, self.projection);
+        oc_update(
+            &mut self.volume,
+            &self.densities,
+            dc,
+            dv,
+            self.options.move_limit,
+            self.options.damping,
+            |x| project(projection, &filter.physical(x)),
+        )
+    }
+
+    /// Solve every load case on the stiffness of `moduli`, keeping the
+    /// displacements and compliances; returns the unit-modulus element
+    /// energies of every case. `displacements` shows the first case.
+    fn solve_cases(&mut self, moduli: &[f64]) -> Vec<Vec<f64>> {
+        let k = self.analysis.stiffness(moduli);
+        let n = self.analysis.u.len();
+        let mut energies = Vec::with_capacity(self.weights.len());
+        for (i, forces) in self.cases.chunks_exact(n).enumerate() {
+            let (u, iterations) = self.analysis.solve_load(&k, forces, &self.case_u[i]);
+            self.analysis.solver_iterations = iterations;
+            self.case_compliance[i] = forces.iter().zip(&u).map(|(f, v)| f * v).sum();
+            energies.push(self.analysis.energies(moduli, &u));
+            self.case_u[i] = u;
+        }
+        self.analysis.u.copy_from_slice(&self.case_u[0]);
+        energies
+    }
+
+    /// MMA update of the worst-case bound formulation in the variables
+    /// (x, beta) from the sensitivities `dcs` of the case compliances by
+    /// the physical densities; beta restarts at the current worst case
+    fn bound_update(&mut self, dcs: &[Vec<f64>]) -> Vec<f64> {
+        let n = self.densities.len();
+        let worst = self
+            .case_compliance
+            .iter()
+            .zip(&self.weights)
+            .map(|(c, w)| w * c)
+            .fold(0.0, f64::max);
+        if self.mma.is_none() {
+            self.reference = worst.max(f64::MIN_POSITIVE);
+            let (mut xmin, mut xmax) = (vec![DENSITY_MIN; n], vec![1.0; n]);
+            xmin.push(0.0);
+            xmax.push(BOUND_MAX);
+            let mut mma = Mma::new(&xmin, &xmax).expect("xmin < xmax");
+            // move_limit is in (0, 1], checked by `new`
+            assert!(mma.set_move_limit(self.options.move_limit));
+            self.mma = Some(mma);
+        }
+        self.compliance = worst;
+        self.objective = worst;
+        let beta = worst / self.reference;
+
+        // Constraints w_i c_i / c_ref - beta <= 0, then V / volfrac - 1 <= 0
+        let mut fval = Vec::with_capacity(self.weights.len() + 1);
+        let mut dfdx = Vec::with_capacity((self.weights.len() + 1) * (n + 1));
+        for ((dc, c), w) in dcs.iter().zip(&self.case_compliance).zip(&self.weights) {
+            let scale = w / self.reference;
+            let scaled: Vec<f64> = dc.iter().map(|d| scale * d).collect();
+            fval.push(scale * c - beta);
+            dfdx.extend(self.chain(scaled).0);
+            dfdx.push(-1.0);
+        }
+        let volfrac = self.options.volfrac;
+        let volume = self.physical.iter().sum::<f64>() / n as f64;
+        fval.push(volume / volfrac - 1.0);
+        let (_, dv) = self.chain(vec![0.0; n]);
+        dfdx.extend(dv.iter().map(|d| d / (n as f64 * volfrac)));
+        dfdx.push(0.0);
+
+        let mut x = self.densities.clone();
+        x.push(beta);
+        let mut df0dx = vec![0.0; n];
+        df0dx.push(1.0);
+        let mma = self.mma.as_mut().expect("created above");
+        let mut xnew = mma
+            .update(&x, beta, &df0dx, &fval, &dfdx)
+            .expect("one value per variable and constraint");
+        xnew.truncate(n);
+        xnew
+    }
+
+    /// Filtered and physical densities of the current design variables
+    fn update_physical(&mut self) {
+        self.filtered = self.filter.physical(&self.densities);
+        self.physical = project(self.projection, &self.filtered);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::{node_count, node_index};
+    use crate::test_util::{clamped_left_edge, left_edge_dofs};
+    use crate::topopt::{DensityFilter, HelmholtzFilter};
+
+    /// Half MBB beam: symmetry on the left edge, roller at the bottom
+    /// right, unit load down at the top left
+    fn mbb(nelx: usize, nely: usize, options: &OptimizerOptions) -> TopOpt {
+        let mut opt = TopOpt::new(nelx, nely, 0, options).unwrap();
+        let elements = [nelx, nely, 0];
+        let mut fixed = left_edge_dofs(elements, &[0]);
+        fixed.push(2 * node_index(elements, nelx, 0, 0) as u32 + 1);
+        assert!(opt.set_fixed_dofs(&fixed));
+        let mut f = vec![0.0; opt.displacements().len()];
+        f[2 * node_index(elements, 0, nely, 0) + 1] = -1.0;
+        assert!(opt.set_forces(&f));
+        opt
+    }
+
+    #[test]
+    fn test_mbb_beam_converges() {
+        let mut options = OptimizerOptions::new();
+        options.max_iter = 60;
+        let mut opt = mbb(30, 10, &options);
+        assert!(!opt.set_forces(&[1.0]));
+        opt.step();
+        let first = opt.compliance();
+        // The compliance of the first step is f^T u of the uniform design
+        let f_u = -opt.displacements()[2 * 10 + 1];
+        assert!((first - f_u).abs() < 1e-6 * first);
+
+        let done = opt.run(100);
+        assert!(done < 100 && opt.converged());
+        assert!(opt.compliance() < 0.7 * first);
+        assert!((opt.volume() - 0.5).abs() < 2e-3);
+        assert!(opt.densities().iter().all(|&x| (1e-3..=1.0).contains(&x)));
+        // Mostly black and white
+        let grey = opt
+            .densities()
+            .iter()
+            .filter(|&&x| x > 0.1 && x < 0.9)
+            .count();
+        assert!(grey < 300 / 2);
+
+        opt.reset();
+        assert_eq!(opt.iteration(), 0);
+        assert_eq!(opt.densities(), vec![0.5; 300]);
+    }
+
+    #[test]
+    fn test_density_filtered_mbb_beam() {
+        for kind in [FilterKind::Density, FilterKind::Helmholtz] {
+            let mut options = OptimizerOptions::new();
+            options.filter = kind;
+            options.rmin = 2.0;
+            options.max_iter = 60;
+            let mut opt = mbb(30, 10, &options);
+            opt.step();
+            let first = opt.compliance();
+            opt.run(100);
+            assert!(opt.converged());
+            assert!(opt.compliance() < 0.7 * first);
+            // The volume constraint holds for the physical densities,
+            // which are the filtered design variables
+            assert!((opt.volume() - 0.5).abs() < 2e-3);
+            // The Helmholtz filter only matches to the PCG tolerance
+            let (physical, tol) = if kind == FilterKind::Density {
+                let filter = DensityFilter::new(30, 10, 0, 2.0).unwrap();
+                (filter.apply(&opt.design()), 1e-12)
+            } else {
+                let solver = SolverOptions::new();
+                let filter = HelmholtzFilter::new(30, 10, 0, 2.0, &solver).unwrap();
+                (filter.apply(&opt.design()), 1e-9)
+            };
+            assert!(physical
+                .unwrap()
+                .iter()
+                .zip(opt.densities())
+                .all(|(a, b)| (a - b).abs() < tol));
+            options.rmin = 0.0;
+            assert!(TopOpt::new(30, 10, 0, &options).is_none());
+            options.rmin = 2.0;
+            options.move_limit = 1.5;
+            assert!(TopOpt::new(30, 10, 0, &options).is_none());
+        }
+    }
+
+    #[test]
+    fn test_projection_gives_black_and_white_design() {
+        let grey = |opt: &TopOpt| {
+            opt.densities()
+                .iter()
+                .filter(|&&x| x > 0.1 && x < 0.9)
+                .count()
+        };
+        let mut options = OptimizerOptions::new();
+        options.filter = FilterKind::Density;
+        options.rmin = 2.0;
+        options.max_iter = 150;
+        let mut plain = mbb(24, 8, &options);
+        plain.run(150);
+        assert_eq!(plain.beta(), None);
+
+        options.projection = true;
+        options.beta_max = 8.0;
+        options.beta_interval = 20;
+        let mut opt = mbb(24, 8, &options);
+        assert_eq!(opt.beta(), Some(1.0));
+        opt.run(150);
+        assert!(opt.converged() && opt.beta() == Some(8.0));
+        assert!((opt.volume() - 0.5).abs() < 5e-3);
+        assert!(2 * grey(&opt) < grey(&plain));
+
+        opt.reset();
+        assert_eq!(opt.beta(), Some(1.0));
+        options.filter = FilterKind::Sensitivity;
+        assert!(TopOpt::new(24, 8, 0, &options).is_none());
+    }
+
+    #[test]
+    fn test_displacement_inverter() {
+        // Upper half of the inverter, symmetric about the bottom edge:
+        // pushed right at the bottom left, clamped at the top left, the
+        // bottom right output should move left against its spring
+        let (nelx, nely) = (20, 10);
+        let options = OptimizerOptions {
+            volfrac: 0.3,
+            move_limit: 0.1,
+            damping: 0.3,
+            max_iter: 40,
+            ..OptimizerOptions::new()
+        };
+        let mut opt = TopOpt::new(nelx, nely, 0, &options).unwrap();
+        let elements = [nelx, nely, 0];
+        let input = 2 * node_index(elements, 0, 0, 0);
+        let output = 2 * node_index(elements, nelx, 0, 0);
+        let mut fixed: Vec<u32> = (0..=nelx)
+            .map(|x| 2 * node_index(elements, x, 0, 0) as u32 + 1)
+            .collect();
+        // Clamped at the top two nodes of the left edge
+        fixed.extend_from_slice(&clamped_left_edge(elements)[2 * (nely - 1)..]);
+        let n = 2 * node_count(elements);
+        let (mut f, mut l) = (vec![0.0; n], vec![0.0; n]);
+        f[input] = 1.0;
+        l[output] = 1.0;
+        assert!(opt.set_fixed_dofs(&fixed) && opt.set_forces(&f));
+        assert!(opt.set_springs(&[input as u32, output as u32], &[0.1, 0.1]));
+        assert!(opt.set_output(&l) && !opt.set_output(&l[1..]));
+
+        opt.step();
+        let first = opt.objective();
+        assert_eq!(first, opt.displacements()[output]);
+        opt.run(40);
+        assert!(opt.objective() < 0.0 && opt.objective() < first - 0.5);
+        assert!((opt.volume() - 0.3).abs() < 2e-3);
+    }
+
+    #[test]
+    fn test_worst_case_load_cases() {
+        // Cantilever clamped on the left edge, pulled at the middle of
+        // the right edge along x or pushed down along y
+        let (nelx, nely) = (24, 12);
+        let elements = [nelx, nely, 0];
+        let fixed = clamped_left_edge(elements);
+        let n = 2 * node_count(elements);
+        let tip = 2 * node_index(elements, nelx, nely / 2, 0);
+        let mut cases = vec![0.0; 2 * n];
+        cases[tip] = 4.0;
+        cases[n + tip + 1] = -1.0;
+
+        let mut worst = Vec::new();
+        for multi_load in [MultiLoad::WeightedSum, MultiLoad::WorstCase] {
+            let mut options = OptimizerOptions::new();
+            options.filter = FilterKind::Density;
+            options.rmin = 1.5;
+            options.multi_load = multi_load;
+            let mut opt = TopOpt::new(nelx, nely, 0, &options).unwrap();
+            assert!(opt.set_fixed_dofs(&fixed));
+            assert!(opt.set_load_cases(&cases, &[1.0, 1.0]));
+            assert!(!opt.set_load_cases(&cases[1..], &[1.0, 1.0]));
+            opt.step();
+            let c = opt.case_compliances();
+            let first = c[0].max(c[1]);
+            opt.run(60);
+            let c = opt.case_compliances();
+            assert!(c[0].max(c[1]) < 0.5 * first);
+            assert!((opt.volume() - 0.5).abs() < 2e-3);
+            worst.push(c[0].max(c[1]));
+            if multi_load == MultiLoad::WorstCase {
+                // The bound evens out the cases the weighted sum leaves apart
+                assert!((c[0] - c[1]).abs() < 0.02 * c[0]);
+            }
+        }
+        assert!(worst[1] < 0.97 * worst[0]);
+    }
+}
@@ -0,0 +1,369 @@
+use wasm_bindgen::prelude::*;
+
+use super::analysis::GridAnalysis;
+use super::filter::DesignFilter;
+use super::mma::Mma;
+use super::{FilterKind, StressAggregation, StressOptions};
+use crate::fem::{centre_stress_matrix, simp_moduli, von_mises};
+use crate::options::SolverOptions;
+
+/// Aggregate of the element stresses `s` >= 0 and its derivatives by
+/// s_e, computed relative to the largest stress so that large exponents
+/// do not overflow
+fn aggregate(kind: StressAggregation, p: f64, s: &[f64]) -> (f64, Vec<f64>) {
+    let smax = s.iter().copied().fold(0.0, f64::max);
+    if smax <= 0.0 {
+        return (0.0, vec![0.0; s.len()]);
+    }
+    match kind {
+        StressAggregation::PNorm => {
+            let sum: f64 = s.iter().map(|v| (v / smax).powf(p)).sum();
+            let scale = sum.powf(1.0 / p - 1.0);
+            let d = s.iter().map(|v| (v / smax).powf(p - 1.0) * scale).collect();
+            (smax * sum.powf(1.0 / p), d)
+        }
+        StressAggregation::Ks => {
+            let w: Vec<f64> = s.iter().map(|v| (p * (v - smax)).exp()).collect();
+            let sum: f64 = w.iter().sum();
+            (smax + sum.ln() / p, w.iter().map(|v| v / sum).collect())
+        }
+    }
+}
+
+/// Minimum volume subject to a von Mises stress limit on a 2D grid of
+/// unit Q4 elements in plane stress
+///
+/// The stress of element e is relaxed to rho_e^q s_vm(D_0 B u_e) at the
+/// element centre (qp-relaxation, Bruggi 2008), which vanishes with the
+/// density and removes the singular optima of the plain stress. The
+/// element stresses are aggregated into one constraint s_agg / limit - 1
+/// <= 0 by the p-norm or KS function, whose sensitivity takes one adjoint
+/// solve K lambda = d s_agg / du per iteration on the stiffness of the
+/// state solve. Design variables are density filtered and updated by
+/// `Mma`; the run therefore costs two solves per iteration. Both
+/// aggregates overestimate the largest stress and approach it from above
+/// as `aggregation_parameter` grows, so the constraint is conservative.
+#[wasm_bindgen]
+pub struct StressTopOpt {
+    analysis: GridAnalysis,
+    options: StressOptions,
+    /// 3 x 8 centre stress operator of the solid
+    operator: Vec<f64>,
+    filter: DesignFilter,
+    mma: Mma,
+    densities: Vec<f64>,
+    physical: Vec<f64>,
+    stresses: Vec<f64>,
+    aggregate: f64,
+    /// Adjoint of the latest iteration, warm-starting the next
+    adjoint: Vec<f64>,
+    iteration: u32,
+    change: f64,
+    converged: bool,
+}
+
+#[wasm_bindgen]
+impl StressTopOpt {
+    /// Uniform design at `options.initial` on a `nelx` x `nely` grid,
+    /// unloaded and unsupported. Returns `undefined` if the grid is empty,
+    /// `limit`, `aggregation_parameter` or `rmin` is not > 0, q not in (0,
+    /// penal), `initial` or `move_limit` not in (0, 1] or `nu` is not
+    /// admissible.
+    pub fn new(nelx: usize, nely: usize, options: &StressOptions) -> Option<StressTopOpt> {
+        let positive = [options.limit, options.aggregation_parameter, options.rmin];
+        let (q, initial) = (options.q, options.initial);
+        if !(positive.into_iter().all(|v| v > 0.0)
+            && q > 0.0
+            && q < options.penal
+            && initial > 0.0
+            && initial <= 1.0)
+        {
+            return None;
+        }
+        let elements = [nelx, nely, 0];
+        let analysis = GridAnalysis::new(elements, options.nu)?;
+        let count = analysis.element_count();
+        let n = analysis.u.len();
+        let filter = DesignFilter::new(FilterKind::Density, elements, options.rmin)?;
+        let densities = vec![initial; count];
+        let mut mma = Mma::new(&vec![0.0; count], &vec![1.0; count])?;
+        if !mma.set_move_limit(options.move_limit) {
+            return None;
+        }
+        Some(StressTopOpt {
+            analysis,
+            options: *options,
+            operator: centre_stress_matrix(options.e0, options.nu),
+            physical: filter.physical(&densities),
+            filter,
+            mma,
+            densities,
+            stresses: vec![0.0; count],
+            aggregate: 0.0,
+            adjoint: vec![0.0; n],
+            iteration: 0,
+            change: 1.0,
+            converged: false,
+        })
+    }
+
+    /// Set the nodal force vector; returns `false`, changing nothing,
+    /// unless it has one entry per DOF
+    pub fn set_forces(&mut self, forces: &[f64]) -> bool {
+        self.analysis.set_forces(forces)
+    }
+
+    /// Set the supported DOFs; returns `false`, changing nothing, if one
+    /// is out of range
+    pub fn set_fixed_dofs(&mut self, fixed_dofs: &[u32]) -> bool {
+        self.analysis.set_fixed_dofs(fixed_dofs)
+    }
+
+    /// Options of the state and adjoint solves (PCG), by default those of
+    /// `solve_pcg`
+    pub fn set_solver_options(&mut self, options: &SolverOptions) {
+        self.analysis.solver = *options;
+    }
+
+    /// One optimization iteration; returns whether the run has converged
+    /// (or reached `max_iter`), after which `step` does nothing
+    pub fn step(&mut self) -> bool {
+        if self.converged {
+            return true;
+        }
+        let physical = self.physical.clone();
+        let (value, gradient) = self.evaluate(&physical);
+        let limit = self.options.limit;
+        let count = physical.len() as f64;
+        let fval = [value / limit - 1.0];
+        let dg: Vec<f64> = gradient.iter().map(|g| g / limit).collect();
+        let (dg, dv) =
+            self.filter
+                .sensitivities(&self.densities, &dg, &vec![1.0 / count; dg.len()]);
+        let volume = physical.iter().sum::<f64>() / count;
+        let xnew = self
+            .mma
+            .update(&self.densities, volume, &dv, &fval, &dg)
+            .expect("design and sensitivities have one entry per element");
+
+        self.change = xnew
+            .iter()
+            .zip(&self.densities)
+            .map(|(a, b)| (a - b).abs())
+            .fold(0.0, f64::max);
+        self.densities = xnew;
+        self.physical = self.filter.physical(&self.densities);
+        self.iteration += 1;
+        self.converged = self.change < self.options.tolx || self.iteration >= self.options.max_iter;
+        self.converged
+    }
+
+    /// Up to `steps` iterations, fewer if the run converges; returns the
+    /// number done
+    pub fn run(&mut self, steps: u32) -> u32 {
+        let mut done = 0;
+        while done < steps && !self.converged {
+            self.step();
+            done += 1;
+        }
+        done
+    }
+
+    /// Physical element densities, one per element
+    #[wasm_bindgen(getter)]
+    pub fn densities(&self) -> Vec<f64> {
+        self.physical.clone()
+    }
+
+    /// Design variables, one per element
+    #[wasm_bindgen(getter)]
+    pub fn design(&self) -> Vec<f64> {
+        self.densities.clone()
+    }
+
+    /// Relaxed von Mises stress rho^q s_vm per element of the design
+    /// before the latest update
+    #[wasm_bindgen(getter)]
+    pub fn stresses(&self) -> Vec<f64> {
+        self.stresses.clone()
+    }
+
+    /// Aggregated stress of the design before the latest update
+    #[wasm_bindgen(getter)]
+    pub fn aggregate(&self) -> f64 {
+        self.aggregate
+    }
+
+    /// Stress constraint aggregate / limit - 1 of the design before the
+    /// latest update, feasible when <= 0
+    #[wasm_bindgen(getter)]
+    pub fn constraint(&self) -> f64 {
+        self.aggregate / self.options.limit - 1.0
+    }
+
+    /// Mean physical density of the current design
+    #[wasm_bindgen(getter)]
+    pub fn volume(&self) -> f64 {
+        self.physical.iter().sum::<f64>() / self.physical.len() as f64
+    }
+
+    /// Displacements of the latest solve
+    #[wasm_bindgen(getter)]
+    pub fn displacements(&self) -> Vec<f64> {
+        self.analysis.u.clone()
+    }
+
+    /// Largest design variable change of the latest update
+    #[wasm_bindgen(getter)]
+    pub fn change(&self) -> f64 {
+        self.change
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn iteration(&self) -> u32 {
+        self.iteration
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn converged(&self) -> bool {
+        self.converged
+    }
+
+    /// PCG iterations of the latest state solve
+    #[wasm_bindgen(getter)]
+    pub fn solver_iterations(&self) -> u32 {
+        self.analysis.solver_iterations
+    }
+}
+
+impl StressTopOpt {
+    /// Aggregated relaxed stress of the physical densities `physical` and
+    /// its gradient by them, from the state and one adjoint solve
+    ///
+    /// With s_e = rho_e^q s_vm(S u_e) and K lambda = sum_e (d s_agg / d
+    /// s_e) (d s_e / d u_e), d s_agg / d rho_e = (d s_agg / d s_e) q
+    /// rho_e^(q-1) s_vm - p rho_e^(p-1) (E0 - Emin) lambda_e^T K_e u_e.
+    fn evaluate(&mut self, physical: &[f64]) -> (f64, Vec<f64>) {
+        let StressOptions {
+            q,
+            penal,
+            e0,
+            emin,
+            aggregation,
+            aggregation_parameter,
+            ..
+        } = self.options;
+        let moduli = simp_moduli(physical, penal, e0, emin);
+        self.analysis.solve(&moduli);
+        let grid = self.analysis.grid(&moduli);
+        let u = &self.analysis.u;
+
+        let mut dofs = Vec::with_capacity(8);
+        let mut centre = Vec::with_capacity(3 * physical.len());
+        for (e, rho) in physical.iter().enumerate() {
+            grid.element_dofs(e, &mut dofs);
+            let s: Vec<f64> = self
+                .operator
+                .chunks_exact(8)
+                .map(|row| row.iter().zip(&dofs).map(|(a, &i)| a * u[i]).sum())
+                .collect();
+            self.stresses[e] = rho.powf(q) * von_mises(&s);
+            centre.extend(s);
+        }
+        let (value, weights) = aggregate(aggregation, aggregation_parameter, &self.stresses);
+        self.aggregate = value;
+
+        // d s_e / d u_e = rho^q S^T V s / s_vm with s_vm^2 = s^T V s
+        let mut load = vec![0.0; u.len()];
+        for (e, s) in centre.chunks_exact(3).enumerate() {
+            let vm = von_mises(s);
+            if vm <= 0.0 {
+                continue;
+            }
+            let coefficient = weights[e] * physical[e].powf(q) / vm;
+            let vs = [s[0] - 0.5 * s[1], s[1] - 0.5 * s[0], 3.0 * s[2]];
+            grid.element_dofs(e, &mut dofs);
+            for (j, &i) in dofs.iter().enumerate() {
+                let g: f64 = (0..3).map(|k| self.operator[k * 8 + j] * vs[k]).sum();
+                load[i] += coefficient * g;
+            }
+        }
+        let k = self.analysis.stiffness(&moduli);
+        (self.adjoint, _) = self.analysis.solve_load(&k, &load, &self.adjoint);
+
+        let mixed = self.analysis.mixed_energies(&moduli, u, &self.adjoint);
+        let gradient = physical
+            .iter()
+            .enumerate()
+            .map(|(e, rho)| {
+                let explicit =
+                    weights[e] * q * rho.powf(q - 1.0) * von_mises(&centre[3 * e..3 * e + 3]);
+                explicit - penal * rho.powf(penal - 1.0) * (e0 - emin) * mixed[e]
+            })
+            .collect();
+        (value, gradient)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{clamped_left_edge, tip_load};
+
+    /// Cantilever clamped on the left edge, loaded down at the lower
+    /// right corner
+    fn cantilever(nelx: usize, nely: usize, options: &StressOptions) -> StressTopOpt {
+        let mut opt = StressTopOpt::new(nelx, nely, options).unwrap();
+        let elements = [nelx, nely, 0];
+        assert!(opt.set_fixed_dofs(&clamped_left_edge(elements)));
+        assert!(opt.set_forces(&tip_load(elements, nely)));
+        let mut solver = SolverOptions::new();
+        solver.tol = 1e-12;
+        opt.set_solver_options(&solver);
+        opt
+    }
+
+    #[test]
+    fn test_aggregate_gradient_matches_finite_differences() {
+        for aggregation in [StressAggregation::PNorm, StressAggregation::Ks] {
+            let options = StressOptions {
+                aggregation,
+                ..StressOptions::new()
+            };
+            let mut opt = cantilever(6, 3, &options);
+            let physical: Vec<f64> = (0..18).map(|e| 0.4 + 0.03 * e as f64).collect();
+            let (value, gradient) = opt.evaluate(&physical);
+            let smax = opt.stresses().iter().copied().fold(0.0, f64::max);
+            match aggregation {
+                StressAggregation::PNorm => assert!(value >= smax),
+                StressAggregation::Ks => assert!(value >= smax && value < smax + 18f64.ln() / 8.0),
+            }
+            for e in [0, 7, 17] {
+                let (mut xp, mut xm) = (physical.clone(), physical.clone());
+                xp[e] += 1e-6;
+                xm[e] -= 1e-6;
+                let fd = (opt.evaluate(&xp).0 - opt.evaluate(&xm).0) / 2e-6;
+                assert!(
+                    (gradient[e] - fd).abs() < 1e-5 * (1.0 + fd.abs()),
+                    "{e}: {}",
+                    fd
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_removes_material_within_stress_limit() {
+        // Limit twice the aggregated stress of the solid design
+        let mut solid = cantilever(20, 10, &StressOptions::new());
+        solid.step();
+        let options = StressOptions {
+            limit: 2.0 * solid.aggregate(),
+            ..StressOptions::new()
+        };
+        let mut opt = cantilever(20, 10, &options);
+        opt.run(50);
+        assert!(opt.volume() < 0.5 && opt.constraint() < 1e-2);
+        assert!(StressTopOpt::new(20, 10, &StressOptions { q: 3.0, ..options }).is_none());
+    }
+}
@@ -0,0 +1,203 @@
+//! Sparse triangular solves on CSR data
+//!
+//! Building blocks for preconditioners composed on the JavaScript side
+//! and for applying factors computed elsewhere. Each routine reads only
+//! the triangle it solves with (and the diagonal), so the L and U of an
+//! ILU factor can be passed as one matrix holding both.
+
+use wasm_bindgen::prelude::*;
+
+use crate::kernels::Csr;
+
+/// Solve L x = b with L the lower triangle of A, diagonal included
+///
+/// With `unit_diagonal` the diagonal is taken as 1 and not read. Returns
+/// `undefined` if a diagonal entry is missing or zero, or `b` does not have
+/// one entry per row.
+#[wasm_bindgen]
+pub fn solve_lower(
+    values: &[f64],
+    col_indices: &[u32],
+    row_ptr: &[u32],
+    b: &[f64],
+    unit_diagonal: bool,
+) -> Option<Vec<f64>> {
+    triangular_solve(
+        &Csr::new(values, col_indices, row_ptr),
+        b,
+        true,
+        false,
+        unit_diagonal,
+    )
+}
+
+/// Solve U x = b with U the upper triangle of A; as `solve_lower`
+#[wasm_bindgen]
+pub fn solve_upper(
+    values: &[f64],
+    col_indices: &[u32],
+    row_ptr: &[u32],
+    b: &[f64],
+    unit_diagonal: bool,
+) -> Option<Vec<f64>> {
+    triangular_solve(
+        &Csr::new(values, col_indices, row_ptr),
+        b,
+        false,
+        false,
+        unit_diagonal,
+    )
+}
+
+/// Solve L^T x = b with L the lower triangle of A, without forming L^T
+/// (the second half of a Cholesky or IC solve); as `solve_lower`
+#[wasm_bindgen]
+pub fn solve_lower_transpose(
+    values: &[f64],
+    col_indices: &[u32],
+    row_ptr: &[u32],
+    b: &[f64],
+    unit_diagonal: bool,
+) -> Option<Vec<f64>> {
+    triangular_solve(
+        &Csr::new(values, col_indices, row_ptr),
+        b,
+        true,
+        true,
+        unit_diagonal,
+    )
+}
+
+/// Solve U^T x = b with U the upper triangle of A; as `solve_lower`
+#[wasm_bindgen]
+pub fn solve_upper_transpose(
+    values: &[f64],
+    col_indices: &[u32],
+    row_ptr: &[u32],
+    b: &[f64],
+    unit_diagonal: bool,
+) -> Option<Vec<f64>> {
+    triangular_solve(
+        &Csr::new(values, col_indices, row_ptr),
+        b,
+        false,
+        true,
+        unit_diagonal,
+    )
+}
+
+/// Solve with the `lower` or upper triangle of `a`, or its transpose
+///
+/// Row-oriented (each x_i from a dot product with solved entries) when the
+/// system is used as stored, column-oriented (each solved x_i updates the
+/// remaining right-hand side) when transposed. Entries within a row may be
+/// in any order.
+fn triangular_solve(
+    a: &Csr,
+    b: &[f64],
+    lower: bool,
+    transpose: bool,
+    unit_diagonal: bool,
+) -> Option<Vec<f64>> {
+    let n = a.n();
+    if b.len() != n {
+        return None;
+    }
+    let diagonal: Vec<f64> = if unit_diagonal {
+        vec![1.0; n]
+    } else {
+        (0..n)
+            .map(|i| {
+                let d: f64 = (a.row_ptr[i] as usize..a.row_ptr[i + 1] as usize)
+                    .filter(|&q| a.col_indices[q] as usize == i)
+                    .map(|q| a.values[q])
+                    .sum();
+                (d != 0.0 && d.is_finite()).then_some(d)
+            })
+            .collect::<Option<_>>()?
+    };
+    let in_triangle = |i: usize, j: usize| if lower { j < i } else { j > i };
+    let entries = |i: usize| {
+        (a.row_ptr[i] as usize..a.row_ptr[i + 1] as usize)
+            .map(|q| (a.col_indices[q] as usize, a.values[q]))
+            .filter(move |&(j, _)| in_triangle(i, j))
+    };
+    // Lower and upper-transposed systems run forward, the others backward
+    let rows: Box<dyn Iterator<Item = usize>> = if lower != transpose {
+        Box::new(0..n)
+    } else {
+        Box::new((0..n).rev())
+    };
+
+    let mut x = b.to_vec();
+    for i in rows {
+        if transpose {
+            x[i] /= diagonal[i];
+            let xi = x[i];
+            for (j, v) in entries(i) {
+                x[j] -= v * xi;
+            }
+        } else {
+            let s: f64 = entries(i).map(|(j, v)| v * x[j]).sum();
+            x[i] = (x[i] - s) / diagonal[i];
+        }
+    }
+    Some(x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::dense_to_csr;
+
+    /// Dense y = M x for a row-major n x n matrix
+    fn multiply(m: &[f64], x: &[f64]) -> Vec<f64> {
+        m.chunks_exact(x.len())
+            .map(|row| row.iter().zip(x).map(|(a, b)| a * b).sum())
+            .collect()
+    }
+
+    #[test]
+    fn test_triangles_of_one_matrix() {
+        let a = [4.0, 1.0, 2.0, -1.0, 3.0, 0.5, 2.0, 1.5, 5.0];
+        let (values, col_indices, row_ptr) = dense_to_csr(&a, 3);
+        let lower = [4.0, 0.0, 0.0, -1.0, 3.0, 0.0, 2.0, 1.5, 5.0];
+        let upper = [4.0, 1.0, 2.0, 0.0, 3.0, 0.5, 0.0, 0.0, 5.0];
+        let transpose =
+            |m: &[f64]| -> Vec<f64> { (0..9).map(|k| m[(k % 3) * 3 + k / 3]).collect() };
+        let b = [1.0, -2.0, 0.5];
+        let cases: [(&[f64], _); 4] = [
+            (
+                &lower,
+                solve_lower(&values, &col_indices, &row_ptr, &b, false),
+            ),
+            (
+                &upper,
+                solve_upper(&values, &col_indices, &row_ptr, &b, false),
+            ),
+            (
+                &transpose(&lower),
+                solve_lower_transpose(&values, &col_indices, &row_ptr, &b, false),
+            ),
+            (
+                &transpose(&upper),
+                solve_upper_transpose(&values, &col_indices, &row_ptr, &b, false),
+            ),
+        ];
+        for (m, x) in cases {
+            for (mx, bi) in multiply(m, &x.unwrap()).iter().zip(&b) {
+                assert!((mx - bi).abs() < 1e-14);
+            }
+        }
+    }
+
+    #[test]
+    fn test_unit_and_missing_diagonal() {
+        // Strictly lower entries only: unit solves work, others have no diagonal
+        let (values, col_indices, row_ptr) = dense_to_csr(&[0.0, 0.0, 2.0, 0.0], 2);
+        let x = solve_lower(&values, &col_indices, &row_ptr, &[1.0, 3.0], true).unwrap();
+        assert_eq!(x, vec![1.0, 1.0]);
+        assert!(solve_lower(&values, &col_indices, &row_ptr, &[1.0, 3.0], false).is_none());
+        assert!(solve_lower(&values, &col_indices, &row_ptr, &[1.0], true).is_none());
+    }
+}